@@ -0,0 +1,18 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.9" tiledversion="1.9.2" name="decoration" tilewidth="32" tileheight="32" tilecount="6" columns="3">
+ <image source="tilesheet.png" width="96" height="64"/>
+ <tile id="0" class="flower" probability="3"/>
+ <tile id="1" class="flower"/>
+ <tile id="2" class="flower" probability="0"/>
+ <tile id="3" class="rock"/>
+ <tile id="4" probability="0.5"/>
+ <wangsets>
+  <wangset name="Ground" type="corner" tile="-1">
+   <wangcolor name="Grass" color="#00ff00" tile="-1" probability="1"/>
+   <wangcolor name="Dirt" color="#804000" tile="-1" probability="1"/>
+   <wangtile tileid="3" wangid="0,1,0,1,0,1,0,1"/>
+   <wangtile tileid="4" wangid="0,1,0,1,0,1,0,1"/>
+   <wangtile tileid="5" wangid="0,1,0,2,0,2,0,1"/>
+  </wangset>
+ </wangsets>
+</tileset>
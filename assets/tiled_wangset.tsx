@@ -0,0 +1,25 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.5" tiledversion="1.7.2" name="terrain" tilewidth="32" tileheight="32" tilecount="6" columns="3">
+ <properties>
+  <property name="tileset property" value="yes"/>
+ </properties>
+ <image source="tilesheet.png" width="96" height="64"/>
+ <wangsets>
+  <wangset name="Ground" type="corner" tile="0">
+   <properties>
+    <property name="wang set property" value="nested"/>
+   </properties>
+   <wangcolor name="Grass" color="#00ff00" tile="0" probability="1"/>
+   <wangcolor name="Dirt" color="#804000" tile="5" probability="0.5">
+    <properties>
+     <property name="walkable" type="bool" value="true"/>
+    </properties>
+   </wangcolor>
+   <wangtile tileid="0" wangid="0,1,0,1,0,1,0,1"/>
+   <wangtile tileid="1" wangid="0,1,0,2,0,2,0,1"/>
+   <wangtile tileid="2" wangid="0,2,0,2,0,1,0,1"/>
+   <wangtile tileid="3" wangid="0,1,0,2,0,2,0,1"/>
+   <wangtile tileid="5" wangid="0,2,0,2,0,2,0,2"/>
+  </wangset>
+ </wangsets>
+</tileset>
@@ -1,10 +1,18 @@
+use std::collections::HashMap;
+
 use xml::attribute::OwnedAttribute;
 
-use crate::{error::TiledError, util::get_attrs};
+use crate::{
+    error::TiledError,
+    ids::{Gid, TileId},
+    tile::Tile,
+    tileset::{MapTileset, Tileset},
+    util::get_attrs,
+};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Frame {
-    pub tile_id: u32,
+    pub tile_id: TileId,
     pub duration: u32,
 }
 
@@ -14,7 +22,7 @@ impl Frame {
             attrs,
             optionals: [],
             required: [
-                ("tileid", tile_id, |v:String| v.parse().ok()),
+                ("tileid", tile_id, |v:String| v.parse().ok().map(TileId)),
                 ("duration", duration, |v:String| v.parse().ok()),
             ],
             TiledError::MalformedAttributes("A frame must have tileid and duration".to_string())
@@ -25,3 +33,193 @@ impl Frame {
         })
     }
 }
+
+/// Returns the length of one loop of the animation, in milliseconds.
+pub fn total_duration(frames: &[Frame]) -> u32 {
+    frames.iter().map(|f| f.duration).sum()
+}
+
+/// Returns the frame that is shown `elapsed_ms` milliseconds after the animation started, along
+/// with how many milliseconds remain until the next frame. Animations loop, as in the editor.
+///
+/// Returns `None` if there are no frames.
+pub fn frame_at(frames: &[Frame], elapsed_ms: u64) -> Option<(&Frame, u32)> {
+    let total = total_duration(frames);
+    if total == 0 {
+        return frames.first().map(|f| (f, 0));
+    }
+    let mut time = (elapsed_ms % total as u64) as u32;
+    for frame in frames {
+        if time < frame.duration {
+            return Some((frame, frame.duration - time));
+        }
+        time -= frame.duration;
+    }
+    unreachable!("the elapsed time is always within the total duration")
+}
+
+/// Rounds `duration` to the nearest multiple of `step` milliseconds, keeping it at least `step`
+/// long, so that frames line up with a fixed frame time. A `step` of 0 leaves it unchanged.
+pub fn normalize_duration(duration: u32, step: u32) -> u32 {
+    if step == 0 {
+        return duration;
+    }
+    let rounded = (u64::from(duration) + u64::from(step / 2)) / u64::from(step) * u64::from(step);
+    rounded.clamp(u64::from(step), u64::from(u32::MAX)) as u32
+}
+
+/// The animated tiles of a tileset, as returned by
+/// [`Map::animated_tiles`](crate::map::Map::animated_tiles).
+#[derive(Debug, PartialEq, Clone)]
+pub struct TilesetAnimations<'map> {
+    pub tileset: &'map MapTileset,
+    /// The frames of each animated tile, keyed by the gid of the tile in the map.
+    pub tiles: HashMap<Gid, &'map [Frame]>,
+}
+
+impl Tileset {
+    /// Whether the tileset has a tile with the id `id`: one within the image for tilesets with
+    /// an image, or one of the tiles of an image collection.
+    fn has_tile(&self, id: TileId) -> bool {
+        match self.images.first() {
+            Some(_) => id.0 < self.tile_count(),
+            None => self.get_tile(id).is_some(),
+        }
+    }
+
+    fn check_frames(&self, id: TileId, frames: &[Frame]) -> Result<(), TiledError> {
+        if !self.has_tile(id) {
+            return Err(TiledError::Other(format!(
+                "tile {} is not part of tileset \"{}\"",
+                id.0, self.name
+            )));
+        }
+        match frames.iter().find(|f| !self.has_tile(f.tile_id)) {
+            Some(frame) => Err(TiledError::Other(format!(
+                "the animation of tile {} shows tile {}, which is not part of tileset \"{}\"",
+                id.0, frame.tile_id, self.name
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the frames of the tile with the id `id`, adding the tile to [`Tileset::tiles`]
+    /// if it had no data yet. New tiles go before the first tile with a greater id, which keeps
+    /// sorted tiles sorted like the editor writes them, though they may come in any order.
+    fn frames_mut(&mut self, id: TileId) -> &mut Vec<Frame> {
+        let index = match self.tiles.iter().position(|t| t.id == id) {
+            Some(index) => index,
+            None => {
+                let index = self
+                    .tiles
+                    .iter()
+                    .position(|t| t.id > id)
+                    .unwrap_or(self.tiles.len());
+                self.tiles.insert(
+                    index,
+                    Tile {
+                        id,
+                        images: Vec::new(),
+                        properties: HashMap::new(),
+                        objectgroup: None,
+                        animation: None,
+                        tile_type: None,
+                        probability: 1.0,
+                        terrain: None,
+                    },
+                );
+                index
+            }
+        };
+        self.tiles[index].animation.get_or_insert_with(Vec::new)
+    }
+
+    /// Replaces the animation of the tile with the id `id`. An empty list of frames removes the
+    /// animation. Fails if the tile, or one of the tiles shown by the frames, isn't part of the
+    /// tileset, leaving the tileset unchanged.
+    pub fn set_animation(&mut self, id: TileId, frames: Vec<Frame>) -> Result<(), TiledError> {
+        self.check_frames(id, &frames)?;
+        if frames.is_empty() {
+            if let Some(tile) = self.tiles.iter_mut().find(|t| t.id == id) {
+                tile.animation = None;
+            }
+        } else {
+            *self.frames_mut(id) = frames;
+        }
+        Ok(())
+    }
+
+    /// Inserts a frame into the animation of the tile with the id `id` at `index`, starting an
+    /// animation if the tile has none. Fails like [`Tileset::set_animation`], or if `index` is
+    /// past the end of the animation.
+    pub fn insert_frame(
+        &mut self,
+        id: TileId,
+        index: usize,
+        frame: Frame,
+    ) -> Result<(), TiledError> {
+        self.check_frames(id, std::slice::from_ref(&frame))?;
+        let len = self
+            .get_tile(id)
+            .and_then(|t| t.animation.as_ref())
+            .map_or(0, Vec::len);
+        if index > len {
+            return Err(TiledError::Other(format!(
+                "cannot insert frame {} into the animation of tile {}, which has {} frames",
+                index, id.0, len
+            )));
+        }
+        self.frames_mut(id).insert(index, frame);
+        Ok(())
+    }
+
+    /// Adds a frame at the end of the animation of the tile with the id `id`, see
+    /// [`Tileset::insert_frame`].
+    pub fn push_frame(&mut self, id: TileId, frame: Frame) -> Result<(), TiledError> {
+        let len = self
+            .get_tile(id)
+            .and_then(|t| t.animation.as_ref())
+            .map_or(0, Vec::len);
+        self.insert_frame(id, len, frame)
+    }
+
+    /// Removes the frame at `index` from the animation of the tile with the id `id`, returning
+    /// it. Removing the last frame removes the animation. Returns `None` if there is no such
+    /// frame.
+    pub fn remove_frame(&mut self, id: TileId, index: usize) -> Option<Frame> {
+        let tile = self.tiles.iter_mut().find(|t| t.id == id)?;
+        let frames = tile.animation.as_mut().filter(|f| index < f.len())?;
+        let frame = frames.remove(index);
+        if frames.is_empty() {
+            tile.animation = None;
+        }
+        Some(frame)
+    }
+
+    /// Rounds the duration of every frame of the tileset with [`normalize_duration`].
+    pub fn normalize_animations(&mut self, step: u32) {
+        for frames in self.tiles.iter_mut().filter_map(|t| t.animation.as_mut()) {
+            for frame in frames {
+                frame.duration = normalize_duration(frame.duration, step);
+            }
+        }
+    }
+
+    /// Moves the frame at `from` in the animation of the tile with the id `id` to `to`, shifting
+    /// the frames in between. Returns `false` if either index is out of the animation.
+    pub fn move_frame(&mut self, id: TileId, from: usize, to: usize) -> bool {
+        let frames = match self.tiles.iter_mut().find(|t| t.id == id) {
+            Some(Tile {
+                animation: Some(frames),
+                ..
+            }) => frames,
+            _ => return false,
+        };
+        if from >= frames.len() || to >= frames.len() {
+            return false;
+        }
+        let frame = frames.remove(from);
+        frames.insert(to, frame);
+        true
+    }
+}
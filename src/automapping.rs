@@ -0,0 +1,527 @@
+//! Applies the rules of automapping rule maps, as made for the editor, to the tile layers of maps
+//! at runtime, so that generated layouts can be decorated with the same rules.
+//!
+//! Rule maps are regular maps whose layers are named after their role:
+//!
+//! - `input_<layer>` layers hold the tiles the layer named `<layer>` of the target map must
+//!   contain for a rule to match. Several input layers for the same layer give alternatives.
+//! - `inputnot_<layer>` layers hold tiles that layer must not contain.
+//! - `output_<layer>` layers hold the tiles placed into that layer where a rule matches. Output
+//!   layers can have an index, as in `output2_<layer>`: layers with the same index are applied
+//!   together, and one of the indices with tiles in a rule is picked for each match.
+//! - `regions`, `regions_input` and `regions_output` layers optionally mark the cells each rule
+//!   covers. Without them, each group of connected cells holding input or output tiles is a rule.
+//!
+//! Tiles of rule maps are found in target maps by the name of their tileset and their id in it.
+//! The special tiles of the editor's automapping tileset are recognized by their `matchType`
+//! property (`Empty`, `Ignore`, `NonEmpty`, `Other` and `Negate`).
+//!
+//! The `DeleteTiles` and `MatchOutsideMap` map properties of rule maps are supported. Options
+//! given to rules through objects, `rules.txt` files and output probabilities are not.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{
+    error::TiledError,
+    ids::TileId,
+    layers::{Layer, LayerTile, TileRect},
+    map::Map,
+    properties::PropertyValue,
+};
+
+/// The rules of an automapping rule map, ready to be applied to maps. See the
+/// [module documentation](self) for how rule maps are interpreted.
+///
+/// ```no_run
+/// use std::path::Path;
+/// use tiled::automapping::AutomappingRules;
+///
+/// let rules = AutomappingRules::from_map(&tiled::parse_file(Path::new("rules.tmx")).unwrap())
+///     .unwrap();
+/// let mut map = tiled::parse_file(Path::new("generated.tmx")).unwrap();
+/// rules.apply(&mut map).unwrap();
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub struct AutomappingRules {
+    rules: Vec<Rule>,
+    /// Whether output layers are cleared within the output region of a rule before placing its
+    /// tiles, from the `DeleteTiles` property of the rule map.
+    pub delete_tiles: bool,
+    /// Whether rules can match partly outside of the target layers, where cells are empty, from
+    /// the `MatchOutsideMap` property of the rule map.
+    pub match_outside_map: bool,
+}
+
+/// What a tile of a rule map stands for.
+#[derive(Debug, PartialEq, Clone)]
+enum RuleTile {
+    Tile {
+        tileset: String,
+        id: TileId,
+        flip_h: bool,
+        flip_v: bool,
+        flip_d: bool,
+    },
+    Empty,
+    Ignore,
+    NonEmpty,
+    Other,
+    Negate,
+}
+
+/// The tiles of a layer of a rule map, by position.
+type RuleCells = HashMap<(i32, i32), RuleTile>;
+
+#[derive(Debug, PartialEq, Clone)]
+struct Rule {
+    inputs: Vec<InputCell>,
+    /// The alternative sets of output tiles, one of which is placed for each match.
+    outputs: Vec<OutputSet>,
+    /// The output region, relative to the top-left corner of the rule.
+    output_cells: Vec<(i32, i32)>,
+    width: i32,
+    height: i32,
+}
+
+/// The conditions on one cell of one layer of the target map.
+#[derive(Debug, PartialEq, Clone)]
+struct InputCell {
+    layer: String,
+    offset: (i32, i32),
+    accepted: Vec<RuleTile>,
+    rejected: Vec<RuleTile>,
+    /// Whether the cell must be empty when no tile is accepted, as in explicit input regions.
+    strict: bool,
+    /// The tiles used by the rule on the same layer, which `Other` tiles don't match.
+    known: Vec<RuleTile>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct OutputSet {
+    /// The layers of the set, all of which are cleared in the output region with `DeleteTiles`.
+    layers: Vec<String>,
+    tiles: Vec<(String, (i32, i32), RuleTile)>,
+}
+
+enum Role<'a> {
+    Input { layer: &'a str, negated: bool },
+    Output { layer: &'a str, index: &'a str },
+    Regions { input: bool, output: bool },
+}
+
+impl<'a> Role<'a> {
+    fn of(name: &'a str) -> Option<Role<'a>> {
+        let regions = |input, output| Some(Role::Regions { input, output });
+        match name {
+            "regions" => return regions(true, true),
+            "regions_input" => return regions(true, false),
+            "regions_output" => return regions(false, true),
+            _ => {}
+        }
+        let (prefix, layer) = name.split_once('_')?;
+        let negated = match prefix.strip_prefix("input") {
+            Some("") => false,
+            Some("not") => true,
+            Some(_) => return None,
+            None => {
+                let index = prefix.strip_prefix("output")?;
+                return Some(Role::Output { layer, index });
+            }
+        };
+        Some(Role::Input { layer, negated })
+    }
+}
+
+impl AutomappingRules {
+    /// Reads the rules of a rule map. Fails if the map has no input layers.
+    pub fn from_map(rule_map: &Map) -> Result<AutomappingRules, TiledError> {
+        let flag = |name: &str| {
+            matches!(
+                rule_map.properties.get(name),
+                Some(PropertyValue::BoolValue(true))
+            )
+        };
+
+        let mut inputs: Vec<(&str, bool, RuleCells)> = Vec::new();
+        let mut outputs: Vec<(&str, &str, RuleCells)> = Vec::new();
+        let mut input_region = HashSet::new();
+        let mut output_region = HashSet::new();
+        let mut explicit_regions = false;
+        for layer in &rule_map.layers {
+            match Role::of(&layer.name) {
+                Some(Role::Input {
+                    layer: name,
+                    negated,
+                }) => {
+                    inputs.push((name, negated, rule_tiles(rule_map, layer)));
+                }
+                Some(Role::Output { layer: name, index }) => {
+                    outputs.push((name, index, rule_tiles(rule_map, layer)));
+                }
+                Some(Role::Regions { input, output }) => {
+                    explicit_regions = true;
+                    for (cell, _) in rule_tiles(rule_map, layer) {
+                        if input {
+                            input_region.insert(cell);
+                        }
+                        if output {
+                            output_region.insert(cell);
+                        }
+                    }
+                }
+                None => {}
+            }
+        }
+        if inputs.is_empty() {
+            return Err(TiledError::Other(
+                "Automapping rule map has no input layers".to_string(),
+            ));
+        }
+        if !explicit_regions {
+            input_region.extend(inputs.iter().flat_map(|(_, _, tiles)| tiles.keys()));
+            output_region.extend(outputs.iter().flat_map(|(_, _, tiles)| tiles.keys()));
+        }
+
+        let mut layer_names: Vec<&str> = Vec::new();
+        for &(name, _, _) in &inputs {
+            if !layer_names.contains(&name) {
+                layer_names.push(name);
+            }
+        }
+
+        let mut rules = Vec::new();
+        for component in components(input_region.union(&output_region).copied().collect()) {
+            let min_x = component.iter().map(|c| c.0).min().unwrap_or(0);
+            let min_y = component.iter().map(|c| c.1).min().unwrap_or(0);
+            let max_x = component.iter().map(|c| c.0).max().unwrap_or(0);
+            let max_y = component.iter().map(|c| c.1).max().unwrap_or(0);
+            let offset = |(x, y): (i32, i32)| (x - min_x, y - min_y);
+
+            let mut rule_inputs = Vec::new();
+            for &name in &layer_names {
+                let layers: Vec<_> = inputs.iter().filter(|(n, _, _)| *n == name).collect();
+                let known: Vec<RuleTile> = layers
+                    .iter()
+                    .flat_map(|(_, _, tiles)| {
+                        component.iter().filter_map(move |cell| tiles.get(cell))
+                    })
+                    .filter(|tile| matches!(tile, RuleTile::Tile { .. }))
+                    .cloned()
+                    .collect();
+                let mut cells = Vec::new();
+                for &cell in component.iter().filter(|cell| input_region.contains(cell)) {
+                    let mut accepted = Vec::new();
+                    let mut rejected = Vec::new();
+                    for (_, negated, tiles) in &layers {
+                        if let Some(tile) = tiles.get(&cell) {
+                            if *negated {
+                                rejected.push(tile.clone());
+                            } else {
+                                accepted.push(tile.clone());
+                            }
+                        }
+                    }
+                    if accepted.contains(&RuleTile::Negate) {
+                        accepted.retain(|tile| *tile != RuleTile::Negate);
+                        rejected.append(&mut accepted);
+                    }
+                    if accepted.is_empty() && rejected.is_empty() && !explicit_regions {
+                        continue;
+                    }
+                    cells.push(InputCell {
+                        layer: name.to_string(),
+                        offset: offset(cell),
+                        accepted,
+                        rejected,
+                        strict: explicit_regions,
+                        known: known.clone(),
+                    });
+                }
+                // Layers without any tile in the rule don't constrain it.
+                if cells
+                    .iter()
+                    .any(|c| !c.accepted.is_empty() || !c.rejected.is_empty())
+                {
+                    rule_inputs.extend(cells);
+                }
+            }
+            if rule_inputs.is_empty() {
+                continue;
+            }
+
+            let output_cells: Vec<(i32, i32)> = component
+                .iter()
+                .filter(|cell| output_region.contains(cell))
+                .copied()
+                .collect();
+            let mut sets: BTreeMap<&str, OutputSet> = BTreeMap::new();
+            for (name, index, tiles) in &outputs {
+                let set = sets.entry(index).or_insert_with(|| OutputSet {
+                    layers: Vec::new(),
+                    tiles: Vec::new(),
+                });
+                set.layers.push(name.to_string());
+                for &cell in &output_cells {
+                    if let Some(tile) = tiles.get(&cell) {
+                        set.tiles
+                            .push((name.to_string(), offset(cell), tile.clone()));
+                    }
+                }
+            }
+            rules.push(Rule {
+                inputs: rule_inputs,
+                outputs: sets
+                    .into_values()
+                    .filter(|set| !set.tiles.is_empty())
+                    .collect(),
+                output_cells: output_cells.into_iter().map(offset).collect(),
+                width: max_x - min_x + 1,
+                height: max_y - min_y + 1,
+            });
+        }
+
+        Ok(AutomappingRules {
+            rules,
+            delete_tiles: flag("DeleteTiles"),
+            match_outside_map: flag("MatchOutsideMap"),
+        })
+    }
+
+    /// The number of rules found in the rule map.
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Returns whether the rule map had no rules at all.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Applies the rules to the tile layers of `map`, one rule after the other in the order of
+    /// their regions in the rule map, top to bottom, then left to right. Each rule is matched
+    /// against the map as left by the previous rules. Returns how many times rules matched.
+    ///
+    /// Tile flip flags are ignored when matching and copied from the rule map when placing tiles.
+    /// Input layers missing from the map count as empty. The map is left untouched and an error
+    /// is returned if it lacks an output layer, or a tileset used by output tiles. The tiles set
+    /// are recorded if the map tracks changes; see [`Map::track_changes`].
+    pub fn apply(&self, map: &mut Map) -> Result<usize, TiledError> {
+        let tilesets: HashMap<String, (u32, u32)> = map
+            .tilesets
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(index, t)| (t.name.clone(), (index as u32, t.first_gid)))
+            .collect();
+        let mut layers: HashMap<String, usize> = HashMap::new();
+        for (index, layer) in map.layers.iter().enumerate().rev() {
+            layers.insert(layer.name.clone(), index);
+        }
+        for set in self.rules.iter().flat_map(|rule| &rule.outputs) {
+            for name in &set.layers {
+                if !layers.contains_key(name) {
+                    return Err(TiledError::Other(format!(
+                        "Automapping output layer {:?} is not in the map",
+                        name
+                    )));
+                }
+            }
+            for (_, _, tile) in &set.tiles {
+                if let RuleTile::Tile { tileset, .. } = tile {
+                    if !tilesets.contains_key(tileset) {
+                        return Err(TiledError::Other(format!(
+                            "Automapping output tileset {:?} is not in the map",
+                            tileset
+                        )));
+                    }
+                }
+            }
+        }
+
+        let translate = |tile: &RuleTile| match tile {
+            RuleTile::Tile {
+                tileset,
+                id,
+                flip_h,
+                flip_v,
+                flip_d,
+            } => tilesets.get(tileset).map(|&(index, first_gid)| LayerTile {
+                gid: first_gid + id.0,
+                flip_h: *flip_h,
+                flip_v: *flip_v,
+                flip_d: *flip_d,
+                resolved: Some((index, *id)),
+            }),
+            _ => None,
+        };
+
+        let mut applied = 0;
+        for rule in &self.rules {
+            let mut area: Option<TileRect> = None;
+            let names = rule.inputs.iter().map(|cell| &cell.layer).chain(
+                rule.outputs
+                    .iter()
+                    .flat_map(|set| set.tiles.iter().map(|(name, _, _)| name)),
+            );
+            for name in names {
+                if let Some(&index) = layers.get(name) {
+                    let extent = map.layers[index].extent();
+                    if extent.width > 0 && extent.height > 0 {
+                        area = Some(area.map_or(extent, |a| a.union(&extent)));
+                    }
+                }
+            }
+            let area = match area {
+                Some(area) => area,
+                None => continue,
+            };
+            let (xs, ys) = if self.match_outside_map {
+                (
+                    area.x - rule.width + 1..area.x + area.width as i32,
+                    area.y - rule.height + 1..area.y + area.height as i32,
+                )
+            } else {
+                (
+                    area.x..area.x + area.width as i32 - rule.width + 1,
+                    area.y..area.y + area.height as i32 - rule.height + 1,
+                )
+            };
+
+            let mut matches = Vec::new();
+            for y in ys {
+                for x in xs.clone() {
+                    let matched = rule.inputs.iter().all(|cell| {
+                        let target = layers
+                            .get(&cell.layer)
+                            .and_then(|&i| {
+                                map.layers[i].get_tile(x + cell.offset.0, y + cell.offset.1)
+                            })
+                            .map_or(0, |tile| tile.gid);
+                        cell.matches(target, &translate)
+                    });
+                    if matched {
+                        matches.push((x, y));
+                    }
+                }
+            }
+
+            for &(x, y) in &matches {
+                let set = match rule.outputs.len() {
+                    0 => continue,
+                    1 => &rule.outputs[0],
+                    n => &rule.outputs[pick(x, y, n)],
+                };
+                if self.delete_tiles {
+                    for name in &set.layers {
+                        for &(dx, dy) in &rule.output_cells {
+                            map.set_tile(layers[name], x + dx, y + dy, LayerTile::new(0));
+                        }
+                    }
+                }
+                for (name, (dx, dy), tile) in &set.tiles {
+                    let tile = match tile {
+                        RuleTile::Empty => LayerTile::new(0),
+                        tile => match translate(tile) {
+                            Some(tile) => tile,
+                            None => continue,
+                        },
+                    };
+                    map.set_tile(layers[name], x + dx, y + dy, tile);
+                }
+            }
+            applied += matches.len();
+        }
+        Ok(applied)
+    }
+}
+
+impl InputCell {
+    fn matches(&self, target: u32, translate: &impl Fn(&RuleTile) -> Option<LayerTile>) -> bool {
+        let is = |tile: &RuleTile| match tile {
+            RuleTile::Tile { .. } => translate(tile).is_some_and(|t| t.gid == target),
+            RuleTile::Empty => target == 0,
+            RuleTile::NonEmpty => target != 0,
+            RuleTile::Other => {
+                target != 0
+                    && !self
+                        .known
+                        .iter()
+                        .any(|known| translate(known).is_some_and(|t| t.gid == target))
+            }
+            RuleTile::Ignore | RuleTile::Negate => false,
+        };
+        let accepted = if self.accepted.contains(&RuleTile::Ignore) {
+            true
+        } else if !self.accepted.is_empty() {
+            self.accepted.iter().any(is)
+        } else {
+            !self.strict || !self.rejected.is_empty() || target == 0
+        };
+        accepted && !self.rejected.iter().any(is)
+    }
+}
+
+/// Reads the non-empty cells of a layer of a rule map.
+fn rule_tiles(rule_map: &Map, layer: &Layer) -> RuleCells {
+    rule_map
+        .resolve_layer(layer)
+        .into_iter()
+        .map(|resolved| {
+            let match_type = resolved
+                .tileset
+                .get_tile(resolved.id)
+                .and_then(|tile| tile.properties.get("matchType"));
+            let tile = match match_type {
+                Some(PropertyValue::StringValue(kind)) if kind == "Empty" => RuleTile::Empty,
+                Some(PropertyValue::StringValue(kind)) if kind == "Ignore" => RuleTile::Ignore,
+                Some(PropertyValue::StringValue(kind)) if kind == "NonEmpty" => RuleTile::NonEmpty,
+                Some(PropertyValue::StringValue(kind)) if kind == "Other" => RuleTile::Other,
+                Some(PropertyValue::StringValue(kind)) if kind == "Negate" => RuleTile::Negate,
+                _ => RuleTile::Tile {
+                    tileset: resolved.tileset.name.clone(),
+                    id: resolved.id,
+                    flip_h: resolved.tile.flip_h,
+                    flip_v: resolved.tile.flip_v,
+                    flip_d: resolved.tile.flip_d,
+                },
+            };
+            ((resolved.x, resolved.y), tile)
+        })
+        .collect()
+}
+
+/// Splits cells into groups of cells connected through their edges, ordered by their top-left
+/// cell, top to bottom, then left to right.
+fn components(mut cells: HashSet<(i32, i32)>) -> Vec<Vec<(i32, i32)>> {
+    let mut sorted: Vec<(i32, i32)> = cells.iter().copied().collect();
+    sorted.sort_by_key(|&(x, y)| (y, x));
+    let mut components = Vec::new();
+    for start in sorted {
+        if !cells.remove(&start) {
+            continue;
+        }
+        let mut component = vec![start];
+        let mut next = 0;
+        while next < component.len() {
+            let (x, y) = component[next];
+            next += 1;
+            for cell in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+                if cells.remove(&cell) {
+                    component.push(cell);
+                }
+            }
+        }
+        components.push(component);
+    }
+    components
+}
+
+/// Picks one of `n` output sets for the match at `(x, y)`. The pick only depends on the position,
+/// so applying the rules again gives the same result.
+fn pick(x: i32, y: i32, n: usize) -> usize {
+    let mut hash = (x as u32).wrapping_mul(0x9E37_79B9) ^ (y as u32).wrapping_mul(0x85EB_CA6B);
+    hash ^= hash >> 16;
+    hash = hash.wrapping_mul(0x7FEB_352D);
+    hash ^= hash >> 15;
+    hash as usize % n
+}
@@ -0,0 +1,188 @@
+//! Brings XML files into a character encoding the XML parser understands, for files saved with a
+//! byte order mark or in a Windows code page by other tools, and decompresses files that were
+//! gzipped as a whole.
+
+use std::{
+    borrow::Cow,
+    io::{BufReader, Chain, Cursor, ErrorKind, Read},
+    ops::Range,
+};
+
+use crate::error::TiledError;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The first bytes of gzip streams.
+const GZIP_MAGIC: &[u8] = b"\x1F\x8B";
+
+/// How far [`read_xml`] reads ahead looking for the end of the XML declaration.
+const HEAD_LIMIT: usize = 1024;
+
+/// Encodings read directly by the XML parser.
+const SUPPORTED: &[&str] = &[
+    "utf-8",
+    "utf8",
+    "ascii",
+    "us-ascii",
+    "iso-8859-1",
+    "latin1",
+    "utf-16",
+    "utf16",
+];
+
+const WINDOWS_1252: &[&str] = &["windows-1252", "cp1252", "x-cp1252"];
+
+/// The characters of the bytes 0x80 to 0x9F in windows-1252. All other bytes are the same as in
+/// ISO-8859-1. Unassigned bytes map to the control characters of the same value.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+#[cfg(feature = "gzip")]
+fn gunzip(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
+    crate::util::decode_gzip(data, 0)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gunzip(_: Vec<u8>) -> Result<Vec<u8>, TiledError> {
+    Err(TiledError::Other(
+        "Reading gzipped documents requires the gzip feature".to_string(),
+    ))
+}
+
+/// An XML document as given to the parser by [`read_xml`].
+pub(crate) enum XmlReader<R> {
+    /// The document is read as it is, after the bytes looked at to tell its encoding.
+    Stream(Chain<Cursor<Vec<u8>>, BufReader<R>>),
+    /// The document had to be decompressed or transcoded as a whole.
+    Decoded(Cursor<Vec<u8>>),
+}
+
+impl<R: Read> Read for XmlReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            XmlReader::Stream(reader) => reader.read(buf),
+            XmlReader::Decoded(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// Whether `head` is enough of a document to tell how to read it.
+fn head_complete(head: &[u8]) -> bool {
+    let text = head.strip_prefix(UTF8_BOM).unwrap_or(head);
+    head.starts_with(GZIP_MAGIC)
+        || (text.len() >= 5 && !text.starts_with(b"<?xml"))
+        || text.windows(2).any(|w| w == b"?>")
+}
+
+/// Prepares an XML document for the parser like [`decode_xml`]. Only the start of the document
+/// is read to find its encoding, and documents the parser can read as they are are streamed
+/// from `reader`. The others are read as a whole to be decompressed or transcoded.
+pub(crate) fn read_xml<R: Read>(reader: R) -> Result<XmlReader<R>, TiledError> {
+    let read_error =
+        |e: std::io::Error| TiledError::Other(format!("Could not read XML document: {}", e));
+    let mut reader = BufReader::new(reader);
+    let mut head = Vec::new();
+    let mut chunk = [0; 256];
+    while head.len() < HEAD_LIMIT && !head_complete(&head) {
+        match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => head.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(read_error(e)),
+        }
+    }
+    if !head.starts_with(GZIP_MAGIC) {
+        if let Cow::Borrowed(text) = decode_xml(Cow::Borrowed(&head))? {
+            let skipped = head.len() - text.len();
+            head.drain(..skipped);
+            return Ok(XmlReader::Stream(Cursor::new(head).chain(reader)));
+        }
+    }
+    reader.read_to_end(&mut head).map_err(read_error)?;
+    let data = decode_xml(Cow::Owned(head))?.into_owned();
+    Ok(XmlReader::Decoded(Cursor::new(data)))
+}
+
+/// Decompresses an XML document if it is gzipped, removes a UTF-8 byte order mark and
+/// transcodes windows-1252 documents to UTF-8. Fails with [`TiledError::UnsupportedEncoding`]
+/// if the document declares any other encoding the parser can't read. Documents the parser can
+/// read as they are, which is most of them, are returned without copying.
+pub(crate) fn decode_xml(data: Cow<'_, [u8]>) -> Result<Cow<'_, [u8]>, TiledError> {
+    let data = if data.starts_with(GZIP_MAGIC) {
+        Cow::Owned(gunzip(data.into_owned())?)
+    } else {
+        data
+    };
+    let has_bom = data.starts_with(UTF8_BOM);
+    let data = match data {
+        Cow::Borrowed(data) if has_bom => Cow::Borrowed(&data[UTF8_BOM.len()..]),
+        Cow::Owned(mut data) if has_bom => {
+            data.drain(..UTF8_BOM.len());
+            Cow::Owned(data)
+        }
+        data => data,
+    };
+
+    let (range, encoding) = match declared_encoding(&data) {
+        Some(declared) => declared,
+        None => return Ok(data),
+    };
+    let name = encoding.to_ascii_lowercase();
+    if has_bom {
+        // The byte order mark takes precedence over the declaration.
+        return Ok(if name == "utf-8" || name == "utf8" {
+            data
+        } else {
+            let rest = data[range.end..].to_vec();
+            Cow::Owned(with_utf8_declaration(&data, range, rest))
+        });
+    }
+    if SUPPORTED.contains(&name.as_str()) {
+        Ok(data)
+    } else if WINDOWS_1252.contains(&name.as_str()) {
+        let text: String = data[range.end..]
+            .iter()
+            .map(|&b| match b {
+                0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+                _ => b as char,
+            })
+            .collect();
+        Ok(Cow::Owned(with_utf8_declaration(
+            &data,
+            range,
+            text.into_bytes(),
+        )))
+    } else {
+        Err(TiledError::UnsupportedEncoding(encoding))
+    }
+}
+
+/// Finds the value of the `encoding` attribute of the XML declaration, if there is one.
+fn declared_encoding(data: &[u8]) -> Option<(Range<usize>, String)> {
+    if !data.starts_with(b"<?xml") {
+        return None;
+    }
+    let end = data.windows(2).position(|w| w == b"?>")?;
+    let declaration = &data[..end];
+    let attr = declaration.windows(8).position(|w| w == b"encoding")? + 8;
+    let mut pos = attr;
+    while pos < end && (declaration[pos] == b'=' || declaration[pos].is_ascii_whitespace()) {
+        pos += 1;
+    }
+    let quote = *declaration.get(pos).filter(|&&q| q == b'"' || q == b'\'')?;
+    let start = pos + 1;
+    let len = declaration[start..].iter().position(|&c| c == quote)?;
+    let value = std::str::from_utf8(&declaration[start..start + len]).ok()?;
+    Some((start..start + len, value.to_string()))
+}
+
+fn with_utf8_declaration(data: &[u8], encoding: Range<usize>, rest: Vec<u8>) -> Vec<u8> {
+    let mut result = data[..encoding.start].to_vec();
+    result.extend_from_slice(b"UTF-8");
+    result.extend(rest);
+    result
+}
@@ -0,0 +1,199 @@
+//! Writing maps for older versions of the Tiled editor, or engine importers that only support
+//! what those versions did, by leaving out or converting what they don't know about.
+
+use std::{fmt, str::FromStr, sync::Arc};
+
+use crate::{
+    error::TiledError,
+    layers::{LayerData, LayerTile, TileRect},
+    map::Map,
+    stitching::tile_offset_to_pixels,
+};
+
+/// A version of the Tiled editor, as `major.minor`.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub struct TiledVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl TiledVersion {
+    pub const fn new(major: u32, minor: u32) -> TiledVersion {
+        TiledVersion { major, minor }
+    }
+}
+
+impl FromStr for TiledVersion {
+    type Err = TiledError;
+
+    /// Parses versions like `1.4` or `1.8.2`, ignoring the patch number.
+    fn from_str(s: &str) -> Result<TiledVersion, TiledError> {
+        let mut parts = s.trim().split('.').map(|p| p.parse::<u32>().ok());
+        match (parts.next(), parts.next()) {
+            (Some(Some(major)), Some(Some(minor))) => Ok(TiledVersion::new(major, minor)),
+            _ => Err(TiledError::Other(format!("Invalid Tiled version '{}'", s))),
+        }
+    }
+}
+
+impl fmt::Display for TiledVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Something [`Map::downgrade`] had to leave out or convert for an older version of Tiled.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DowngradeWarning {
+    /// The parallax origin of the map, added in Tiled 1.8, was reset.
+    ParallaxOrigin,
+    /// The parallax factor of a layer or group layer, added in Tiled 1.5, was reset.
+    Parallax { layer_id: u32 },
+    /// The wang sets of a tileset, whose format changed in Tiled 1.5, were left out.
+    WangSets { tileset: String },
+    /// The tint colour of a layer or group layer, added in Tiled 1.4, was removed.
+    TintColour { layer_id: u32 },
+    /// The class of a layer or group layer, added in Tiled 1.9, was removed.
+    LayerClass { layer_id: u32 },
+    /// The infinite map, supported since Tiled 1.1, was made finite, covering the tiles it
+    /// uses. Its contents moved by the given number of tiles.
+    Infinite { moved_x: i32, moved_y: i32 },
+}
+
+impl fmt::Display for DowngradeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DowngradeWarning::ParallaxOrigin => write!(f, "the parallax origin was reset"),
+            DowngradeWarning::Parallax { layer_id } => {
+                write!(f, "the parallax factor of layer {} was reset", layer_id)
+            }
+            DowngradeWarning::WangSets { tileset } => {
+                write!(f, "the wang sets of tileset \"{}\" were left out", tileset)
+            }
+            DowngradeWarning::TintColour { layer_id } => {
+                write!(f, "the tint colour of layer {} was removed", layer_id)
+            }
+            DowngradeWarning::LayerClass { layer_id } => {
+                write!(f, "the class of layer {} was removed", layer_id)
+            }
+            DowngradeWarning::Infinite { moved_x, moved_y } => write!(
+                f,
+                "the map was made finite, moving its contents by ({}, {}) tiles",
+                moved_x, moved_y
+            ),
+        }
+    }
+}
+
+impl Map {
+    /// Returns a copy of the map without what versions of Tiled older than `target` don't
+    /// support, along with what was lost. Its [`version`](Map::version) is set to `target`.
+    /// Features are dropped as follows:
+    ///
+    /// - Before 1.9, the classes of layers.
+    /// - Before 1.8, the parallax origin.
+    /// - Before 1.5, parallax factors, and wang sets, whose older format isn't supported.
+    /// - Before 1.4, tint colours.
+    /// - Before 1.1, infinite maps, which become finite maps covering their
+    ///   [used bounds](Map::used_bounds).
+    ///
+    /// Targets older than 1.1 are otherwise treated like 1.1.
+    pub fn downgrade(&self, target: TiledVersion) -> (Map, Vec<DowngradeWarning>) {
+        let mut map = self.clone();
+        let mut warnings = Vec::new();
+        map.version = target.to_string();
+        if target < TiledVersion::new(1, 8)
+            && (map.parallax_origin_x, map.parallax_origin_y) != (0.0, 0.0)
+        {
+            map.parallax_origin_x = 0.0;
+            map.parallax_origin_y = 0.0;
+            warnings.push(DowngradeWarning::ParallaxOrigin);
+        }
+        macro_rules! downgrade_layers {
+            ($($layers:expr),*) => {
+                $(for layer in $layers.iter_mut() {
+                    if target < TiledVersion::new(1, 9) && layer.user_type.take().is_some() {
+                        warnings.push(DowngradeWarning::LayerClass { layer_id: layer.id });
+                    }
+                    if target < TiledVersion::new(1, 5)
+                        && (layer.parallax_x, layer.parallax_y) != (1.0, 1.0)
+                    {
+                        layer.parallax_x = 1.0;
+                        layer.parallax_y = 1.0;
+                        warnings.push(DowngradeWarning::Parallax { layer_id: layer.id });
+                    }
+                    if target < TiledVersion::new(1, 4) && layer.tint_colour.take().is_some() {
+                        warnings.push(DowngradeWarning::TintColour { layer_id: layer.id });
+                    }
+                })*
+            };
+        }
+        downgrade_layers!(map.layers, map.image_layers, map.object_groups, map.groups);
+        if target < TiledVersion::new(1, 5) {
+            for tileset in map.tilesets.iter_mut() {
+                if !tileset.wang_sets.is_empty() {
+                    let name = tileset.name.clone();
+                    Arc::make_mut(&mut tileset.tileset).wang_sets.clear();
+                    warnings.push(DowngradeWarning::WangSets { tileset: name });
+                }
+            }
+        }
+        if target < TiledVersion::new(1, 1) && map.infinite {
+            let rect = map.used_bounds().unwrap_or(TileRect {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            });
+            make_finite(&mut map, rect);
+            warnings.push(DowngradeWarning::Infinite {
+                moved_x: -rect.x,
+                moved_y: -rect.y,
+            });
+        }
+        (map, warnings)
+    }
+
+    /// Writes the map in the Tiled JSON format like [`Map::write_json`], for the version
+    /// `target` of Tiled. See [`Map::downgrade`] for what is left out; it is returned.
+    #[cfg(feature = "json")]
+    pub fn write_json_for<W: std::io::Write>(
+        &self,
+        writer: W,
+        target: TiledVersion,
+    ) -> std::io::Result<Vec<DowngradeWarning>> {
+        let (map, warnings) = self.downgrade(target);
+        map.write_json(writer)?;
+        Ok(warnings)
+    }
+}
+
+/// Turns an infinite map into a finite one holding the tiles of `rect`, with its top-left
+/// corner at (0, 0).
+fn make_finite(map: &mut Map, rect: TileRect) {
+    for layer in map.layers.iter_mut() {
+        let rows = (0..rect.height as i32)
+            .map(|y| {
+                (0..rect.width as i32)
+                    .map(|x| {
+                        layer
+                            .get_tile(rect.x + x, rect.y + y)
+                            .unwrap_or_else(|| LayerTile::new(0))
+                    })
+                    .collect()
+            })
+            .collect();
+        layer.tiles = LayerData::Finite(rows);
+    }
+    let (dx, dy) = tile_offset_to_pixels(map, (-rect.x, -rect.y));
+    for group in map.object_groups.iter_mut() {
+        for object in group.objects.iter_mut() {
+            object.x += dx;
+            object.y += dy;
+        }
+    }
+    map.width = rect.width;
+    map.height = rect.height;
+    map.infinite = false;
+    map.update_gid_table();
+}
@@ -0,0 +1,184 @@
+//! Deserialization of properties into user types with serde, enabled by the `serde` feature.
+
+use std::{collections::hash_map, fmt};
+
+use serde::{
+    de::{self, value::BorrowedStrDeserializer, DeserializeSeed, MapAccess, Visitor},
+    forward_to_deserialize_any, Deserialize, Deserializer,
+};
+
+use crate::properties::{Properties, PropertyError, PropertyErrorKind, PropertyValue};
+
+/// Deserializes `properties` into a `T`, such as a struct with a field for each property. Like
+/// with [`FromProperties`](crate::properties::FromProperties), errors name the property at
+/// fault.
+///
+/// Values are read as their type in the editor, except that colors are read as a `u32` packed as
+/// `0xAARRGGBB`, objects as their id and properties of unknown types as the text of their value.
+/// String properties can be read into enums with unit variants.
+///
+/// ```
+/// use serde::Deserialize;
+/// use tiled::properties::{from_properties, Properties, PropertyValue};
+///
+/// #[derive(Deserialize)]
+/// struct Enemy {
+///     speed: f32,
+///     boss: Option<bool>,
+/// }
+///
+/// let mut properties = Properties::new();
+/// properties.insert("speed".to_string(), PropertyValue::IntValue(3));
+/// let enemy: Enemy = from_properties(&properties).unwrap();
+/// assert_eq!((enemy.speed, enemy.boss), (3.0, None));
+///
+/// let error = from_properties::<Enemy>(&Properties::new()).err().unwrap();
+/// assert_eq!(error.to_string(), "property 'speed' is missing");
+/// ```
+pub fn from_properties<'de, T: Deserialize<'de>>(
+    properties: &'de Properties,
+) -> Result<T, PropertyError> {
+    T::deserialize(PropertiesDeserializer::new(properties))
+}
+
+/// A serde `Deserializer` reading properties as a map from their names to their values. See
+/// [`from_properties`].
+#[derive(Debug, Clone, Copy)]
+pub struct PropertiesDeserializer<'de> {
+    properties: &'de Properties,
+}
+
+impl<'de> PropertiesDeserializer<'de> {
+    pub fn new(properties: &'de Properties) -> PropertiesDeserializer<'de> {
+        PropertiesDeserializer { properties }
+    }
+}
+
+impl de::Error for PropertyError {
+    fn custom<T: fmt::Display>(msg: T) -> PropertyError {
+        // The name is filled in once the error reaches the property it comes from.
+        PropertyError {
+            name: String::new(),
+            kind: PropertyErrorKind::Invalid(msg.to_string()),
+        }
+    }
+
+    fn missing_field(field: &'static str) -> PropertyError {
+        PropertyError {
+            name: field.to_string(),
+            kind: PropertyErrorKind::Missing,
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for PropertiesDeserializer<'de> {
+    type Error = PropertyError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PropertyError> {
+        visitor.visit_map(PropertiesAccess {
+            entries: self.properties.iter(),
+            value: None,
+        })
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map struct enum identifier
+        ignored_any
+    }
+}
+
+/// The properties, as the entries of a map.
+struct PropertiesAccess<'de> {
+    entries: hash_map::Iter<'de, String, PropertyValue>,
+    /// The property whose name was just read.
+    value: Option<(&'de str, &'de PropertyValue)>,
+}
+
+impl<'de> MapAccess<'de> for PropertiesAccess<'de> {
+    type Error = PropertyError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, PropertyError> {
+        match self.entries.next() {
+            Some((name, value)) => {
+                self.value = Some((name, value));
+                seed.deserialize(BorrowedStrDeserializer::new(name))
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, PropertyError> {
+        let (name, value) = self
+            .value
+            .take()
+            .expect("the value of a property is read after its name");
+        seed.deserialize(ValueDeserializer(value))
+            .map_err(|mut error| {
+                if error.name.is_empty() {
+                    error.name = name.to_string();
+                }
+                error
+            })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+/// The value of a single property.
+struct ValueDeserializer<'de>(&'de PropertyValue);
+
+impl<'de> Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = PropertyError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PropertyError> {
+        match self.0 {
+            PropertyValue::BoolValue(v) => visitor.visit_bool(*v),
+            PropertyValue::FloatValue(v) => visitor.visit_f64(v.value),
+            PropertyValue::IntValue(v) => visitor.visit_i32(*v),
+            PropertyValue::ColorValue(v) | PropertyValue::ObjectValue(v) => visitor.visit_u32(*v),
+            PropertyValue::StringValue(v) | PropertyValue::FileValue(v) => {
+                visitor.visit_borrowed_str(v)
+            }
+            PropertyValue::Unknown { raw_value, .. } => visitor.visit_borrowed_str(raw_value),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, PropertyError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, PropertyError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, PropertyError> {
+        match self.0 {
+            PropertyValue::StringValue(v) => visitor.visit_enum(BorrowedStrDeserializer::new(v)),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
@@ -0,0 +1,117 @@
+//! Plain copies of objects, made only of standard types, for handing object data to scripting
+//! layers or storing it without depending on the types of this crate. Every field is public and
+//! ownership is simple, so the types are easy to serialize with any library.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    objects::{Object, ObjectGroup, ObjectShape},
+    properties::{Properties, PropertyValue},
+};
+
+/// An object as plain data, see [`ObjectGroup::to_descriptors`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct ObjectDescriptor {
+    pub id: u32,
+    pub name: String,
+    /// The class of the object, empty if it has none.
+    pub class: String,
+    /// The position of the object, in pixels.
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    /// The rotation of the object, in degrees clockwise.
+    pub rotation: f32,
+    pub visible: bool,
+    /// The gid of tile objects, with the flip flags in the upper bits, or 0.
+    pub gid: u32,
+    pub shape: ShapeDescriptor,
+    /// The properties of the object, sorted by name.
+    pub properties: BTreeMap<String, PlainValue>,
+}
+
+/// The shape of an [`ObjectDescriptor`]. Rectangles and ellipses take the size of the object.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ShapeDescriptor {
+    Rectangle,
+    Ellipse,
+    Point,
+    /// The points of the polygon, relative to the position of the object.
+    Polygon(Vec<(f32, f32)>),
+    /// The points of the polyline, relative to the position of the object.
+    Polyline(Vec<(f32, f32)>),
+    /// A text object, holding its text.
+    Text(String),
+}
+
+/// The value of a property as plain data. Colours become `#AARRGGBB` strings, files their path
+/// and object references the id of the object, or 0.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PlainValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+}
+
+impl From<&PropertyValue> for PlainValue {
+    fn from(value: &PropertyValue) -> Self {
+        match value {
+            PropertyValue::BoolValue(b) => PlainValue::Bool(*b),
+            PropertyValue::FloatValue(f) => PlainValue::Float(f.value),
+            PropertyValue::IntValue(i) => PlainValue::Int(i64::from(*i)),
+            PropertyValue::ObjectValue(id) => PlainValue::Int(i64::from(*id)),
+            PropertyValue::ColorValue(c) => PlainValue::String(format!("#{:08x}", c)),
+            PropertyValue::StringValue(s) | PropertyValue::FileValue(s) => {
+                PlainValue::String(s.clone())
+            }
+            PropertyValue::Unknown { raw_value, .. } => PlainValue::String(raw_value.clone()),
+        }
+    }
+}
+
+/// Flattens properties into plain values, sorted by name.
+pub fn plain_properties(properties: &Properties) -> BTreeMap<String, PlainValue> {
+    properties
+        .iter()
+        .map(|(name, value)| (name.clone(), value.into()))
+        .collect()
+}
+
+impl From<&Object> for ObjectDescriptor {
+    fn from(object: &Object) -> Self {
+        let shape = match &object.shape {
+            ObjectShape::Rect { .. } => ShapeDescriptor::Rectangle,
+            ObjectShape::Ellipse { .. } => ShapeDescriptor::Ellipse,
+            ObjectShape::Point(..) => ShapeDescriptor::Point,
+            ObjectShape::Polygon { points } => ShapeDescriptor::Polygon(points.clone()),
+            ObjectShape::Polyline { points } => ShapeDescriptor::Polyline(points.clone()),
+            ObjectShape::Text { text, .. } => ShapeDescriptor::Text(text.contents.clone()),
+        };
+        ObjectDescriptor {
+            id: object.id,
+            name: object.name.clone(),
+            class: object.obj_type.clone(),
+            x: object.x,
+            y: object.y,
+            width: object.width,
+            height: object.height,
+            rotation: object.rotation,
+            visible: object.visible,
+            gid: object.gid,
+            shape,
+            properties: plain_properties(&object.properties),
+        }
+    }
+}
+
+impl ObjectGroup {
+    /// Copies the objects of the layer into plain [`ObjectDescriptor`]s, in the order of
+    /// [`ObjectGroup::objects`]. Only the properties of the objects themselves are included;
+    /// use [`plain_properties`] on [`Object::resolved_properties`] to include those of their
+    /// tile too.
+    pub fn to_descriptors(&self) -> Vec<ObjectDescriptor> {
+        self.objects.iter().map(ObjectDescriptor::from).collect()
+    }
+}
@@ -1,7 +1,10 @@
 use std::fmt;
 
+use crate::validation::InvalidGid;
+
 #[derive(Debug, Copy, Clone)]
 pub enum ParseTileError {
+    #[deprecated(note = "invalid colours are reported as `TiledError::InvalidColour`")]
     ColourError,
     OrientationError,
 }
@@ -17,7 +20,28 @@ pub enum TiledError {
     DecompressingError(std::io::Error),
     Base64DecodingError(base64::DecodeError),
     XmlDecodingError(xml::reader::Error),
+    /// An error occured when writing an XML-based file, such as a template.
+    XmlEncodingError(xml::writer::Error),
     PrematureEnd(String),
+    /// Layers or objects use gids that don't belong to any tileset. Only reported when enabled
+    /// with [`Loader::with_gid_validation`](crate::loader::Loader::with_gid_validation).
+    InvalidGids(Vec<InvalidGid>),
+    /// The gids of two tilesets overlap, holding their indices in the tilesets of the map. Only
+    /// reported when enabled with
+    /// [`Loader::with_gid_validation`](crate::loader::Loader::with_gid_validation).
+    OverlappingTilesets { first: usize, second: usize },
+    /// A colour attribute or property that isn't a valid colour, holding the offending string.
+    InvalidColour(String),
+    /// The file declares a character encoding that can't be read, holding its name.
+    UnsupportedEncoding(String),
+    /// A map or chunk has more cells than allowed by
+    /// [`Loader::with_cell_limit`](crate::loader::Loader::with_cell_limit).
+    TooManyCells { width: u32, height: u32, limit: u64 },
+    /// A size that must not be 0, such as the tile size of a map, is 0. Holds what the size is of.
+    ZeroSize(String),
+    /// Parsing was abandoned because the
+    /// [`CancellationToken`](crate::loader::CancellationToken) of the loader was cancelled.
+    Cancelled,
     Other(String),
 }
 
@@ -28,7 +52,30 @@ impl fmt::Display for TiledError {
             TiledError::DecompressingError(ref e) => write!(fmt, "{}", e),
             TiledError::Base64DecodingError(ref e) => write!(fmt, "{}", e),
             TiledError::XmlDecodingError(ref e) => write!(fmt, "{}", e),
+            TiledError::XmlEncodingError(ref e) => write!(fmt, "{}", e),
             TiledError::PrematureEnd(ref e) => write!(fmt, "{}", e),
+            TiledError::InvalidGids(ref gids) => {
+                let gids: Vec<String> = gids.iter().map(|g| g.to_string()).collect();
+                write!(fmt, "{}", gids.join(", "))
+            }
+            TiledError::OverlappingTilesets { first, second } => {
+                write!(fmt, "the gids of tilesets {} and {} overlap", first, second)
+            }
+            TiledError::InvalidColour(ref s) => write!(fmt, "Invalid colour '{}'", s),
+            TiledError::UnsupportedEncoding(ref s) => {
+                write!(fmt, "Unsupported character encoding '{}'", s)
+            }
+            TiledError::TooManyCells {
+                width,
+                height,
+                limit,
+            } => write!(
+                fmt,
+                "{}x{} cells exceed the limit of {} cells",
+                width, height, limit
+            ),
+            TiledError::ZeroSize(ref s) => write!(fmt, "The {} must not be 0", s),
+            TiledError::Cancelled => write!(fmt, "Parsing was cancelled"),
             TiledError::Other(ref s) => write!(fmt, "{}", s),
         }
     }
@@ -42,7 +89,15 @@ impl std::error::Error for TiledError {
             TiledError::DecompressingError(ref e) => Some(e as &dyn std::error::Error),
             TiledError::Base64DecodingError(ref e) => Some(e as &dyn std::error::Error),
             TiledError::XmlDecodingError(ref e) => Some(e as &dyn std::error::Error),
+            TiledError::XmlEncodingError(ref e) => Some(e as &dyn std::error::Error),
             TiledError::PrematureEnd(_) => None,
+            TiledError::InvalidGids(_) => None,
+            TiledError::OverlappingTilesets { .. } => None,
+            TiledError::InvalidColour(_) => None,
+            TiledError::UnsupportedEncoding(_) => None,
+            TiledError::TooManyCells { .. } => None,
+            TiledError::ZeroSize(_) => None,
+            TiledError::Cancelled => None,
             TiledError::Other(_) => None,
         }
     }
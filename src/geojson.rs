@@ -0,0 +1,110 @@
+//! Writing object layers as GeoJSON, for inspecting them with GIS tools.
+
+use crate::{
+    descriptor::{plain_properties, PlainValue},
+    json::{JsonObject, JsonValue},
+    objects::{Object, ObjectGroup, ObjectShape},
+};
+
+fn plain_to_json(value: &PlainValue) -> JsonValue {
+    match value {
+        PlainValue::Bool(b) => JsonValue::Bool(*b),
+        PlainValue::Int(i) => JsonValue::Number(i.to_string()),
+        PlainValue::Float(f) if f.is_finite() => JsonValue::Number(f.to_string()),
+        PlainValue::Float(_) => JsonValue::Null,
+        PlainValue::String(s) => JsonValue::String(s.clone()),
+    }
+}
+
+/// The number of sides of the polygons standing in for ellipses in GeoJSON.
+const ELLIPSE_SIDES: usize = 32;
+
+/// Returns the GeoJSON geometry of an object, with its rotation applied around its position.
+fn object_geometry(object: &Object) -> JsonValue {
+    let (sin, cos) = object.rotation.to_radians().sin_cos();
+    let position = |&(x, y): &(f32, f32)| -> JsonValue {
+        vec![
+            JsonValue::from(object.x + x * cos - y * sin),
+            JsonValue::from(object.y + x * sin + y * cos),
+        ]
+        .into()
+    };
+    // Rings of polygons end with their first point.
+    let ring = |points: &[(f32, f32)]| -> JsonValue {
+        let ring: Vec<JsonValue> = points.iter().chain(points.first()).map(position).collect();
+        vec![JsonValue::from(ring)].into()
+    };
+    let rectangle = |top: f32, width: f32, height: f32| {
+        ring(&[
+            (0.0, top),
+            (width, top),
+            (width, top + height),
+            (0.0, top + height),
+        ])
+    };
+    let (kind, coordinates) = match &object.shape {
+        ObjectShape::Point(..) => ("Point", position(&(0.0, 0.0))),
+        ObjectShape::Polyline { points } => (
+            "LineString",
+            points.iter().map(position).collect::<Vec<_>>().into(),
+        ),
+        ObjectShape::Polygon { points } => ("Polygon", ring(points)),
+        ObjectShape::Ellipse { width, height } => {
+            let (rx, ry) = (width / 2.0, height / 2.0);
+            let points: Vec<(f32, f32)> = (0..ELLIPSE_SIDES)
+                .map(|i| {
+                    let angle = i as f32 / ELLIPSE_SIDES as f32 * std::f32::consts::TAU;
+                    (rx + rx * angle.cos(), ry + ry * angle.sin())
+                })
+                .collect();
+            ("Polygon", ring(&points))
+        }
+        // Tile objects are anchored at their bottom-left corner.
+        _ if object.gid != 0 => (
+            "Polygon",
+            rectangle(-object.height, object.width, object.height),
+        ),
+        ObjectShape::Rect { width, height } | ObjectShape::Text { width, height, .. } => {
+            ("Polygon", rectangle(0.0, *width, *height))
+        }
+    };
+    JsonObject::new()
+        .field("type", kind)
+        .field("coordinates", coordinates)
+        .into()
+}
+
+fn object_to_geojson(object: &Object) -> JsonValue {
+    let mut properties = vec![
+        ("name".to_string(), JsonValue::from(object.name.as_str())),
+        (
+            "class".to_string(),
+            JsonValue::from(object.obj_type.as_str()),
+        ),
+    ];
+    for (name, value) in plain_properties(&object.properties) {
+        properties.retain(|(n, _)| *n != name);
+        properties.push((name, plain_to_json(&value)));
+    }
+    JsonObject::new()
+        .field("type", "Feature")
+        .field("id", object.id)
+        .field("geometry", object_geometry(object))
+        .field("properties", JsonValue::Object(properties))
+        .into()
+}
+
+pub(crate) fn object_group_to_geojson(group: &ObjectGroup) -> JsonValue {
+    JsonObject::new()
+        .field("type", "FeatureCollection")
+        .field("name", group.name.as_str())
+        .field(
+            "features",
+            group
+                .objects
+                .iter()
+                .map(object_to_geojson)
+                .collect::<Vec<JsonValue>>(),
+        )
+        .into()
+}
@@ -0,0 +1,387 @@
+//! Hashes of parsed content that stay the same between runs and platforms.
+//!
+//! The content is fed to the hash field by field, leaving out what only depends on how it was
+//! written to its file, such as the encoding of tile data. Numbers are written in little-endian
+//! and strings and lists with their length, so that the hash doesn't depend on the platform and
+//! adjacent fields can't be mistaken for each other.
+
+use crate::{
+    image::Image,
+    layers::{GroupLayer, ImageLayer, Layer, LayerData, LayerTile},
+    map::Map,
+    objects::{Object, ObjectGroup, ObjectShape},
+    properties::{Colour, Properties, PropertyValue},
+    tileset::Tileset,
+};
+
+/// Writes the attributes shared by all kinds of layers.
+macro_rules! layer_attributes {
+    ($h:expr, $layer:expr) => {{
+        let layer = $layer;
+        $h.u32(layer.id);
+        $h.str(&layer.name);
+        $h.option(layer.user_type.as_deref(), Fnv1a::str);
+        for value in [
+            layer.offset_x,
+            layer.offset_y,
+            layer.parallax_x,
+            layer.parallax_y,
+            layer.opacity,
+        ] {
+            $h.f32(value);
+        }
+        $h.bool(layer.visible);
+        $h.colour(layer.tint_colour.as_ref());
+        $h.properties(&layer.properties);
+    }};
+}
+
+/// The 64-bit FNV-1a hash.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn bool(&mut self, value: bool) {
+        self.bytes(&[value as u8]);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn i32(&mut self, value: i32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    fn f32(&mut self, value: f32) {
+        self.u32(value.to_bits());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.len(value.len());
+        self.bytes(value.as_bytes());
+    }
+
+    fn len(&mut self, len: usize) {
+        self.bytes(&(len as u64).to_le_bytes());
+    }
+
+    /// Writes whether there is a value, followed by the value if there is one.
+    fn option<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        self.bool(value.is_some());
+        if let Some(value) = value {
+            write(self, value);
+        }
+    }
+
+    fn colour(&mut self, colour: Option<&Colour>) {
+        self.option(colour, |h, c| h.bytes(&[c.red, c.green, c.blue, c.alpha]));
+    }
+
+    /// Writes the properties ordered by name, since their order in the file doesn't matter.
+    fn properties(&mut self, properties: &Properties) {
+        let mut names: Vec<&String> = properties.keys().collect();
+        names.sort();
+        self.len(names.len());
+        for name in names {
+            self.str(name);
+            match &properties[name] {
+                PropertyValue::BoolValue(v) => {
+                    self.str("bool");
+                    self.bool(*v);
+                }
+                PropertyValue::FloatValue(v) => {
+                    self.str("float");
+                    self.bytes(&v.value.to_bits().to_le_bytes());
+                }
+                PropertyValue::IntValue(v) => {
+                    self.str("int");
+                    self.i32(*v);
+                }
+                PropertyValue::ColorValue(v) => {
+                    self.str("color");
+                    self.u32(*v);
+                }
+                PropertyValue::StringValue(v) => {
+                    self.str("string");
+                    self.str(v);
+                }
+                PropertyValue::FileValue(v) => {
+                    self.str("file");
+                    self.str(v);
+                }
+                PropertyValue::ObjectValue(v) => {
+                    self.str("object");
+                    self.u32(*v);
+                }
+                PropertyValue::Unknown {
+                    type_name,
+                    raw_value,
+                } => {
+                    self.str(type_name);
+                    self.str(raw_value);
+                }
+            }
+        }
+    }
+
+    /// Writes the position of a layer in the map, which is the same for all kinds of layers.
+    fn layer_position(&mut self, layer_index: u32, parent_group: Option<usize>) {
+        self.u32(layer_index);
+        self.option(parent_group, |h, group| h.len(group));
+    }
+
+    fn tile_rows(&mut self, rows: &[Vec<LayerTile>]) {
+        self.len(rows.len());
+        for row in rows {
+            self.len(row.len());
+            for tile in row {
+                self.u32(tile.raw_gid());
+            }
+        }
+    }
+
+    fn layer(&mut self, layer: &Layer) {
+        layer_attributes!(self, layer);
+        match &layer.tiles {
+            LayerData::Finite(rows) => {
+                self.str("finite");
+                self.tile_rows(rows);
+            }
+            LayerData::Infinite(chunks) => {
+                self.str("infinite");
+                let mut chunks: Vec<_> = chunks.values().collect();
+                chunks.sort_by_key(|c| (c.y, c.x));
+                self.len(chunks.len());
+                for chunk in chunks {
+                    self.i32(chunk.x);
+                    self.i32(chunk.y);
+                    self.u32(chunk.width);
+                    self.u32(chunk.height);
+                    self.tile_rows(&chunk.tiles);
+                }
+            }
+        }
+    }
+
+    fn image(&mut self, image: &Image) {
+        self.str(&image.source);
+        self.option(image.width, Fnv1a::i32);
+        self.option(image.height, Fnv1a::i32);
+        self.colour(image.transparent_colour.as_ref());
+    }
+
+    fn image_layer(&mut self, layer: &ImageLayer) {
+        layer_attributes!(self, layer);
+        self.option(layer.image.as_ref(), Fnv1a::image);
+    }
+
+    fn points(&mut self, points: &[(f32, f32)]) {
+        self.len(points.len());
+        for &(x, y) in points {
+            self.f32(x);
+            self.f32(y);
+        }
+    }
+
+    fn object(&mut self, object: &Object) {
+        self.u32(object.id);
+        self.u32(object.gid);
+        self.str(&object.name);
+        self.str(&object.obj_type);
+        for value in [
+            object.x,
+            object.y,
+            object.width,
+            object.height,
+            object.rotation,
+        ] {
+            self.f32(value);
+        }
+        self.bool(object.visible);
+        match &object.shape {
+            ObjectShape::Rect { .. } => self.str("rect"),
+            ObjectShape::Ellipse { .. } => self.str("ellipse"),
+            ObjectShape::Polyline { points } => {
+                self.str("polyline");
+                self.points(points);
+            }
+            ObjectShape::Polygon { points } => {
+                self.str("polygon");
+                self.points(points);
+            }
+            ObjectShape::Point(..) => self.str("point"),
+            ObjectShape::Text { text, .. } => {
+                let style = &text.style;
+                self.str("text");
+                self.str(&text.contents);
+                self.str(&style.font_family);
+                self.u32(style.pixel_size);
+                self.colour(Some(&style.color));
+                for flag in [
+                    style.wrap,
+                    style.bold,
+                    style.italic,
+                    style.underline,
+                    style.strikeout,
+                    style.kerning,
+                ] {
+                    self.bool(flag);
+                }
+                self.str(&style.halign.to_string());
+                self.str(&style.valign.to_string());
+            }
+        }
+        self.properties(&object.properties);
+    }
+
+    fn object_group(&mut self, group: &ObjectGroup) {
+        layer_attributes!(self, group);
+        self.colour(group.colour.as_ref());
+        self.str(&group.draw_order.to_string());
+        self.len(group.objects.len());
+        for object in &group.objects {
+            self.object(object);
+        }
+    }
+
+    fn group(&mut self, group: &GroupLayer) {
+        layer_attributes!(self, group);
+    }
+
+    fn tileset(&mut self, tileset: &Tileset) {
+        self.str(&tileset.name);
+        self.u32(tileset.tile_width);
+        self.u32(tileset.tile_height);
+        self.u32(tileset.spacing);
+        self.u32(tileset.margin);
+        self.u32(tileset.columns());
+        self.u32(tileset.tilecount.unwrap_or(tileset.tiles.len() as u32));
+        self.option(tileset.object_alignment.as_ref(), |h, a| {
+            h.str(&a.to_string())
+        });
+        self.option(tileset.images.first(), Fnv1a::image);
+        self.properties(&tileset.properties);
+        self.len(tileset.tiles.len());
+        for tile in &tileset.tiles {
+            self.u32(tile.id.0);
+            self.option(tile.images.first(), Fnv1a::image);
+            self.option(tile.objectgroup.as_ref(), Fnv1a::object_group);
+            self.option(tile.animation.as_ref(), |h, frames| {
+                h.len(frames.len());
+                for frame in frames {
+                    h.u32(frame.tile_id.0);
+                    h.u32(frame.duration);
+                }
+            });
+            self.option(tile.tile_type.as_deref(), Fnv1a::str);
+            self.f32(tile.probability);
+            self.properties(&tile.properties);
+        }
+        self.len(tileset.wang_sets.len());
+        for set in &tileset.wang_sets {
+            self.str(&set.name);
+            self.str(&set.wang_set_type.to_string());
+            self.option(set.tile, Fnv1a::u32);
+            self.properties(&set.properties);
+            self.len(set.colors.len());
+            for color in &set.colors {
+                self.str(&color.name);
+                self.colour(Some(&color.color));
+                self.option(color.tile, Fnv1a::u32);
+                self.f32(color.probability);
+                self.properties(&color.properties);
+            }
+            self.len(set.wang_tiles.len());
+            for tile in &set.wang_tiles {
+                self.u32(tile.tile_id.0);
+                self.bytes(&tile.wang_id.0);
+            }
+        }
+    }
+}
+
+/// Returns the hash of what `write` feeds to it.
+fn hash(write: impl FnOnce(&mut Fnv1a)) -> u64 {
+    let mut h = Fnv1a::new();
+    write(&mut h);
+    h.0
+}
+
+/// Hashes the content of a tile layer, without its position in the map.
+pub(crate) fn hash_layer(layer: &Layer) -> u64 {
+    hash(|h| h.layer(layer))
+}
+
+/// Hashes the content of an image layer, without its position in the map.
+pub(crate) fn hash_image_layer(layer: &ImageLayer) -> u64 {
+    hash(|h| h.image_layer(layer))
+}
+
+/// Hashes the content of an object group, without its position in the map.
+pub(crate) fn hash_object_group(group: &ObjectGroup) -> u64 {
+    hash(|h| h.object_group(group))
+}
+
+/// Hashes the content of a tileset, without its first GID.
+pub(crate) fn hash_tileset(tileset: &Tileset) -> u64 {
+    hash(|h| h.tileset(tileset))
+}
+
+/// Hashes the content of a map: its attributes, layers and tilesets.
+pub(crate) fn hash_map(map: &Map) -> u64 {
+    hash(|h| map_content(h, map))
+}
+
+fn map_content(h: &mut Fnv1a, map: &Map) {
+    h.str(&map.version);
+    h.str(&map.orientation.to_string());
+    for value in [map.width, map.height, map.tile_width, map.tile_height] {
+        h.u32(value);
+    }
+    h.colour(map.background_colour.as_ref());
+    h.bool(map.infinite);
+    h.str(&map.stagger_axis.to_string());
+    h.str(&map.stagger_index.to_string());
+    h.option(map.hex_side_length, Fnv1a::u32);
+    h.f32(map.parallax_origin_x);
+    h.f32(map.parallax_origin_y);
+    h.properties(&map.properties);
+
+    h.len(map.layers.len());
+    for layer in &map.layers {
+        h.layer_position(layer.layer_index, layer.parent_group);
+        h.layer(layer);
+    }
+    h.len(map.image_layers.len());
+    for layer in &map.image_layers {
+        h.layer_position(layer.layer_index, layer.parent_group);
+        h.image_layer(layer);
+    }
+    h.len(map.object_groups.len());
+    for group in &map.object_groups {
+        h.option(group.layer_index, Fnv1a::u32);
+        h.option(group.parent_group, |h, group| h.len(group));
+        h.object_group(group);
+    }
+    h.len(map.groups.len());
+    for group in &map.groups {
+        h.layer_position(group.layer_index, group.parent_group);
+        h.group(group);
+    }
+    h.len(map.tilesets.len());
+    for tileset in &map.tilesets {
+        h.u32(tileset.first_gid);
+        h.tileset(tileset);
+    }
+}
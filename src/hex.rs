@@ -0,0 +1,112 @@
+//! Coordinate helpers for hexagonal maps.
+//!
+//! Tiled stores hexagonal maps with "offset" coordinates, where every other row (or column) is
+//! shifted by half a tile. Distances and neighbors are much easier to compute in axial or cube
+//! coordinates, so [`HexLayout`] converts between them, taking the map's stagger axis and
+//! stagger index into account.
+
+use crate::map::{StaggerAxis, StaggerIndex};
+
+/// Axial coordinates of a hex tile.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct Axial {
+    pub q: i32,
+    pub r: i32,
+}
+
+/// Cube coordinates of a hex tile. `x + y + z` is always 0.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct Cube {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl Axial {
+    pub fn to_cube(self) -> Cube {
+        Cube {
+            x: self.q,
+            y: -self.q - self.r,
+            z: self.r,
+        }
+    }
+
+    /// The number of steps between two hex tiles.
+    pub fn distance(self, other: Axial) -> u32 {
+        let dq = self.q - other.q;
+        let dr = self.r - other.r;
+        ((dq.abs() + dr.abs() + (dq + dr).abs()) / 2) as u32
+    }
+}
+
+impl Cube {
+    pub fn to_axial(self) -> Axial {
+        Axial {
+            q: self.x,
+            r: self.z,
+        }
+    }
+}
+
+const AXIAL_DIRECTIONS: [(i32, i32); 6] = [(1, 0), (1, -1), (0, -1), (-1, 0), (-1, 1), (0, 1)];
+
+/// The stagger settings of a hexagonal map, as returned by
+/// [`Map::hex_layout`](crate::map::Map::hex_layout).
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct HexLayout {
+    pub stagger_axis: StaggerAxis,
+    pub stagger_index: StaggerIndex,
+}
+
+impl HexLayout {
+    /// How far the row (or column) `index` is shifted back, in tiles, relative to axial
+    /// coordinates.
+    fn shift(&self, index: i32) -> i32 {
+        match self.stagger_index {
+            StaggerIndex::Odd => (index - (index & 1)) / 2,
+            StaggerIndex::Even => (index + (index & 1)) / 2,
+        }
+    }
+
+    /// Converts tile coordinates of the map into axial coordinates.
+    pub fn offset_to_axial(&self, x: i32, y: i32) -> Axial {
+        match self.stagger_axis {
+            StaggerAxis::Y => Axial {
+                q: x - self.shift(y),
+                r: y,
+            },
+            StaggerAxis::X => Axial {
+                q: x,
+                r: y - self.shift(x),
+            },
+        }
+    }
+
+    /// Converts axial coordinates into tile coordinates of the map.
+    pub fn axial_to_offset(&self, axial: Axial) -> (i32, i32) {
+        match self.stagger_axis {
+            StaggerAxis::Y => (axial.q + self.shift(axial.r), axial.r),
+            StaggerAxis::X => (axial.q, axial.r + self.shift(axial.q)),
+        }
+    }
+
+    /// Returns the tile coordinates of the six tiles around the given one. Coordinates may lie
+    /// outside of the map.
+    pub fn neighbors(&self, x: i32, y: i32) -> [(i32, i32); 6] {
+        let center = self.offset_to_axial(x, y);
+        let mut neighbors = [(0, 0); 6];
+        for (neighbor, (dq, dr)) in neighbors.iter_mut().zip(AXIAL_DIRECTIONS.iter()) {
+            *neighbor = self.axial_to_offset(Axial {
+                q: center.q + dq,
+                r: center.r + dr,
+            });
+        }
+        neighbors
+    }
+
+    /// The number of steps between two tiles, given in tile coordinates of the map.
+    pub fn distance(&self, from: (i32, i32), to: (i32, i32)) -> u32 {
+        self.offset_to_axial(from.0, from.1)
+            .distance(self.offset_to_axial(to.0, to.1))
+    }
+}
@@ -0,0 +1,73 @@
+//! Typed tile ids, telling the ids of tiles within their tileset apart from the gids maps use to
+//! refer to them.
+
+use std::fmt;
+
+use crate::tileset::MapTileset;
+
+/// A global tile id: how a map refers to a tile of one of its tilesets, by adding the id of the
+/// tile to the first gid of its tileset in that map. Gid 0 means no tile. The flip flags stored
+/// along with the gids of layer tiles aren't part of it.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default)]
+pub struct Gid(pub u32);
+
+impl Gid {
+    /// The gid of empty cells.
+    pub const EMPTY: Gid = Gid(0);
+
+    /// The id of the tile in `tileset`, or `None` if the gid is below the first gid of the
+    /// tileset. See [`MapTileset::tile_id`].
+    pub fn tile_id(self, tileset: &MapTileset) -> Option<TileId> {
+        tileset.tile_id(self)
+    }
+}
+
+/// The id of a tile within its tileset, as used by the tiles of [`Tileset::tiles`], animation
+/// frames and wang sets. Unlike a [`Gid`], it is the same whatever map uses the tileset.
+///
+/// [`Tileset::tiles`]: crate::tileset::Tileset::tiles
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default)]
+pub struct TileId(pub u32);
+
+impl TileId {
+    /// The gid of the tile in the map `tileset` belongs to. See [`MapTileset::gid`].
+    pub fn gid(self, tileset: &MapTileset) -> Gid {
+        tileset.gid(self)
+    }
+}
+
+impl From<u32> for Gid {
+    fn from(gid: u32) -> Gid {
+        Gid(gid)
+    }
+}
+
+impl From<Gid> for u32 {
+    fn from(gid: Gid) -> u32 {
+        gid.0
+    }
+}
+
+impl From<u32> for TileId {
+    fn from(id: u32) -> TileId {
+        TileId(id)
+    }
+}
+
+impl From<TileId> for u32 {
+    fn from(id: TileId) -> u32 {
+        id.0
+    }
+}
+
+impl fmt::Display for Gid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl fmt::Display for TileId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
@@ -1,42 +1,135 @@
-use std::io::Read;
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
 
 use xml::{attribute::OwnedAttribute, EventReader};
 
-use crate::{error::TiledError, properties::Colour, util::*};
+use crate::{error::TiledError, loader::PathResolver, properties::Colour, util::*};
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+/// An image of a tileset, a tile of an image collection tileset or an image layer. All of them
+/// are parsed the same way.
+#[derive(Debug, Clone)]
 pub struct Image {
-    /// The filepath of the image
+    /// The filepath of the image, with backslashes replaced by forward slashes.
     pub source: String,
-    pub width: i32,
-    pub height: i32,
+    /// The filepath of the image, as written in the file.
+    pub original_source: String,
+    /// Width of the image in pixels, if the file says. The image layers of older versions of
+    /// the editor don't.
+    pub width: Option<i32>,
+    /// Height of the image in pixels, if the file says.
+    pub height: Option<i32>,
     pub transparent_colour: Option<Colour>,
+    /// The format of the image, such as `png`, if it is declared by the file. The editor only
+    /// declares it for images embedded in the file.
+    pub format: Option<String>,
+    /// The path of the external tileset defining the image, relative to the map and with
+    /// backslashes replaced by forward slashes. The image's `source` is relative to this file.
+    /// `None` for images defined in the map itself, or in a tileset parsed on its own.
+    ///
+    /// It is not compared by `==`, so an external tileset equals the same tileset embedded in a
+    /// map.
+    pub defining_file: Option<String>,
 }
 
+impl PartialEq for Image {
+    fn eq(&self, other: &Image) -> bool {
+        self.source == other.source
+            && self.original_source == other.original_source
+            && self.width == other.width
+            && self.height == other.height
+            && self.transparent_colour == other.transparent_colour
+            && self.format == other.format
+    }
+}
+
+impl Eq for Image {}
+
 impl Image {
+    /// The path of the image relative to the map, taking into account the external tileset
+    /// defining it, if any.
+    pub fn source_relative_to_map(&self) -> String {
+        match self
+            .defining_file
+            .as_ref()
+            .and_then(|f| f.rfind('/').map(|i| &f[..=i]))
+        {
+            Some(dir) => format!("{}{}", dir, self.source),
+            None => self.source.clone(),
+        }
+    }
+
+    /// Returns the path of the image file for a map stored at `map_path`, resolving the image
+    /// relative to the file defining it like the [`Loader`](crate::loader::Loader) does for
+    /// external files.
+    pub fn resolve_path(&self, map_path: &Path, resolver: &PathResolver) -> PathBuf {
+        let defining_file = match &self.defining_file {
+            Some(file) => resolver.resolve(map_path, file),
+            None => map_path.to_owned(),
+        };
+        resolver.resolve(&defining_file, &self.source)
+    }
+
+    /// The format of the image: the declared [`Image::format`] if there is one, and otherwise
+    /// the lowercase extension of the source.
+    pub fn file_format(&self) -> Option<String> {
+        if let Some(format) = &self.format {
+            return Some(format.to_ascii_lowercase());
+        }
+        let name = self.source.rsplit('/').next()?;
+        let dot = name.rfind('.')?;
+        Some(name[dot + 1..].to_ascii_lowercase()).filter(|ext| !ext.is_empty())
+    }
+
+    /// Decodes the contents of the image file, read from wherever [`Image::resolve_path`] points
+    /// to, with the `image` crate. This is enabled by the `image` feature, and supports the
+    /// formats enabled for that crate. The format is taken from [`Image::file_format`], or
+    /// guessed from `data` if that is unknown.
+    ///
+    /// The [`transparent_colour`](Image::transparent_colour) is not applied.
+    #[cfg(feature = "image")]
+    pub fn decode(&self, data: &[u8]) -> Result<::image::RgbaImage, TiledError> {
+        let format = self
+            .file_format()
+            .and_then(::image::ImageFormat::from_extension);
+        let decoded = match format {
+            Some(format) => ::image::load_from_memory_with_format(data, format),
+            None => ::image::load_from_memory(data),
+        };
+        decoded
+            .map(|image| image.into_rgba8())
+            .map_err(|e| TiledError::Other(format!("cannot decode {}: {}", self.source, e)))
+    }
+
     pub(crate) fn new<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
     ) -> Result<Image, TiledError> {
-        let (c, (s, w, h)) = get_attrs!(
+        let ((c, w, h, f), s) = get_attrs!(
             attrs,
             optionals: [
-                ("trans", trans, |v:String| v.parse().ok()),
+                ("trans", trans, Some),
+                ("width", width, |v:String| v.parse().ok()),
+                ("height", height, |v:String| v.parse().ok()),
+                ("format", format, Some),
             ],
             required: [
                 ("source", source, |v| Some(v)),
-                ("width", width, |v:String| v.parse().ok()),
-                ("height", height, |v:String| v.parse().ok()),
             ],
-            TiledError::MalformedAttributes("image must have a source, width and height with correct types".to_string())
+            TiledError::MalformedAttributes("image must have a source".to_string())
         );
+        let c = c.map(|c: String| c.parse::<Colour>()).transpose()?;
 
         parse_tag!(parser, "image", { "" => |_| Ok(()) });
         Ok(Image {
-            source: s,
+            source: normalize_source(&s),
+            original_source: s,
             width: w,
             height: h,
             transparent_colour: c,
+            format: f,
+            defining_file: None,
         })
     }
 }
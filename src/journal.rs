@@ -0,0 +1,217 @@
+//! Recording the changes made to a map through its editing methods, so that editors can undo and
+//! redo them and networked editors can send them to other clients.
+
+use crate::{layers::LayerTile, map::Map, objects::Object, tileset::GidResolver};
+
+/// A change made to a map, as recorded by [`Map::take_changes`]. Layers are identified by their
+/// ID, which stays the same when layers are added or removed.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Change {
+    /// A tile of a tile layer was replaced.
+    Tile {
+        layer_id: u32,
+        x: i32,
+        y: i32,
+        old: LayerTile,
+        new: LayerTile,
+    },
+    /// An object was inserted into an object layer at `index`.
+    ObjectAdded {
+        layer_id: u32,
+        index: usize,
+        object: Object,
+    },
+    /// The object at `index` of an object layer was removed.
+    ObjectRemoved {
+        layer_id: u32,
+        index: usize,
+        object: Object,
+    },
+}
+
+impl Change {
+    /// Returns the change undoing this one.
+    pub fn inverse(&self) -> Change {
+        match self.clone() {
+            Change::Tile {
+                layer_id,
+                x,
+                y,
+                old,
+                new,
+            } => Change::Tile {
+                layer_id,
+                x,
+                y,
+                old: new,
+                new: old,
+            },
+            Change::ObjectAdded {
+                layer_id,
+                index,
+                object,
+            } => Change::ObjectRemoved {
+                layer_id,
+                index,
+                object,
+            },
+            Change::ObjectRemoved {
+                layer_id,
+                index,
+                object,
+            } => Change::ObjectAdded {
+                layer_id,
+                index,
+                object,
+            },
+        }
+    }
+}
+
+/// The changes recorded since they were last taken, if changes are tracked. Not compared when
+/// comparing maps.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Journal(Option<Vec<Change>>);
+
+impl Journal {
+    /// Records a change if changes are tracked, only building it then.
+    pub(crate) fn record(&mut self, change: impl FnOnce() -> Change) {
+        if let Some(changes) = &mut self.0 {
+            changes.push(change());
+        }
+    }
+
+    /// The changes not taken yet, or `None` if changes aren't tracked.
+    pub(crate) fn changes(&self) -> Option<&Vec<Change>> {
+        self.0.as_ref()
+    }
+}
+
+impl PartialEq for Journal {
+    fn eq(&self, _: &Journal) -> bool {
+        true
+    }
+}
+
+impl Map {
+    /// Starts or stops recording the changes made through [`Map::set_tile`],
+    /// [`Map::apply_stamp`], [`Map::add_object`], [`Map::remove_object`] and
+    /// [`Map::apply_change`]. Stopping discards the changes not taken yet. Changes made to the
+    /// fields of the map directly are never recorded.
+    pub fn track_changes(&mut self, enabled: bool) {
+        if enabled != self.journal.0.is_some() {
+            self.journal.0 = if enabled { Some(Vec::new()) } else { None };
+        }
+    }
+
+    /// Returns the changes recorded since the last call, oldest first, and starts a new list.
+    /// Returns nothing if changes aren't tracked; see [`Map::track_changes`].
+    pub fn take_changes(&mut self) -> Vec<Change> {
+        match &mut self.journal.0 {
+            Some(changes) => std::mem::take(changes),
+            None => Vec::new(),
+        }
+    }
+
+    /// Sets a tile of the tile layer at index `layer` of [`Map::layers`], like
+    /// [`Layer::set_tile`](crate::layers::Layer::set_tile), and records the change. The tileset
+    /// of the tile is looked up and stored on it. Returns whether the tile was set.
+    pub fn set_tile(&mut self, layer: usize, x: i32, y: i32, tile: LayerTile) -> bool {
+        let mut tile = LayerTile::new(tile.raw_gid());
+        tile.resolved = GidResolver::new(&self.gid_table, &self.tilesets)
+            .resolve(tile.gid)
+            .map(|(index, id)| (index as u32, id));
+        let layer = match self.layers.get_mut(layer) {
+            Some(layer) => layer,
+            None => return false,
+        };
+        let old = layer.get_tile(x, y).unwrap_or_else(|| LayerTile::new(0));
+        if !layer.set_tile(x, y, tile) {
+            return false;
+        }
+        if old != tile {
+            self.journal.record(|| Change::Tile {
+                layer_id: layer.id,
+                x,
+                y,
+                old,
+                new: tile,
+            });
+        }
+        true
+    }
+
+    /// Adds an object at the end of the object layer at index `group` of
+    /// [`Map::object_groups`], and records the change. Returns `false` if there is no such
+    /// layer.
+    pub fn add_object(&mut self, group: usize, object: Object) -> bool {
+        match self.object_groups.get(group) {
+            Some(layer) => {
+                let index = layer.objects.len();
+                self.insert_object(group, index, object);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the object with the given ID from the object layer at index `group` of
+    /// [`Map::object_groups`], and records the change. Returns the removed object, if any.
+    pub fn remove_object(&mut self, group: usize, id: u32) -> Option<Object> {
+        let layer = self.object_groups.get_mut(group)?;
+        let index = layer.objects.iter().position(|o| o.id == id)?;
+        let object = layer.objects.remove(index);
+        self.journal.record(|| Change::ObjectRemoved {
+            layer_id: layer.id,
+            index,
+            object: object.clone(),
+        });
+        Some(object)
+    }
+
+    /// Makes a recorded change again, such as the [inverse](Change::inverse) of a change to undo
+    /// it, or a change received from another client. The change is recorded in turn. Returns
+    /// `false` if its layer or object isn't in the map.
+    pub fn apply_change(&mut self, change: &Change) -> bool {
+        match change {
+            Change::Tile {
+                layer_id,
+                x,
+                y,
+                new,
+                ..
+            } => match self.layers.iter().position(|l| l.id == *layer_id) {
+                Some(layer) => self.set_tile(layer, *x, *y, *new),
+                None => false,
+            },
+            Change::ObjectAdded {
+                layer_id,
+                index,
+                object,
+            } => match self.object_groups.iter().position(|g| g.id == *layer_id) {
+                Some(group) => {
+                    let index = (*index).min(self.object_groups[group].objects.len());
+                    self.insert_object(group, index, object.clone());
+                    true
+                }
+                None => false,
+            },
+            Change::ObjectRemoved {
+                layer_id, object, ..
+            } => match self.object_groups.iter().position(|g| g.id == *layer_id) {
+                Some(group) => self.remove_object(group, object.id).is_some(),
+                None => false,
+            },
+        }
+    }
+
+    fn insert_object(&mut self, group: usize, index: usize, object: Object) {
+        let layer = &mut self.object_groups[group];
+        self.journal.record(|| Change::ObjectAdded {
+            layer_id: layer.id,
+            index,
+            object: object.clone(),
+        });
+        layer.objects.insert(index, object);
+    }
+}
@@ -0,0 +1,415 @@
+use std::{fmt, io::Write, str::CharIndices};
+
+use crate::{error::TiledError, properties::FloatProperty};
+
+/// A minimal JSON document model, only as complete as the Tiled JSON format requires.
+///
+/// Objects keep their insertion order so that the output matches the layout the Tiled editor
+/// produces and stays stable between runs.
+#[derive(Debug, PartialEq, Clone)]
+pub(crate) enum JsonValue {
+    Null,
+    Bool(bool),
+    /// Numbers are kept pre-formatted so that `f32` values are written with their shortest
+    /// representation instead of picking up noise from a conversion to `f64`.
+    Number(String),
+    String(String),
+    Array(Vec<JsonValue>),
+    Object(Vec<(String, JsonValue)>),
+}
+
+impl From<bool> for JsonValue {
+    fn from(v: bool) -> Self {
+        JsonValue::Bool(v)
+    }
+}
+
+impl From<u32> for JsonValue {
+    fn from(v: u32) -> Self {
+        JsonValue::Number(v.to_string())
+    }
+}
+
+impl From<i32> for JsonValue {
+    fn from(v: i32) -> Self {
+        JsonValue::Number(v.to_string())
+    }
+}
+
+impl From<f32> for JsonValue {
+    fn from(v: f32) -> Self {
+        if v.is_finite() {
+            JsonValue::Number(v.to_string())
+        } else {
+            JsonValue::Null
+        }
+    }
+}
+
+impl From<&FloatProperty> for JsonValue {
+    /// Uses the text the property was read from when it is a valid JSON number.
+    fn from(v: &FloatProperty) -> Self {
+        if !v.value.is_finite() {
+            return JsonValue::Null;
+        }
+        let text = v.to_string();
+        if is_json_number(&text) {
+            JsonValue::Number(text)
+        } else {
+            JsonValue::Number(v.value.to_string())
+        }
+    }
+}
+
+/// Checks `s` against the JSON number grammar, which is stricter than Rust's float parsing
+/// (no `.5`, `5.`, `+5` or leading zeros).
+fn is_json_number(s: &str) -> bool {
+    let s = s.strip_prefix('-').unwrap_or(s);
+    let digits = |s: &str| s.bytes().take_while(u8::is_ascii_digit).count();
+    let int = digits(s);
+    if int == 0 || (int > 1 && s.starts_with('0')) {
+        return false;
+    }
+    let mut rest = &s[int..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let len = digits(fraction);
+        if len == 0 {
+            return false;
+        }
+        rest = &fraction[len..];
+    }
+    if let Some(exponent) = rest.strip_prefix(|c| c == 'e' || c == 'E') {
+        let exponent = exponent
+            .strip_prefix(|c| c == '+' || c == '-')
+            .unwrap_or(exponent);
+        let len = digits(exponent);
+        if len == 0 {
+            return false;
+        }
+        rest = &exponent[len..];
+    }
+    rest.is_empty()
+}
+
+impl From<&str> for JsonValue {
+    fn from(v: &str) -> Self {
+        JsonValue::String(v.to_owned())
+    }
+}
+
+impl From<String> for JsonValue {
+    fn from(v: String) -> Self {
+        JsonValue::String(v)
+    }
+}
+
+impl From<Vec<JsonValue>> for JsonValue {
+    fn from(v: Vec<JsonValue>) -> Self {
+        JsonValue::Array(v)
+    }
+}
+
+/// Helper for building JSON objects field by field.
+#[derive(Default)]
+pub(crate) struct JsonObject(Vec<(String, JsonValue)>);
+
+impl JsonObject {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn field(mut self, name: &str, value: impl Into<JsonValue>) -> Self {
+        self.0.push((name.to_owned(), value.into()));
+        self
+    }
+
+    pub(crate) fn field_if(self, cond: bool, name: &str, value: impl Into<JsonValue>) -> Self {
+        if cond {
+            self.field(name, value)
+        } else {
+            self
+        }
+    }
+}
+
+impl From<JsonObject> for JsonValue {
+    fn from(o: JsonObject) -> Self {
+        JsonValue::Object(o.0)
+    }
+}
+
+impl JsonValue {
+    pub(crate) fn write_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writeln!(writer, "{}", self)
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        const STEP: usize = 1;
+        match self {
+            JsonValue::Null => write!(f, "null"),
+            JsonValue::Bool(b) => write!(f, "{}", b),
+            JsonValue::Number(n) => write!(f, "{}", n),
+            JsonValue::String(s) => write_json_string(f, s),
+            JsonValue::Array(items) if items.is_empty() => write!(f, "[]"),
+            JsonValue::Array(items) => {
+                // Arrays of plain values (such as tile data) are kept on one line, like Tiled does.
+                if items
+                    .iter()
+                    .all(|i| !matches!(i, JsonValue::Array(_) | JsonValue::Object(_)))
+                {
+                    write!(f, "[")?;
+                    for (i, item) in items.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        item.fmt_indented(f, indent)?;
+                    }
+                    return write!(f, "]");
+                }
+                writeln!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    write!(f, "{:1$}", "", indent + STEP)?;
+                    item.fmt_indented(f, indent + STEP)?;
+                    if i + 1 < items.len() {
+                        write!(f, ",")?;
+                    }
+                    writeln!(f)?;
+                }
+                write!(f, "{:1$}]", "", indent)
+            }
+            JsonValue::Object(fields) if fields.is_empty() => write!(f, "{{}}"),
+            JsonValue::Object(fields) => {
+                writeln!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    write!(f, "{:1$}", "", indent + STEP)?;
+                    write_json_string(f, name)?;
+                    write!(f, ":")?;
+                    value.fmt_indented(f, indent + STEP)?;
+                    if i + 1 < fields.len() {
+                        write!(f, ",")?;
+                    }
+                    writeln!(f)?;
+                }
+                write!(f, "{:1$}}}", "", indent)
+            }
+        }
+    }
+}
+
+impl fmt::Display for JsonValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+fn write_json_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+impl JsonValue {
+    /// Parses a JSON document, such as a project file.
+    pub(crate) fn parse(text: &str) -> Result<JsonValue, TiledError> {
+        let mut parser = JsonParser { text, pos: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(value)
+    }
+
+    /// The value of the field called `name`, if this is an object that has one.
+    pub(crate) fn get(&self, name: &str) -> Option<&JsonValue> {
+        match self {
+            JsonValue::Object(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            JsonValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_bool(&self) -> Option<bool> {
+        match self {
+            JsonValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_array(&self) -> &[JsonValue] {
+        match self {
+            JsonValue::Array(items) => items,
+            _ => &[],
+        }
+    }
+}
+
+/// Reads the digits of a `\u` escape, along with the second half of surrogate pairs, which
+/// encode characters outside of the basic plane. Invalid escapes become U+FFFD.
+fn unicode_escape(chars: &mut CharIndices<'_>) -> char {
+    fn hex(chars: &mut CharIndices<'_>) -> Option<u32> {
+        let digits: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+        u32::from_str_radix(&digits, 16).ok()
+    }
+    let code = match hex(chars) {
+        Some(high @ 0xd800..=0xdbff) => match (chars.next(), chars.next()) {
+            (Some((_, '\\')), Some((_, 'u'))) => match hex(chars) {
+                Some(low @ 0xdc00..=0xdfff) => {
+                    Some(0x10000 + ((high - 0xd800) << 10) + low - 0xdc00)
+                }
+                _ => None,
+            },
+            _ => None,
+        },
+        code => code,
+    };
+    code.and_then(char::from_u32).unwrap_or('\u{fffd}')
+}
+
+/// A recursive descent parser for [`JsonValue::parse`]. Numbers are kept as written, like the
+/// ones built for writing.
+struct JsonParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn error(&self, what: &str) -> TiledError {
+        TiledError::Other(format!("invalid JSON at byte {}: {}", self.pos, what))
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), TiledError> {
+        if self.text[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", literal)))
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue, TiledError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'n') => self.expect("null").map(|_| JsonValue::Null),
+            Some(b't') => self.expect("true").map(|_| JsonValue::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| JsonValue::Bool(false)),
+            Some(b'"') => self.string().map(JsonValue::String),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {
+                            self.pos += 1;
+                            return Ok(JsonValue::Array(items));
+                        }
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(JsonValue::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let name = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    fields.push((name, self.value()?));
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {
+                            self.pos += 1;
+                            return Ok(JsonValue::Object(fields));
+                        }
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            Some(_) => {
+                let rest = &self.text[self.pos..];
+                let len = rest
+                    .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+                    .unwrap_or(rest.len());
+                if !is_json_number(&rest[..len]) {
+                    return Err(self.error("expected a value"));
+                }
+                self.pos += len;
+                Ok(JsonValue::Number(rest[..len].to_string()))
+            }
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, TiledError> {
+        self.expect("\"")?;
+        let mut s = String::new();
+        let mut chars = self.text[self.pos..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(s);
+                }
+                '\\' => {
+                    let escaped = match chars.next().map(|(_, c)| c) {
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => unicode_escape(&mut chars),
+                        Some(c @ ('"' | '\\' | '/')) => c,
+                        _ => {
+                            self.pos += i;
+                            return Err(self.error("invalid escape"));
+                        }
+                    };
+                    s.push(escaped);
+                }
+                c => s.push(c),
+            }
+        }
+        self.pos = self.text.len();
+        Err(self.error("unterminated string"))
+    }
+}
@@ -0,0 +1,523 @@
+use crate::{
+    animation::Frame,
+    image::Image,
+    json::{JsonObject, JsonValue},
+    layers::{Chunk, GroupLayer, ImageLayer, Layer, LayerData, LayerTile},
+    map::{Map, Orientation},
+    objects::{Object, ObjectGroup, ObjectShape},
+    properties::{Colour, Properties, PropertyValue},
+    text::{Text, TextStyle},
+    tile::Tile,
+    tileset::Tileset,
+    wangset::WangSet,
+};
+
+pub(crate) fn colour_to_json(c: &Colour) -> JsonValue {
+    c.to_string().into()
+}
+
+pub(crate) fn properties_to_json(properties: &Properties) -> JsonValue {
+    let mut names: Vec<&String> = properties.keys().collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| {
+            let (ty, value): (&str, JsonValue) = match &properties[name] {
+                PropertyValue::BoolValue(v) => ("bool", (*v).into()),
+                PropertyValue::FloatValue(v) => ("float", v.into()),
+                PropertyValue::IntValue(v) => ("int", (*v).into()),
+                PropertyValue::ColorValue(v) => ("color", format!("#{:08x}", v).into()),
+                PropertyValue::StringValue(v) => ("string", v.as_str().into()),
+                PropertyValue::FileValue(v) => ("file", v.as_str().into()),
+                PropertyValue::ObjectValue(v) => ("object", (*v).into()),
+                PropertyValue::Unknown {
+                    type_name,
+                    raw_value,
+                } => (type_name.as_str(), raw_value.as_str().into()),
+            };
+            JsonObject::new()
+                .field("name", name.as_str())
+                .field("type", ty)
+                .field("value", value)
+                .into()
+        })
+        .collect::<Vec<JsonValue>>()
+        .into()
+}
+
+fn with_properties(obj: JsonObject, properties: &Properties) -> JsonObject {
+    obj.field_if(
+        !properties.is_empty(),
+        "properties",
+        properties_to_json(properties),
+    )
+}
+
+/// Adds the `class` of a layer, if it has one.
+fn with_class(obj: JsonObject, class: &Option<String>) -> JsonObject {
+    match class {
+        Some(class) => obj.field("class", class.as_str()),
+        None => obj,
+    }
+}
+
+fn tile_rows_to_json(rows: &[Vec<LayerTile>]) -> JsonValue {
+    rows.iter()
+        .flat_map(|row| row.iter().map(|t| t.raw_gid().into()))
+        .collect::<Vec<JsonValue>>()
+        .into()
+}
+
+fn chunk_to_json(chunk: &Chunk) -> JsonValue {
+    JsonObject::new()
+        .field("data", tile_rows_to_json(&chunk.tiles))
+        .field("height", chunk.height)
+        .field("width", chunk.width)
+        .field("x", chunk.x)
+        .field("y", chunk.y)
+        .into()
+}
+
+pub(crate) fn layer_to_json(layer: &Layer, map: &Map) -> JsonValue {
+    let obj = with_class(JsonObject::new(), &layer.user_type);
+    let obj = match &layer.tiles {
+        LayerData::Finite(rows) => obj
+            .field("data", tile_rows_to_json(rows))
+            .field("height", map.height)
+            .field("width", map.width),
+        LayerData::Infinite(chunks) => {
+            let mut chunks: Vec<&Chunk> = chunks.values().collect();
+            chunks.sort_by_key(|c| (c.y, c.x));
+            let start_x = chunks.iter().map(|c| c.x).min().unwrap_or(0);
+            let start_y = chunks.iter().map(|c| c.y).min().unwrap_or(0);
+            let end_x = chunks
+                .iter()
+                .map(|c| c.x + c.width as i32)
+                .max()
+                .unwrap_or(0);
+            let end_y = chunks
+                .iter()
+                .map(|c| c.y + c.height as i32)
+                .max()
+                .unwrap_or(0);
+            obj.field(
+                "chunks",
+                chunks
+                    .into_iter()
+                    .map(chunk_to_json)
+                    .collect::<Vec<JsonValue>>(),
+            )
+            .field("height", end_y - start_y)
+            .field("startx", start_x)
+            .field("starty", start_y)
+            .field("width", end_x - start_x)
+        }
+    };
+    let obj = obj
+        .field("id", layer.id)
+        .field("name", layer.name.as_str())
+        .field_if(layer.offset_x != 0.0, "offsetx", layer.offset_x)
+        .field_if(layer.offset_y != 0.0, "offsety", layer.offset_y)
+        .field("opacity", layer.opacity)
+        .field_if(layer.parallax_x != 1.0, "parallaxx", layer.parallax_x)
+        .field_if(layer.parallax_y != 1.0, "parallaxy", layer.parallax_y);
+    let obj = with_properties(obj, &layer.properties);
+    let obj = match &layer.tint_colour {
+        Some(c) => obj.field("tintcolor", colour_to_json(c)),
+        None => obj,
+    };
+    obj.field("type", "tilelayer")
+        .field("visible", layer.visible)
+        .field("x", 0)
+        .field("y", 0)
+        .into()
+}
+
+pub(crate) fn image_layer_to_json(layer: &ImageLayer) -> JsonValue {
+    let obj = with_class(JsonObject::new(), &layer.user_type).field("id", layer.id);
+    let obj = match &layer.image {
+        Some(image) => image_fields(obj, image),
+        None => obj.field("image", ""),
+    };
+    let obj = obj
+        .field("name", layer.name.as_str())
+        .field_if(layer.offset_x != 0.0, "offsetx", layer.offset_x)
+        .field_if(layer.offset_y != 0.0, "offsety", layer.offset_y)
+        .field("opacity", layer.opacity)
+        .field_if(layer.parallax_x != 1.0, "parallaxx", layer.parallax_x)
+        .field_if(layer.parallax_y != 1.0, "parallaxy", layer.parallax_y);
+    let obj = with_properties(obj, &layer.properties);
+    let obj = match &layer.tint_colour {
+        Some(c) => obj.field("tintcolor", colour_to_json(c)),
+        None => obj,
+    };
+    obj.field("type", "imagelayer")
+        .field("visible", layer.visible)
+        .field("x", 0)
+        .field("y", 0)
+        .into()
+}
+
+fn points_to_json(points: &[(f32, f32)]) -> JsonValue {
+    points
+        .iter()
+        .map(|&(x, y)| JsonObject::new().field("x", x).field("y", y).into())
+        .collect::<Vec<JsonValue>>()
+        .into()
+}
+
+pub(crate) fn object_to_json(object: &Object) -> JsonValue {
+    let obj = JsonObject::new();
+    let obj = match &object.shape {
+        ObjectShape::Ellipse { .. } => obj.field("ellipse", true),
+        ObjectShape::Point(..) => obj.field("point", true),
+        _ => obj,
+    };
+    let obj = obj
+        .field_if(object.gid != 0, "gid", object.gid)
+        .field("height", object.height)
+        .field("id", object.id)
+        .field("name", object.name.as_str());
+    let obj = match &object.shape {
+        ObjectShape::Polygon { points } => obj.field("polygon", points_to_json(points)),
+        ObjectShape::Polyline { points } => obj.field("polyline", points_to_json(points)),
+        _ => obj,
+    };
+    let obj = with_properties(obj, &object.properties).field("rotation", object.rotation);
+    let obj = match &object.shape {
+        ObjectShape::Text { text, .. } => obj.field("text", text_to_json(text)),
+        _ => obj,
+    };
+    obj.field("type", object.obj_type.as_str())
+        .field("visible", object.visible)
+        .field("width", object.width)
+        .field("x", object.x)
+        .field("y", object.y)
+        .into()
+}
+
+/// Serializes the `text` member of a text object. Like the editor, only the style properties
+/// that differ from their default value are written.
+fn text_to_json(text: &Text) -> JsonValue {
+    let style = &text.style;
+    let defaults = TextStyle::default();
+    JsonObject::new()
+        .field_if(style.bold, "bold", true)
+        .field_if(
+            style.color != defaults.color,
+            "color",
+            colour_to_json(&style.color),
+        )
+        .field_if(
+            style.font_family != defaults.font_family,
+            "fontfamily",
+            style.font_family.as_str(),
+        )
+        .field_if(
+            style.halign != defaults.halign,
+            "halign",
+            style.halign.to_string(),
+        )
+        .field_if(style.italic, "italic", true)
+        .field_if(!style.kerning, "kerning", false)
+        .field_if(
+            style.pixel_size != defaults.pixel_size,
+            "pixelsize",
+            style.pixel_size,
+        )
+        .field_if(style.strikeout, "strikeout", true)
+        .field("text", text.contents.as_str())
+        .field_if(style.underline, "underline", true)
+        .field_if(
+            style.valign != defaults.valign,
+            "valign",
+            style.valign.to_string(),
+        )
+        .field_if(style.wrap, "wrap", true)
+        .into()
+}
+
+pub(crate) fn object_group_to_json(group: &ObjectGroup) -> JsonValue {
+    let obj = with_class(JsonObject::new(), &group.user_type);
+    let obj = match &group.colour {
+        Some(c) => obj.field("color", colour_to_json(c)),
+        None => obj,
+    };
+    let obj = obj
+        .field("draworder", group.draw_order.to_string())
+        .field("id", group.id)
+        .field("name", group.name.as_str())
+        .field(
+            "objects",
+            group
+                .objects
+                .iter()
+                .map(object_to_json)
+                .collect::<Vec<JsonValue>>(),
+        )
+        .field_if(group.offset_x != 0.0, "offsetx", group.offset_x)
+        .field_if(group.offset_y != 0.0, "offsety", group.offset_y)
+        .field("opacity", group.opacity)
+        .field_if(group.parallax_x != 1.0, "parallaxx", group.parallax_x)
+        .field_if(group.parallax_y != 1.0, "parallaxy", group.parallax_y);
+    let obj = with_properties(obj, &group.properties);
+    let obj = match &group.tint_colour {
+        Some(c) => obj.field("tintcolor", colour_to_json(c)),
+        None => obj,
+    };
+    obj.field("type", "objectgroup")
+        .field("visible", group.visible)
+        .field("x", 0)
+        .field("y", 0)
+        .into()
+}
+
+fn image_fields(obj: JsonObject, image: &Image) -> JsonObject {
+    let obj = obj
+        .field("image", image.source.as_str())
+        .field_if(
+            image.height.is_some(),
+            "imageheight",
+            image.height.unwrap_or(0),
+        )
+        .field_if(
+            image.width.is_some(),
+            "imagewidth",
+            image.width.unwrap_or(0),
+        );
+    match &image.transparent_colour {
+        Some(c) => obj.field("transparentcolor", colour_to_json(c)),
+        None => obj,
+    }
+}
+
+fn frame_to_json(frame: &Frame) -> JsonValue {
+    JsonObject::new()
+        .field("duration", frame.duration)
+        .field("tileid", frame.tile_id.0)
+        .into()
+}
+
+fn tile_to_json(tile: &Tile) -> JsonValue {
+    let obj = JsonObject::new();
+    let obj = match &tile.animation {
+        Some(frames) => obj.field(
+            "animation",
+            frames.iter().map(frame_to_json).collect::<Vec<JsonValue>>(),
+        ),
+        None => obj,
+    };
+    let obj = obj.field("id", tile.id.0);
+    let obj = match tile.images.first() {
+        Some(image) => image_fields(obj, image),
+        None => obj,
+    };
+    let obj = match &tile.objectgroup {
+        Some(group) => obj.field("objectgroup", object_group_to_json(group)),
+        None => obj,
+    };
+    let obj = obj.field_if(tile.probability != 1.0, "probability", tile.probability);
+    let obj = with_properties(obj, &tile.properties);
+    match &tile.tile_type {
+        Some(t) => obj.field("type", t.as_str()),
+        None => obj,
+    }
+    .into()
+}
+
+fn wang_set_to_json(set: &WangSet) -> JsonValue {
+    let tile_id = |tile: Option<u32>| tile.map_or(-1, |t| t as i32);
+    let colors = set
+        .colors
+        .iter()
+        .map(|c| {
+            let obj = JsonObject::new()
+                .field("color", colour_to_json(&c.color))
+                .field("name", c.name.as_str())
+                .field("probability", c.probability);
+            with_properties(obj, &c.properties)
+                .field("tile", tile_id(c.tile))
+                .into()
+        })
+        .collect::<Vec<JsonValue>>();
+    let wang_tiles = set
+        .wang_tiles
+        .iter()
+        .map(|t| {
+            let wang_id = t.wang_id.0.iter().map(|&c| (c as u32).into());
+            JsonObject::new()
+                .field("tileid", t.tile_id.0)
+                .field("wangid", wang_id.collect::<Vec<JsonValue>>())
+                .into()
+        })
+        .collect::<Vec<JsonValue>>();
+    let obj = JsonObject::new()
+        .field("colors", colors)
+        .field("name", set.name.as_str());
+    with_properties(obj, &set.properties)
+        .field("tile", tile_id(set.tile))
+        .field("type", set.wang_set_type.to_string())
+        .field("wangtiles", wang_tiles)
+        .into()
+}
+
+pub(crate) fn tileset_to_json(tileset: &Tileset, first_gid: Option<u32>) -> JsonValue {
+    let obj = JsonObject::new().field("columns", tileset.columns());
+    let obj = match first_gid {
+        Some(gid) => obj.field("firstgid", gid),
+        None => obj,
+    };
+    let obj = match tileset.images.first() {
+        Some(image) => image_fields(obj, image),
+        None => obj,
+    };
+    let obj = obj
+        .field("margin", tileset.margin)
+        .field("name", tileset.name.as_str());
+    let obj = match &tileset.object_alignment {
+        Some(alignment) => obj.field("objectalignment", alignment.to_string()),
+        None => obj,
+    };
+    let obj = with_properties(obj, &tileset.properties)
+        .field("spacing", tileset.spacing)
+        .field(
+            "tilecount",
+            tileset.tilecount.unwrap_or(tileset.tiles.len() as u32),
+        )
+        .field("tileheight", tileset.tile_height)
+        .field_if(
+            !tileset.tiles.is_empty(),
+            "tiles",
+            tileset
+                .tiles
+                .iter()
+                .map(tile_to_json)
+                .collect::<Vec<JsonValue>>(),
+        )
+        .field("tilewidth", tileset.tile_width)
+        .field_if(
+            !tileset.wang_sets.is_empty(),
+            "wangsets",
+            tileset
+                .wang_sets
+                .iter()
+                .map(wang_set_to_json)
+                .collect::<Vec<JsonValue>>(),
+        );
+    match first_gid {
+        Some(_) => obj,
+        None => obj.field("type", "tileset"),
+    }
+    .into()
+}
+
+/// Serializes the layers directly inside of the given group (or at the top level of the map),
+/// with group layers containing their children.
+fn layers_to_json(map: &Map, parent: Option<usize>) -> Vec<JsonValue> {
+    // The map keeps each kind of layer in its own list, so put them back into file order.
+    let mut layers: Vec<(u32, JsonValue)> = Vec::new();
+    layers.extend(
+        map.layers
+            .iter()
+            .filter(|l| l.parent_group == parent)
+            .map(|l| (l.layer_index, layer_to_json(l, map))),
+    );
+    layers.extend(
+        map.image_layers
+            .iter()
+            .filter(|l| l.parent_group == parent)
+            .map(|l| (l.layer_index, image_layer_to_json(l))),
+    );
+    layers.extend(
+        map.object_groups
+            .iter()
+            .filter(|g| g.parent_group == parent)
+            .map(|g| (g.layer_index.unwrap_or(0), object_group_to_json(g))),
+    );
+    layers.extend(
+        map.groups
+            .iter()
+            .enumerate()
+            .filter(|(_, g)| g.parent_group == parent)
+            .map(|(i, g)| (g.layer_index, group_to_json(map, i, g))),
+    );
+    layers.sort_by_key(|(index, _)| *index);
+    layers.into_iter().map(|(_, l)| l).collect()
+}
+
+fn group_to_json(map: &Map, index: usize, group: &GroupLayer) -> JsonValue {
+    let obj = with_class(JsonObject::new(), &group.user_type)
+        .field("id", group.id)
+        .field("layers", layers_to_json(map, Some(index)))
+        .field("name", group.name.as_str())
+        .field_if(group.offset_x != 0.0, "offsetx", group.offset_x)
+        .field_if(group.offset_y != 0.0, "offsety", group.offset_y)
+        .field("opacity", group.opacity)
+        .field_if(group.parallax_x != 1.0, "parallaxx", group.parallax_x)
+        .field_if(group.parallax_y != 1.0, "parallaxy", group.parallax_y);
+    let obj = with_properties(obj, &group.properties);
+    let obj = match &group.tint_colour {
+        Some(c) => obj.field("tintcolor", colour_to_json(c)),
+        None => obj,
+    };
+    obj.field("type", "group")
+        .field("visible", group.visible)
+        .field("x", 0)
+        .field("y", 0)
+        .into()
+}
+
+pub(crate) fn map_to_json(map: &Map) -> JsonValue {
+    let next_layer_id = map.next_layer_id();
+    let next_object_id = map.next_object_id();
+
+    let obj = JsonObject::new();
+    let obj = match &map.background_colour {
+        Some(c) => obj.field("backgroundcolor", colour_to_json(c)),
+        None => obj,
+    };
+    let staggered = matches!(
+        map.orientation,
+        Orientation::Staggered | Orientation::Hexagonal
+    );
+    let obj = obj
+        .field("compressionlevel", -1)
+        .field("height", map.height);
+    let obj = match map.hex_side_length {
+        Some(length) => obj.field("hexsidelength", length),
+        None => obj,
+    };
+    let obj = obj
+        .field("infinite", map.infinite)
+        .field("layers", layers_to_json(map, None))
+        .field("nextlayerid", next_layer_id)
+        .field("nextobjectid", next_object_id)
+        .field("orientation", map.orientation.to_string())
+        .field_if(
+            map.parallax_origin_x != 0.0,
+            "parallaxoriginx",
+            map.parallax_origin_x,
+        )
+        .field_if(
+            map.parallax_origin_y != 0.0,
+            "parallaxoriginy",
+            map.parallax_origin_y,
+        );
+    with_properties(obj, &map.properties)
+        .field("renderorder", "right-down")
+        .field_if(staggered, "staggeraxis", map.stagger_axis.to_string())
+        .field_if(staggered, "staggerindex", map.stagger_index.to_string())
+        .field("tileheight", map.tile_height)
+        .field(
+            "tilesets",
+            map.tilesets
+                .iter()
+                .map(|t| tileset_to_json(t, Some(t.first_gid)))
+                .collect::<Vec<JsonValue>>(),
+        )
+        .field("tilewidth", map.tile_width)
+        .field("type", "map")
+        .field("version", map.version.as_str())
+        .field("width", map.width)
+        .into()
+}
@@ -1,24 +1,44 @@
-use std::{collections::HashMap, io::Read};
+use std::{collections::HashMap, fmt, io::Read, str::FromStr};
 
 use xml::{attribute::OwnedAttribute, EventReader};
 
 use crate::{
     error::TiledError,
+    ids::{Gid, TileId},
     image::Image,
-    properties::{parse_properties, Properties},
+    loader::Loader,
+    map::{check_cells, Map, ResolvedTile},
+    properties::{parse_properties, Colour, Properties},
     util::*,
 };
 
 /// Stores the proper tile gid, along with how it is flipped.
+///
+/// Tiles of parsed layers also know their tileset, found once while parsing, so that it doesn't
+/// need to be looked up by gid again; see [`LayerTile::tileset_index`].
 // Maybe PartialEq and Eq should be custom, so that it ignores tile-flipping?
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy)]
 pub struct LayerTile {
     pub gid: u32,
     pub flip_h: bool,
     pub flip_v: bool,
     pub flip_d: bool,
+    /// The index of the tileset in the map and the id of the tile in it.
+    pub(crate) resolved: Option<(u32, TileId)>,
 }
 
+/// Tiles are compared by gid and flip flags only, as their tileset follows from their gid.
+impl PartialEq for LayerTile {
+    fn eq(&self, other: &LayerTile) -> bool {
+        self.gid == other.gid
+            && self.flip_h == other.flip_h
+            && self.flip_v == other.flip_v
+            && self.flip_d == other.flip_d
+    }
+}
+
+impl Eq for LayerTile {}
+
 const FLIPPED_HORIZONTALLY_FLAG: u32 = 0x80000000;
 const FLIPPED_VERTICALLY_FLAG: u32 = 0x40000000;
 const FLIPPED_DIAGONALLY_FLAG: u32 = 0x20000000;
@@ -38,6 +58,103 @@ impl LayerTile {
             flip_h,
             flip_v,
             flip_d,
+            resolved: None,
+        }
+    }
+
+    /// The index in [`Map::tilesets`] of the tileset of the tile, as found when the map was
+    /// parsed or [`Map::update_gid_table`] was last called. `None` for empty tiles, tiles whose
+    /// gid is below the first gid of every tileset, and tiles created with [`LayerTile::new`].
+    /// [`Map::tileset_of`] falls back to looking the tileset up by gid for those. Changing the
+    /// `gid` of a tile doesn't update it, so use [`LayerTile::new`] to replace tiles.
+    pub fn tileset_index(&self) -> Option<usize> {
+        self.resolved.map(|(index, _)| index as usize)
+    }
+
+    /// The id of the tile in its tileset, if the tileset is known. See
+    /// [`LayerTile::tileset_index`].
+    pub fn tile_id(&self) -> Option<TileId> {
+        self.resolved.map(|(_, id)| id)
+    }
+
+    /// Encodes the tile back into the value stored in the file, with the flip flags in the
+    /// upper bits of the gid.
+    pub fn raw_gid(&self) -> u32 {
+        let mut id = self.gid;
+        if self.flip_h {
+            id |= FLIPPED_HORIZONTALLY_FLAG;
+        }
+        if self.flip_v {
+            id |= FLIPPED_VERTICALLY_FLAG;
+        }
+        if self.flip_d {
+            id |= FLIPPED_DIAGONALLY_FLAG;
+        }
+        id
+    }
+}
+
+/// How the tile data of a layer is encoded in the file.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Encoding {
+    Csv,
+    Base64,
+}
+
+impl FromStr for Encoding {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<Encoding, TiledError> {
+        match s {
+            "csv" => Ok(Self::Csv),
+            "base64" => Ok(Self::Base64),
+            _ => Err(TiledError::MalformedAttributes(format!(
+                "Invalid encoding '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encoding::Csv => write!(f, "csv"),
+            Encoding::Base64 => write!(f, "base64"),
+        }
+    }
+}
+
+/// How base64 encoded tile data is compressed.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Compression {
+    Zlib,
+    Gzip,
+    Zstd,
+}
+
+impl FromStr for Compression {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<Compression, TiledError> {
+        match s {
+            "zlib" => Ok(Self::Zlib),
+            "gzip" => Ok(Self::Gzip),
+            "zstd" => Ok(Self::Zstd),
+            _ => Err(TiledError::MalformedAttributes(format!(
+                "Invalid compression '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::Zlib => write!(f, "zlib"),
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Zstd => write!(f, "zstd"),
         }
     }
 }
@@ -51,25 +168,41 @@ pub struct Layer {
     pub offset_y: f32,
     pub parallax_x: f32,
     pub parallax_y: f32,
+    /// Colour multiplied with everything drawn in the layer.
+    pub tint_colour: Option<Colour>,
+    /// The class of the layer, added in Tiled 1.9.
+    pub user_type: Option<String>,
     /// The tiles are arranged in rows. Each tile is a number which can be used
     ///  to find which tileset it belongs to and can then be rendered.
+    ///
+    /// Finite layers always have as many rows and columns as the map, with empty tiles where
+    /// the data is missing or too short, except layers declaring a width or height of 0, which
+    /// have no rows.
     pub tiles: LayerData,
+    /// The encoding the tile data was stored with in the file, or `None` for the legacy format
+    /// with one XML element per tile.
+    pub encoding: Option<Encoding>,
+    /// The compression the tile data was stored with in the file, if any.
+    pub compression: Option<Compression>,
     pub properties: Properties,
     pub layer_index: u32,
     /// The ID of the layer, as shown in the editor.
     /// Layer ID stays the same even if layers are reordered or modified in the editor.
     pub id: u32,
+    /// The index in [`Map::groups`] of the group layer containing this layer, if any.
+    pub parent_group: Option<usize>,
 }
 
 impl Layer {
     pub(crate) fn new<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
-        width: u32,
+        (width, height): (u32, u32),
         layer_index: u32,
         infinite: bool,
+        options: &Loader,
     ) -> Result<Layer, TiledError> {
-        let ((o, v, ox, oy, n, px, py, id), ()) = get_attrs!(
+        let ((o, v, ox, oy, n, px, py, tc, id, lw, lh, ut), ()) = get_attrs!(
             attrs,
             optionals: [
                 ("opacity", opacity, |v:String| v.parse().ok()),
@@ -79,20 +212,42 @@ impl Layer {
                 ("name", name, |v| Some(v)),
                 ("parallaxx", parallax_x, |v:String| v.parse().ok()),
                 ("parallaxy", parallax_y, |v:String| v.parse().ok()),
+                ("tintcolor", tint_colour, Some),
                 ("id", id, |v:String| v.parse().ok()),
+                ("width", layer_width, |v:String| v.parse().ok()),
+                ("height", layer_height, |v:String| v.parse().ok()),
+                ("class", user_type, Some),
             ],
             required: [],
             // this error should never happen since there are no required attrs
             TiledError::MalformedAttributes("layer parsing error".to_string())
         );
+        let tc = tc.map(|c: String| c.parse::<Colour>()).transpose()?;
+        // Some tools write layers without tiles as zero-sized layers.
+        let zero_sized = !infinite && (lw == Some(0u32) || lh == Some(0u32));
         let mut tiles: LayerData = LayerData::Finite(Default::default());
+        let mut encoding = None;
+        let mut compression = None;
         let mut properties = HashMap::new();
         parse_tag!(parser, "layer", {
-            "data" => |attrs| {
-                if infinite {
-                    tiles = parse_infinite_data(parser, attrs, width)?;
+            "data" => |attrs: Vec<OwnedAttribute>| {
+                let (e, c) = get_attrs!(
+                    attrs,
+                    optionals: [
+                        ("encoding", encoding, |v:String| v.parse().ok()),
+                        ("compression", compression, |v:String| v.parse().ok()),
+                    ],
+                    required: [],
+                    TiledError::MalformedAttributes("data parsing error".to_string())
+                ).0;
+                encoding = e;
+                compression = c;
+                if zero_sized {
+                    parse_tag!(parser, "data", { "" => |_| Ok(()) });
+                } else if infinite {
+                    tiles = parse_infinite_data(parser, attrs, width, options)?;
                 } else {
-                    tiles = parse_data(parser, attrs, width)?;
+                    tiles = parse_data(parser, attrs, width, height)?;
                 }
                 Ok(())
             },
@@ -101,6 +256,14 @@ impl Layer {
                 Ok(())
             },
         });
+        if let LayerData::Finite(rows) = &mut tiles {
+            if !zero_sized {
+                rows.resize(height as usize, Vec::new());
+                for row in rows.iter_mut() {
+                    row.resize(width as usize, LayerTile::new(0));
+                }
+            }
+        }
 
         Ok(Layer {
             name: n.unwrap_or(String::new()),
@@ -110,19 +273,632 @@ impl Layer {
             offset_y: oy.unwrap_or(0.0),
             parallax_x: px.unwrap_or(1.0),
             parallax_y: py.unwrap_or(1.0),
+            tint_colour: tc,
+            user_type: ut,
             tiles: tiles,
+            encoding,
+            compression,
             properties: properties,
             layer_index,
             id: id.unwrap_or(0),
+            parent_group: None,
+        })
+    }
+
+    /// Returns the tile at the given coordinates, or `None` if they are outside of the layer.
+    /// Empty cells are returned as tiles with a gid of 0. See [`LayerData::get_tile`].
+    pub fn get_tile(&self, x: i32, y: i32) -> Option<LayerTile> {
+        self.tiles.get_tile(x, y)
+    }
+
+    /// Sets the tile at the given coordinates. See [`LayerData::set_tile`].
+    pub fn set_tile(&mut self, x: i32, y: i32, tile: LayerTile) -> bool {
+        self.tiles.set_tile(x, y, tile)
+    }
+
+    /// Copies the tiles inside `rect` into a new grid. Parts of the rectangle outside of the layer
+    /// are filled with empty tiles.
+    pub fn copy_region(&self, rect: TileRect) -> TileGrid {
+        let mut grid = TileGrid::new(rect.width, rect.height);
+        for y in 0..rect.height {
+            for x in 0..rect.width {
+                if let Some(tile) = self.get_tile(rect.x + x as i32, rect.y + y as i32) {
+                    grid.set(x, y, tile);
+                }
+            }
+        }
+        grid
+    }
+
+    /// Pastes the non-empty tiles of `stamp` into the layer, with the top-left corner of the
+    /// stamp at the given tile coordinates. Empty tiles of the stamp leave the layer untouched,
+    /// as with the stamp brush of the editor. Tiles falling outside of a finite layer are
+    /// dropped. The gids of the stamp must be valid in the map of the layer.
+    ///
+    /// The pasted tiles don't know their tileset, see [`LayerTile::tileset_index`];
+    /// [`Map::apply_stamp`] looks them up.
+    pub fn apply_stamp(&mut self, x: i32, y: i32, stamp: &TileGrid) {
+        for (row, tiles) in stamp.rows().enumerate() {
+            for (column, tile) in tiles.iter().enumerate() {
+                if tile.gid != 0 {
+                    let tile = LayerTile::new(tile.raw_gid());
+                    self.set_tile(x + column as i32, y + row as i32, tile);
+                }
+            }
+        }
+    }
+
+    /// Sets every tile inside `rect` to `tile`, returning how many were set. Tiles falling
+    /// outside of a finite layer are dropped.
+    ///
+    /// Like with [`Layer::apply_stamp`], the tiles drawn by this and the other drawing methods
+    /// don't know their tileset, see [`LayerTile::tileset_index`].
+    pub fn fill_rect(&mut self, rect: TileRect, tile: LayerTile) -> usize {
+        let mut set = 0;
+        for y in rect.y..rect.y + rect.height as i32 {
+            for x in rect.x..rect.x + rect.width as i32 {
+                if self.set_tile(x, y, tile) {
+                    set += 1;
+                }
+            }
+        }
+        set
+    }
+
+    /// Draws a straight line of `tile` between the tile coordinates `from` and `to`, both
+    /// included, returning how many tiles were set. Tiles falling outside of a finite layer are
+    /// dropped.
+    pub fn line(&mut self, from: (i32, i32), to: (i32, i32), tile: LayerTile) -> usize {
+        // Bresenham's algorithm, stepping along both axes when the error allows it.
+        let (dx, dy) = ((to.0 - from.0).abs(), -(to.1 - from.1).abs());
+        let (step_x, step_y) = ((to.0 - from.0).signum(), (to.1 - from.1).signum());
+        let (mut x, mut y) = from;
+        let mut error = dx + dy;
+        let mut set = 0;
+        loop {
+            if self.set_tile(x, y, tile) {
+                set += 1;
+            }
+            if (x, y) == to {
+                return set;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Replaces the tile at the given coordinates, and every tile connected to it through their
+    /// sides that is the same tile with the same flip flags, with `tile`, like the bucket fill
+    /// tool of the editor. Infinite layers are only filled inside of their existing chunks.
+    /// Returns how many tiles were set: 0 if the coordinates are outside of the layer or the tile
+    /// there already is `tile`.
+    pub fn flood_fill(&mut self, x: i32, y: i32, tile: LayerTile) -> usize {
+        let target = match self.get_tile(x, y) {
+            Some(target) if target != tile => target,
+            _ => return 0,
+        };
+        let mut set = 0;
+        let mut pending = vec![(x, y)];
+        while let Some((x, y)) = pending.pop() {
+            if self.get_tile(x, y) != Some(target) || !self.set_tile(x, y, tile) {
+                continue;
+            }
+            set += 1;
+            pending.extend_from_slice(&[(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)]);
+        }
+        set
+    }
+
+    /// Replaces every tile with the gid `from` by the tile with the gid `to`, keeping how each
+    /// of them is flipped. Returns how many tiles were replaced.
+    pub fn replace(&mut self, from: Gid, to: Gid) -> usize {
+        if from == to {
+            return 0;
+        }
+        let mut replaced = 0;
+        for cell in self.tiles.tiles_mut() {
+            if cell.gid == from.0 {
+                *cell = LayerTile::new(to.0 | (cell.raw_gid() & ALL_FLIP_FLAGS));
+                replaced += 1;
+            }
+        }
+        replaced
+    }
+
+    /// Iterates over the chunks of an infinite layer, along with the tile coordinates of their
+    /// top-left corners, sorted by row and then column. Finite layers have no chunks.
+    pub fn chunks(&self) -> impl Iterator<Item = ((i32, i32), &Chunk)> {
+        let mut chunks: Vec<((i32, i32), &Chunk)> = match &self.tiles {
+            LayerData::Finite(_) => Vec::new(),
+            LayerData::Infinite(chunks) => chunks.iter().map(|(&pos, c)| (pos, c)).collect(),
+        };
+        chunks.sort_by_key(|&((x, y), _)| (y, x));
+        chunks.into_iter()
+    }
+
+    /// Returns the smallest rectangle of tiles containing every non-empty tile of the layer, or
+    /// `None` if the layer is empty. Unlike the map size, this is meaningful for infinite maps.
+    pub fn used_bounds(&self) -> Option<TileRect> {
+        let mut bounds: Option<TileRect> = None;
+        let mut add = |x: i32, y: i32| {
+            let tile = TileRect {
+                x,
+                y,
+                width: 1,
+                height: 1,
+            };
+            bounds = Some(bounds.map_or(tile, |b| b.union(&tile)));
+        };
+        match &self.tiles {
+            LayerData::Finite(rows) => {
+                for (y, row) in rows.iter().enumerate() {
+                    for (x, tile) in row.iter().enumerate() {
+                        if tile.gid != 0 {
+                            add(x as i32, y as i32);
+                        }
+                    }
+                }
+            }
+            LayerData::Infinite(chunks) => {
+                for chunk in chunks.values() {
+                    for ((x, y), tile) in chunk.tiles() {
+                        if tile.gid != 0 {
+                            add(x, y);
+                        }
+                    }
+                }
+            }
+        }
+        bounds
+    }
+
+    /// Like [`Map::tile_at_pixel`], but taking the offset of this layer into account.
+    pub fn tile_at_pixel(&self, map: &Map, x: f32, y: f32) -> Option<(i32, i32)> {
+        map.tile_at_pixel(x - self.offset_x, y - self.offset_y)
+    }
+
+    /// Builds a grid telling which tiles of the layer match `predicate`, such as tiles with a
+    /// `solid` property or tiles of a collision tileset, for pathfinding or collision checks that
+    /// don't need the tiles themselves. Empty cells and tiles of unknown tilesets never match.
+    ///
+    /// The grid covers the whole layer for finite layers, and the [used
+    /// bounds](Layer::used_bounds) of infinite layers.
+    pub fn to_bool_grid(
+        &self,
+        map: &Map,
+        mut predicate: impl FnMut(&ResolvedTile<'_>) -> bool,
+    ) -> BoolGrid {
+        let mut grid = BoolGrid::new(self.extent());
+        for tile in map.resolve_layer(self) {
+            if predicate(&tile) {
+                grid.set(tile.x, tile.y, true);
+            }
+        }
+        grid
+    }
+
+    /// Writes the tiles of the layer as comma-separated gids, one row per line, as the CSV export
+    /// of the editor does. The gids include the flip flags, see [`LayerTile::raw_gid`]. Finite
+    /// layers are written whole, infinite ones within their [used bounds](Layer::used_bounds).
+    pub fn to_csv(&self) -> String {
+        self.region_to_csv(self.extent())
+    }
+
+    /// Like [`Layer::to_csv`], writing the tiles inside `rect`. Cells outside of the layer are
+    /// written as empty tiles.
+    pub fn region_to_csv(&self, rect: TileRect) -> String {
+        let mut csv = String::new();
+        for y in rect.y..rect.y + rect.height as i32 {
+            for x in rect.x..rect.x + rect.width as i32 {
+                if x != rect.x {
+                    csv.push(',');
+                }
+                let gid = self.get_tile(x, y).map_or(0, |tile| tile.raw_gid());
+                csv.push_str(&gid.to_string());
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// The tiles grids built from the layer cover: the whole layer if it is finite, else its used
+    /// bounds.
+    pub(crate) fn extent(&self) -> TileRect {
+        match &self.tiles {
+            LayerData::Finite(rows) => TileRect {
+                x: 0,
+                y: 0,
+                width: rows.first().map_or(0, |row| row.len() as u32),
+                height: rows.len() as u32,
+            },
+            LayerData::Infinite(_) => self.used_bounds().unwrap_or(TileRect {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 0,
+            }),
+        }
+    }
+}
+
+/// The smallest rectangle containing the [extents](Layer::extent) of all `layers`.
+pub(crate) fn combined_extent<'a>(layers: impl IntoIterator<Item = &'a Layer>) -> TileRect {
+    layers
+        .into_iter()
+        .map(Layer::extent)
+        .filter(|extent| extent.width > 0 && extent.height > 0)
+        .reduce(|a, b| a.union(&b))
+        .unwrap_or(TileRect {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
         })
+}
+
+/// A rectangle of tiles, in tile coordinates.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct TileRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TileRect {
+    /// Returns the smallest rectangle containing both rectangles.
+    pub fn union(&self, other: &TileRect) -> TileRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width as i32).max(other.x + other.width as i32);
+        let bottom = (self.y + self.height as i32).max(other.y + other.height as i32);
+        TileRect {
+            x,
+            y,
+            width: (right - x) as u32,
+            height: (bottom - y) as u32,
+        }
+    }
+
+    /// Returns whether the tile at the given coordinates is inside the rectangle.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && x < self.x + self.width as i32
+            && y < self.y + self.height as i32
     }
 }
+
+/// An owned, dense rectangle of tiles, stored row by row.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TileGrid {
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<LayerTile>,
+}
+
+impl TileGrid {
+    /// Creates a grid filled with empty tiles.
+    pub fn new(width: u32, height: u32) -> TileGrid {
+        TileGrid {
+            width,
+            height,
+            tiles: vec![LayerTile::new(0); width as usize * height as usize],
+        }
+    }
+
+    /// Returns the tile at the given coordinates of the grid, or `None` if they are outside of it.
+    pub fn get(&self, x: u32, y: u32) -> Option<LayerTile> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        self.tiles
+            .get(y as usize * self.width as usize + x as usize)
+            .copied()
+    }
+
+    /// Sets the tile at the given coordinates of the grid. Does nothing if they are outside of it.
+    pub fn set(&mut self, x: u32, y: u32, tile: LayerTile) {
+        if x < self.width && y < self.height {
+            self.tiles[y as usize * self.width as usize + x as usize] = tile;
+        }
+    }
+
+    /// Iterates over the rows of the grid.
+    pub fn rows(&self) -> impl Iterator<Item = &[LayerTile]> {
+        self.tiles.chunks(self.width.max(1) as usize)
+    }
+}
+
+/// Tiles cut from several layers of a map at once, to be pasted into a map with
+/// [`Map::apply_stamp`], such as a prefab made of ground and decoration tiles.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TileStamp {
+    /// The names of the layers the tiles were cut from, along with their tiles. All grids have
+    /// the same size.
+    pub layers: Vec<(String, TileGrid)>,
+}
+
+impl TileStamp {
+    /// Copies the tiles inside `rect` from every tile layer of `map`, in the order of
+    /// [`Map::layers`]. See [`Layer::copy_region`].
+    pub fn from_map(map: &Map, rect: TileRect) -> TileStamp {
+        TileStamp {
+            layers: map
+                .layers
+                .iter()
+                .map(|layer| (layer.name.clone(), layer.copy_region(rect)))
+                .collect(),
+        }
+    }
+
+    /// Copies the tiles inside `rect` from a single layer.
+    pub fn from_layer(layer: &Layer, rect: TileRect) -> TileStamp {
+        TileStamp {
+            layers: vec![(layer.name.clone(), layer.copy_region(rect))],
+        }
+    }
+
+    /// The width of the stamp, in tiles.
+    pub fn width(&self) -> u32 {
+        self.layers.first().map_or(0, |(_, grid)| grid.width)
+    }
+
+    /// The height of the stamp, in tiles.
+    pub fn height(&self) -> u32 {
+        self.layers.first().map_or(0, |(_, grid)| grid.height)
+    }
+}
+
+/// A rectangle of tiles reduced to one bit each, as returned by [`Layer::to_bool_grid`].
+///
+/// The bits are packed row by row into 64-bit words, least significant bit first, without padding
+/// between rows: the tile at `(x, y)` of the grid is bit `i % 64` of word `i / 64`, where
+/// `i = y * width + x`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct BoolGrid {
+    /// The tiles the grid covers, in tile coordinates of the layer.
+    pub bounds: TileRect,
+    words: Vec<u64>,
+}
+
+impl BoolGrid {
+    /// Creates a grid covering `bounds`, with every tile unset.
+    pub fn new(bounds: TileRect) -> BoolGrid {
+        let len = bounds.width as usize * bounds.height as usize;
+        BoolGrid {
+            bounds,
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    /// Returns whether the tile at the given layer coordinates is set. Tiles outside of the grid
+    /// are not.
+    pub fn get(&self, x: i32, y: i32) -> bool {
+        match self.index(x, y) {
+            Some(i) => self.words[i / 64] & (1 << (i % 64)) != 0,
+            None => false,
+        }
+    }
+
+    /// Sets or clears the tile at the given layer coordinates. Does nothing if they are outside
+    /// of the grid.
+    pub fn set(&mut self, x: i32, y: i32, value: bool) {
+        if let Some(i) = self.index(x, y) {
+            if value {
+                self.words[i / 64] |= 1 << (i % 64);
+            } else {
+                self.words[i / 64] &= !(1 << (i % 64));
+            }
+        }
+    }
+
+    /// Returns the number of tiles that are set.
+    pub fn count(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    /// Returns the packed bits of the grid.
+    pub fn words(&self) -> &[u64] {
+        &self.words
+    }
+
+    /// Unpacks the grid into one `bool` per tile, row by row.
+    pub fn to_vec(&self) -> Vec<bool> {
+        let len = self.bounds.width as usize * self.bounds.height as usize;
+        (0..len)
+            .map(|i| self.words[i / 64] & (1 << (i % 64)) != 0)
+            .collect()
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if !self.bounds.contains(x, y) {
+            return None;
+        }
+        let (x, y) = ((x - self.bounds.x) as usize, (y - self.bounds.y) as usize);
+        Some(y * self.bounds.width as usize + x)
+    }
+}
+
+/// The tiles of a layer.
+///
+/// All tile coordinates are signed: finite layers are stored densely, as rows starting at (0, 0),
+/// so they simply have nothing at negative coordinates. Infinite layers are stored as chunks,
+/// keyed by the tile coordinates of their top-left corner, which can be negative. Accessing
+/// either through [`LayerData::get_tile`] uses the same coordinates as the editor.
 #[derive(Debug, PartialEq, Clone)]
 pub enum LayerData {
     Finite(Vec<Vec<LayerTile>>),
     Infinite(HashMap<(i32, i32), Chunk>),
 }
 
+impl LayerData {
+    /// Iterates over all tiles of the layer, in no particular order.
+    pub(crate) fn tiles_mut(&mut self) -> Box<dyn Iterator<Item = &mut LayerTile> + '_> {
+        match self {
+            LayerData::Finite(rows) => Box::new(rows.iter_mut().flatten()),
+            LayerData::Infinite(chunks) => {
+                Box::new(chunks.values_mut().flat_map(|c| c.tiles.iter_mut().flatten()))
+            }
+        }
+    }
+
+    /// Returns the tile at the given coordinates, or `None` if they are outside of the finite
+    /// layer or of every chunk of the infinite layer.
+    pub fn get_tile(&self, x: i32, y: i32) -> Option<LayerTile> {
+        match self {
+            LayerData::Finite(rows) => {
+                if x < 0 || y < 0 {
+                    return None;
+                }
+                rows.get(y as usize)?.get(x as usize).copied()
+            }
+            LayerData::Infinite(chunks) => {
+                // Chunks usually share the same size and are aligned to it, in which case the
+                // chunk can be looked up directly.
+                if let Some(first) = chunks.values().next() {
+                    let (w, h) = (first.width as i32, first.height as i32);
+                    if w > 0 && h > 0 {
+                        let key = (x.div_euclid(w) * w, y.div_euclid(h) * h);
+                        if let Some(tile) = chunks.get(&key).and_then(|c| c.get_tile(x, y)) {
+                            return Some(tile);
+                        }
+                    }
+                }
+                chunks.values().find_map(|c| c.get_tile(x, y))
+            }
+        }
+    }
+
+    /// Sets the tile at the given coordinates. Finite layers ignore coordinates outside of them,
+    /// in which case `false` is returned. Infinite layers get a new chunk when needed, aligned to
+    /// the size of the existing chunks (16x16 tiles, as in the editor, if there are none).
+    /// Returns whether the tile was set.
+    pub fn set_tile(&mut self, x: i32, y: i32, tile: LayerTile) -> bool {
+        match self {
+            LayerData::Finite(rows) => {
+                if x < 0 || y < 0 {
+                    return false;
+                }
+                match rows.get_mut(y as usize).and_then(|r| r.get_mut(x as usize)) {
+                    Some(cell) => {
+                        *cell = tile;
+                        true
+                    }
+                    None => false,
+                }
+            }
+            LayerData::Infinite(chunks) => {
+                let (w, h) = chunks
+                    .values()
+                    .next()
+                    .map(|c| (c.width.max(1), c.height.max(1)))
+                    .unwrap_or((16, 16));
+                let existing = chunks
+                    .values_mut()
+                    .find(|c| c.get_tile(x, y).is_some());
+                let chunk = match existing {
+                    Some(chunk) => chunk,
+                    None => {
+                        let key = (
+                            x.div_euclid(w as i32) * w as i32,
+                            y.div_euclid(h as i32) * h as i32,
+                        );
+                        if chunks.contains_key(&key) {
+                            // Chunks of irregular sizes; the aligned one doesn't cover the tile.
+                            return false;
+                        }
+                        chunks.entry(key).or_insert_with(|| Chunk {
+                            x: key.0,
+                            y: key.1,
+                            width: w,
+                            height: h,
+                            tiles: vec![vec![LayerTile::new(0); w as usize]; h as usize],
+                        })
+                    }
+                };
+                chunk.tiles[(y - chunk.y) as usize][(x - chunk.x) as usize] = tile;
+                true
+            }
+        }
+    }
+}
+
+/// A group layer. The layers inside of it are stored with the other layers of the map, and refer
+/// to the group by its index in [`Map::groups`]. The offset, parallax factor, opacity, tint and
+/// visibility of a group apply to all layers inside of it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GroupLayer {
+    pub name: String,
+    pub opacity: f32,
+    pub visible: bool,
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub parallax_x: f32,
+    pub parallax_y: f32,
+    pub tint_colour: Option<Colour>,
+    /// The class of the layer, added in Tiled 1.9.
+    pub user_type: Option<String>,
+    pub properties: Properties,
+    pub layer_index: u32,
+    /// The ID of the layer, as shown in the editor.
+    /// Layer ID stays the same even if layers are reordered or modified in the editor.
+    pub id: u32,
+    /// The index in [`Map::groups`] of the group layer containing this group, if any.
+    pub parent_group: Option<usize>,
+}
+
+impl GroupLayer {
+    /// Reads the attributes of a `<group>` element. Its contents are parsed by the map.
+    pub(crate) fn new(
+        attrs: Vec<OwnedAttribute>,
+        layer_index: u32,
+        parent_group: Option<usize>,
+    ) -> Result<GroupLayer, TiledError> {
+        let ((o, v, ox, oy, n, px, py, tc, id, ut), ()) = get_attrs!(
+            attrs,
+            optionals: [
+                ("opacity", opacity, |v:String| v.parse().ok()),
+                ("visible", visible, |v:String| v.parse().ok().map(|x:i32| x == 1)),
+                ("offsetx", offset_x, |v:String| v.parse().ok()),
+                ("offsety", offset_y, |v:String| v.parse().ok()),
+                ("name", name, Some),
+                ("parallaxx", parallax_x, |v:String| v.parse().ok()),
+                ("parallaxy", parallax_y, |v:String| v.parse().ok()),
+                ("tintcolor", tint_colour, Some),
+                ("id", id, |v:String| v.parse().ok()),
+                ("class", user_type, Some),
+            ],
+            required: [],
+            // this error should never happen since there are no required attrs
+            TiledError::MalformedAttributes("group layer parsing error".to_string())
+        );
+        let tc = tc.map(|c: String| c.parse::<Colour>()).transpose()?;
+        Ok(GroupLayer {
+            name: n.unwrap_or_default(),
+            opacity: o.unwrap_or(1.0),
+            visible: v.unwrap_or(true),
+            offset_x: ox.unwrap_or(0.0),
+            offset_y: oy.unwrap_or(0.0),
+            parallax_x: px.unwrap_or(1.0),
+            parallax_y: py.unwrap_or(1.0),
+            tint_colour: tc,
+            user_type: ut,
+            properties: HashMap::new(),
+            layer_index,
+            id: id.unwrap_or(0),
+            parent_group,
+        })
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct ImageLayer {
     pub name: String,
@@ -130,12 +906,20 @@ pub struct ImageLayer {
     pub visible: bool,
     pub offset_x: f32,
     pub offset_y: f32,
+    pub parallax_x: f32,
+    pub parallax_y: f32,
+    /// Colour multiplied with everything drawn in the layer.
+    pub tint_colour: Option<Colour>,
+    /// The class of the layer, added in Tiled 1.9.
+    pub user_type: Option<String>,
     pub image: Option<Image>,
     pub properties: Properties,
     pub layer_index: u32,
     /// The ID of the layer, as shown in the editor.
     /// Layer ID stays the same even if layers are reordered or modified in the editor.
     pub id: u32,
+    /// The index in [`Map::groups`] of the group layer containing this layer, if any.
+    pub parent_group: Option<usize>,
 }
 
 impl ImageLayer {
@@ -144,7 +928,7 @@ impl ImageLayer {
         attrs: Vec<OwnedAttribute>,
         layer_index: u32,
     ) -> Result<ImageLayer, TiledError> {
-        let ((o, v, ox, oy, n, id), ()) = get_attrs!(
+        let ((o, v, ox, oy, n, px, py, tc, id, ut), ()) = get_attrs!(
             attrs,
             optionals: [
                 ("opacity", opacity, |v:String| v.parse().ok()),
@@ -152,12 +936,17 @@ impl ImageLayer {
                 ("offsetx", offset_x, |v:String| v.parse().ok()),
                 ("offsety", offset_y, |v:String| v.parse().ok()),
                 ("name", name, |v| Some(v)),
+                ("parallaxx", parallax_x, |v:String| v.parse().ok()),
+                ("parallaxy", parallax_y, |v:String| v.parse().ok()),
+                ("tintcolor", tint_colour, Some),
                 ("id", id, |v:String| v.parse().ok()),
+                ("class", user_type, Some),
             ],
             required: [],
             // this error should never happen since there are no required attrs
             TiledError::MalformedAttributes("image layer parsing error".to_string())
         );
+        let tc = tc.map(|c: String| c.parse::<Colour>()).transpose()?;
         let mut properties = HashMap::new();
         let mut image: Option<Image> = None;
         parse_tag!(parser, "imagelayer", {
@@ -176,10 +965,15 @@ impl ImageLayer {
             visible: v.unwrap_or(true),
             offset_x: ox.unwrap_or(0.0),
             offset_y: oy.unwrap_or(0.0),
+            parallax_x: px.unwrap_or(1.0),
+            parallax_y: py.unwrap_or(1.0),
+            tint_colour: tc,
+            user_type: ut,
             image,
             properties,
             layer_index,
             id: id.unwrap_or(0),
+            parent_group: None,
         })
     }
 }
@@ -199,6 +993,7 @@ impl Chunk {
         attrs: Vec<OwnedAttribute>,
         encoding: Option<String>,
         compression: Option<String>,
+        cell_limit: u64,
     ) -> Result<Chunk, TiledError> {
         let ((), (x, y, width, height)) = get_attrs!(
             attrs,
@@ -212,7 +1007,8 @@ impl Chunk {
             TiledError::MalformedAttributes("layer must have a name".to_string())
         );
 
-        let tiles = parse_data_line(encoding, compression, parser, width)?;
+        check_cells(width, height, cell_limit)?;
+        let tiles = parse_data_line(encoding, compression, parser, width, height)?;
 
         Ok(Chunk {
             x,
@@ -222,4 +1018,23 @@ impl Chunk {
             tiles,
         })
     }
+
+    /// Returns the tile at the given coordinates of the layer, or `None` if they are not inside
+    /// of this chunk.
+    pub fn get_tile(&self, x: i32, y: i32) -> Option<LayerTile> {
+        let (x, y) = (x - self.x, y - self.y);
+        if x < 0 || y < 0 {
+            return None;
+        }
+        self.tiles.get(y as usize)?.get(x as usize).copied()
+    }
+
+    /// Iterates over the tiles of the chunk, along with their coordinates in the layer.
+    pub fn tiles(&self) -> impl Iterator<Item = ((i32, i32), LayerTile)> + '_ {
+        self.tiles.iter().enumerate().flat_map(move |(y, row)| {
+            row.iter()
+                .enumerate()
+                .map(move |(x, &tile)| ((self.x + x as i32, self.y + y as i32), tile))
+        })
+    }
 }
@@ -0,0 +1,964 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    fs::File,
+    io::Read,
+    path::{Component, Path, PathBuf},
+    rc::Rc,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use crate::{
+    error::TiledError,
+    map::Map,
+    tileset::Tileset,
+    util::{parse_impl, parse_slice_impl},
+    validation::overlapping_tilesets,
+};
+
+/// How the relative `source` paths of external tilesets, templates and images are turned into
+/// file paths.
+///
+/// The [`Loader`] uses it to find external tilesets and templates. Images aren't loaded by this
+/// crate, but [`PathResolver::resolve`] can be used to find them the same way.
+#[derive(Clone, Default)]
+pub enum PathResolver {
+    /// Sources are relative to the file containing the reference, which is how the editor writes
+    /// them. This is the default.
+    #[default]
+    RelativeToReferencingFile,
+    /// Sources are relative to a project directory, whichever file contains the reference.
+    ProjectRoot(PathBuf),
+    /// Sources are relative to the file containing the reference like with
+    /// [`PathResolver::RelativeToReferencingFile`], but paths are normalized and kept inside of
+    /// an asset directory, the way many engines address assets. The path of the map may be given
+    /// relative to the asset directory or including it, so that maps in subdirectories resolve
+    /// consistently. See [`PathResolver::asset_path`].
+    AssetRoot(PathBuf),
+    /// Sources are resolved by a function, given the path of the file containing the reference
+    /// and the source.
+    Custom(Arc<ResolveFn>),
+}
+
+/// The function of a [`PathResolver::Custom`] resolver.
+pub type ResolveFn = dyn Fn(&Path, &str) -> PathBuf + Send + Sync;
+
+impl PathResolver {
+    /// Creates a [`PathResolver::Custom`] resolver.
+    pub fn custom(resolve: impl Fn(&Path, &str) -> PathBuf + Send + Sync + 'static) -> Self {
+        PathResolver::Custom(Arc::new(resolve))
+    }
+
+    /// Returns the path of the file `source` refers to, when it is referenced by the file at
+    /// `referencing_file`.
+    pub fn resolve(&self, referencing_file: &Path, source: &str) -> PathBuf {
+        match self {
+            PathResolver::RelativeToReferencingFile => referencing_file.with_file_name(source),
+            PathResolver::ProjectRoot(root) => root.join(source),
+            PathResolver::AssetRoot(root) => root.join(asset_path(root, referencing_file, source)),
+            PathResolver::Custom(resolve) => resolve(referencing_file, source),
+        }
+    }
+
+    /// For [`PathResolver::AssetRoot`] resolvers, returns the path of the file `source` refers
+    /// to relative to the asset directory, with forward slashes, such as `tiles/grass.tsx`.
+    /// Paths leaving the asset directory keep their leading `..`. Returns `None` for other
+    /// resolvers.
+    pub fn asset_path(&self, referencing_file: &Path, source: &str) -> Option<String> {
+        match self {
+            PathResolver::AssetRoot(root) => {
+                let path = asset_path(root, referencing_file, source);
+                let components: Vec<_> = path.iter().map(|c| c.to_string_lossy()).collect();
+                Some(components.join("/"))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The normalized path of `source` relative to `root`, when referenced by `referencing_file`,
+/// which is either relative to `root` or starts with it.
+fn asset_path(root: &Path, referencing_file: &Path, source: &str) -> PathBuf {
+    let referencing_file = referencing_file
+        .strip_prefix(root)
+        .unwrap_or(referencing_file);
+    normalize_path(&referencing_file.with_file_name(source))
+}
+
+impl fmt::Debug for PathResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathResolver::RelativeToReferencingFile => write!(f, "RelativeToReferencingFile"),
+            PathResolver::ProjectRoot(root) => f.debug_tuple("ProjectRoot").field(root).finish(),
+            PathResolver::AssetRoot(root) => f.debug_tuple("AssetRoot").field(root).finish(),
+            PathResolver::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// The kind of an external file referenced by a map.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ResourceKind {
+    Tileset,
+    Template,
+}
+
+/// What the [`Loader`] does with external files referenced by absolute paths, such as
+/// `C:/Users/me/tiles.tsx`, which usually only exist on the machine of the author of the map.
+/// Set with [`Loader::with_absolute_sources`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum AbsoluteSources {
+    /// Loading the file fails.
+    Reject,
+    /// Everything but the file name is removed, so the file is looked for next to the file
+    /// referencing it.
+    StripToFileName,
+    /// The path is given to the [`PathResolver`] unchanged. This is the default.
+    #[default]
+    PassThrough,
+}
+
+/// Something unusual about a map or its external files that didn't stop it from loading,
+/// recorded in [`Map::load_warnings`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum LoadWarning {
+    /// A file was referenced by an absolute path, and loaded from `path` as allowed by the
+    /// [`AbsoluteSources`] policy of the loader.
+    AbsoluteSource {
+        kind: ResourceKind,
+        /// The source as written in the referencing file.
+        source: String,
+        /// The path the file was loaded from, relative to the map like [`FileRequest::path`].
+        path: String,
+    },
+    /// A file wasn't found at its path, but was found at `path` by ignoring case, as allowed by
+    /// [`Loader::with_case_insensitive_fallback`].
+    CaseMismatch {
+        kind: ResourceKind,
+        /// The source as written in the referencing file.
+        source: String,
+        /// The path of the file that was loaded instead.
+        path: PathBuf,
+    },
+    /// The gids of two tilesets overlap, so [`Map::get_tileset_by_gid`] only finds one of them
+    /// for the shared gids. Holds their indices in [`Map::tilesets`]. With
+    /// [`Loader::with_gid_validation`], this fails with [`TiledError::OverlappingTilesets`]
+    /// instead.
+    OverlappingTilesets { first: usize, second: usize },
+}
+
+impl fmt::Display for LoadWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadWarning::AbsoluteSource { kind, source, path } => write!(
+                f,
+                "{:?} referenced by absolute path \"{}\" was loaded from \"{}\"",
+                kind, source, path
+            ),
+            LoadWarning::CaseMismatch { kind, source, path } => write!(
+                f,
+                "{:?} \"{}\" was loaded from {:?}, whose case differs",
+                kind, source, path
+            ),
+            LoadWarning::OverlappingTilesets { first, second } => {
+                write!(f, "the gids of tilesets {} and {} overlap", first, second)
+            }
+        }
+    }
+}
+
+/// Whether a source, with its backslashes already replaced, is an absolute path on any platform.
+fn is_absolute_source(source: &str) -> bool {
+    let bytes = source.as_bytes();
+    source.starts_with('/')
+        || (bytes.len() >= 3 && bytes[0].is_ascii_alphabetic() && &bytes[1..3] == b":/")
+}
+
+fn file_name(source: &str) -> &str {
+    source.rsplit('/').next().unwrap_or(source)
+}
+
+/// Looks for the file at `path` ignoring the case of the names of the file and its directories,
+/// for maps made on a case-insensitive file system. Returns the path with the case of the file
+/// system, or `None` if no file matches. If several do, the first one in sorted order is used.
+pub fn find_case_insensitive(path: &Path) -> Option<PathBuf> {
+    let mut found = PathBuf::new();
+    for component in path.components() {
+        let name = match component {
+            Component::Normal(name) => name,
+            component => {
+                found.push(component);
+                continue;
+            }
+        };
+        let exact = found.join(name);
+        if exact.exists() {
+            found = exact;
+            continue;
+        }
+        let dir = if found.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &found
+        };
+        let lowercase = name.to_str()?.to_lowercase();
+        let matching = std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .filter(|entry| entry.to_lowercase() == lowercase)
+            .min()?;
+        found.push(matching);
+    }
+    Some(found).filter(|found| found.is_file())
+}
+
+/// The warnings recorded while loading a map, shared with the function loading its files.
+type Warnings = Rc<RefCell<Vec<LoadWarning>>>;
+
+/// An external file needed while parsing a map, as given to the function of
+/// [`Loader::parse_with_resource_loader`].
+///
+/// Images are never loaded by this crate, so they aren't requested.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FileRequest<'a> {
+    pub kind: ResourceKind,
+    /// The source of the file, as written in the referencing file, with backslashes replaced by
+    /// forward slashes.
+    pub source: &'a str,
+    /// The path of the file containing the reference relative to the map, such as a template
+    /// referencing a tileset, or `None` if the map itself references the file.
+    pub referencing_file: Option<&'a str>,
+    /// The path of the file relative to the map, with `source` resolved against the referencing
+    /// file.
+    pub path: String,
+    /// The path of the file as resolved by the [`PathResolver`] of the loader, if the path of
+    /// the map is known.
+    pub resolved_path: Option<PathBuf>,
+    /// The path of the file relative to the asset directory, if the loader uses a
+    /// [`PathResolver::AssetRoot`] resolver and the path of the map is known.
+    pub asset_path: Option<String>,
+}
+
+impl<'a> FileRequest<'a> {
+    pub(crate) fn new(
+        kind: ResourceKind,
+        referencing_file: Option<&'a str>,
+        source: &'a str,
+    ) -> FileRequest<'a> {
+        // Sources in templates are relative to the template rather than to the map.
+        let path = match referencing_file.and_then(|f| f.rfind('/').map(|i| &f[..=i])) {
+            Some(dir) => format!("{}{}", dir, source),
+            None => source.to_string(),
+        };
+        FileRequest {
+            kind,
+            source,
+            referencing_file,
+            path,
+            resolved_path: None,
+            asset_path: None,
+        }
+    }
+}
+
+/// Configurable entry point for parsing maps.
+///
+/// The free functions of the crate ([`parse`](crate::parse), [`parse_file`](crate::parse_file),
+/// ...) use the default settings. A `Loader` allows enabling additional checks:
+///
+/// ```no_run
+/// use tiled::loader::Loader;
+///
+/// let map = Loader::new()
+///     .with_gid_validation(true)
+///     .parse_file(std::path::Path::new("map.tmx"))
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Loader {
+    validate_gids: bool,
+    pub(crate) path_resolver: PathResolver,
+    pub(crate) tolerant: bool,
+    pub(crate) cell_limit: u64,
+    cancellation: Option<CancellationToken>,
+    absolute_sources: AbsoluteSources,
+    case_insensitive: bool,
+}
+
+/// A flag for abandoning parsing from another thread, set with [`Loader::with_cancellation`].
+///
+/// Clones of a token share the flag, so a clone can be kept to cancel a load running on a
+/// background thread. Parsing then fails with [`TiledError::Cancelled`] before the next tileset,
+/// layer or chunk.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl From<Arc<AtomicBool>> for CancellationToken {
+    /// Uses an existing flag, which cancels parsing once it is `true`.
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        CancellationToken(flag)
+    }
+}
+
+/// The contents of the external files read while parsing, keyed by their path, along with the
+/// tilesets parsed from them.
+///
+/// Passing the same cache to [`Loader::parse_file_cached`] for several maps reads the tilesets
+/// and templates they share only once. Tilesets are also parsed once, and the maps share the same
+/// [`Arc<Tileset>`](Tileset) as long as they reference the tileset with the same `source`, since
+/// the paths of its images are relative to it. The cache can be shared between threads.
+#[derive(Debug, Default)]
+pub struct FileCache {
+    files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+    tilesets: Mutex<HashMap<(PathBuf, String), Arc<Tileset>>>,
+}
+
+impl FileCache {
+    pub fn new() -> FileCache {
+        FileCache::default()
+    }
+
+    /// Number of files in the cache.
+    pub fn len(&self) -> usize {
+        self.files().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files().is_empty()
+    }
+
+    /// Whether the file at `path`, as resolved by the [`PathResolver`], is in the cache.
+    pub fn contains(&self, path: &Path) -> bool {
+        self.files().contains_key(path)
+    }
+
+    /// Removes every file and tileset, so that they are read again the next time they're used.
+    pub fn clear(&self) {
+        self.files().clear();
+        self.tilesets().clear();
+    }
+
+    fn files(&self) -> MutexGuard<'_, HashMap<PathBuf, Vec<u8>>> {
+        // The map is never left half-updated, so it's still usable after a panic.
+        self.files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn tilesets(&self) -> MutexGuard<'_, HashMap<(PathBuf, String), Arc<Tileset>>> {
+        self.tilesets.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+type ResolveRequest<'a> = Box<dyn FnMut(&mut FileRequest<'_>) -> Result<(), TiledError> + 'a>;
+type LoadFile<'a> = Box<dyn FnMut(&FileRequest<'_>) -> Result<Vec<u8>, TiledError> + 'a>;
+
+/// Loads the external files of a map while it is parsed. Requests are resolved according to the
+/// [`Loader`] before they're given to the underlying loader.
+pub(crate) struct ExternalFiles<'a> {
+    resolve: ResolveRequest<'a>,
+    load: LoadFile<'a>,
+    cache: Option<&'a FileCache>,
+}
+
+impl<'a> ExternalFiles<'a> {
+    /// Loads the files requested with `load` as they are, without resolving them or caching.
+    pub(crate) fn new(
+        load: impl FnMut(&FileRequest<'_>) -> Result<Vec<u8>, TiledError> + 'a,
+    ) -> ExternalFiles<'a> {
+        ExternalFiles {
+            resolve: Box::new(|_: &mut FileRequest<'_>| Ok(())),
+            load: Box::new(load),
+            cache: None,
+        }
+    }
+
+    pub(crate) fn load(&mut self, mut request: FileRequest<'_>) -> Result<Vec<u8>, TiledError> {
+        (self.resolve)(&mut request)?;
+        (self.load)(&request)
+    }
+
+    /// Loads the tileset requested and parses it with `parse`, unless the [`FileCache`] already
+    /// holds it for the same file and source.
+    pub(crate) fn load_tileset(
+        &mut self,
+        mut request: FileRequest<'_>,
+        parse: impl FnOnce(&[u8]) -> Result<Tileset, TiledError>,
+    ) -> Result<Arc<Tileset>, TiledError> {
+        (self.resolve)(&mut request)?;
+        let key = match (self.cache, &request.resolved_path) {
+            (Some(cache), Some(path)) => Some((cache, (path.clone(), request.source.to_string()))),
+            _ => None,
+        };
+        if let Some((cache, key)) = &key {
+            if let Some(tileset) = cache.tilesets().get(key) {
+                return Ok(tileset.clone());
+            }
+        }
+        let tileset = Arc::new(parse((self.load)(&request)?.as_slice())?);
+        match key {
+            // Another thread may have parsed it in the meantime, in which case that one is kept.
+            Some((cache, key)) => Ok(cache.tilesets().entry(key).or_insert(tileset).clone()),
+            None => Ok(tileset),
+        }
+    }
+}
+
+/// Files kept in memory, for loading maps along with their external files without touching the
+/// file system, such as in tests or for maps bundled into the executable with `include_bytes!`.
+///
+/// ```
+/// use std::path::Path;
+/// use tiled::loader::{Loader, MemoryReader};
+///
+/// let mut files = MemoryReader::new();
+/// files.insert("maps/level.tmx", &include_bytes!("../assets/tiled_base64_external.tmx")[..]);
+/// files.insert("maps/tilesheet.tsx", &include_bytes!("../assets/tilesheet.tsx")[..]);
+/// let map = files
+///     .parse_map(&Loader::new(), Path::new("maps/level.tmx"))
+///     .unwrap();
+/// assert_eq!(map.tilesets[0].name, "tilesheet");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryReader {
+    files: HashMap<PathBuf, Cow<'static, [u8]>>,
+}
+
+impl MemoryReader {
+    pub fn new() -> MemoryReader {
+        MemoryReader::default()
+    }
+
+    /// Adds the file at `path`, replacing any file already there. `.` and `..` in the path are
+    /// resolved, so `maps/../tilesheet.tsx` is the same file as `tilesheet.tsx`.
+    pub fn insert(&mut self, path: impl AsRef<Path>, data: impl Into<Cow<'static, [u8]>>) {
+        self.files
+            .insert(normalize_path(path.as_ref()), data.into());
+    }
+
+    /// Holds the files of a directory embedded with the `include_dir` crate, under their paths
+    /// relative to it, including those of its subdirectories. The files aren't copied. Requires
+    /// the `include_dir` feature.
+    ///
+    /// ```ignore
+    /// static ASSETS: include_dir::Dir = include_dir::include_dir!("$CARGO_MANIFEST_DIR/assets");
+    ///
+    /// let map = MemoryReader::from_include_dir(&ASSETS).parse_map(&Loader::new(), path)?;
+    /// ```
+    #[cfg(feature = "include_dir")]
+    pub fn from_include_dir(dir: &'static include_dir::Dir<'static>) -> MemoryReader {
+        let mut reader = MemoryReader::new();
+        let mut dirs = vec![dir];
+        while let Some(dir) = dirs.pop() {
+            for file in dir.files() {
+                reader.insert(file.path(), file.contents());
+            }
+            dirs.extend(dir.dirs());
+        }
+        reader
+    }
+
+    /// Holds the files embedded by a type deriving `RustEmbed`, under their paths relative to
+    /// its folder. Requires the `rust-embed` feature.
+    ///
+    /// ```ignore
+    /// #[derive(rust_embed::RustEmbed)]
+    /// #[folder = "assets/"]
+    /// struct Assets;
+    ///
+    /// let map = MemoryReader::from_rust_embed::<Assets>().parse_map(&Loader::new(), path)?;
+    /// ```
+    #[cfg(feature = "rust-embed")]
+    pub fn from_rust_embed<E: rust_embed::RustEmbed>() -> MemoryReader {
+        E::iter()
+            .filter_map(|path| {
+                let file = E::get(&path)?;
+                Some((path.into_owned(), file.data))
+            })
+            .collect()
+    }
+
+    /// Returns the contents of the file at `path`, if there is one.
+    pub fn get(&self, path: &Path) -> Option<&[u8]> {
+        self.files.get(&normalize_path(path)).map(|data| &**data)
+    }
+
+    /// Returns the contents of a requested file, found at its
+    /// [`resolved_path`](FileRequest::resolved_path) if there is one and at its path relative to
+    /// the map otherwise. This is the function to pass to
+    /// [`Loader::parse_with_resource_loader`].
+    pub fn read(&self, request: &FileRequest<'_>) -> Result<Vec<u8>, TiledError> {
+        let path = match &request.resolved_path {
+            Some(path) => path.clone(),
+            None => PathBuf::from(&request.path),
+        };
+        self.get(&path)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| TiledError::Other(format!("File not found in memory: {:?}", path)))
+    }
+
+    /// Parses the map at `path` with `loader`, loading its external files from memory too.
+    pub fn parse_map(&self, loader: &Loader, path: &Path) -> Result<Map, TiledError> {
+        let data = self
+            .get(path)
+            .ok_or_else(|| TiledError::Other(format!("Map file not found: {:?}", path)))?;
+        loader.parse_with_resource_loader(data, Some(path), |request| self.read(request))
+    }
+}
+
+impl<P: AsRef<Path>, D: Into<Cow<'static, [u8]>>> Extend<(P, D)> for MemoryReader {
+    fn extend<I: IntoIterator<Item = (P, D)>>(&mut self, files: I) {
+        for (path, data) in files {
+            self.insert(path, data);
+        }
+    }
+}
+
+impl<P: AsRef<Path>, D: Into<Cow<'static, [u8]>>> std::iter::FromIterator<(P, D)> for MemoryReader {
+    fn from_iter<I: IntoIterator<Item = (P, D)>>(files: I) -> MemoryReader {
+        let mut reader = MemoryReader::new();
+        reader.extend(files);
+        reader
+    }
+}
+
+/// Embeds files into the executable and returns a [`MemoryReader`] holding them, so maps can be
+/// shipped inside a single binary. The first argument is the directory of the files, relative to
+/// the source file using the macro like with `include_bytes!`. The files are stored under their
+/// paths relative to that directory.
+///
+/// ```
+/// use std::path::Path;
+/// use tiled::loader::Loader;
+///
+/// let files = tiled::include_files!("../assets", "tiled_base64_external.tmx", "tilesheet.tsx");
+/// let map = files
+///     .parse_map(&Loader::new(), Path::new("tiled_base64_external.tmx"))
+///     .unwrap();
+/// assert_eq!(map.tilesets[0].name, "tilesheet");
+/// ```
+#[macro_export]
+macro_rules! include_files {
+    ($dir:literal, $($file:literal),+ $(,)?) => {{
+        let mut files = $crate::loader::MemoryReader::new();
+        $(
+            files.insert($file, &include_bytes!(concat!($dir, "/", $file))[..]);
+        )+
+        files
+    }};
+}
+
+/// Resolves the `.` and `..` components of a path without looking at the file system.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                _ => normalized.push(".."),
+            },
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Loader {
+            validate_gids: false,
+            path_resolver: PathResolver::default(),
+            tolerant: false,
+            cell_limit: Loader::DEFAULT_CELL_LIMIT,
+            cancellation: None,
+            absolute_sources: AbsoluteSources::default(),
+            case_insensitive: false,
+        }
+    }
+}
+
+impl Loader {
+    /// The default of [`Loader::with_cell_limit`], the cells of an 8192x8192 map.
+    pub const DEFAULT_CELL_LIMIT: u64 = 8192 * 8192;
+
+    pub fn new() -> Loader {
+        Loader::default()
+    }
+
+    /// Sets the largest number of cells, width times height, that finite maps and the chunks of
+    /// infinite maps may have. Larger ones fail with [`TiledError::TooManyCells`], protecting
+    /// against huge allocations when working with the tiles of crafted maps.
+    pub fn with_cell_limit(mut self, limit: u64) -> Loader {
+        self.cell_limit = limit;
+        self
+    }
+
+    /// If enabled, parsing fails with [`TiledError::InvalidGids`] when a layer or object uses a
+    /// gid outside of the range of every tileset, and with [`TiledError::OverlappingTilesets`]
+    /// when the gids of two tilesets overlap. Disabled by default.
+    pub fn with_gid_validation(mut self, enabled: bool) -> Loader {
+        self.validate_gids = enabled;
+        self
+    }
+
+    /// If enabled, external tilesets that can't be loaded or parsed don't abort parsing the map.
+    /// They are replaced by empty placeholder tilesets recording the error in
+    /// [`Tileset::load_error`](crate::tileset::Tileset::load_error). Disabled by default.
+    ///
+    /// Images are never loaded by this crate, so missing images don't cause errors either way.
+    pub fn with_tolerant_mode(mut self, enabled: bool) -> Loader {
+        self.tolerant = enabled;
+        self
+    }
+
+    /// Checks `token` while parsing, failing with [`TiledError::Cancelled`] soon after it is
+    /// cancelled.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Loader {
+        self.cancellation = Some(token);
+        self
+    }
+
+    /// Sets how the sources of external tilesets and templates are turned into file paths, when
+    /// parsing with [`Loader::parse_file`] or [`Loader::parse_with_path`].
+    pub fn with_path_resolver(mut self, path_resolver: PathResolver) -> Loader {
+        self.path_resolver = path_resolver;
+        self
+    }
+
+    /// Sets what happens to external files referenced by absolute paths. Unless they're
+    /// rejected, a [`LoadWarning::AbsoluteSource`] is recorded for each of them.
+    pub fn with_absolute_sources(mut self, policy: AbsoluteSources) -> Loader {
+        self.absolute_sources = policy;
+        self
+    }
+
+    /// If enabled, external files that don't exist are looked for again ignoring case with
+    /// [`find_case_insensitive`], recording a [`LoadWarning::CaseMismatch`] when they're found.
+    /// This only applies to files read from the file system. Disabled by default.
+    pub fn with_case_insensitive_fallback(mut self, enabled: bool) -> Loader {
+        self.case_insensitive = enabled;
+        self
+    }
+
+    /// Parse a file hopefully containing a Tiled map. External files are found with the
+    /// [`PathResolver`], relative to the map file's path by default.
+    pub fn parse_file(&self, path: &Path) -> Result<Map, TiledError> {
+        let file = File::open(path)
+            .map_err(|_| TiledError::Other(format!("Map file not found: {:?}", path)))?;
+        self.parse_with_path(file, path)
+    }
+
+    /// Like [`Loader::parse_file`], but external files are looked up in `cache` first, and added
+    /// to it when they're read.
+    pub fn parse_file_cached(&self, path: &Path, cache: &FileCache) -> Result<Map, TiledError> {
+        let file = File::open(path)
+            .map_err(|_| TiledError::Other(format!("Map file not found: {:?}", path)))?;
+        let warnings = Warnings::default();
+        let load = self.file_loader(Some(path), Some(cache), &warnings);
+        let map = parse_impl(file, load, self)?;
+        self.check(map, &warnings)
+    }
+
+    /// Parses the map files at `paths` on several threads, returning the maps in the same order.
+    /// The maps share a [`FileCache`], so tilesets and templates used by several maps are read
+    /// once. Fails with the error of the first map in `paths` that couldn't be parsed.
+    pub fn load_many<P: AsRef<Path> + Sync>(&self, paths: &[P]) -> Result<Vec<Map>, TiledError> {
+        self.load_many_cached(paths, &FileCache::new())
+    }
+
+    /// Like [`Loader::load_many`], reusing the files already in `cache` and adding the new ones.
+    pub fn load_many_cached<P: AsRef<Path> + Sync>(
+        &self,
+        paths: &[P],
+        cache: &FileCache,
+    ) -> Result<Vec<Map>, TiledError> {
+        let threads = thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .min(paths.len());
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<(usize, Result<Map, TiledError>)> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut parsed = Vec::new();
+                        loop {
+                            let index = next.fetch_add(1, Ordering::Relaxed);
+                            match paths.get(index) {
+                                Some(path) => parsed
+                                    .push((index, self.parse_file_cached(path.as_ref(), cache))),
+                                None => return parsed,
+                            }
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| match worker.join() {
+                    Ok(parsed) => parsed,
+                    Err(panic) => std::panic::resume_unwind(panic),
+                })
+                .collect()
+        });
+        results.sort_by_key(|&(index, _)| index);
+        results.into_iter().map(|(_, map)| map).collect()
+    }
+
+    /// Parse a buffer hopefully containing the contents of a Tiled map stored at `path`, which
+    /// is used to find external files.
+    pub fn parse_with_path<R: Read>(&self, reader: R, path: &Path) -> Result<Map, TiledError> {
+        let warnings = Warnings::default();
+        let map = parse_impl(reader, self.file_loader(Some(path), None, &warnings), self)?;
+        self.check(map, &warnings)
+    }
+
+    /// Parse a buffer hopefully containing the contents of a Tiled map, loading external files
+    /// with `file_loader`. It is given the path of each file relative to the map, regardless of
+    /// the [`PathResolver`].
+    pub fn parse_with_file_loader<R: Read>(
+        &self,
+        reader: R,
+        mut file_loader: impl FnMut(&str) -> Result<Vec<u8>, TiledError>,
+    ) -> Result<Map, TiledError> {
+        let warnings = Warnings::default();
+        let load = |request: &FileRequest<'_>| file_loader(&request.path);
+        let map = parse_impl(reader, self.resolving(None, None, &warnings, load), self)?;
+        self.check(map, &warnings)
+    }
+
+    /// Parse a buffer hopefully containing the contents of a Tiled map, loading external files
+    /// with `resource_loader`. It is given a [`FileRequest`] describing each file. If `map_path`
+    /// is given, the request also holds the path resolved by the [`PathResolver`].
+    ///
+    /// ```
+    /// use tiled::loader::{FileRequest, Loader, ResourceKind};
+    ///
+    /// let map = std::fs::File::open("assets/tiled_base64_external.tmx").unwrap();
+    /// let map = Loader::new()
+    ///     .parse_with_resource_loader(map, None, |request: &FileRequest| {
+    ///         assert_eq!(request.kind, ResourceKind::Tileset);
+    ///         std::fs::read(format!("assets/{}", request.path))
+    ///             .map_err(|e| tiled::error::TiledError::Other(e.to_string()))
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn parse_with_resource_loader<R: Read>(
+        &self,
+        reader: R,
+        map_path: Option<&Path>,
+        resource_loader: impl FnMut(&FileRequest<'_>) -> Result<Vec<u8>, TiledError>,
+    ) -> Result<Map, TiledError> {
+        let warnings = Warnings::default();
+        let load = self.resolving(map_path, None, &warnings, resource_loader);
+        let map = parse_impl(reader, load, self)?;
+        self.check(map, &warnings)
+    }
+
+    /// Parse a buffer hopefully containing the contents of a Tiled map without external files.
+    pub fn parse<R: Read>(&self, reader: R) -> Result<Map, TiledError> {
+        let warnings = Warnings::default();
+        let map = parse_impl(reader, self.file_loader(None, None, &warnings), self)?;
+        self.check(map, &warnings)
+    }
+
+    /// Parse the contents of a Tiled map stored at `path`, which is used to find external files.
+    ///
+    /// Unlike [`Loader::parse_with_path`], the data is parsed in place instead of being read into
+    /// a buffer first, unless it is in an encoding that has to be transcoded. This makes it
+    /// suitable for parsing large maps from a memory-mapped file.
+    pub fn parse_slice_with_path(&self, data: &[u8], path: &Path) -> Result<Map, TiledError> {
+        let warnings = Warnings::default();
+        let map = parse_slice_impl(data, self.file_loader(Some(path), None, &warnings), self)?;
+        self.check(map, &warnings)
+    }
+
+    /// Parses the map file at `path` from a memory mapping of it, with
+    /// [`Loader::parse_slice_with_path`], instead of reading it into a buffer first. This avoids
+    /// holding a copy of the file in memory while parsing very large maps. External files are
+    /// read as usual.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified, by this process or any other, until the function returns.
+    #[cfg(feature = "memmap2")]
+    pub unsafe fn parse_file_mapped(&self, path: &Path) -> Result<Map, TiledError> {
+        let file = File::open(path)
+            .map_err(|_| TiledError::Other(format!("Map file not found: {:?}", path)))?;
+        let data = memmap2::Mmap::map(&file).map_err(|e| {
+            TiledError::Other(format!("Failed to map file: {:?}, error {:?}", path, e))
+        })?;
+        self.parse_slice_with_path(&data, path)
+    }
+
+    /// Parse the contents of a Tiled map without external files in place, like
+    /// [`Loader::parse_slice_with_path`].
+    pub fn parse_slice(&self, data: &[u8]) -> Result<Map, TiledError> {
+        let warnings = Warnings::default();
+        let map = parse_slice_impl(data, self.file_loader(None, None, &warnings), self)?;
+        self.check(map, &warnings)
+    }
+
+    /// Wraps `load` to apply the [`AbsoluteSources`] policy and fill in the
+    /// [`FileRequest::resolved_path`] and [`FileRequest::asset_path`] of the requests, if the
+    /// path of the map is known. Tilesets are looked up in `cache` if there is one.
+    fn resolving<'a>(
+        &self,
+        map_path: Option<&Path>,
+        cache: Option<&'a FileCache>,
+        warnings: &Warnings,
+        load: impl FnMut(&FileRequest<'_>) -> Result<Vec<u8>, TiledError> + 'a,
+    ) -> ExternalFiles<'a> {
+        let resolver = self.path_resolver.clone();
+        let map_path = map_path.map(Path::to_owned);
+        let absolute_sources = self.absolute_sources;
+        let warnings = warnings.clone();
+        let resolve = move |request: &mut FileRequest<'_>| {
+            let absolute = is_absolute_source(request.source);
+            match absolute_sources {
+                AbsoluteSources::Reject if absolute => {
+                    return Err(TiledError::Other(format!(
+                        "External file referenced by absolute path: {:?}",
+                        request.source
+                    )));
+                }
+                AbsoluteSources::StripToFileName => {
+                    // Files referenced by a stripped template are looked for next to it.
+                    let referencing_file = request.referencing_file.map(|f| {
+                        if is_absolute_source(f) {
+                            file_name(f)
+                        } else {
+                            f
+                        }
+                    });
+                    let source = if absolute {
+                        file_name(request.source)
+                    } else {
+                        request.source
+                    };
+                    let original = request.source;
+                    *request = FileRequest::new(request.kind, referencing_file, source);
+                    request.source = original;
+                }
+                _ => {}
+            }
+            if absolute {
+                warnings.borrow_mut().push(LoadWarning::AbsoluteSource {
+                    kind: request.kind,
+                    source: request.source.to_string(),
+                    path: request.path.clone(),
+                });
+            }
+            if let Some(map_path) = &map_path {
+                let referencing_file = match request.referencing_file {
+                    Some(file) => resolver.resolve(map_path, file),
+                    None => map_path.clone(),
+                };
+                let source = match absolute_sources {
+                    AbsoluteSources::StripToFileName if absolute => file_name(request.source),
+                    _ => request.source,
+                };
+                request.resolved_path = Some(resolver.resolve(&referencing_file, source));
+                request.asset_path = resolver.asset_path(&referencing_file, source);
+            }
+            Ok(())
+        };
+        ExternalFiles {
+            resolve: Box::new(resolve),
+            load: Box::new(load),
+            cache,
+        }
+    }
+
+    fn file_loader<'a>(
+        &self,
+        map_path: Option<&Path>,
+        cache: Option<&'a FileCache>,
+        warnings: &Warnings,
+    ) -> ExternalFiles<'a> {
+        let case_insensitive = self.case_insensitive;
+        let fallback_warnings = warnings.clone();
+        self.resolving(map_path, cache, warnings, move |request: &FileRequest<'_>| {
+            let mut path = request.resolved_path.as_ref().ok_or_else(|| {
+                TiledError::Other(
+                    "Maps with external files must know their file location.  See parse_with_path(Path)."
+                        .to_string(),
+                )
+            })?;
+            let found;
+            if case_insensitive && !path.exists() {
+                if let Some(path_found) = find_case_insensitive(path) {
+                    fallback_warnings
+                        .borrow_mut()
+                        .push(LoadWarning::CaseMismatch {
+                            kind: request.kind,
+                            source: request.source.to_string(),
+                            path: path_found.clone(),
+                        });
+                    found = path_found;
+                    path = &found;
+                }
+            }
+            if let Some(data) = cache.and_then(|c| c.files().get(path).cloned()) {
+                return Ok(data);
+            }
+            let data = std::fs::read(path).map_err(|e| {
+                TiledError::Other(format!(
+                    "Failed to read external file: {:?}, error {:?}",
+                    path, e
+                ))
+            })?;
+            if let Some(cache) = cache {
+                cache.files().insert(path.clone(), data.clone());
+            }
+            Ok(data)
+        })
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<(), TiledError> {
+        match &self.cancellation {
+            Some(token) if token.is_cancelled() => Err(TiledError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    fn check(&self, mut map: Map, warnings: &Warnings) -> Result<Map, TiledError> {
+        map.load_warnings = warnings.take();
+        let overlapping = overlapping_tilesets(&map);
+        if self.validate_gids {
+            if let Some(&(first, second)) = overlapping.first() {
+                return Err(TiledError::OverlappingTilesets { first, second });
+            }
+            let invalid = map.validate_gids();
+            if !invalid.is_empty() {
+                return Err(TiledError::InvalidGids(invalid));
+            }
+        }
+        map.load_warnings.extend(
+            overlapping
+                .into_iter()
+                .map(|(first, second)| LoadWarning::OverlappingTilesets { first, second }),
+        );
+        Ok(map)
+    }
+}
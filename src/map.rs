@@ -1,17 +1,40 @@
-use std::{collections::HashMap, fmt, io::Read, path::Path, str::FromStr};
+use std::{
+    collections::HashMap,
+    fmt,
+    io::Read,
+    str::FromStr,
+    sync::Arc,
+};
 
 use xml::{attribute::OwnedAttribute, EventReader};
 
 use crate::{
+    animation::{frame_at, Frame, TilesetAnimations},
     error::{ParseTileError, TiledError},
-    layers::{ImageLayer, Layer},
-    objects::ObjectGroup,
-    properties::{parse_properties, Colour, Properties},
-    tileset::Tileset,
+    hex::HexLayout,
+    ids::{Gid, TileId},
+    journal::{Change, Journal},
+    layers::{
+        combined_extent, GroupLayer, ImageLayer, Layer, LayerData, LayerTile, TileRect, TileStamp,
+    },
+    loader::{ExternalFiles, LoadWarning, Loader},
+    memory::{memory_usage, MemoryUsage},
+    objects::{MapObject, ObjectGroup, Objects},
+    project::Project,
+    projection::{pixel_to_tile, tile_to_pixel},
+    properties::{parse_properties, Colour, Properties, PropertyValue},
+    template::Template,
+    tileset::{GidResolver, GidTable, MapTileset, TileImage, TileUsage, Tileset},
     util::*,
+    validation::{
+        validate, validate_against, validate_gids, InvalidGid, SchemaViolation, ValidationWarning,
+    },
 };
 
 /// All Tiled files will be parsed into this. Holds all the layers and tilesets
+///
+/// Like every parsed type of this crate, maps are `Send` and `Sync`, so they can be parsed on a
+/// background thread and used on another one.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Map {
     pub version: String,
@@ -22,26 +45,52 @@ pub struct Map {
     pub height: u32,
     pub tile_width: u32,
     pub tile_height: u32,
-    pub tilesets: Vec<Tileset>,
+    /// The tilesets used by the map, with the gids of their tiles in this map. After changing
+    /// the tilesets directly, call [`Map::update_gid_table`] to keep finding tilesets by gid fast.
+    pub tilesets: Vec<MapTileset>,
     pub layers: Vec<Layer>,
     pub image_layers: Vec<ImageLayer>,
     pub object_groups: Vec<ObjectGroup>,
+    /// The group layers of the map. Layers inside of groups are stored in the lists above like
+    /// all other layers, with their `parent_group` set.
+    pub groups: Vec<GroupLayer>,
     pub properties: Properties,
     pub background_colour: Option<Colour>,
     pub infinite: bool,
+    /// For staggered and hexagonal maps, which axis is staggered.
+    pub stagger_axis: StaggerAxis,
+    /// For staggered and hexagonal maps, whether the odd or even rows/columns are shifted.
+    pub stagger_index: StaggerIndex,
+    /// For hexagonal maps, the length of the side of a hex tile edge, in pixels.
+    pub hex_side_length: Option<u32>,
+    /// The point of the map, in pixels, where layers with a parallax factor are drawn at their
+    /// normal position.
+    pub parallax_origin_x: f32,
+    pub parallax_origin_y: f32,
+    /// Anything unusual about the external files of the map noticed while loading it, which
+    /// didn't prevent it from loading.
+    pub load_warnings: Vec<LoadWarning>,
+    pub(crate) gid_table: GidTable,
+    pub(crate) journal: Journal,
 }
 
 impl Map {
     pub(crate) fn new<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
-        mut external_file_loader: impl FnMut(&str)->Result<Vec<u8>, TiledError>,
+        mut external_file_loader: ExternalFiles<'_>,
+        options: &Loader,
     ) -> Result<Map, TiledError> {
-        let ((c, infinite), (v, o, w, h, tw, th)) = get_attrs!(
+        let ((c, infinite, sa, si, hsl, pox, poy), (v, o, w, h, tw, th)) = get_attrs!(
             attrs,
             optionals: [
-                ("backgroundcolor", colour, |v:String| v.parse().ok()),
+                ("backgroundcolor", colour, Some),
                 ("infinite", infinite, |v:String| Some(v == "1")),
+                ("staggeraxis", stagger_axis, |v:String| v.parse().ok()),
+                ("staggerindex", stagger_index, |v:String| v.parse().ok()),
+                ("hexsidelength", hex_side_length, |v:String| v.parse().ok()),
+                ("parallaxoriginx", parallax_origin_x, |v:String| v.parse().ok()),
+                ("parallaxoriginy", parallax_origin_y, |v:String| v.parse().ok()),
             ],
             required: [
                 ("version", version, |v| Some(v)),
@@ -53,26 +102,52 @@ impl Map {
             ],
             TiledError::MalformedAttributes("map must have a version, width and height with correct types".to_string())
         );
+        let c = c.map(|c: String| c.parse::<Colour>()).transpose()?;
+        let infinite = infinite.unwrap_or(false);
+        // Infinite maps have no size of their own, and are saved with a width and height of 0.
+        let mut sizes = vec![(tw, "map tile width"), (th, "map tile height")];
+        if !infinite {
+            sizes.extend_from_slice(&[(w, "map width"), (h, "map height")]);
+        }
+        for &(size, what) in sizes.iter() {
+            if size == 0 {
+                return Err(TiledError::ZeroSize(what.to_string()));
+            }
+        }
+        if !infinite {
+            check_cells(w, h, options.cell_limit)?;
+        }
 
         let mut tilesets = Vec::new();
-        let mut layers = Vec::new();
-        let mut image_layers = Vec::new();
         let mut properties = HashMap::new();
-        let mut object_groups = Vec::new();
-        let mut layer_index = 0;
+        let mut lists = LayerLists {
+            map_size: (w, h),
+            infinite,
+            ..LayerLists::default()
+        };
         parse_tag!(parser, "map", {
-            "tileset" => |attrs| {
-                tilesets.push(Tileset::new(parser, attrs, &mut external_file_loader)?);
+            "tileset" => |attrs: Vec<OwnedAttribute>| {
+                options.check_cancelled()?;
+                let tileset = match Tileset::parse_map_tileset(
+                    parser,
+                    attrs.clone(),
+                    &mut external_file_loader,
+                ) {
+                    Err(e) if options.tolerant => Tileset::placeholder(&attrs, tw, th, e)?,
+                    result => result?,
+                };
+                tilesets.push(tileset);
                 Ok(())
             },
             "layer" => |attrs| {
-                layers.push(Layer::new(parser, attrs, w, layer_index, infinite.unwrap_or(false))?);
-                layer_index += 1;
+                options.check_cancelled()?;
+                let layer = Layer::new(parser, attrs, (w, h), lists.next_index, infinite, options)?;
+                lists.add_layer(layer, None);
                 Ok(())
             },
             "imagelayer" => |attrs| {
-                image_layers.push(ImageLayer::new(parser, attrs, layer_index)?);
-                layer_index += 1;
+                options.check_cancelled()?;
+                lists.add_image_layer(ImageLayer::new(parser, attrs, lists.next_index)?, None);
                 Ok(())
             },
             "properties" => |_| {
@@ -80,65 +155,634 @@ impl Map {
                 Ok(())
             },
             "objectgroup" => |attrs| {
-                object_groups.push(ObjectGroup::new(parser, attrs, Some(layer_index))?);
-                layer_index += 1;
+                options.check_cancelled()?;
+                let group = ObjectGroup::new(
+                    parser,
+                    attrs,
+                    Some(lists.next_index),
+                    &tilesets,
+                    &mut lists.templates,
+                    &mut external_file_loader,
+                )?;
+                lists.add_object_group(group, None);
                 Ok(())
             },
+            "group" => |attrs| {
+                options.check_cancelled()?;
+                lists.parse_group(parser, attrs, None, &tilesets, &mut external_file_loader, options)
+            },
         });
-        Ok(Map {
+        let mut map = Map {
             version: v,
             orientation: o,
             width: w,
             height: h,
             tile_width: tw,
             tile_height: th,
+            gid_table: GidTable::new(&tilesets),
+            journal: Journal::default(),
             tilesets,
-            layers,
-            image_layers,
-            object_groups,
+            layers: lists.layers,
+            image_layers: lists.image_layers,
+            object_groups: lists.object_groups,
+            groups: lists.groups,
             properties,
             background_colour: c,
-            infinite: infinite.unwrap_or(false),
-        })
+            infinite,
+            stagger_axis: sa.unwrap_or(StaggerAxis::Y),
+            stagger_index: si.unwrap_or(StaggerIndex::Odd),
+            hex_side_length: hsl,
+            parallax_origin_x: pox.unwrap_or(0.0),
+            parallax_origin_y: poy.unwrap_or(0.0),
+            load_warnings: Vec::new(),
+        };
+        map.resolve_tiles();
+        Ok(map)
     }
 
     /// This function will return the correct Tileset given a GID.
-    pub fn get_tileset_by_gid(&self, gid: u32) -> Option<&Tileset> {
-        let mut maximum_gid: i32 = -1;
-        let mut maximum_ts = None;
-        for tileset in self.tilesets.iter() {
-            if tileset.first_gid as i32 > maximum_gid && tileset.first_gid <= gid {
-                maximum_gid = tileset.first_gid as i32;
-                maximum_ts = Some(tileset);
+    ///
+    /// The tilesets are found with a binary search in a table sorted by first gid.
+    pub fn get_tileset_by_gid(&self, gid: u32) -> Option<&MapTileset> {
+        let (index, _) = self.gid_table.find(&self.tilesets, gid)?;
+        Some(&self.tilesets[index])
+    }
+
+    /// Adds a tileset to the map, keeping the table for finding tilesets by gid up to date.
+    pub fn add_tileset(&mut self, tileset: MapTileset) {
+        self.tilesets.push(tileset);
+        self.update_gid_table();
+    }
+
+    /// Rebuilds the table used for finding tilesets by gid, and the tilesets stored on the tiles
+    /// of the layers, after [`Map::tilesets`] was changed directly. Until then,
+    /// [`Map::get_tileset_by_gid`] still returns the right tileset as long as the number of
+    /// tilesets changed, but has to look through all of them.
+    pub fn update_gid_table(&mut self) {
+        self.gid_table = GidTable::new(&self.tilesets);
+        self.resolve_tiles();
+    }
+
+    /// Stores the tileset of each tile on the tiles of all layers.
+    fn resolve_tiles(&mut self) {
+        let mut resolver = GidResolver::new(&self.gid_table, &self.tilesets);
+        for layer in self.layers.iter_mut() {
+            for tile in layer.tiles.tiles_mut() {
+                tile.resolved = resolver
+                    .resolve(tile.gid)
+                    .map(|(index, id)| (index as u32, id));
             }
         }
-        maximum_ts
+    }
+
+    /// Returns the tileset of a tile of one of the layers of the map. Tiles of parsed layers know
+    /// the index of their tileset, so it is returned without looking it up by gid; see
+    /// [`LayerTile::tileset_index`].
+    pub fn tileset_of(&self, tile: &LayerTile) -> Option<&MapTileset> {
+        match tile.tileset_index() {
+            Some(index) if index < self.tilesets.len() => Some(&self.tilesets[index]),
+            _ => self.get_tileset_by_gid(tile.gid),
+        }
+    }
+
+    /// Finds the tileset of every non-empty tile of `layer`, row by row, and the chunks of
+    /// infinite layers in the order of [`Layer::chunks`]. Like with
+    /// [`Map::get_tileset_by_gid`], the tileset of a tile is the one with the highest first gid
+    /// not above the gid of the tile, and tiles with a gid below all of them are left out.
+    ///
+    /// The tilesets stored on the tiles are used when there are any. Otherwise, neighboring tiles
+    /// usually come from the same tileset, which is then reused without searching for it again,
+    /// making this faster than calling [`Map::get_tileset_by_gid`] for each tile.
+    pub fn resolve_layer(&self, layer: &Layer) -> Vec<ResolvedTile<'_>> {
+        let mut resolved = Vec::new();
+        let mut resolver = GidResolver::new(&self.gid_table, &self.tilesets);
+        let mut resolve = |x: i32, y: i32, tile: LayerTile| {
+            let found = match tile.resolved {
+                Some((index, id)) if (index as usize) < self.tilesets.len() => {
+                    Some((index as usize, id))
+                }
+                _ => resolver.resolve(tile.gid),
+            };
+            if let Some((index, id)) = found {
+                resolved.push(ResolvedTile {
+                    x,
+                    y,
+                    tile,
+                    tileset: &self.tilesets[index],
+                    tileset_index: index,
+                    id,
+                });
+            }
+        };
+        match &layer.tiles {
+            LayerData::Finite(rows) => {
+                for (y, row) in rows.iter().enumerate() {
+                    for (x, tile) in row.iter().enumerate() {
+                        resolve(x as i32, y as i32, *tile);
+                    }
+                }
+            }
+            LayerData::Infinite(_) => {
+                for (_, chunk) in layer.chunks() {
+                    for ((x, y), tile) in chunk.tiles() {
+                        resolve(x, y, tile);
+                    }
+                }
+            }
+        }
+        resolved
     }
 
     /// Computes the rectangle on the image where the sprite is stored for the given tile ID.
     /// If the ID is not found in any tileset, or if there is no image associated with the tileset, `None` is returned.
+    /// `None` is also returned if the tile size of the tileset doesn't fit its image.
     /// On success, returns `Some(x, y, w, h)`, where `(x, y)` is the coordinates of the top-left corner, and `(w, h)` are the width and height of the rectangle
+    #[deprecated(note = "the id is a gid; use `Map::tile_image_source` or `Map::tile_rectangle`")]
     pub fn get_tile_rectangle_by_id(&self, id: u32) -> Option<(u32, u32, u32, u32)> {
-        let tileset = self.get_tileset_by_gid(id)?;
-        let img = tileset.images.get(0)?; // we suppose there is only 1 image per tileset
+        self.tile_rectangle(Gid(id))
+    }
+
+    /// Computes the rectangle on the image of its tileset where the sprite of the tile with the
+    /// given gid is stored. See [`Tileset::tile_rectangle`].
+    pub fn tile_rectangle(&self, gid: Gid) -> Option<(u32, u32, u32, u32)> {
+        let tileset = self.get_tileset_by_gid(gid.0)?;
+        tileset.tile_rectangle(tileset.tile_id(gid)?)
+    }
 
-        let id = id - tileset.first_gid;
-        let columns =
-            img.width as u32 / (tileset.spacing + tileset.tile_width);
+    /// Returns the image the tile with the given gid is drawn from and the rectangle of the tile
+    /// in it, for tilesets with a single image as well as image collection tilesets. See
+    /// [`Tileset::tile_image`].
+    pub fn tile_image_source(&self, gid: Gid) -> Option<TileImage<'_>> {
+        let tileset = self.get_tileset_by_gid(gid.0)?;
+        tileset.tile_image(tileset.tile_id(gid)?)
+    }
+
+    /// Like [`Map::tile_image_source`], but if the tile is animated, returns the image of the
+    /// frame shown `elapsed_ms` milliseconds after the animation started. Animations loop; see
+    /// [`frame_at`](crate::animation::frame_at).
+    pub fn tile_rect_at_time(&self, gid: Gid, elapsed_ms: u64) -> Option<TileImage<'_>> {
+        let tileset = self.get_tileset_by_gid(gid.0)?;
+        let id = tileset.tile_id(gid)?;
+        let frames = tileset.get_tile(id).and_then(|t| t.animation.as_deref());
+        match frames.and_then(|frames| frame_at(frames, elapsed_ms)) {
+            Some((frame, _)) => tileset.tile_image(frame.tile_id),
+            None => tileset.tile_image(id),
+        }
+    }
 
-        // coordinates in tiles
-        let x = id % columns;
-        let y = id.div_euclid(columns);
+    /// Iterates over every object of every object layer of the map, in layer order.
+    ///
+    /// ```no_run
+    /// # let map = tiled::parse_file(std::path::Path::new("map.tmx")).unwrap();
+    /// for spawn in map.objects().with_class("spawn") {
+    ///     println!("{} at {}, {}", spawn.object.name, spawn.object.x, spawn.object.y);
+    /// }
+    /// ```
+    pub fn objects(&self) -> Objects<'_> {
+        let mut groups: Vec<&ObjectGroup> = self.object_groups.iter().collect();
+        groups.sort_by_key(|g| g.layer_index);
+        Objects::new(groups.into_iter().flat_map(|layer| {
+            layer
+                .objects
+                .iter()
+                .map(move |object| MapObject { layer, object })
+        }))
+    }
 
-        // coordinates in pixels
-        let x = tileset.margin + (tileset.tile_width + tileset.spacing) * x;
-        let y = tileset.margin + (tileset.tile_height + tileset.spacing) * y;
+    /// Pastes each layer of `stamp` into the first tile layer with the same name, with the
+    /// top-left corner of the stamp at the given tile coordinates. Layers of the stamp missing
+    /// from the map are skipped. Unlike [`Layer::apply_stamp`], the tileset of each pasted tile
+    /// is looked up and stored on it.
+    ///
+    /// Returns how many layers were stamped.
+    pub fn apply_stamp(&mut self, x: i32, y: i32, stamp: &TileStamp) -> usize {
+        let mut resolver = GidResolver::new(&self.gid_table, &self.tilesets);
+        let mut stamped = 0;
+        for (name, grid) in &stamp.layers {
+            let layer = match self.layers.iter_mut().find(|l| &l.name == name) {
+                Some(layer) => layer,
+                None => continue,
+            };
+            for (row, tiles) in grid.rows().enumerate() {
+                for (column, tile) in tiles.iter().enumerate() {
+                    if tile.gid != 0 {
+                        let mut tile = LayerTile::new(tile.raw_gid());
+                        tile.resolved = resolver
+                            .resolve(tile.gid)
+                            .map(|(index, id)| (index as u32, id));
+                        let (x, y) = (x + column as i32, y + row as i32);
+                        let old = layer.get_tile(x, y).unwrap_or_else(|| LayerTile::new(0));
+                        if layer.set_tile(x, y, tile) && old != tile {
+                            self.journal.record(|| Change::Tile {
+                                layer_id: layer.id,
+                                x,
+                                y,
+                                old,
+                                new: tile,
+                            });
+                        }
+                    }
+                }
+            }
+            stamped += 1;
+        }
+        stamped
+    }
 
-        let w = tileset.tile_width;
-        let h = tileset.tile_height;
+    /// Replaces the tiles of every tile layer and tile object whose gid is a key of
+    /// `replacements` by the tile with the gid it maps to, keeping how each of them is flipped.
+    /// Templates are copied rather than changed when other maps share them. Useful for moving
+    /// levels to an updated tileset, or swapping in another look for the same level.
+    ///
+    /// Every tile is looked up once, so gids can be swapped with each other. Like
+    /// [`Map::prune_tilesets`], the changes aren't recorded by [`Map::track_changes`]. Returns how
+    /// many layer tiles and objects were changed.
+    pub fn replace_gids(&mut self, replacements: &HashMap<Gid, Gid>) -> usize {
+        let mut resolver = GidResolver::new(&self.gid_table, &self.tilesets);
+        let mut replace = |raw: u32| {
+            let tile = LayerTile::new(raw);
+            let gid = replacements
+                .get(&Gid(tile.gid))
+                .filter(|gid| gid.0 != tile.gid)?;
+            let mut tile = LayerTile { gid: gid.0, ..tile };
+            tile.resolved = resolver
+                .resolve(tile.gid)
+                .map(|(index, id)| (index as u32, id));
+            Some(tile)
+        };
+        let mut replaced = 0;
+        for layer in self.layers.iter_mut() {
+            for cell in layer.tiles.tiles_mut() {
+                if let Some(tile) = replace(cell.raw_gid()) {
+                    *cell = tile;
+                    replaced += 1;
+                }
+            }
+        }
+        // Templates are shared by their instances, so each is only replaced once.
+        let mut templates: HashMap<*const Template, Arc<Template>> = HashMap::new();
+        for group in self.object_groups.iter_mut() {
+            for object in group.objects.iter_mut() {
+                if let Some(tile) = replace(object.gid) {
+                    object.gid = tile.raw_gid();
+                    replaced += 1;
+                }
+                if let Some(instance) = &mut object.template {
+                    let template = templates
+                        .entry(Arc::as_ptr(&instance.template))
+                        .or_insert_with(|| match replace(instance.template.object.gid) {
+                            Some(tile) => {
+                                let mut template = Template::clone(&instance.template);
+                                template.object.gid = tile.raw_gid();
+                                Arc::new(template)
+                            }
+                            None => instance.template.clone(),
+                        });
+                    instance.template = template.clone();
+                }
+            }
+        }
+        replaced
+    }
+
+    /// Writes every tile layer as CSV, see [`Layer::to_csv`], returning the name of each layer
+    /// along with its tiles, in the order of [`Map::layers`]. All layers cover the same
+    /// rectangle, so that they line up: the whole map if it is finite, else the union of the
+    /// used bounds of the layers.
+    pub fn layers_to_csv(&self) -> Vec<(String, String)> {
+        let rect = combined_extent(&self.layers);
+        self.layers
+            .iter()
+            .map(|layer| (layer.name.clone(), layer.region_to_csv(rect)))
+            .collect()
+    }
+
+    /// Returns the objects of all object layers that have the property `name` set to `value`,
+    /// in the order of [`Map::objects`]. Unlike [`Objects::with_property`], properties tile
+    /// objects get from their tile or tileset count, as given by [`Object::resolved_property`].
+    ///
+    /// [`Object::resolved_property`]: crate::objects::Object::resolved_property
+    pub fn find_objects_with_property(
+        &self,
+        name: impl Into<String>,
+        value: PropertyValue,
+    ) -> Objects<'_> {
+        let name = name.into();
+        Objects::new(
+            self.objects()
+                .filter(move |o| o.object.resolved_property(self, &name) == Some(&value)),
+        )
+    }
+
+    /// Returns the coordinates of the tile under the given position, in pixels relative to the
+    /// top-left corner of the map. This handles the diamond shapes of isometric and staggered
+    /// maps, so it can be used for mouse picking. The coordinates are returned even if they lie
+    /// outside of the map.
+    ///
+    /// Returns `None` for hexagonal maps, which are not supported yet.
+    pub fn tile_at_pixel(&self, x: f32, y: f32) -> Option<(i32, i32)> {
+        pixel_to_tile(self, x, y)
+    }
+
+    /// Returns the top-left corner of the bounding box of the given tile, in pixels relative to
+    /// the top-left corner of the map. This is where the editor draws the tile, before applying
+    /// layer offsets and the tileset's tile offset.
+    ///
+    /// Returns `None` for hexagonal maps, which are not supported yet.
+    pub fn tile_to_pixel(&self, x: i32, y: i32) -> Option<(f32, f32)> {
+        tile_to_pixel(self, x, y)
+    }
+
+    /// Returns the smallest rectangle of tiles containing every non-empty tile and every object of
+    /// the map, or `None` if there are none. Infinite maps report a size of 0x0, so this is what
+    /// camera clamping and minimaps should use for them.
+    pub fn used_bounds(&self) -> Option<TileRect> {
+        let mut bounds = self
+            .layers
+            .iter()
+            .filter_map(Layer::used_bounds)
+            .reduce(|a, b| a.union(&b));
+        // Objects of isometric maps are positioned in units of the tile height on both axes.
+        let (unit_x, unit_y) = match self.orientation {
+            Orientation::Isometric => (self.tile_height as f32, self.tile_height as f32),
+            _ => (self.tile_width as f32, self.tile_height as f32),
+        };
+        if unit_x <= 0.0 || unit_y <= 0.0 {
+            return bounds;
+        }
+        for group in &self.object_groups {
+            for object in &group.objects {
+                let (min_x, min_y, max_x, max_y) = object.bounds();
+                let x = (min_x / unit_x).floor() as i32;
+                let y = (min_y / unit_y).floor() as i32;
+                let rect = TileRect {
+                    x,
+                    y,
+                    width: ((max_x / unit_x).ceil() as i32 - x).max(1) as u32,
+                    height: ((max_y / unit_y).ceil() as i32 - y).max(1) as u32,
+                };
+                bounds = Some(bounds.map_or(rect, |b| b.union(&rect)));
+            }
+        }
+        bounds
+    }
+
+    /// Returns every gid used by a layer or object that is not part of any tileset of the map.
+    /// Such gids usually come from corrupt files, and would otherwise only show up as
+    /// [`Map::get_tileset_by_gid`] returning the wrong tileset.
+    pub fn validate_gids(&self) -> Vec<InvalidGid> {
+        validate_gids(self)
+    }
+
+    /// Checks the map for duplicate layer names, duplicate object ids, tilesets whose gids
+    /// overlap, gids outside of every tileset and tilesets that don't match their image (see
+    /// [`Tileset::validate_image`]). Maps with such problems can be used, but
+    /// behave unpredictably when looking things up by name, id or gid.
+    pub fn validate(&self) -> Vec<ValidationWarning> {
+        validate(self)
+    }
+
+    /// Checks the classes of the layers, objects and tiles of the map against the custom types
+    /// of `project`, along with the properties they set for the members of their class. Classes
+    /// missing from the project, classes used for things they aren't meant for, properties of
+    /// another type than their member and values missing from the enum of their member are
+    /// reported, in the order of the layers, then objects, then tiles.
+    ///
+    /// Unset members aren't reported, as they take the default value of their class, like in
+    /// the editor.
+    pub fn validate_against(&self, project: &Project) -> Vec<SchemaViolation> {
+        validate_against(self, project)
+    }
+
+    /// Returns the hex grid layout of the map, which converts between its offset coordinates and
+    /// axial coordinates. Only meaningful for hexagonal maps.
+    pub fn hex_layout(&self) -> HexLayout {
+        HexLayout {
+            stagger_axis: self.stagger_axis,
+            stagger_index: self.stagger_index,
+        }
+    }
+
+    /// Returns, for each tileset with animated tiles, the gids of those tiles and their frames.
+    ///
+    /// Cells using one of these gids need to be redrawn as the animation advances, while all
+    /// others can be baked into static geometry.
+    pub fn animated_tiles(&self) -> Vec<TilesetAnimations<'_>> {
+        self.tilesets
+            .iter()
+            .filter_map(|tileset| {
+                let tiles: HashMap<Gid, &[Frame]> = tileset
+                    .tiles
+                    .iter()
+                    .filter_map(|tile| {
+                        let frames = tile.animation.as_deref()?;
+                        Some((tileset.gid(tile.id), frames))
+                    })
+                    .collect();
+                if tiles.is_empty() {
+                    None
+                } else {
+                    Some(TilesetAnimations { tileset, tiles })
+                }
+            })
+            .collect()
+    }
+
+    /// Returns, for each tileset in the order of [`Map::tilesets`], how many times each of its
+    /// tiles is used by the cells of tile layers and by tile objects, to find the tiles that can
+    /// be pruned from bloated tilesets or left out of texture atlases. Tiles only used as
+    /// animation frames of other tiles aren't counted.
+    pub fn tile_usage(&self) -> Vec<TileUsage<'_>> {
+        let mut usage: Vec<TileUsage> = self
+            .tilesets
+            .iter()
+            .map(|tileset| TileUsage {
+                tileset,
+                counts: HashMap::new(),
+            })
+            .collect();
+        for layer in &self.layers {
+            for tile in self.resolve_layer(layer) {
+                *usage[tile.tileset_index].counts.entry(tile.id).or_insert(0) += 1;
+            }
+        }
+        let mut resolver = GidResolver::new(&self.gid_table, &self.tilesets);
+        for group in &self.object_groups {
+            for object in &group.objects {
+                let gid = LayerTile::new(object.gid).gid;
+                if let Some((index, id)) = resolver.resolve(gid) {
+                    *usage[index].counts.entry(id).or_insert(0) += 1;
+                }
+            }
+        }
+        usage
+    }
+
+    /// Writes the map in the Tiled JSON format (`.tmj`), which the Tiled editor can open.
+    ///
+    /// Tilesets are always embedded in the output, since the map does not remember which of them
+    /// were loaded from external files.
+    #[cfg(feature = "json")]
+    pub fn write_json<W: std::io::Write>(&self, writer: W) -> std::io::Result<()> {
+        crate::json_writer::map_to_json(self).write_to(writer)
+    }
+
+    /// Returns a hash of the content of the map, for telling whether a file that was saved again
+    /// actually changed. Files differing only in formatting, such as the order of attributes,
+    /// whitespace or the encoding and compression of the tile data, have the same hash.
+    ///
+    /// The hash is the same on every run and platform, but may change with new versions of this
+    /// crate.
+    pub fn content_hash(&self) -> u64 {
+        crate::hash::hash_map(self)
+    }
+
+    /// Estimates the heap memory used by the map, for each layer and tileset.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        memory_usage(self)
+    }
 
-        Some((x, y, w, h))
+    /// Updates the map to `new`, a newer version of it, replacing only the layers and tilesets
+    /// whose content changed. As long as no layers were added, removed or reordered, the indices
+    /// of all layers stay valid, so anything built from the unchanged layers can be kept.
+    pub fn update_from(&mut self, new: Map) -> crate::reload::MapChanges {
+        crate::reload::update(self, new)
     }
+
+    /// Parses the file at `path` again with `loader` and updates the map with it, like
+    /// [`Map::update_from`]. On error, the map is left unchanged.
+    pub fn reload(
+        &mut self,
+        loader: &Loader,
+        path: &std::path::Path,
+    ) -> Result<crate::reload::MapChanges, TiledError> {
+        let new = loader.parse_file(path)?;
+        Ok(self.update_from(new))
+    }
+}
+
+/// Fails if a grid of `width` by `height` cells is larger than `limit`.
+pub(crate) fn check_cells(width: u32, height: u32, limit: u64) -> Result<(), TiledError> {
+    if width as u64 * height as u64 > limit {
+        return Err(TiledError::TooManyCells {
+            width,
+            height,
+            limit,
+        });
+    }
+    Ok(())
+}
+
+/// The layers of a map while it is being parsed. Group layers are parsed recursively, with the
+/// layers inside of them stored in the same lists as the top-level ones.
+#[derive(Default)]
+struct LayerLists {
+    layers: Vec<Layer>,
+    image_layers: Vec<ImageLayer>,
+    object_groups: Vec<ObjectGroup>,
+    groups: Vec<GroupLayer>,
+    /// The `layer_index` of the next layer, counting layers of all kinds in file order.
+    next_index: u32,
+    /// Width and height of the map in tiles, as needed for parsing tile layers.
+    map_size: (u32, u32),
+    infinite: bool,
+    /// The templates loaded so far, by source, shared by all of their instances.
+    templates: HashMap<String, Arc<Template>>,
+}
+
+impl LayerLists {
+    fn add_layer(&mut self, mut layer: Layer, parent: Option<usize>) {
+        layer.parent_group = parent;
+        self.layers.push(layer);
+        self.next_index += 1;
+    }
+
+    fn add_image_layer(&mut self, mut layer: ImageLayer, parent: Option<usize>) {
+        layer.parent_group = parent;
+        self.image_layers.push(layer);
+        self.next_index += 1;
+    }
+
+    fn add_object_group(&mut self, mut group: ObjectGroup, parent: Option<usize>) {
+        group.parent_group = parent;
+        self.object_groups.push(group);
+        self.next_index += 1;
+    }
+
+    /// Parses a `<group>` element along with all layers inside of it.
+    fn parse_group<R: Read>(
+        &mut self,
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        parent: Option<usize>,
+        tilesets: &[MapTileset],
+        file_loader: &mut ExternalFiles<'_>,
+        options: &Loader,
+    ) -> Result<(), TiledError> {
+        let index = self.groups.len();
+        self.groups
+            .push(GroupLayer::new(attrs, self.next_index, parent)?);
+        self.next_index += 1;
+        let mut properties = HashMap::new();
+        parse_tag!(parser, "group", {
+            "layer" => |attrs| {
+                options.check_cancelled()?;
+                let layer = Layer::new(
+                    parser,
+                    attrs,
+                    self.map_size,
+                    self.next_index,
+                    self.infinite,
+                    options,
+                )?;
+                self.add_layer(layer, Some(index));
+                Ok(())
+            },
+            "imagelayer" => |attrs| {
+                options.check_cancelled()?;
+                let layer = ImageLayer::new(parser, attrs, self.next_index)?;
+                self.add_image_layer(layer, Some(index));
+                Ok(())
+            },
+            "objectgroup" => |attrs| {
+                options.check_cancelled()?;
+                let group = ObjectGroup::new(
+                    parser,
+                    attrs,
+                    Some(self.next_index),
+                    tilesets,
+                    &mut self.templates,
+                    file_loader,
+                )?;
+                self.add_object_group(group, Some(index));
+                Ok(())
+            },
+            "group" => |attrs| {
+                options.check_cancelled()?;
+                self.parse_group(parser, attrs, Some(index), tilesets, file_loader, options)
+            },
+            "properties" => |_| {
+                properties = parse_properties(parser)?;
+                Ok(())
+            },
+        });
+        self.groups[index].properties = properties;
+        Ok(())
+    }
+}
+
+/// A tile of a layer along with its tileset, as returned by [`Map::resolve_layer`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResolvedTile<'map> {
+    /// The tile coordinates of the tile in the layer.
+    pub x: i32,
+    pub y: i32,
+    pub tile: LayerTile,
+    pub tileset: &'map MapTileset,
+    /// The index of the tileset in [`Map::tilesets`].
+    pub tileset_index: usize,
+    /// The id of the tile in its tileset.
+    pub id: TileId,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -173,3 +817,59 @@ impl fmt::Display for Orientation {
         }
     }
 }
+
+/// The axis that is staggered in staggered and hexagonal maps.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum StaggerAxis {
+    X,
+    Y,
+}
+
+impl FromStr for StaggerAxis {
+    type Err = ParseTileError;
+
+    fn from_str(s: &str) -> Result<StaggerAxis, ParseTileError> {
+        match s {
+            "x" => Ok(StaggerAxis::X),
+            "y" => Ok(StaggerAxis::Y),
+            _ => Err(ParseTileError::OrientationError),
+        }
+    }
+}
+
+impl fmt::Display for StaggerAxis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StaggerAxis::X => write!(f, "x"),
+            StaggerAxis::Y => write!(f, "y"),
+        }
+    }
+}
+
+/// Which rows or columns are shifted in staggered and hexagonal maps.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum StaggerIndex {
+    Odd,
+    Even,
+}
+
+impl FromStr for StaggerIndex {
+    type Err = ParseTileError;
+
+    fn from_str(s: &str) -> Result<StaggerIndex, ParseTileError> {
+        match s {
+            "odd" => Ok(StaggerIndex::Odd),
+            "even" => Ok(StaggerIndex::Even),
+            _ => Err(ParseTileError::OrientationError),
+        }
+    }
+}
+
+impl fmt::Display for StaggerIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StaggerIndex::Odd => write!(f, "odd"),
+            StaggerIndex::Even => write!(f, "even"),
+        }
+    }
+}
@@ -0,0 +1,353 @@
+//! Estimating the heap memory used by parsed maps.
+
+use std::{
+    collections::{HashMap, HashSet},
+    mem::size_of,
+};
+
+use crate::{
+    animation::Frame,
+    image::Image,
+    journal::Change,
+    layers::{Chunk, GroupLayer, ImageLayer, Layer, LayerData, LayerTile},
+    loader::LoadWarning,
+    map::Map,
+    objects::{Object, ObjectField, ObjectGroup, ObjectShape, TemplateInstance},
+    properties::{Colour, PropertyValue},
+    template::{Template, TemplateTileset},
+    text::Text,
+    tile::Tile,
+    tileset::{MapTileset, Tileset},
+    wangset::{Terrain, WangColor, WangSet, WangTile},
+};
+
+/// An estimate of the heap memory used by a map, in bytes, returned by [`Map::memory_usage`].
+///
+/// Only memory allocated by the map is counted, not the size of the [`Map`] itself. The sizes of
+/// allocations are estimated from the capacities of strings, vectors and hash maps, without the
+/// overhead of the allocator.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct MemoryUsage {
+    /// The memory of each tile layer, along with its id, in the order of [`Map::layers`].
+    pub layers: Vec<(u32, usize)>,
+    /// The memory of each image layer, along with its id, in the order of [`Map::image_layers`].
+    pub image_layers: Vec<(u32, usize)>,
+    /// The memory of each object group, along with its id, in the order of
+    /// [`Map::object_groups`].
+    pub object_groups: Vec<(u32, usize)>,
+    /// The memory of each tileset, in the order of [`Map::tilesets`]. Tilesets shared with other
+    /// maps are counted in full.
+    pub tilesets: Vec<usize>,
+    /// Everything else: the group layers, the properties and other strings of the map, and the
+    /// lists holding the layers and tilesets.
+    pub other: usize,
+}
+
+impl MemoryUsage {
+    /// The memory used by the whole map.
+    pub fn total(&self) -> usize {
+        self.layers.iter().map(|&(_, size)| size).sum::<usize>()
+            + self
+                .image_layers
+                .iter()
+                .map(|&(_, size)| size)
+                .sum::<usize>()
+            + self
+                .object_groups
+                .iter()
+                .map(|&(_, size)| size)
+                .sum::<usize>()
+            + self.tilesets.iter().sum::<usize>()
+            + self.other
+    }
+}
+
+/// Values owning heap memory.
+trait HeapSize {
+    /// The number of bytes allocated by the value, not counting its own size.
+    fn heap_size(&self) -> usize;
+}
+
+/// Implements [`HeapSize`] for types that don't own heap memory.
+macro_rules! no_heap {
+    ($($t:ty),*) => {
+        $(impl HeapSize for $t {
+            fn heap_size(&self) -> usize {
+                0
+            }
+        })*
+    };
+}
+
+no_heap!(
+    u32,
+    (i32, i32),
+    (f32, f32),
+    LayerTile,
+    Colour,
+    Frame,
+    WangTile,
+    ObjectField
+);
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, HeapSize::heap_size)
+    }
+}
+
+impl<T: HeapSize> HeapSize for Box<T> {
+    fn heap_size(&self) -> usize {
+        size_of::<T>() + (**self).heap_size()
+    }
+}
+
+// Hash tables store a control byte for each bucket next to the entries.
+impl<K: HeapSize, V: HeapSize> HeapSize for HashMap<K, V> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * (size_of::<(K, V)>() + 1)
+            + self
+                .iter()
+                .map(|(k, v)| k.heap_size() + v.heap_size())
+                .sum::<usize>()
+    }
+}
+
+impl<T: HeapSize> HeapSize for HashSet<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * (size_of::<T>() + 1) + self.iter().map(HeapSize::heap_size).sum::<usize>()
+    }
+}
+
+impl HeapSize for PropertyValue {
+    fn heap_size(&self) -> usize {
+        match self {
+            PropertyValue::FloatValue(f) => f.text.heap_size(),
+            PropertyValue::StringValue(s) | PropertyValue::FileValue(s) => s.heap_size(),
+            PropertyValue::Unknown {
+                type_name,
+                raw_value,
+            } => type_name.heap_size() + raw_value.heap_size(),
+            PropertyValue::BoolValue(_)
+            | PropertyValue::IntValue(_)
+            | PropertyValue::ColorValue(_)
+            | PropertyValue::ObjectValue(_) => 0,
+        }
+    }
+}
+
+impl HeapSize for Chunk {
+    fn heap_size(&self) -> usize {
+        self.tiles.heap_size()
+    }
+}
+
+impl HeapSize for LayerData {
+    fn heap_size(&self) -> usize {
+        match self {
+            LayerData::Finite(rows) => rows.heap_size(),
+            LayerData::Infinite(chunks) => chunks.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for Layer {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size()
+            + self.user_type.heap_size()
+            + self.tiles.heap_size()
+            + self.properties.heap_size()
+    }
+}
+
+impl HeapSize for Image {
+    fn heap_size(&self) -> usize {
+        self.source.heap_size()
+            + self.original_source.heap_size()
+            + self.format.heap_size()
+            + self.defining_file.heap_size()
+    }
+}
+
+impl HeapSize for ImageLayer {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size()
+            + self.user_type.heap_size()
+            + self.image.heap_size()
+            + self.properties.heap_size()
+    }
+}
+
+impl HeapSize for GroupLayer {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size() + self.user_type.heap_size() + self.properties.heap_size()
+    }
+}
+
+impl HeapSize for Text {
+    fn heap_size(&self) -> usize {
+        self.contents.heap_size() + self.style.font_family.heap_size()
+    }
+}
+
+impl HeapSize for ObjectShape {
+    fn heap_size(&self) -> usize {
+        match self {
+            ObjectShape::Polyline { points } | ObjectShape::Polygon { points } => {
+                points.heap_size()
+            }
+            ObjectShape::Text { text, .. } => text.heap_size(),
+            ObjectShape::Rect { .. } | ObjectShape::Ellipse { .. } | ObjectShape::Point(..) => 0,
+        }
+    }
+}
+
+impl HeapSize for TemplateTileset {
+    fn heap_size(&self) -> usize {
+        self.source.heap_size()
+    }
+}
+
+impl HeapSize for Template {
+    fn heap_size(&self) -> usize {
+        self.tileset.heap_size() + self.object.heap_size()
+    }
+}
+
+impl HeapSize for TemplateInstance {
+    fn heap_size(&self) -> usize {
+        self.source.heap_size()
+            + self.original_source.heap_size()
+            + self.template.heap_size()
+            + self.overridden_fields.heap_size()
+            + self.own_properties.heap_size()
+    }
+}
+
+impl HeapSize for Object {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size()
+            + self.obj_type.heap_size()
+            + self.shape.heap_size()
+            + self.properties.heap_size()
+            + self.template.heap_size()
+    }
+}
+
+impl HeapSize for ObjectGroup {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size()
+            + self.user_type.heap_size()
+            + self.objects.heap_size()
+            + self.properties.heap_size()
+    }
+}
+
+impl HeapSize for Tile {
+    fn heap_size(&self) -> usize {
+        self.images.heap_size()
+            + self.properties.heap_size()
+            + self.objectgroup.heap_size()
+            + self.animation.heap_size()
+            + self.tile_type.heap_size()
+    }
+}
+
+impl HeapSize for WangColor {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size() + self.properties.heap_size()
+    }
+}
+
+impl HeapSize for Terrain {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size() + self.properties.heap_size()
+    }
+}
+
+impl HeapSize for WangSet {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size()
+            + self.colors.heap_size()
+            + self.wang_tiles.heap_size()
+            + self.properties.heap_size()
+    }
+}
+
+impl HeapSize for Tileset {
+    fn heap_size(&self) -> usize {
+        self.name.heap_size()
+            + self.images.heap_size()
+            + self.tiles.heap_size()
+            + self.properties.heap_size()
+            + self.wang_sets.heap_size()
+            + self.terrain_types.heap_size()
+            + self.load_error.heap_size()
+    }
+}
+
+impl HeapSize for Change {
+    fn heap_size(&self) -> usize {
+        match self {
+            Change::Tile { .. } => 0,
+            Change::ObjectAdded { object, .. } | Change::ObjectRemoved { object, .. } => {
+                object.heap_size()
+            }
+        }
+    }
+}
+
+impl HeapSize for LoadWarning {
+    fn heap_size(&self) -> usize {
+        match self {
+            LoadWarning::AbsoluteSource { source, path, .. } => {
+                source.heap_size() + path.heap_size()
+            }
+            LoadWarning::CaseMismatch { source, path, .. } => source.heap_size() + path.capacity(),
+            LoadWarning::OverlappingTilesets { .. } => 0,
+        }
+    }
+}
+
+impl HeapSize for MapTileset {
+    /// Includes the shared allocation of the tileset, even if other maps use it too.
+    fn heap_size(&self) -> usize {
+        2 * size_of::<usize>() + size_of::<Tileset>() + self.tileset.heap_size()
+    }
+}
+
+/// The memory of each item of `items`, along with its id.
+fn with_ids<T: HeapSize>(items: &[T], id: impl Fn(&T) -> u32) -> Vec<(u32, usize)> {
+    items.iter().map(|i| (id(i), i.heap_size())).collect()
+}
+
+pub(crate) fn memory_usage(map: &Map) -> MemoryUsage {
+    MemoryUsage {
+        layers: with_ids(&map.layers, |l| l.id),
+        image_layers: with_ids(&map.image_layers, |l| l.id),
+        object_groups: with_ids(&map.object_groups, |g| g.id),
+        tilesets: map.tilesets.iter().map(HeapSize::heap_size).collect(),
+        other: map.version.heap_size()
+            + map.groups.heap_size()
+            + map.properties.heap_size()
+            + map.tilesets.capacity() * size_of::<MapTileset>()
+            + map.gid_table.heap_size()
+            + map.journal.changes().map_or(0, HeapSize::heap_size)
+            + map.load_warnings.heap_size()
+            + map.layers.capacity() * size_of::<Layer>()
+            + map.image_layers.capacity() * size_of::<ImageLayer>()
+            + map.object_groups.capacity() * size_of::<ObjectGroup>(),
+    }
+}
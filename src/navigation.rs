@@ -0,0 +1,128 @@
+//! Movement cost grids built from tile layers, in the form grid-based pathfinders such as A* take
+//! as input.
+
+use crate::{
+    layers::{combined_extent, Layer, TileRect},
+    map::{Map, ResolvedTile},
+};
+
+/// The cost of entering each tile of a rectangle, as returned by [`Map::cost_grid`]. Blocked
+/// tiles cost [`f32::INFINITY`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct CostGrid {
+    /// The tiles the grid covers, in tile coordinates.
+    pub bounds: TileRect,
+    /// The costs, row by row.
+    pub costs: Vec<f32>,
+}
+
+impl CostGrid {
+    /// Creates a grid covering `bounds` where every tile costs `cost`.
+    pub fn new(bounds: TileRect, cost: f32) -> CostGrid {
+        CostGrid {
+            bounds,
+            costs: vec![cost; bounds.width as usize * bounds.height as usize],
+        }
+    }
+
+    /// Returns the cost of entering the tile at the given coordinates. Tiles outside of the grid
+    /// are blocked.
+    pub fn get(&self, x: i32, y: i32) -> f32 {
+        self.index(x, y).map_or(f32::INFINITY, |i| self.costs[i])
+    }
+
+    /// Sets the cost of the tile at the given coordinates. Does nothing if they are outside of
+    /// the grid.
+    pub fn set(&mut self, x: i32, y: i32, cost: f32) {
+        if let Some(i) = self.index(x, y) {
+            self.costs[i] = cost;
+        }
+    }
+
+    /// Returns whether the tile at the given coordinates can be entered at all.
+    pub fn is_walkable(&self, x: i32, y: i32) -> bool {
+        self.get(x, y).is_finite()
+    }
+
+    /// Iterates over the walkable tiles next to the given one, along with the cost of moving
+    /// there: the cost of the tile, times √2 for diagonal moves. Diagonal moves are only included
+    /// if `diagonal` is set, and never cut corners past blocked tiles.
+    pub fn neighbours(
+        &self,
+        x: i32,
+        y: i32,
+        diagonal: bool,
+    ) -> impl Iterator<Item = ((i32, i32), f32)> + '_ {
+        const STEPS: [(i32, i32); 8] = [
+            (0, -1),
+            (1, 0),
+            (0, 1),
+            (-1, 0),
+            (1, -1),
+            (1, 1),
+            (-1, 1),
+            (-1, -1),
+        ];
+        let steps = if diagonal { &STEPS[..] } else { &STEPS[..4] };
+        steps.iter().filter_map(move |&(dx, dy)| {
+            let (nx, ny) = (x + dx, y + dy);
+            let mut cost = self.get(nx, ny);
+            if dx != 0 && dy != 0 {
+                if !self.is_walkable(x + dx, y) || !self.is_walkable(x, y + dy) {
+                    return None;
+                }
+                cost *= std::f32::consts::SQRT_2;
+            }
+            if cost.is_finite() {
+                Some(((nx, ny), cost))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if !self.bounds.contains(x, y) {
+            return None;
+        }
+        let (x, y) = ((x - self.bounds.x) as usize, (y - self.bounds.y) as usize);
+        Some(y * self.bounds.width as usize + x)
+    }
+}
+
+impl Map {
+    /// Builds a movement cost grid from the tiles of `layers`.
+    ///
+    /// `cost` maps the tiles to the cost of entering them, using whatever the tiles tell about
+    /// it, such as their properties or tileset; `None` means the tile doesn't affect movement.
+    /// The cost of a cell is the highest one of its tiles in any of the layers, so a blocking tile
+    /// ([`f32::INFINITY`]) on any layer blocks the cell. Cells without any tile that affects
+    /// movement cost `default`.
+    ///
+    /// The grid covers all the layers: the whole map for finite layers, and the used bounds of
+    /// infinite ones.
+    pub fn cost_grid(
+        &self,
+        layers: &[&Layer],
+        default: f32,
+        mut cost: impl FnMut(&ResolvedTile<'_>) -> Option<f32>,
+    ) -> CostGrid {
+        let bounds = combined_extent(layers.iter().copied());
+        let mut highest: Vec<Option<f32>> =
+            vec![None; bounds.width as usize * bounds.height as usize];
+        let mut grid = CostGrid::new(bounds, default);
+        for layer in layers {
+            for tile in self.resolve_layer(layer) {
+                if let (Some(i), Some(cost)) = (grid.index(tile.x, tile.y), cost(&tile)) {
+                    highest[i] = Some(highest[i].map_or(cost, |c| c.max(cost)));
+                }
+            }
+        }
+        for (cell, cost) in grid.costs.iter_mut().zip(highest) {
+            if let Some(cost) = cost {
+                *cell = cost;
+            }
+        }
+        grid
+    }
+}
@@ -1,11 +1,26 @@
-use std::{collections::HashMap, io::Read};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fmt,
+    io::Read,
+    str::FromStr,
+    sync::Arc,
+};
 
 use xml::{attribute::OwnedAttribute, EventReader};
 
 use crate::{
     error::TiledError,
-    properties::{parse_properties, Colour, Properties},
-    util::{get_attrs, parse_tag},
+    ids::Gid,
+    layers::LayerTile,
+    loader::ExternalFiles,
+    map::Map,
+    project::Project,
+    properties::{parse_properties, Colour, Properties, PropertyValue},
+    template::Template,
+    text::Text,
+    tileset::MapTileset,
+    util::{get_attrs, normalize_source, parse_tag},
 };
 
 #[derive(Debug, PartialEq, Clone)]
@@ -15,8 +30,16 @@ pub struct ObjectGroup {
     pub visible: bool,
     pub objects: Vec<Object>,
     pub colour: Option<Colour>,
+    /// Colour multiplied with everything drawn in the layer.
+    pub tint_colour: Option<Colour>,
+    /// The class of the layer, added in Tiled 1.9.
+    pub user_type: Option<String>,
+    pub offset_x: f32,
+    pub offset_y: f32,
     pub parallax_x: f32,
     pub parallax_y: f32,
+    /// The order in which the editor draws the objects of the layer.
+    pub draw_order: DrawOrder,
     /**
      * Layer index is not preset for tile collision boxes
      */
@@ -25,6 +48,8 @@ pub struct ObjectGroup {
     /// The ID of the layer, as shown in the editor.
     /// Layer ID stays the same even if layers are reordered or modified in the editor.
     pub id: u32,
+    /// The index in [`Map::groups`] of the group layer containing this layer, if any.
+    pub parent_group: Option<usize>,
 }
 
 impl ObjectGroup {
@@ -32,27 +57,37 @@ impl ObjectGroup {
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
         layer_index: Option<u32>,
+        tilesets: &[MapTileset],
+        templates: &mut HashMap<String, Arc<Template>>,
+        file_loader: &mut ExternalFiles<'_>,
     ) -> Result<ObjectGroup, TiledError> {
-        let ((o, v, c, n, px, py, id), ()) = get_attrs!(
+        let ((o, v, c, tc, ox, oy, n, px, py, id, d, ut), ()) = get_attrs!(
             attrs,
             optionals: [
                 ("opacity", opacity, |v:String| v.parse().ok()),
                 ("visible", visible, |v:String| v.parse().ok().map(|x:i32| x == 1)),
-                ("color", colour, |v:String| v.parse().ok()),
+                ("color", colour, Some),
+                ("tintcolor", tint_colour, Some),
+                ("offsetx", offset_x, |v:String| v.parse().ok()),
+                ("offsety", offset_y, |v:String| v.parse().ok()),
                 ("name", name, |v:String| v.into()),
                 ("parallaxx", parallax_x, |v:String| v.parse().ok()),
                 ("parallaxy", parallax_y, |v:String| v.parse().ok()),
                 ("id", id, |v:String| v.parse::<u32>().ok()),
+                ("draworder", draw_order, |v:String| v.parse().ok()),
+                ("class", user_type, Some),
             ],
             required: [],
             // this error should never happen since there are no required attrs
             TiledError::MalformedAttributes("object group parsing error".to_string())
         );
+        let c = c.map(|c: String| c.parse::<Colour>()).transpose()?;
+        let tc = tc.map(|c: String| c.parse::<Colour>()).transpose()?;
         let mut objects = Vec::new();
         let mut properties = HashMap::new();
         parse_tag!(parser, "objectgroup", {
             "object" => |attrs| {
-                objects.push(Object::new(parser, attrs)?);
+                objects.push(Object::new(parser, attrs, tilesets, templates, file_loader)?);
                 Ok(())
             },
             "properties" => |_| {
@@ -65,14 +100,81 @@ impl ObjectGroup {
             opacity: o.unwrap_or(1.0),
             visible: v.unwrap_or(true),
             objects: objects,
+            offset_x: ox.unwrap_or(0.0),
+            offset_y: oy.unwrap_or(0.0),
             parallax_x: px.unwrap_or(1.0),
             parallax_y: py.unwrap_or(1.0),
+            draw_order: d.unwrap_or(DrawOrder::TopDown),
             colour: c,
+            tint_colour: tc,
+            user_type: ut,
             layer_index,
             properties,
             id: id.unwrap_or(0),
+            parent_group: None,
         })
     }
+
+    /// Iterates over the objects in the order the editor draws them. With
+    /// [`DrawOrder::TopDown`], they are sorted by their y coordinate, so that objects further
+    /// down cover those above them, and objects at the same y are kept in the order of their
+    /// ids. With [`DrawOrder::Index`], they are drawn in the order of [`ObjectGroup::objects`].
+    pub fn objects_in_draw_order(&self) -> impl Iterator<Item = &Object> {
+        let mut objects: Vec<&Object> = self.objects.iter().collect();
+        if self.draw_order == DrawOrder::TopDown {
+            objects.sort_by(|a, b| {
+                a.y.partial_cmp(&b.y)
+                    .unwrap_or(Ordering::Equal)
+                    .then(a.id.cmp(&b.id))
+            });
+        }
+        objects.into_iter()
+    }
+
+    /// Writes the objects of the layer as a GeoJSON feature collection, to inspect them with
+    /// GIS tools. Coordinates are in pixels like the positions of objects, with y growing
+    /// downwards, and the objects' rotation is applied.
+    ///
+    /// Points become `Point`s and polylines `LineString`s. Polygons, rectangles, text and tile
+    /// objects become `Polygon`s, and ellipses polygons of 32 sides. The properties of each
+    /// feature are the name and class of the object, followed by its own properties sorted by
+    /// name, which take precedence if they are also called `name` or `class`.
+    pub fn to_geojson(&self) -> String {
+        crate::geojson::object_group_to_geojson(self).to_string()
+    }
+}
+
+/// The order in which the objects of an object layer are drawn.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum DrawOrder {
+    /// Sorted by y coordinate, the default.
+    TopDown,
+    /// In the order the objects are stored in the layer.
+    Index,
+}
+
+impl FromStr for DrawOrder {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<DrawOrder, TiledError> {
+        match s {
+            "topdown" => Ok(DrawOrder::TopDown),
+            "index" => Ok(DrawOrder::Index),
+            _ => Err(TiledError::MalformedAttributes(format!(
+                "Invalid draw order '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for DrawOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DrawOrder::TopDown => write!(f, "topdown"),
+            DrawOrder::Index => write!(f, "index"),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -82,6 +184,7 @@ pub enum ObjectShape {
     Polyline { points: Vec<(f32, f32)> },
     Polygon { points: Vec<(f32, f32)> },
     Point(f32, f32),
+    Text { width: f32, height: f32, text: Text },
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -89,6 +192,7 @@ pub struct Object {
     pub id: u32,
     pub gid: u32,
     pub name: String,
+    /// The class of the object, written as `type` before Tiled 1.9 and as `class` since.
     pub obj_type: String,
     pub width: f32,
     pub height: f32,
@@ -98,39 +202,389 @@ pub struct Object {
     pub visible: bool,
     pub shape: ObjectShape,
     pub properties: Properties,
+    /// Set if the object is an instance of a template. All other fields already hold the
+    /// resolved values, with those not set by the instance inherited from the template.
+    pub template: Option<Box<TemplateInstance>>,
+}
+
+/// The fields of an object that a template instance can set itself or inherit from its template.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub enum ObjectField {
+    Name,
+    Type,
+    Gid,
+    Width,
+    Height,
+    Rotation,
+    Visible,
+    Shape,
+}
+
+impl ObjectField {
+    /// All fields, in the order they are inherited: the shape comes last, as it depends on the
+    /// size of the object.
+    pub const ALL: [ObjectField; 8] = [
+        ObjectField::Name,
+        ObjectField::Type,
+        ObjectField::Gid,
+        ObjectField::Width,
+        ObjectField::Height,
+        ObjectField::Rotation,
+        ObjectField::Visible,
+        ObjectField::Shape,
+    ];
+}
+
+/// Information about the template an object was instantiated from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TemplateInstance {
+    /// The path of the template file, with backslashes replaced by forward slashes.
+    pub source: String,
+    /// The path of the template file, as written in the map.
+    pub original_source: String,
+    /// The template, with its tile object's gid already translated to the gids of the map. It is
+    /// shared by all instances of the template in the map.
+    pub template: Arc<Template>,
+    /// The fields the instance sets itself instead of inheriting them from the template.
+    pub overridden_fields: HashSet<ObjectField>,
+    /// The properties set on the instance itself, before merging those of the template.
+    pub own_properties: Properties,
 }
 
 impl Object {
-    fn new<R: Read>(
+    /// Returns the properties of this object as the editor presents them: for tile objects, the
+    /// properties of the tileset and of the tile act as defaults, which the object's own
+    /// properties (including those inherited from its template) override.
+    pub fn resolved_properties(&self, map: &Map) -> Properties {
+        let mut properties = Properties::new();
+        if self.gid != 0 {
+            let gid = LayerTile::new(self.gid).gid;
+            if let Some(tileset) = map.get_tileset_by_gid(gid) {
+                properties.extend(tileset.properties.clone());
+                let tile = tileset.tile_id(Gid(gid)).and_then(|id| tileset.get_tile(id));
+                if let Some(tile) = tile {
+                    properties.extend(tile.properties.clone());
+                }
+            }
+        }
+        properties.extend(self.properties.clone());
+        properties
+    }
+
+    /// Like [`Object::resolved_properties`], with the default values of the members of the
+    /// object's class in `project` below all other properties, so that every member of the class
+    /// is set. Tile objects without a class of their own use the class of their tile, like in
+    /// the editor.
+    pub fn resolved_properties_with_classes(&self, map: &Map, project: &Project) -> Properties {
+        let class = if self.obj_type.is_empty() && self.gid != 0 {
+            let gid = LayerTile::new(self.gid).gid;
+            map.get_tileset_by_gid(gid)
+                .and_then(|tileset| tileset.get_tile(tileset.tile_id(Gid(gid))?))
+                .and_then(|tile| tile.tile_type.as_deref())
+        } else {
+            Some(self.obj_type.as_str())
+        };
+        let mut properties = class.map_or_else(Properties::new, |c| project.class_defaults(c));
+        properties.extend(self.resolved_properties(map));
+        properties
+    }
+
+    /// Returns the value of a single property as [`Object::resolved_properties`] would, without
+    /// merging all of them: the object's own value if it has one, else that of its tile, else
+    /// that of the tile's tileset.
+    pub fn resolved_property<'a>(&'a self, map: &'a Map, name: &str) -> Option<&'a PropertyValue> {
+        if let Some(value) = self.properties.get(name) {
+            return Some(value);
+        }
+        if self.gid == 0 {
+            return None;
+        }
+        let gid = LayerTile::new(self.gid).gid;
+        let tileset = map.get_tileset_by_gid(gid)?;
+        tileset
+            .tile_id(Gid(gid))
+            .and_then(|id| tileset.get_tile(id))
+            .and_then(|tile| tile.properties.get(name))
+            .or_else(|| tileset.properties.get(name))
+    }
+
+    /// Returns the axis-aligned bounding box of the object as `(min_x, min_y, max_x, max_y)`, in
+    /// pixels. Rotation is not taken into account.
+    pub fn bounds(&self) -> (f32, f32, f32, f32) {
+        match &self.shape {
+            ObjectShape::Polyline { points } | ObjectShape::Polygon { points } => {
+                let mut bounds = (self.x, self.y, self.x, self.y);
+                for &(px, py) in points {
+                    bounds.0 = bounds.0.min(self.x + px);
+                    bounds.1 = bounds.1.min(self.y + py);
+                    bounds.2 = bounds.2.max(self.x + px);
+                    bounds.3 = bounds.3.max(self.y + py);
+                }
+                bounds
+            }
+            ObjectShape::Point(x, y) => (*x, *y, *x, *y),
+            // Tile objects are anchored at their bottom-left corner.
+            _ if self.gid != 0 => (self.x, self.y - self.height, self.x + self.width, self.y),
+            _ => (self.x, self.y, self.x + self.width, self.y + self.height),
+        }
+    }
+
+    /// Returns whether the given point, in the same coordinates as the object's position, is
+    /// inside of the object. The object's rotation is taken into account. Polylines and points
+    /// have no area, and are hit within one pixel of them; see
+    /// [`Object::contains_point_with_tolerance`].
+    pub fn contains_point(&self, x: f32, y: f32) -> bool {
+        self.contains_point_with_tolerance(x, y, 1.0)
+    }
+
+    /// Like [`Object::contains_point`], with polylines and points being hit within `tolerance`
+    /// pixels of them.
+    pub fn contains_point_with_tolerance(&self, x: f32, y: f32, tolerance: f32) -> bool {
+        // Bring the point into the object's frame: objects rotate clockwise around their
+        // position.
+        let (sin, cos) = (-self.rotation.to_radians()).sin_cos();
+        let (dx, dy) = (x - self.x, y - self.y);
+        let (lx, ly) = (dx * cos - dy * sin, dx * sin + dy * cos);
+        match &self.shape {
+            ObjectShape::Ellipse { width, height } => {
+                if *width <= 0.0 || *height <= 0.0 {
+                    return false;
+                }
+                let (rx, ry) = (width / 2.0, height / 2.0);
+                let (ex, ey) = ((lx - rx) / rx, (ly - ry) / ry);
+                ex * ex + ey * ey <= 1.0
+            }
+            ObjectShape::Polygon { points } => {
+                let mut inside = false;
+                for (i, &(x1, y1)) in points.iter().enumerate() {
+                    let (x2, y2) = points[(i + 1) % points.len()];
+                    if (y1 > ly) != (y2 > ly) && lx < x1 + (ly - y1) / (y2 - y1) * (x2 - x1) {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+            ObjectShape::Polyline { points } => points
+                .windows(2)
+                .any(|s| distance_to_segment((lx, ly), s[0], s[1]) <= tolerance),
+            ObjectShape::Point(..) => (lx * lx + ly * ly).sqrt() <= tolerance,
+            // Tile objects are anchored at their bottom-left corner.
+            _ if self.gid != 0 => {
+                (0.0..=self.width).contains(&lx) && (-self.height..=0.0).contains(&ly)
+            }
+            ObjectShape::Rect { width, height } | ObjectShape::Text { width, height, .. } => {
+                (0.0..=*width).contains(&lx) && (0.0..=*height).contains(&ly)
+            }
+        }
+    }
+
+    /// Returns whether `field` is set by this object itself rather than inherited from its
+    /// template. Always false for objects that are not template instances.
+    pub fn is_overridden(&self, field: ObjectField) -> bool {
+        matches!(&self.template, Some(t) if t.overridden_fields.contains(&field))
+    }
+
+    /// Returns the fields a template instance inherits from its template but which don't hold
+    /// the values of the template anymore, because the object or its template was changed after
+    /// the map was parsed. Always empty for objects that are not template instances.
+    pub fn template_drift(&self) -> Vec<ObjectField> {
+        let instance = match &self.template {
+            Some(instance) => instance,
+            None => return Vec::new(),
+        };
+        ObjectField::ALL
+            .iter()
+            .copied()
+            .filter(|&field| {
+                !instance.overridden_fields.contains(&field)
+                    && !self.field_matches(field, &instance.template.object)
+            })
+            .collect()
+    }
+
+    /// Sets all fields and properties a template instance inherits from its template back to the
+    /// values of the template. Does nothing for objects that are not template instances.
+    pub fn reapply_template(&mut self) {
+        let instance = match self.template.take() {
+            Some(instance) => instance,
+            None => return,
+        };
+        let template = &instance.template.object;
+        for &field in ObjectField::ALL.iter() {
+            if !instance.overridden_fields.contains(&field) {
+                self.inherit(field, template);
+            }
+        }
+        let mut properties = template.properties.clone();
+        properties.extend(instance.own_properties.clone());
+        self.properties = properties;
+        self.template = Some(instance);
+    }
+
+    /// Makes the object an instance of `template`, found at `source`, and applies it like
+    /// [`Object::reapply_template`]. Template instances keep the fields and properties they set
+    /// themselves. Other objects keep those that differ from the template. The gid of the
+    /// template's tile object must already be one of the map, as for the templates loaded with
+    /// the map.
+    pub fn relink_template(&mut self, source: &str, template: Arc<Template>) {
+        let (overridden_fields, own_properties) = match self.template.take() {
+            Some(instance) => (instance.overridden_fields, instance.own_properties),
+            None => {
+                let overridden = ObjectField::ALL
+                    .iter()
+                    .copied()
+                    .filter(|&field| !self.field_matches(field, &template.object))
+                    .collect();
+                let own = self
+                    .properties
+                    .iter()
+                    .filter(|(k, v)| template.object.properties.get(*k) != Some(v))
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect();
+                (overridden, own)
+            }
+        };
+        self.template = Some(Box::new(TemplateInstance {
+            source: normalize_source(source),
+            original_source: source.to_string(),
+            template,
+            overridden_fields,
+            own_properties,
+        }));
+        self.reapply_template();
+    }
+
+    /// Whether `field` of this object holds the value it would inherit from `template`.
+    fn field_matches(&self, field: ObjectField, template: &Object) -> bool {
+        match field {
+            ObjectField::Name => self.name == template.name,
+            ObjectField::Type => self.obj_type == template.obj_type,
+            ObjectField::Gid => self.gid == template.gid,
+            ObjectField::Width => self.width == template.width,
+            ObjectField::Height => self.height == template.height,
+            ObjectField::Rotation => self.rotation == template.rotation,
+            ObjectField::Visible => self.visible == template.visible,
+            ObjectField::Shape => {
+                let (w, h, x, y) = (self.width, self.height, self.x, self.y);
+                self.shape == inherited_shape(Some(&template.shape), w, h, x, y)
+            }
+        }
+    }
+
+    /// Sets `field` of this object to the value it inherits from `template`.
+    fn inherit(&mut self, field: ObjectField, template: &Object) {
+        match field {
+            ObjectField::Name => self.name = template.name.clone(),
+            ObjectField::Type => self.obj_type = template.obj_type.clone(),
+            ObjectField::Gid => self.gid = template.gid,
+            ObjectField::Width => self.width = template.width,
+            ObjectField::Height => self.height = template.height,
+            ObjectField::Rotation => self.rotation = template.rotation,
+            ObjectField::Visible => self.visible = template.visible,
+            ObjectField::Shape => {
+                let (w, h, x, y) = (self.width, self.height, self.x, self.y);
+                self.shape = inherited_shape(Some(&template.shape), w, h, x, y);
+            }
+        }
+    }
+
+    pub(crate) fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+        tilesets: &[MapTileset],
+        templates: &mut HashMap<String, Arc<Template>>,
+        file_loader: &mut ExternalFiles<'_>,
+    ) -> Result<Object, TiledError> {
+        let source = attrs
+            .iter()
+            .find(|a| a.name.local_name == "template")
+            .map(|a| a.value.clone());
+        match source {
+            Some(source) => {
+                let normalized = normalize_source(&source);
+                let template = match templates.get(&normalized) {
+                    Some(template) => template.clone(),
+                    None => {
+                        let template =
+                            Arc::new(Template::load(&normalized, tilesets, file_loader)?);
+                        templates.insert(normalized, template.clone());
+                        template
+                    }
+                };
+                Object::parse(parser, attrs, Some((source, template)), false)
+            }
+            None => Object::parse(parser, attrs, None, false),
+        }
+    }
+
+    /// Parses an `<object>` element. If the object is a template instance, `base` holds the
+    /// template source and the loaded template, whose values are used for everything the
+    /// instance does not set. Objects defined inside of templates don't have a position.
+    pub(crate) fn parse<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
+        base: Option<(String, Arc<Template>)>,
+        in_template: bool,
     ) -> Result<Object, TiledError> {
-        let ((id, gid, n, t, w, h, v, r), (x, y)) = get_attrs!(
+        let ((id, gid, n, t, c, w, h, v, r, x, y), ()) = get_attrs!(
             attrs,
             optionals: [
                 ("id", id, |v:String| v.parse().ok()),
                 ("gid", gid, |v:String| v.parse().ok()),
                 ("name", name, |v:String| v.parse().ok()),
                 ("type", obj_type, |v:String| v.parse().ok()),
+                ("class", class, Some),
                 ("width", width, |v:String| v.parse().ok()),
                 ("height", height, |v:String| v.parse().ok()),
                 ("visible", visible, |v:String| v.parse().ok().map(|x:i32| x == 1)),
                 ("rotation", rotation, |v:String| v.parse().ok()),
-            ],
-            required: [
                 ("x", x, |v:String| v.parse().ok()),
                 ("y", y, |v:String| v.parse().ok()),
             ],
-            TiledError::MalformedAttributes("objects must have an x and a y number".to_string())
+            required: [],
+            // this error should never happen since there are no required attrs
+            TiledError::MalformedAttributes("object parsing error".to_string())
         );
-        let v = v.unwrap_or(true);
-        let w = w.unwrap_or(0f32);
-        let h = h.unwrap_or(0f32);
-        let r = r.unwrap_or(0f32);
+        // Tiled 1.9 renamed the `type` of objects to `class`.
+        let t = c.or(t);
+        let (x, y) = match (x, y) {
+            (Some(x), Some(y)) => (x, y),
+            _ if in_template => (0.0, 0.0),
+            _ => {
+                return Err(TiledError::MalformedAttributes(
+                    "objects must have an x and a y number".to_string(),
+                ))
+            }
+        };
+
+        let mut overridden_fields = HashSet::new();
+        for (field, set) in [
+            (ObjectField::Name, n.is_some()),
+            (ObjectField::Type, t.is_some()),
+            (ObjectField::Gid, gid.is_some()),
+            (ObjectField::Width, w.is_some()),
+            (ObjectField::Height, h.is_some()),
+            (ObjectField::Rotation, r.is_some()),
+            (ObjectField::Visible, v.is_some()),
+        ] {
+            if set {
+                overridden_fields.insert(field);
+            }
+        }
+
+        let inherited = base.as_ref().map(|(_, t)| &t.object);
+        let v = v.or(inherited.map(|o| o.visible)).unwrap_or(true);
+        let w = w.or(inherited.map(|o| o.width)).unwrap_or(0f32);
+        let h = h.or(inherited.map(|o| o.height)).unwrap_or(0f32);
+        let r = r.or(inherited.map(|o| o.rotation)).unwrap_or(0f32);
         let id = id.unwrap_or(0u32);
-        let gid = gid.unwrap_or(0u32);
-        let n = n.unwrap_or(String::new());
-        let t = t.unwrap_or(String::new());
+        let gid = gid.or(inherited.map(|o| o.gid)).unwrap_or(0u32);
+        let n = n
+            .or_else(|| inherited.map(|o| o.name.clone()))
+            .unwrap_or_default();
+        let t = t
+            .or_else(|| inherited.map(|o| o.obj_type.clone()))
+            .unwrap_or_default();
         let mut shape = None;
         let mut properties = HashMap::new();
 
@@ -154,30 +608,56 @@ impl Object {
                 shape = Some(Object::new_point(x, y)?);
                 Ok(())
             },
+            "text" => |attrs| {
+                shape = Some(ObjectShape::Text {
+                    width: w,
+                    height: h,
+                    text: Text::new(parser, attrs)?,
+                });
+                Ok(())
+            },
             "properties" => |_| {
                 properties = parse_properties(parser)?;
                 Ok(())
             },
         });
 
-        let shape = shape.unwrap_or(ObjectShape::Rect {
-            width: w,
-            height: h,
-        });
+        if shape.is_some() {
+            overridden_fields.insert(ObjectField::Shape);
+        }
+        let shape =
+            shape.unwrap_or_else(|| inherited_shape(inherited.map(|o| &o.shape), w, h, x, y));
+
+        let (properties, template) = match base {
+            Some((source, template)) => {
+                let mut merged = template.object.properties.clone();
+                merged.extend(properties.clone());
+                let instance = TemplateInstance {
+                    source: normalize_source(&source),
+                    original_source: source,
+                    template,
+                    overridden_fields,
+                    own_properties: properties,
+                };
+                (merged, Some(Box::new(instance)))
+            }
+            None => (properties, None),
+        };
 
         Ok(Object {
-            id: id,
-            gid: gid,
-            name: n.clone(),
-            obj_type: t.clone(),
+            id,
+            gid,
+            name: n,
+            obj_type: t,
             width: w,
             height: h,
-            x: x,
-            y: y,
+            x,
+            y,
             rotation: r,
             visible: v,
-            shape: shape,
-            properties: properties,
+            shape,
+            properties,
+            template,
         })
     }
 
@@ -232,3 +712,109 @@ impl Object {
         Ok(points)
     }
 }
+
+fn distance_to_segment(p: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    let (abx, aby) = (b.0 - a.0, b.1 - a.1);
+    let length_squared = abx * abx + aby * aby;
+    let t = if length_squared > 0.0 {
+        (((p.0 - a.0) * abx + (p.1 - a.1) * aby) / length_squared).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (dx, dy) = (p.0 - (a.0 + t * abx), p.1 - (a.1 + t * aby));
+    (dx * dx + dy * dy).sqrt()
+}
+
+/// An object of a map, along with the object layer containing it.
+#[derive(Debug, Clone, Copy)]
+pub struct MapObject<'map> {
+    pub layer: &'map ObjectGroup,
+    pub object: &'map Object,
+}
+
+/// Iterator over objects of a map, as returned by [`Map::objects`](crate::map::Map::objects).
+///
+/// Besides the usual iterator adapters, it offers filters for the most common object queries,
+/// which can be chained.
+pub struct Objects<'map> {
+    inner: Box<dyn Iterator<Item = MapObject<'map>> + 'map>,
+}
+
+impl<'map> Objects<'map> {
+    pub(crate) fn new(iter: impl Iterator<Item = MapObject<'map>> + 'map) -> Self {
+        Objects {
+            inner: Box::new(iter),
+        }
+    }
+
+    /// Only keep objects whose type (class) is `class`.
+    pub fn with_class(self, class: impl Into<String>) -> Self {
+        let class = class.into();
+        Objects::new(self.inner.filter(move |o| o.object.obj_type == class))
+    }
+
+    /// Only keep objects named `name`.
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        Objects::new(self.inner.filter(move |o| o.object.name == name))
+    }
+
+    /// Only keep objects that have the property `name` set to `value`.
+    pub fn with_property(self, name: impl Into<String>, value: PropertyValue) -> Self {
+        let name = name.into();
+        Objects::new(
+            self.inner
+                .filter(move |o| o.object.properties.get(&name) == Some(&value)),
+        )
+    }
+
+    /// Only keep objects that have the property `name`, whatever its value.
+    pub fn has_property(self, name: impl Into<String>) -> Self {
+        let name = name.into();
+        Objects::new(
+            self.inner
+                .filter(move |o| o.object.properties.contains_key(&name)),
+        )
+    }
+}
+
+impl<'map> Iterator for Objects<'map> {
+    type Item = MapObject<'map>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+}
+
+/// The shape of a template instance that doesn't set its own, given the shape of its template
+/// and the size and position of the instance.
+fn inherited_shape(
+    template: Option<&ObjectShape>,
+    w: f32,
+    h: f32,
+    x: f32,
+    y: f32,
+) -> ObjectShape {
+    match template {
+        Some(ObjectShape::Ellipse { .. }) => ObjectShape::Ellipse {
+            width: w,
+            height: h,
+        },
+        Some(ObjectShape::Point(..)) => ObjectShape::Point(x, y),
+        Some(ObjectShape::Polyline { points }) => ObjectShape::Polyline {
+            points: points.clone(),
+        },
+        Some(ObjectShape::Polygon { points }) => ObjectShape::Polygon {
+            points: points.clone(),
+        },
+        Some(ObjectShape::Text { text, .. }) => ObjectShape::Text {
+            width: w,
+            height: h,
+            text: text.clone(),
+        },
+        Some(ObjectShape::Rect { .. }) | None => ObjectShape::Rect {
+            width: w,
+            height: h,
+        },
+    }
+}
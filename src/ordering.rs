@@ -0,0 +1,137 @@
+//! Reordering layers and moving them into and out of group layers, keeping the `layer_index` of
+//! the layers of all kinds in the draw order.
+
+use std::collections::HashMap;
+
+use crate::map::Map;
+
+/// A layer of any kind, by its index in the list of layers of its kind.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Node {
+    Tile(usize),
+    Image(usize),
+    Objects(usize),
+    Group(usize),
+}
+
+impl Map {
+    /// Moves the layer or group layer with the ID `id` to position `index` among the layers of
+    /// its group, or among the top-level layers, 0 being drawn first. Groups are moved along
+    /// with the layers inside of them. Indices past the last layer move it last.
+    ///
+    /// The lists of layers of the map keep their order: only the
+    /// [`layer_index`](crate::layers::Layer::layer_index) of the layers changes, for every layer
+    /// between the old and new position. Returns `false` if no layer has the ID `id`.
+    pub fn move_layer(&mut self, id: u32, index: usize) -> bool {
+        match self.find_node(id) {
+            Some((node, parent)) => {
+                self.place(node, parent, index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the layer or group layer with the ID `id` into the group layer with the ID `group`,
+    /// or out of all groups if it is `None`, at position `index` among the layers there, like
+    /// [`Map::move_layer`].
+    ///
+    /// Returns `false` if no layer has the ID `id`, no group layer has the ID `group`, or the
+    /// layer moved is that group or contains it.
+    pub fn move_layer_into(&mut self, id: u32, group: Option<u32>, index: usize) -> bool {
+        let node = match self.find_node(id) {
+            Some((node, _)) => node,
+            None => return false,
+        };
+        let parent = match group {
+            Some(group) => match self.groups.iter().position(|g| g.id == group) {
+                Some(parent) => Some(parent),
+                None => return false,
+            },
+            None => None,
+        };
+        if let Node::Group(moved) = node {
+            let mut ancestor = parent;
+            while let Some(group) = ancestor {
+                if group == moved {
+                    return false;
+                }
+                ancestor = self.groups[group].parent_group;
+            }
+        }
+        self.place(node, parent, index);
+        true
+    }
+
+    /// Finds the layer with the ID `id`, and the group containing it.
+    fn find_node(&self, id: u32) -> Option<(Node, Option<usize>)> {
+        self.layer_nodes()
+            .into_iter()
+            .find(|&(node, _, _)| self.node_id(node) == id)
+            .map(|(node, parent, _)| (node, parent))
+    }
+
+    fn node_id(&self, node: Node) -> u32 {
+        match node {
+            Node::Tile(i) => self.layers[i].id,
+            Node::Image(i) => self.image_layers[i].id,
+            Node::Objects(i) => self.object_groups[i].id,
+            Node::Group(i) => self.groups[i].id,
+        }
+    }
+
+    /// All layers, with the group containing them and their `layer_index`.
+    fn layer_nodes(&self) -> Vec<(Node, Option<usize>, u32)> {
+        let layers = self.layers.iter().enumerate();
+        let image_layers = self.image_layers.iter().enumerate();
+        let object_groups = self.object_groups.iter().enumerate();
+        let groups = self.groups.iter().enumerate();
+        layers
+            .map(|(i, l)| (Node::Tile(i), l.parent_group, l.layer_index))
+            .chain(image_layers.map(|(i, l)| (Node::Image(i), l.parent_group, l.layer_index)))
+            .chain(
+                object_groups
+                    .map(|(i, g)| (Node::Objects(i), g.parent_group, g.layer_index.unwrap_or(0))),
+            )
+            .chain(groups.map(|(i, g)| (Node::Group(i), g.parent_group, g.layer_index)))
+            .collect()
+    }
+
+    /// Moves `node` into `parent` at `index`, and numbers all layers again in draw order: each
+    /// group right before the layers inside of it.
+    fn place(&mut self, node: Node, parent: Option<usize>, index: usize) {
+        let mut nodes = self.layer_nodes();
+        nodes.sort_by_key(|&(_, _, layer_index)| layer_index);
+        let mut children: HashMap<Option<usize>, Vec<Node>> = HashMap::new();
+        for (other, other_parent, _) in nodes {
+            if other != node {
+                children.entry(other_parent).or_default().push(other);
+            }
+        }
+        let siblings = children.entry(parent).or_default();
+        siblings.insert(index.min(siblings.len()), node);
+        match node {
+            Node::Tile(i) => self.layers[i].parent_group = parent,
+            Node::Image(i) => self.image_layers[i].parent_group = parent,
+            Node::Objects(i) => self.object_groups[i].parent_group = parent,
+            Node::Group(i) => self.groups[i].parent_group = parent,
+        }
+
+        let mut stack: Vec<Node> = children.remove(&None).unwrap_or_default();
+        stack.reverse();
+        let mut next_index = 0;
+        while let Some(node) = stack.pop() {
+            match node {
+                Node::Tile(i) => self.layers[i].layer_index = next_index,
+                Node::Image(i) => self.image_layers[i].layer_index = next_index,
+                Node::Objects(i) => self.object_groups[i].layer_index = Some(next_index),
+                Node::Group(i) => {
+                    self.groups[i].layer_index = next_index;
+                    let inside = children.remove(&Some(i)).unwrap_or_default();
+                    stack.extend(inside.into_iter().rev());
+                }
+            }
+            next_index += 1;
+        }
+    }
+}
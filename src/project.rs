@@ -0,0 +1,226 @@
+//! The custom property types of a Tiled project (`.tiled-project` file), which give classes to
+//! maps, layers, objects and tiles, along with the members each class is expected to have.
+
+use std::io::Read;
+
+use crate::{
+    error::TiledError,
+    json::JsonValue,
+    properties::{Properties, PropertyValue},
+};
+
+/// A Tiled project. Only the custom property types are read, as the rest of the file only
+/// configures the editor.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Project {
+    pub property_types: Vec<PropertyType>,
+}
+
+/// A custom property type defined in a project.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PropertyType {
+    Class(CustomClass),
+    Enum(CustomEnum),
+}
+
+/// A class, listing the members the things of this class have, with their default values.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CustomClass {
+    pub id: u32,
+    pub name: String,
+    /// What the class can be given to, such as `"object"`, `"tile"` or `"layer"`, as written in
+    /// the project. Projects saved before Tiled 1.9 don't say, and leave this empty.
+    pub use_as: Vec<String>,
+    pub members: Vec<ClassMember>,
+}
+
+impl CustomClass {
+    /// Returns the member called `name`, if the class has one.
+    pub fn member(&self, name: &str) -> Option<&ClassMember> {
+        self.members.iter().find(|m| m.name == name)
+    }
+
+    /// Whether the class can be given to things of the given kind, such as `"object"`. Classes
+    /// of projects saved before Tiled 1.9 can be used for anything.
+    pub fn usable_as(&self, kind: &str) -> bool {
+        self.use_as.is_empty() || self.use_as.iter().any(|u| u == kind)
+    }
+}
+
+/// A member of a [`CustomClass`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct ClassMember {
+    pub name: String,
+    /// The name of the type of the member, as written in the `type` attribute of properties,
+    /// such as `"int"`. Members using an enum are stored as `"string"` or `"int"`, and members
+    /// using another class as `"class"`.
+    pub type_name: String,
+    /// The name of the enum or class the member uses, if any.
+    pub property_type: Option<String>,
+    /// The default value of the member. Members using another class hold their JSON value as an
+    /// unknown value.
+    pub value: PropertyValue,
+}
+
+/// An enum, whose values are stored as strings or as their index.
+#[derive(Debug, PartialEq, Clone)]
+pub struct CustomEnum {
+    pub id: u32,
+    pub name: String,
+    pub storage_type: EnumStorage,
+    pub values: Vec<String>,
+    /// Whether several values can be set at once, as a comma-separated string or as bit flags.
+    pub values_as_flags: bool,
+}
+
+impl CustomEnum {
+    /// Whether `value` is a valid value of the enum.
+    pub fn accepts(&self, value: &PropertyValue) -> bool {
+        match (self.storage_type, value) {
+            (EnumStorage::String, PropertyValue::StringValue(s)) if self.values_as_flags => {
+                s.is_empty() || s.split(',').all(|v| self.values.iter().any(|e| e == v))
+            }
+            (EnumStorage::String, PropertyValue::StringValue(s)) => {
+                self.values.iter().any(|e| e == s)
+            }
+            (EnumStorage::Int, PropertyValue::IntValue(i)) if self.values_as_flags => {
+                *i >= 0 && (*i as u64) < 1u64 << self.values.len().min(32)
+            }
+            (EnumStorage::Int, PropertyValue::IntValue(i)) => {
+                *i >= 0 && (*i as usize) < self.values.len()
+            }
+            _ => false,
+        }
+    }
+}
+
+/// How the values of a [`CustomEnum`] are stored in properties.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EnumStorage {
+    String,
+    Int,
+}
+
+impl Project {
+    /// Parses a project file.
+    pub fn parse<R: Read>(mut reader: R) -> Result<Project, TiledError> {
+        let mut text = String::new();
+        reader
+            .read_to_string(&mut text)
+            .map_err(|e| TiledError::Other(e.to_string()))?;
+        let json = JsonValue::parse(&text)?;
+        let property_types = json
+            .get("propertyTypes")
+            .map_or(&[][..], |t| t.as_array())
+            .iter()
+            .map(parse_property_type)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Project {
+            property_types: property_types.into_iter().flatten().collect(),
+        })
+    }
+
+    /// Returns the class called `name`, if the project defines one.
+    pub fn class(&self, name: &str) -> Option<&CustomClass> {
+        self.property_types.iter().find_map(|t| match t {
+            PropertyType::Class(class) if class.name == name => Some(class),
+            _ => None,
+        })
+    }
+
+    /// Returns the default value of every member of the class called `name`, or nothing if the
+    /// project has no such class.
+    pub fn class_defaults(&self, name: &str) -> Properties {
+        self.class(name).map_or_else(Properties::new, |class| {
+            class
+                .members
+                .iter()
+                .map(|m| (m.name.clone(), m.value.clone()))
+                .collect()
+        })
+    }
+
+    /// Returns the enum called `name`, if the project defines one.
+    pub fn enum_type(&self, name: &str) -> Option<&CustomEnum> {
+        self.property_types.iter().find_map(|t| match t {
+            PropertyType::Enum(e) if e.name == name => Some(e),
+            _ => None,
+        })
+    }
+}
+
+fn malformed(what: &str) -> TiledError {
+    TiledError::MalformedAttributes(format!("project property types {}", what))
+}
+
+/// Parses an entry of `propertyTypes`. Types of kinds this crate doesn't know are skipped.
+fn parse_property_type(json: &JsonValue) -> Result<Option<PropertyType>, TiledError> {
+    let name = json
+        .get("name")
+        .and_then(JsonValue::as_str)
+        .ok_or_else(|| malformed("must have a name"))?
+        .to_string();
+    let id = match json.get("id") {
+        Some(JsonValue::Number(n)) => n.parse().map_err(|_| malformed("must have integer ids"))?,
+        _ => 0,
+    };
+    let strings = |field: &str| -> Vec<String> {
+        json.get(field)
+            .map_or(&[][..], |v| v.as_array())
+            .iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect()
+    };
+    match json.get("type").and_then(JsonValue::as_str) {
+        Some("class") => {
+            let members = json
+                .get("members")
+                .map_or(&[][..], |m| m.as_array())
+                .iter()
+                .map(parse_member)
+                .collect::<Result<_, _>>()?;
+            Ok(Some(PropertyType::Class(CustomClass {
+                id,
+                name,
+                use_as: strings("useAs"),
+                members,
+            })))
+        }
+        Some("enum") => Ok(Some(PropertyType::Enum(CustomEnum {
+            id,
+            name,
+            storage_type: match json.get("storageType").and_then(JsonValue::as_str) {
+                Some("int") => EnumStorage::Int,
+                _ => EnumStorage::String,
+            },
+            values: strings("values"),
+            values_as_flags: json
+                .get("valuesAsFlags")
+                .and_then(JsonValue::as_bool)
+                .unwrap_or(false),
+        }))),
+        _ => Ok(None),
+    }
+}
+
+fn parse_member(json: &JsonValue) -> Result<ClassMember, TiledError> {
+    let field = |name: &str| json.get(name).and_then(JsonValue::as_str);
+    let name = field("name").ok_or_else(|| malformed("must have named members"))?;
+    let type_name = field("type").unwrap_or("string");
+    let raw = match (json.get("value"), type_name) {
+        (Some(JsonValue::String(s)), _) => s.clone(),
+        (Some(JsonValue::Number(n)), _) => n.clone(),
+        (Some(JsonValue::Bool(b)), _) => b.to_string(),
+        (Some(value @ JsonValue::Object(_)), _) => value.to_string(),
+        // Members without a value default to the zero value of their type.
+        (_, "bool") => "false".to_string(),
+        (_, "int") | (_, "float") | (_, "object") => "0".to_string(),
+        _ => String::new(),
+    };
+    Ok(ClassMember {
+        name: name.to_string(),
+        type_name: type_name.to_string(),
+        property_type: field("propertyType").map(str::to_string),
+        value: PropertyValue::new(type_name.to_string(), raw)?,
+    })
+}
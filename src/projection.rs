@@ -0,0 +1,154 @@
+//! Conversion between pixel positions and tile coordinates for the different map orientations,
+//! matching the renderers of the Tiled editor.
+
+use crate::map::{Map, Orientation, StaggerAxis, StaggerIndex};
+
+/// Converts a position in map pixels into the coordinates of the tile under it. Coordinates
+/// outside of the map are returned as well. Returns `None` for hexagonal maps.
+pub(crate) fn pixel_to_tile(map: &Map, x: f32, y: f32) -> Option<(i32, i32)> {
+    let tile_width = map.tile_width as f32;
+    let tile_height = map.tile_height as f32;
+    if tile_width <= 0.0 || tile_height <= 0.0 {
+        return None;
+    }
+    match map.orientation {
+        Orientation::Orthogonal => Some((
+            (x / tile_width).floor() as i32,
+            (y / tile_height).floor() as i32,
+        )),
+        Orientation::Isometric => {
+            // The top corner of the first tile is in the middle of the left edge of the map.
+            let x = x - map.height as f32 * tile_width / 2.0;
+            let tile_x = x / tile_width;
+            let tile_y = y / tile_height;
+            Some((
+                (tile_y + tile_x).floor() as i32,
+                (tile_y - tile_x).floor() as i32,
+            ))
+        }
+        Orientation::Staggered => Some(staggered_pixel_to_tile(map, x, y)),
+        Orientation::Hexagonal => None,
+    }
+}
+
+/// Returns the top-left corner of the bounding box of the given tile, in map pixels. Returns
+/// `None` for hexagonal maps.
+pub(crate) fn tile_to_pixel(map: &Map, x: i32, y: i32) -> Option<(f32, f32)> {
+    let tile_width = map.tile_width as f32;
+    let tile_height = map.tile_height as f32;
+    match map.orientation {
+        Orientation::Orthogonal => Some((x as f32 * tile_width, y as f32 * tile_height)),
+        Orientation::Isometric => {
+            let origin_x = map.height as f32 * tile_width / 2.0;
+            Some((
+                origin_x + (x - y) as f32 * tile_width / 2.0 - tile_width / 2.0,
+                (x + y) as f32 * tile_height / 2.0,
+            ))
+        }
+        Orientation::Staggered => Some(staggered_tile_to_pixel(map, x, y)),
+        Orientation::Hexagonal => None,
+    }
+}
+
+/// Returns whether the row or column `index` along the stagger axis is shifted by half a tile.
+fn is_shifted(map: &Map, index: i32) -> bool {
+    let stagger_even = (map.stagger_index == StaggerIndex::Even) as i32;
+    (index & 1) ^ stagger_even == 1
+}
+
+fn staggered_tile_to_pixel(map: &Map, x: i32, y: i32) -> (f32, f32) {
+    let tile_width = map.tile_width as f32;
+    let tile_height = map.tile_height as f32;
+    match map.stagger_axis {
+        StaggerAxis::X => {
+            let shift = if is_shifted(map, x) {
+                tile_height / 2.0
+            } else {
+                0.0
+            };
+            (x as f32 * tile_width / 2.0, y as f32 * tile_height + shift)
+        }
+        StaggerAxis::Y => {
+            let shift = if is_shifted(map, y) {
+                tile_width / 2.0
+            } else {
+                0.0
+            };
+            (x as f32 * tile_width + shift, y as f32 * tile_height / 2.0)
+        }
+    }
+}
+
+fn staggered_pixel_to_tile(map: &Map, x: f32, y: f32) -> (i32, i32) {
+    let tile_width = map.tile_width as f32;
+    let tile_height = map.tile_height as f32;
+    let stagger_x = map.stagger_axis == StaggerAxis::X;
+    let stagger_even = map.stagger_index == StaggerIndex::Even;
+
+    let (x, y) = match (stagger_x, stagger_even) {
+        (true, true) => (x - tile_width / 2.0, y),
+        (false, true) => (x, y - tile_height / 2.0),
+        _ => (x, y),
+    };
+
+    // Every tile-sized rectangle of the grid contains one whole diamond, with the corners
+    // belonging to its neighbors.
+    let mut reference = (
+        (x / tile_width).floor() as i32,
+        (y / tile_height).floor() as i32,
+    );
+    let rel_x = x - reference.0 as f32 * tile_width;
+    let rel_y = y - reference.1 as f32 * tile_height;
+    {
+        let index = if stagger_x {
+            &mut reference.0
+        } else {
+            &mut reference.1
+        };
+        *index *= 2;
+        if stagger_even {
+            *index += 1;
+        }
+    }
+
+    let half_height = tile_height / 2.0;
+    let y_pos = rel_x * (tile_height / tile_width);
+    let (dx, dy) = if half_height - y_pos > rel_y {
+        (-1, -1)
+    } else if -half_height + y_pos > rel_y {
+        (1, -1)
+    } else if half_height + y_pos < rel_y {
+        (-1, 1)
+    } else if half_height * 3.0 - y_pos < rel_y {
+        (1, 1)
+    } else {
+        return reference;
+    };
+    staggered_neighbor(map, reference, dx, dy)
+}
+
+/// Returns the tile diagonally adjacent to `tile`, in the direction given by `dx` and `dy`
+/// (each -1 or 1).
+fn staggered_neighbor(map: &Map, (x, y): (i32, i32), dx: i32, dy: i32) -> (i32, i32) {
+    match map.stagger_axis {
+        StaggerAxis::Y => {
+            // Shifted rows reach into the next column, others into the previous one.
+            let shifted = is_shifted(map, y);
+            let x = match (dx, shifted) {
+                (1, true) => x + 1,
+                (-1, false) => x - 1,
+                _ => x,
+            };
+            (x, y + dy)
+        }
+        StaggerAxis::X => {
+            let shifted = is_shifted(map, x);
+            let y = match (dy, shifted) {
+                (1, true) => y + 1,
+                (-1, false) => y - 1,
+                _ => y,
+            };
+            (x + dx, y)
+        }
+    }
+}
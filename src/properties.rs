@@ -1,56 +1,141 @@
-use std::{collections::HashMap, io::Read, str::FromStr};
+use std::{collections::HashMap, fmt, io::Read, str::FromStr};
 
 use xml::{EventReader, attribute::OwnedAttribute, reader::XmlEvent};
 
 use crate::{
-    error::{ParseTileError, TiledError},
+    error::TiledError,
+    ids::TileId,
+    map::Map,
     util::{get_attrs, parse_tag},
 };
 
+#[cfg(feature = "serde")]
+pub use crate::de::{from_properties, PropertiesDeserializer};
+
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub struct Colour {
     pub red: u8,
     pub green: u8,
     pub blue: u8,
+    /// 255 unless the colour was written with an alpha component (`#AARRGGBB`).
+    pub alpha: u8,
 }
 
 impl FromStr for Colour {
-    type Err = ParseTileError;
+    type Err = TiledError;
+
+    /// Parses `#RRGGBB` and `#AARRGGBB` colours, as well as their `#RGB` and `#ARGB` shorthands.
+    /// The `#` is optional.
+    fn from_str(s: &str) -> Result<Colour, TiledError> {
+        let invalid = || TiledError::InvalidColour(s.to_string());
+        let hex = s.trim();
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        let digits = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(invalid)?;
+        let components: Vec<u8> = match digits.len() {
+            3 | 4 => digits.iter().map(|d| d * 17).collect(),
+            6 | 8 => digits.chunks(2).map(|p| p[0] * 16 + p[1]).collect(),
+            _ => return Err(invalid()),
+        };
+        let (alpha, rgb) = match components.len() {
+            4 => (components[0], &components[1..]),
+            _ => (255, &components[..]),
+        };
+        Ok(Colour {
+            red: rgb[0],
+            green: rgb[1],
+            blue: rgb[2],
+            alpha,
+        })
+    }
+}
 
-    fn from_str(s: &str) -> Result<Colour, ParseTileError> {
-        let s = if s.starts_with("#") { &s[1..] } else { s };
-        if s.len() != 6 {
-            return Err(ParseTileError::ColourError);
+impl fmt::Display for Colour {
+    /// Formats the colour the way Tiled writes it: `#RRGGBB`, or `#AARRGGBB` if it is not
+    /// fully opaque.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.alpha != 255 {
+            write!(f, "#{:02x}", self.alpha)?;
+        } else {
+            write!(f, "#")?;
         }
-        let r = u8::from_str_radix(&s[0..2], 16);
-        let g = u8::from_str_radix(&s[2..4], 16);
-        let b = u8::from_str_radix(&s[4..6], 16);
-        if r.is_ok() && g.is_ok() && b.is_ok() {
-            return Ok(Colour {
-                red: r.unwrap(),
-                green: g.unwrap(),
-                blue: b.unwrap(),
-            });
+        write!(f, "{:02x}{:02x}{:02x}", self.red, self.green, self.blue)
+    }
+}
+
+/// The value of a float property. The text it was read from is kept, so that writing the
+/// property back produces exactly what was in the file.
+#[derive(Debug, Clone)]
+pub struct FloatProperty {
+    pub value: f64,
+    /// The text the value was parsed from, when it was read from a file. It is only used for
+    /// writing as long as it still parses to `value`.
+    pub text: Option<String>,
+}
+
+impl FloatProperty {
+    /// Returns the value as an `f32`.
+    pub fn as_f32(&self) -> f32 {
+        self.value as f32
+    }
+}
+
+impl From<f64> for FloatProperty {
+    fn from(value: f64) -> Self {
+        FloatProperty { value, text: None }
+    }
+}
+
+impl From<f32> for FloatProperty {
+    /// Converts through the shortest representation of the `f32`, so that `0.3f32` becomes
+    /// `0.3` instead of `0.30000001192092896`.
+    fn from(value: f32) -> Self {
+        FloatProperty {
+            value: value.to_string().parse().unwrap_or(value as f64),
+            text: None,
+        }
+    }
+}
+
+impl PartialEq for FloatProperty {
+    /// Compares the values only, regardless of how they were written.
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl fmt::Display for FloatProperty {
+    /// Writes the original text if there is one and it still matches the value.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.text {
+            Some(text) if text.parse::<f64>().ok() == Some(self.value) => write!(f, "{}", text),
+            _ => write!(f, "{}", self.value),
         }
-        Err(ParseTileError::ColourError)
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum PropertyValue {
     BoolValue(bool),
-    FloatValue(f32),
+    FloatValue(FloatProperty),
     IntValue(i32),
+    /// The colour packed as `0xAARRGGBB`, or 0 if unset
     ColorValue(u32),
     StringValue(String),
     /// Holds the path relative to the map or tileset
     FileValue(String),
     /// Holds the id of a referenced object, or 0 if unset
     ObjectValue(u32),
+    /// A property of a type this crate doesn't know, such as one added by a newer version of
+    /// the editor. The value is kept as written, so that it survives writing the file back.
+    Unknown { type_name: String, raw_value: String },
 }
 
 impl PropertyValue {
-    fn new(property_type: String, value: String) -> Result<PropertyValue, TiledError> {
+    pub(crate) fn new(property_type: String, value: String) -> Result<PropertyValue, TiledError> {
         // Check the property type against the value.
         match property_type.as_str() {
             "bool" => match value.parse() {
@@ -58,35 +143,251 @@ impl PropertyValue {
                 Err(err) => Err(TiledError::Other(err.to_string())),
             },
             "float" => match value.parse() {
-                Ok(val) => Ok(PropertyValue::FloatValue(val)),
+                Ok(val) => Ok(PropertyValue::FloatValue(FloatProperty {
+                    value: val,
+                    text: Some(value),
+                })),
                 Err(err) => Err(TiledError::Other(err.to_string())),
             },
             "int" => match value.parse() {
                 Ok(val) => Ok(PropertyValue::IntValue(val)),
                 Err(err) => Err(TiledError::Other(err.to_string())),
             },
-            "color" if value.len() > 1 => match u32::from_str_radix(&value[1..], 16) {
-                Ok(color) => Ok(PropertyValue::ColorValue(color)),
-                Err(_) => Err(TiledError::Other(format!(
-                    "Improperly formatted color property"
-                ))),
-            },
+            // Tiled writes colours that were never set as empty values.
+            "color" if value.is_empty() => Ok(PropertyValue::ColorValue(0)),
+            "color" => {
+                let c: Colour = value.parse()?;
+                Ok(PropertyValue::ColorValue(u32::from_be_bytes([
+                    c.alpha, c.red, c.green, c.blue,
+                ])))
+            }
             "string" => Ok(PropertyValue::StringValue(value)),
             "object" => match value.parse() {
                 Ok(val) => Ok(PropertyValue::ObjectValue(val)),
                 Err(err) => Err(TiledError::Other(err.to_string())),
             },
             "file" => Ok(PropertyValue::FileValue(value)),
-            _ => Err(TiledError::Other(format!(
-                "Unknown property type \"{}\"",
-                property_type
-            ))),
+            _ => Ok(PropertyValue::Unknown {
+                type_name: property_type,
+                raw_value: value,
+            }),
         }
     }
 }
 
 pub type Properties = HashMap<String, PropertyValue>;
 
+impl PropertyValue {
+    /// The name of the type of the value, as written in the `type` attribute of properties.
+    pub fn type_name(&self) -> &str {
+        match self {
+            PropertyValue::BoolValue(_) => "bool",
+            PropertyValue::FloatValue(_) => "float",
+            PropertyValue::IntValue(_) => "int",
+            PropertyValue::ColorValue(_) => "color",
+            PropertyValue::StringValue(_) => "string",
+            PropertyValue::FileValue(_) => "file",
+            PropertyValue::ObjectValue(_) => "object",
+            PropertyValue::Unknown { type_name, .. } => type_name,
+        }
+    }
+}
+
+/// Why a property could not be converted with [`FromPropertyValue`].
+#[derive(Debug, PartialEq, Clone)]
+pub enum PropertyErrorKind {
+    /// The property is not set.
+    Missing,
+    /// The property has another type. Holds the name of the expected type and the value found.
+    WrongType {
+        expected: &'static str,
+        found: PropertyValue,
+    },
+    /// The property has the right type, but its value isn't accepted.
+    Invalid(String),
+}
+
+/// An error converting properties into a user type, naming the property at fault.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PropertyError {
+    pub name: String,
+    pub kind: PropertyErrorKind,
+}
+
+impl fmt::Display for PropertyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            PropertyErrorKind::Missing => write!(f, "property '{}' is missing", self.name),
+            PropertyErrorKind::WrongType { expected, found } => write!(
+                f,
+                "property '{}' should be of type {} but is of type {}",
+                self.name,
+                expected,
+                found.type_name()
+            ),
+            PropertyErrorKind::Invalid(reason) => {
+                write!(f, "property '{}' is invalid: {}", self.name, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PropertyError {}
+
+/// Types the value of a single property can be converted into, used by
+/// [`PropertiesExt::get_as`] and implementations of [`FromProperties`].
+pub trait FromPropertyValue: Sized {
+    /// Converts the value of a property, which is `None` if the property isn't set.
+    fn from_property_value(value: Option<&PropertyValue>) -> Result<Self, PropertyErrorKind>;
+}
+
+/// Returns the error for a property that is missing or doesn't have the `expected` type.
+fn unexpected(expected: &'static str, value: Option<&PropertyValue>) -> PropertyErrorKind {
+    match value {
+        Some(found) => PropertyErrorKind::WrongType {
+            expected,
+            found: found.clone(),
+        },
+        None => PropertyErrorKind::Missing,
+    }
+}
+
+impl FromPropertyValue for bool {
+    fn from_property_value(value: Option<&PropertyValue>) -> Result<Self, PropertyErrorKind> {
+        match value {
+            Some(PropertyValue::BoolValue(v)) => Ok(*v),
+            _ => Err(unexpected("bool", value)),
+        }
+    }
+}
+
+impl FromPropertyValue for i32 {
+    fn from_property_value(value: Option<&PropertyValue>) -> Result<Self, PropertyErrorKind> {
+        match value {
+            Some(PropertyValue::IntValue(v)) => Ok(*v),
+            _ => Err(unexpected("int", value)),
+        }
+    }
+}
+
+impl FromPropertyValue for u32 {
+    fn from_property_value(value: Option<&PropertyValue>) -> Result<Self, PropertyErrorKind> {
+        let v = i32::from_property_value(value)?;
+        if v < 0 {
+            return Err(PropertyErrorKind::Invalid(format!("{} is negative", v)));
+        }
+        Ok(v as u32)
+    }
+}
+
+impl FromPropertyValue for f64 {
+    /// Accepts int properties too, since the editor lets users pick either for numbers.
+    fn from_property_value(value: Option<&PropertyValue>) -> Result<Self, PropertyErrorKind> {
+        match value {
+            Some(PropertyValue::FloatValue(v)) => Ok(v.value),
+            Some(PropertyValue::IntValue(v)) => Ok(*v as f64),
+            _ => Err(unexpected("float", value)),
+        }
+    }
+}
+
+impl FromPropertyValue for f32 {
+    fn from_property_value(value: Option<&PropertyValue>) -> Result<Self, PropertyErrorKind> {
+        f64::from_property_value(value).map(|v| v as f32)
+    }
+}
+
+impl FromPropertyValue for String {
+    /// Accepts string and file properties.
+    fn from_property_value(value: Option<&PropertyValue>) -> Result<Self, PropertyErrorKind> {
+        match value {
+            Some(PropertyValue::StringValue(v)) | Some(PropertyValue::FileValue(v)) => {
+                Ok(v.clone())
+            }
+            _ => Err(unexpected("string", value)),
+        }
+    }
+}
+
+impl FromPropertyValue for Colour {
+    fn from_property_value(value: Option<&PropertyValue>) -> Result<Self, PropertyErrorKind> {
+        match value {
+            Some(PropertyValue::ColorValue(v)) => {
+                let [alpha, red, green, blue] = v.to_be_bytes();
+                Ok(Colour {
+                    red,
+                    green,
+                    blue,
+                    alpha,
+                })
+            }
+            _ => Err(unexpected("color", value)),
+        }
+    }
+}
+
+impl FromPropertyValue for PropertyValue {
+    fn from_property_value(value: Option<&PropertyValue>) -> Result<Self, PropertyErrorKind> {
+        value.cloned().ok_or(PropertyErrorKind::Missing)
+    }
+}
+
+impl<T: FromPropertyValue> FromPropertyValue for Option<T> {
+    /// Missing properties become `None`.
+    fn from_property_value(value: Option<&PropertyValue>) -> Result<Self, PropertyErrorKind> {
+        value.map(|v| T::from_property_value(Some(v))).transpose()
+    }
+}
+
+/// Types that can be built from a set of properties, such as the configuration of the game
+/// entity an object stands for.
+///
+/// ```
+/// use tiled::properties::{FromProperties, Properties, PropertiesExt, PropertyError};
+///
+/// struct Enemy {
+///     speed: f32,
+///     boss: Option<bool>,
+/// }
+///
+/// impl FromProperties for Enemy {
+///     fn from_properties(properties: &Properties) -> Result<Self, PropertyError> {
+///         Ok(Enemy {
+///             speed: properties.get_as("speed")?,
+///             boss: properties.get_as("boss")?,
+///         })
+///     }
+/// }
+///
+/// let error = Properties::new().deserialize_into::<Enemy>().err().unwrap();
+/// assert_eq!(error.to_string(), "property 'speed' is missing");
+/// ```
+pub trait FromProperties: Sized {
+    fn from_properties(properties: &Properties) -> Result<Self, PropertyError>;
+}
+
+/// Conversions of [`Properties`] into user types.
+pub trait PropertiesExt {
+    /// Converts the property called `name`, see [`FromPropertyValue`].
+    fn get_as<T: FromPropertyValue>(&self, name: &str) -> Result<T, PropertyError>;
+
+    /// Builds a `T` from the properties, see [`FromProperties`].
+    fn deserialize_into<T: FromProperties>(&self) -> Result<T, PropertyError>;
+}
+
+impl PropertiesExt for Properties {
+    fn get_as<T: FromPropertyValue>(&self, name: &str) -> Result<T, PropertyError> {
+        T::from_property_value(self.get(name)).map_err(|kind| PropertyError {
+            name: name.to_string(),
+            kind,
+        })
+    }
+
+    fn deserialize_into<T: FromProperties>(&self) -> Result<T, PropertyError> {
+        T::from_properties(self)
+    }
+}
+
 pub(crate) fn parse_properties<R: Read>(
     parser: &mut EventReader<R>,
 ) -> Result<Properties, TiledError> {
@@ -154,4 +455,172 @@ impl FromStr for Alignment {
             _ => Err(TiledError::MalformedAttributes(format!("Invalid alignment type '{}'", s).to_string())),
         }
     }
-}
\ No newline at end of file
+}
+
+impl fmt::Display for Alignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Alignment::TopLeft => write!(f, "topleft"),
+            Alignment::Top => write!(f, "top"),
+            Alignment::TopRight => write!(f, "topright"),
+            Alignment::Left => write!(f, "left"),
+            Alignment::Center => write!(f, "center"),
+            Alignment::Right => write!(f, "right"),
+            Alignment::BottomLeft => write!(f, "bottomleft"),
+            Alignment::Bottom => write!(f, "bottom"),
+            Alignment::BottomRight => write!(f, "bottomright"),
+        }
+    }
+}
+
+/// What a property belongs to, as listed by [`Map::property_index`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PropertyOwner {
+    Map,
+    /// A tile, object, image or group layer.
+    Layer {
+        layer_id: u32,
+    },
+    Object {
+        layer_id: u32,
+        object_id: u32,
+    },
+    /// The tileset at index `tileset` of [`Map::tilesets`].
+    Tileset {
+        tileset: usize,
+    },
+    /// A tile of the tileset at index `tileset` of [`Map::tilesets`].
+    Tile {
+        tileset: usize,
+        tile_id: TileId,
+    },
+}
+
+impl fmt::Display for PropertyOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropertyOwner::Map => write!(f, "map"),
+            PropertyOwner::Layer { layer_id } => write!(f, "layer {}", layer_id),
+            PropertyOwner::Object {
+                layer_id,
+                object_id,
+            } => write!(f, "object {} of layer {}", object_id, layer_id),
+            PropertyOwner::Tileset { tileset } => write!(f, "tileset {}", tileset),
+            PropertyOwner::Tile { tileset, tile_id } => {
+                write!(f, "tile {} of tileset {}", tile_id.0, tileset)
+            }
+        }
+    }
+}
+
+/// A property set on something in a map.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PropertyUse<'map> {
+    pub owner: PropertyOwner,
+    pub name: &'map str,
+    pub value: &'map PropertyValue,
+}
+
+/// Every property of a map, as returned by [`Map::property_index`].
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct PropertyIndex<'map> {
+    uses: Vec<PropertyUse<'map>>,
+    by_name: HashMap<&'map str, Vec<usize>>,
+}
+
+impl<'map> PropertyIndex<'map> {
+    /// All properties of the map: those of the map itself, then of its layers in file order, of
+    /// their objects, of the tilesets and of their tiles. The properties of each owner are
+    /// sorted by name.
+    pub fn uses(&self) -> &[PropertyUse<'map>] {
+        &self.uses
+    }
+
+    /// The properties called `name`, in the order of [`PropertyIndex::uses`].
+    pub fn named<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a PropertyUse<'map>> + 'a {
+        self.by_name
+            .get(name)
+            .map_or(&[][..], Vec::as_slice)
+            .iter()
+            .map(move |&i| &self.uses[i])
+    }
+
+    /// The names of all properties of the map, sorted.
+    pub fn names(&self) -> Vec<&'map str> {
+        let mut names: Vec<&'map str> = self.by_name.keys().copied().collect();
+        names.sort_unstable();
+        names
+    }
+
+    fn add(&mut self, owner: PropertyOwner, properties: &'map Properties) {
+        let mut sorted: Vec<_> = properties.iter().collect();
+        sorted.sort_by_key(|&(name, _)| name);
+        for (name, value) in sorted {
+            self.by_name
+                .entry(name.as_str())
+                .or_default()
+                .push(self.uses.len());
+            self.uses.push(PropertyUse { owner, name, value });
+        }
+    }
+}
+
+impl Map {
+    /// Lists every property set on the map, its layers, objects, tilesets and tiles, to find
+    /// where a property is used. Objects only count the properties they set themselves, not
+    /// those of their tile.
+    pub fn property_index(&self) -> PropertyIndex<'_> {
+        let mut index = PropertyIndex::default();
+        index.add(PropertyOwner::Map, &self.properties);
+
+        let mut layers: Vec<(u32, u32, &Properties)> = self
+            .layers
+            .iter()
+            .map(|l| (l.layer_index, l.id, &l.properties))
+            .chain(
+                self.image_layers
+                    .iter()
+                    .map(|l| (l.layer_index, l.id, &l.properties)),
+            )
+            .chain(
+                self.object_groups
+                    .iter()
+                    .filter_map(|l| Some((l.layer_index?, l.id, &l.properties))),
+            )
+            .chain(
+                self.groups
+                    .iter()
+                    .map(|l| (l.layer_index, l.id, &l.properties)),
+            )
+            .collect();
+        layers.sort_by_key(|&(index, ..)| index);
+        for (_, layer_id, properties) in layers {
+            index.add(PropertyOwner::Layer { layer_id }, properties);
+        }
+
+        for group in &self.object_groups {
+            for object in &group.objects {
+                let owner = PropertyOwner::Object {
+                    layer_id: group.id,
+                    object_id: object.id,
+                };
+                index.add(owner, &object.properties);
+            }
+        }
+
+        for (tileset_index, tileset) in self.tilesets.iter().enumerate() {
+            let owner = PropertyOwner::Tileset {
+                tileset: tileset_index,
+            };
+            index.add(owner, &tileset.properties);
+            for tile in &tileset.tiles {
+                let owner = PropertyOwner::Tile {
+                    tileset: tileset_index,
+                    tile_id: tile.id,
+                };
+                index.add(owner, &tile.properties);
+            }
+        }
+        index
+    }
+}
@@ -0,0 +1,194 @@
+//! Removing the tiles a map doesn't use from its tilesets and giving the tilesets consecutive
+//! gids, to keep shipped maps and texture atlases small.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    ids::TileId,
+    layers::LayerTile,
+    map::Map,
+    template::Template,
+    tileset::{GidResolver, Tileset},
+};
+
+/// What [`Map::prune_tilesets`] did to one of the tilesets of a map.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrunedTileset {
+    /// The first gid of the tileset before pruning.
+    pub old_first_gid: u32,
+    /// The first gid of the tileset after pruning.
+    pub first_gid: u32,
+    /// The ids the kept tiles had before pruning, in ascending order.
+    pub kept: Vec<TileId>,
+    /// Whether the other tiles were removed and the kept ones renumbered in order from 0. Only
+    /// image collection tilesets are pruned this way: the tiles of a tilesheet can't be cut out
+    /// of its image, so they keep their ids, and `kept` only tells which of them are needed, for
+    /// instance when packing them into an atlas.
+    pub renumbered: bool,
+}
+
+impl PrunedTileset {
+    /// The id the tile with the id `old` has after pruning, or `None` if it wasn't kept.
+    pub fn new_id(&self, old: TileId) -> Option<TileId> {
+        let index = self.kept.binary_search(&old).ok()?;
+        Some(if self.renumbered {
+            TileId(index as u32)
+        } else {
+            old
+        })
+    }
+}
+
+impl Map {
+    /// Removes the tiles the map doesn't use from its image collection tilesets, and gives
+    /// every tileset the first gid right after the tiles of the previous one, updating the
+    /// gids of tile layers, tile objects and templates to match. Tiles are kept if they are
+    /// counted by [`Map::tile_usage`] or are frames of the animation of a kept tile.
+    ///
+    /// Pruned tilesets are copied rather than changed when other maps share them. Animations
+    /// and wang sets are renumbered along with the tiles, and wang tiles of removed tiles are
+    /// dropped. Pruned external tilesets no longer match their file, and the changes aren't
+    /// recorded by [`Map::track_changes`].
+    ///
+    /// Returns what was done to each tileset, in the order of [`Map::tilesets`].
+    pub fn prune_tilesets(&mut self) -> Vec<PrunedTileset> {
+        let kept: Vec<Vec<TileId>> = self
+            .tile_usage()
+            .iter()
+            .map(|usage| {
+                let mut kept: HashSet<TileId> = usage.counts.keys().copied().collect();
+                let mut pending: Vec<TileId> = kept.iter().copied().collect();
+                while let Some(id) = pending.pop() {
+                    let tile = usage.tileset.get_tile(id);
+                    for frame in tile
+                        .and_then(|t| t.animation.as_ref())
+                        .into_iter()
+                        .flatten()
+                    {
+                        if kept.insert(frame.tile_id) {
+                            pending.push(frame.tile_id);
+                        }
+                    }
+                }
+                let mut kept: Vec<TileId> = kept.into_iter().collect();
+                kept.sort();
+                kept
+            })
+            .collect();
+        let mut next_gid = 1;
+        let pruned: Vec<PrunedTileset> = self
+            .tilesets
+            .iter()
+            .zip(kept)
+            .map(|(tileset, kept)| {
+                let renumbered = tileset.images.is_empty();
+                let first_gid = next_gid;
+                next_gid += if renumbered {
+                    kept.len() as u32
+                } else {
+                    // Gids beyond the tiles of the image still belong to the tileset.
+                    let used = kept.last().map_or(0, |id| id.0 + 1);
+                    tileset.tile_count().max(used)
+                };
+                PrunedTileset {
+                    old_first_gid: tileset.first_gid,
+                    first_gid,
+                    kept,
+                    renumbered,
+                }
+            })
+            .collect();
+
+        let mut resolver = GidResolver::new(&self.gid_table, &self.tilesets);
+        let mut remap = |raw: u32| {
+            let tile = LayerTile::new(raw);
+            let gid = resolver.resolve(tile.gid).and_then(|(index, id)| {
+                let tileset = &pruned[index];
+                Some(tileset.first_gid + tileset.new_id(id)?.0)
+            });
+            match gid {
+                Some(gid) => LayerTile { gid, ..tile }.raw_gid(),
+                None => raw,
+            }
+        };
+        for layer in self.layers.iter_mut() {
+            for tile in layer.tiles.tiles_mut() {
+                if tile.gid != 0 {
+                    *tile = LayerTile::new(remap(tile.raw_gid()));
+                }
+            }
+        }
+        // Templates are shared by their instances, so each is only translated once.
+        let mut templates: HashMap<*const Template, Arc<Template>> = HashMap::new();
+        for group in self.object_groups.iter_mut() {
+            for object in group.objects.iter_mut() {
+                if object.gid != 0 {
+                    object.gid = remap(object.gid);
+                }
+                if let Some(instance) = &mut object.template {
+                    if instance.template.object.gid != 0 {
+                        let template = templates
+                            .entry(Arc::as_ptr(&instance.template))
+                            .or_insert_with(|| {
+                                let mut template = Template::clone(&instance.template);
+                                template.object.gid = remap(template.object.gid);
+                                Arc::new(template)
+                            });
+                        instance.template = template.clone();
+                    }
+                }
+            }
+        }
+
+        for (tileset, pruned) in self.tilesets.iter_mut().zip(&pruned) {
+            tileset.first_gid = pruned.first_gid;
+            let unchanged = tileset.tiles.len() == pruned.kept.len()
+                && pruned
+                    .kept
+                    .iter()
+                    .enumerate()
+                    .all(|(i, id)| id.0 == i as u32);
+            if pruned.renumbered && !unchanged {
+                tileset.tileset = Arc::new(renumber(&tileset.tileset, &pruned.kept));
+            }
+        }
+        self.update_gid_table();
+        pruned
+    }
+}
+
+/// Copies an image collection tileset with only the tiles of `kept`, numbered in order.
+fn renumber(tileset: &Tileset, kept: &[TileId]) -> Tileset {
+    let ids: HashMap<TileId, TileId> = kept
+        .iter()
+        .enumerate()
+        .map(|(index, &id)| (id, TileId(index as u32)))
+        .collect();
+    let new_id = |id: u32| ids.get(&TileId(id)).map(|id| id.0);
+    let mut tileset = tileset.clone();
+    tileset.tiles.retain(|tile| ids.contains_key(&tile.id));
+    for tile in tileset.tiles.iter_mut() {
+        tile.id = ids[&tile.id];
+        for frame in tile.animation.iter_mut().flatten() {
+            frame.tile_id = ids.get(&frame.tile_id).copied().unwrap_or(frame.tile_id);
+        }
+    }
+    tileset.tiles.sort_by_key(|tile| tile.id);
+    for wang_set in tileset.wang_sets.iter_mut() {
+        wang_set.tile = wang_set.tile.and_then(new_id);
+        for colour in wang_set.colors.iter_mut() {
+            colour.tile = colour.tile.and_then(new_id);
+        }
+        wang_set
+            .wang_tiles
+            .retain(|tile| ids.contains_key(&tile.tile_id));
+        for tile in wang_set.wang_tiles.iter_mut() {
+            tile.tile_id = ids[&tile.tile_id];
+        }
+    }
+    tileset.tilecount = Some(kept.len() as u32);
+    tileset
+}
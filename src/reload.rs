@@ -0,0 +1,179 @@
+//! Updating a parsed map in place when its file changes, for hot reloading.
+
+use std::collections::HashSet;
+
+use crate::{
+    hash::{hash_image_layer, hash_layer, hash_object_group, hash_tileset},
+    map::Map,
+};
+
+/// What [`Map::update_from`] changed in a map.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct MapChanges {
+    /// Whether layers were added, removed, reordered or moved between groups. If they were, the
+    /// layer lists of the map were replaced, and indices into them may refer to other layers.
+    /// Otherwise, changed layers were replaced in place and all indices stay valid.
+    pub layers_restructured: bool,
+    /// Whether tilesets were added, removed or reordered, in which case [`Map::tilesets`] was
+    /// replaced.
+    pub tilesets_restructured: bool,
+    /// IDs of the layers of any kind that are new or whose content changed.
+    pub layers: Vec<u32>,
+    /// Indices in [`Map::tilesets`] of the tilesets that are new or whose content changed.
+    pub tilesets: Vec<usize>,
+    /// Whether the attributes or properties of the map itself changed.
+    pub map: bool,
+}
+
+impl MapChanges {
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        *self == MapChanges::default()
+    }
+}
+
+/// Finds the items of `new` that aren't in `old` with the same id and hash, returning their
+/// indices, along with whether the ids and positions of the items stayed the same.
+fn diff<P: PartialEq>(
+    old: impl Iterator<Item = (u32, P, u64)>,
+    new: impl Iterator<Item = (u32, P, u64)>,
+) -> (Vec<usize>, bool) {
+    let old: Vec<(u32, P, u64)> = old.collect();
+    let new: Vec<(u32, P, u64)> = new.collect();
+    let same_shape =
+        old.len() == new.len() && old.iter().zip(&new).all(|(a, b)| a.0 == b.0 && a.1 == b.1);
+    let old: HashSet<(u32, u64)> = old.iter().map(|&(id, _, hash)| (id, hash)).collect();
+    let changed = new
+        .iter()
+        .enumerate()
+        .filter(|(_, &(id, _, hash))| !old.contains(&(id, hash)))
+        .map(|(i, _)| i)
+        .collect();
+    (changed, same_shape)
+}
+
+/// Replaces the items of `old` at `changed` by those of `new` if the list has the same shape,
+/// and the whole list otherwise.
+fn patch<T>(old: &mut Vec<T>, new: Vec<T>, changed: &[usize], same_shape: bool) {
+    if same_shape {
+        for (i, item) in new.into_iter().enumerate() {
+            if changed.contains(&i) {
+                old[i] = item;
+            }
+        }
+    } else {
+        *old = new;
+    }
+}
+
+pub(crate) fn update(map: &mut Map, new: Map) -> MapChanges {
+    let mut changes = MapChanges::default();
+
+    let (layers, same_layers) = diff(
+        map.layers
+            .iter()
+            .map(|l| (l.id, (l.layer_index, l.parent_group), hash_layer(l))),
+        new.layers
+            .iter()
+            .map(|l| (l.id, (l.layer_index, l.parent_group), hash_layer(l))),
+    );
+    let (image_layers, same_image_layers) = diff(
+        map.image_layers
+            .iter()
+            .map(|l| (l.id, (l.layer_index, l.parent_group), hash_image_layer(l))),
+        new.image_layers
+            .iter()
+            .map(|l| (l.id, (l.layer_index, l.parent_group), hash_image_layer(l))),
+    );
+    let (object_groups, same_object_groups) = diff(
+        map.object_groups
+            .iter()
+            .map(|g| (g.id, (g.layer_index, g.parent_group), hash_object_group(g))),
+        new.object_groups
+            .iter()
+            .map(|g| (g.id, (g.layer_index, g.parent_group), hash_object_group(g))),
+    );
+    let groups: Vec<usize> = (0..new.groups.len())
+        .filter(|&i| map.groups.get(i) != Some(&new.groups[i]))
+        .collect();
+    let same_groups = map.groups.len() == new.groups.len()
+        && map.groups.iter().zip(&new.groups).all(|(a, b)| {
+            (a.id, a.layer_index, a.parent_group) == (b.id, b.layer_index, b.parent_group)
+        });
+    let (tilesets, same_tilesets) = diff(
+        map.tilesets
+            .iter()
+            .map(|t| (t.first_gid, (), hash_tileset(t))),
+        new.tilesets
+            .iter()
+            .map(|t| (t.first_gid, (), hash_tileset(t))),
+    );
+
+    changes.layers = (layers.iter().map(|&i| new.layers[i].id))
+        .chain(image_layers.iter().map(|&i| new.image_layers[i].id))
+        .chain(object_groups.iter().map(|&i| new.object_groups[i].id))
+        .chain(groups.iter().map(|&i| new.groups[i].id))
+        .collect();
+    changes.layers_restructured =
+        !(same_layers && same_image_layers && same_object_groups && same_groups);
+    changes.tilesets_restructured = !same_tilesets;
+    changes.tilesets = tilesets.clone();
+    changes.map = (
+        &map.version,
+        map.orientation,
+        (map.width, map.height, map.tile_width, map.tile_height),
+        &map.properties,
+        &map.background_colour,
+        map.infinite,
+        map.stagger_axis,
+        map.stagger_index,
+        map.hex_side_length,
+        (map.parallax_origin_x, map.parallax_origin_y),
+    ) != (
+        &new.version,
+        new.orientation,
+        (new.width, new.height, new.tile_width, new.tile_height),
+        &new.properties,
+        &new.background_colour,
+        new.infinite,
+        new.stagger_axis,
+        new.stagger_index,
+        new.hex_side_length,
+        (new.parallax_origin_x, new.parallax_origin_y),
+    );
+
+    // Layers are kept in place only if they all are, so that indices stay consistent between
+    // the lists.
+    let same_shape = !changes.layers_restructured;
+    patch(&mut map.layers, new.layers, &layers, same_shape);
+    patch(
+        &mut map.image_layers,
+        new.image_layers,
+        &image_layers,
+        same_shape,
+    );
+    patch(
+        &mut map.object_groups,
+        new.object_groups,
+        &object_groups,
+        same_shape,
+    );
+    patch(&mut map.groups, new.groups, &groups, same_shape);
+    patch(&mut map.tilesets, new.tilesets, &tilesets, same_tilesets);
+    map.update_gid_table();
+    map.version = new.version;
+    map.orientation = new.orientation;
+    map.width = new.width;
+    map.height = new.height;
+    map.tile_width = new.tile_width;
+    map.tile_height = new.tile_height;
+    map.properties = new.properties;
+    map.background_colour = new.background_colour;
+    map.infinite = new.infinite;
+    map.stagger_axis = new.stagger_axis;
+    map.stagger_index = new.stagger_index;
+    map.hex_side_length = new.hex_side_length;
+    map.parallax_origin_x = new.parallax_origin_x;
+    map.parallax_origin_y = new.parallax_origin_y;
+    changes
+}
@@ -0,0 +1,435 @@
+//! Software rendering of maps into [`RgbaImage`]s of the `image` crate, enabled by the `image`
+//! feature, for thumbnails, golden-image tests and documentation.
+//!
+//! The images of tilesets and image layers are requested from the caller, who can decode them
+//! with [`Image::decode`] or get them from elsewhere, such as an asset cache.
+
+use std::collections::HashMap;
+
+use ::image::Rgba;
+
+use crate::{
+    error::TiledError,
+    ids::TileId,
+    image::Image,
+    layers::{ImageLayer, Layer, LayerTile, TileRect},
+    map::{Map, Orientation},
+    objects::ObjectGroup,
+    tileset::MapTileset,
+    transform::{LayerRef, Tint},
+};
+
+/// The images maps are drawn into, and tileset images are given to the renderer as.
+pub use ::image::RgbaImage;
+
+/// Draws `pixel` over the pixel of `canvas` at the given coordinates, after multiplying it with
+/// `tint`.
+fn blend_pixel(canvas: &mut RgbaImage, x: i32, y: i32, pixel: [u8; 4], tint: &Tint) {
+    if x < 0 || y < 0 || x as u32 >= canvas.width() || y as u32 >= canvas.height() {
+        return;
+    }
+    let alpha = pixel[3] as f32 / 255.0 * tint.alpha;
+    if alpha <= 0.0 {
+        return;
+    }
+    let dst = &mut canvas.get_pixel_mut(x as u32, y as u32).0;
+    let dst_alpha = dst[3] as f32 / 255.0;
+    let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+    let tints = [tint.red, tint.green, tint.blue];
+    for c in 0..3 {
+        let src = pixel[c] as f32 * tints[c];
+        let value = (src * alpha + dst[c] as f32 * dst_alpha * (1.0 - alpha)) / out_alpha;
+        dst[c] = value.round().clamp(0.0, 255.0) as u8;
+    }
+    dst[3] = (out_alpha * 255.0).round() as u8;
+}
+
+/// What [`Map::render_to_image`] and [`Map::render_minimap`] draw.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RenderOptions {
+    /// Whether to fill the image with the background colour of the map, if it has one.
+    pub background: bool,
+    /// Whether to draw layers that are hidden, or inside hidden groups.
+    pub hidden_layers: bool,
+    /// Whether to draw the tile objects of object layers. Other objects are never drawn, and
+    /// minimaps never draw objects.
+    pub tile_objects: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions {
+            background: true,
+            hidden_layers: false,
+            tile_objects: true,
+        }
+    }
+}
+
+/// A rectangle of an image, as `(x, y, width, height)` in pixels.
+type PixelRect = (u32, u32, u32, u32);
+
+/// Part of a source image, drawn into a rectangle of the map.
+struct Sprite {
+    source: PixelRect,
+    dest: (f32, f32, f32, f32),
+    flip_h: bool,
+    flip_v: bool,
+    flip_d: bool,
+}
+
+struct Renderer<'map, F> {
+    map: &'map Map,
+    canvas: RgbaImage,
+    /// The position of the top-left corner of the canvas, in map pixels.
+    origin: (f32, f32),
+    images: ImageCache<F>,
+}
+
+/// The images loaded so far, by path relative to the map.
+struct ImageCache<F> {
+    images: HashMap<String, Option<RgbaImage>>,
+    load_image: F,
+}
+
+impl Map {
+    /// Draws the map into a new image, one pixel per map pixel, the way the editor shows it with
+    /// the view on the parallax origin: layers are composited in draw order with the opacity,
+    /// tint and offset they get from their groups, and tiles are flipped as the layers say.
+    /// Finite maps are drawn whole, infinite maps within their [used bounds](Map::used_bounds).
+    ///
+    /// `load_image` is called once for each image needed, with the path of the image relative to
+    /// the map available as [`Image::source_relative_to_map`]. Images it returns `None` for are
+    /// left out. The transparent colour of images is applied to what it returns.
+    ///
+    /// Animated tiles are drawn as the tile itself rather than one of its frames. Objects are
+    /// drawn unrotated, and only on orthogonal and staggered maps. Fails for hexagonal maps.
+    pub fn render_to_image(
+        &self,
+        options: &RenderOptions,
+        load_image: impl FnMut(&Image) -> Option<RgbaImage>,
+    ) -> Result<RgbaImage, TiledError> {
+        if self.orientation == Orientation::Hexagonal {
+            return Err(TiledError::Other(
+                "Hexagonal maps can't be rendered".to_string(),
+            ));
+        }
+        let (min, max) = pixel_bounds(self, drawn_rect(self));
+        let mut renderer = Renderer {
+            map: self,
+            canvas: RgbaImage::new(
+                (max.0 - min.0).ceil().max(0.0) as u32,
+                (max.1 - min.1).ceil().max(0.0) as u32,
+            ),
+            origin: min,
+            images: ImageCache::new(load_image),
+        };
+        if options.background {
+            fill_background(self, &mut renderer.canvas);
+        }
+        for flat in self.iter_layers_recursive() {
+            if !flat.visible && !options.hidden_layers {
+                continue;
+            }
+            let offset = (flat.transform.offset_x, flat.transform.offset_y);
+            match flat.layer {
+                LayerRef::Tile(layer) => renderer.draw_tile_layer(layer, offset, &flat.tint),
+                LayerRef::Image(layer) => renderer.draw_image_layer(layer, offset, &flat.tint),
+                LayerRef::Objects(group) if options.tile_objects => {
+                    renderer.draw_objects(group, offset, &flat.tint, options.hidden_layers)
+                }
+                LayerRef::Objects(_) => {}
+            }
+        }
+        Ok(renderer.canvas)
+    }
+
+    /// Draws an overview of the map into a new image, one pixel per tile of the same rectangle
+    /// of tiles [`Map::render_to_image`] draws, in grid coordinates whatever the orientation of
+    /// the map. Each tile is drawn in the average colour of its image, computed the first time
+    /// the tile is met, and tile layers are composited with the opacity and tint they get from
+    /// their groups. Offsets and objects are ignored.
+    ///
+    /// `load_image` is called as for [`Map::render_to_image`], for the images of the tiles used.
+    pub fn render_minimap(
+        &self,
+        options: &RenderOptions,
+        load_image: impl FnMut(&Image) -> Option<RgbaImage>,
+    ) -> RgbaImage {
+        let rect = drawn_rect(self);
+        let mut canvas = RgbaImage::new(rect.width, rect.height);
+        if options.background {
+            fill_background(self, &mut canvas);
+        }
+        let mut images = ImageCache::new(load_image);
+        let mut colours: HashMap<u32, Option<[u8; 4]>> = HashMap::new();
+        for flat in self.iter_layers_recursive() {
+            let layer = match flat.layer {
+                LayerRef::Tile(layer) if flat.visible || options.hidden_layers => layer,
+                _ => continue,
+            };
+            for tile in self.resolve_layer(layer) {
+                let colour = *colours.entry(tile.tile.gid).or_insert_with(|| {
+                    let (image, source) = tile_image(tile.tileset, tile.id.0)?;
+                    average_colour(images.load(image)?, source)
+                });
+                if let Some(colour) = colour {
+                    blend_pixel(
+                        &mut canvas,
+                        tile.x - rect.x,
+                        tile.y - rect.y,
+                        colour,
+                        &flat.tint,
+                    );
+                }
+            }
+        }
+        canvas
+    }
+}
+
+impl<'map, F: FnMut(&Image) -> Option<RgbaImage>> Renderer<'map, F> {
+    fn draw_tile_layer(&mut self, layer: &Layer, offset: (f32, f32), tint: &Tint) {
+        let map = self.map;
+        for tile in map.resolve_layer(layer) {
+            let (x, y) = match map.tile_to_pixel(tile.x, tile.y) {
+                Some(position) => position,
+                None => continue,
+            };
+            if let Some((image, source)) = tile_image(tile.tileset, tile.id.0) {
+                // Tiles larger than the grid stick out at the top, as in the editor.
+                let bottom = y + map.tile_height as f32;
+                let sprite = Sprite {
+                    source,
+                    dest: (
+                        x + offset.0,
+                        bottom - source.3 as f32 + offset.1,
+                        source.2 as f32,
+                        source.3 as f32,
+                    ),
+                    flip_h: tile.tile.flip_h,
+                    flip_v: tile.tile.flip_v,
+                    flip_d: tile.tile.flip_d,
+                };
+                self.draw(image, &sprite, tint);
+            }
+        }
+    }
+
+    fn draw_image_layer(&mut self, layer: &ImageLayer, offset: (f32, f32), tint: &Tint) {
+        if let Some(image) = &layer.image {
+            let (width, height) = match self.images.load(image) {
+                Some(loaded) => loaded.dimensions(),
+                None => return,
+            };
+            let sprite = Sprite {
+                source: (0, 0, width, height),
+                dest: (offset.0, offset.1, width as f32, height as f32),
+                flip_h: false,
+                flip_v: false,
+                flip_d: false,
+            };
+            self.draw(image, &sprite, tint);
+        }
+    }
+
+    fn draw_objects(&mut self, group: &ObjectGroup, offset: (f32, f32), tint: &Tint, hidden: bool) {
+        let map = self.map;
+        if map.orientation == Orientation::Isometric {
+            return;
+        }
+        for object in group.objects_in_draw_order() {
+            if object.gid == 0 || (!object.visible && !hidden) {
+                continue;
+            }
+            let tile = LayerTile::new(object.gid);
+            let tileset = match map.get_tileset_by_gid(tile.gid) {
+                Some(tileset) => tileset,
+                None => continue,
+            };
+            if let Some((image, source)) = tile_image(tileset, tile.gid - tileset.first_gid) {
+                let (width, height) = if object.width > 0.0 && object.height > 0.0 {
+                    (object.width, object.height)
+                } else {
+                    (source.2 as f32, source.3 as f32)
+                };
+                // Tile objects are positioned by their bottom-left corner.
+                let sprite = Sprite {
+                    source,
+                    dest: (
+                        object.x + offset.0,
+                        object.y - height + offset.1,
+                        width,
+                        height,
+                    ),
+                    flip_h: tile.flip_h,
+                    flip_v: tile.flip_v,
+                    flip_d: tile.flip_d,
+                };
+                self.draw(image, &sprite, tint);
+            }
+        }
+    }
+
+    /// Draws a sprite, scaling it to its destination with nearest-neighbour sampling.
+    fn draw(&mut self, image: &Image, sprite: &Sprite, tint: &Tint) {
+        let origin = self.origin;
+        let canvas = &mut self.canvas;
+        let source = match self.images.load(image) {
+            Some(source) => source,
+            None => return,
+        };
+        let (sx, sy, sw, sh) = sprite.source;
+        // The size of the sprite once flipped diagonally, before scaling.
+        let (fw, fh) = if sprite.flip_d { (sh, sw) } else { (sw, sh) };
+        let (dx, dy, dw, dh) = sprite.dest;
+        let (left, top) = (
+            (dx - origin.0).round() as i32,
+            (dy - origin.1).round() as i32,
+        );
+        let (width, height) = (dw.round() as i32, dh.round() as i32);
+        if fw == 0 || fh == 0 || width <= 0 || height <= 0 {
+            return;
+        }
+        // Only the part of the sprite on the canvas is sampled.
+        let clip = |start: i32, size: i32, canvas_size: u32| {
+            let first = (-(start as i64)).clamp(0, size as i64);
+            let end = (canvas_size as i64 - start as i64).clamp(first, size as i64);
+            first as i32..end as i32
+        };
+        let columns = clip(left, width, canvas.width());
+        for y in clip(top, height, canvas.height()) {
+            for x in columns.clone() {
+                let mut u = (x as u64 * fw as u64 / width as u64) as u32;
+                let mut v = (y as u64 * fh as u64 / height as u64) as u32;
+                if sprite.flip_h {
+                    u = fw - 1 - u;
+                }
+                if sprite.flip_v {
+                    v = fh - 1 - v;
+                }
+                if sprite.flip_d {
+                    std::mem::swap(&mut u, &mut v);
+                }
+                if let Some(pixel) = source.get_pixel_checked(sx + u, sy + v) {
+                    blend_pixel(canvas, left + x, top + y, pixel.0, tint);
+                }
+            }
+        }
+    }
+}
+
+impl<F: FnMut(&Image) -> Option<RgbaImage>> ImageCache<F> {
+    fn new(load_image: F) -> ImageCache<F> {
+        ImageCache {
+            images: HashMap::new(),
+            load_image,
+        }
+    }
+
+    fn load(&mut self, image: &Image) -> Option<&RgbaImage> {
+        let load_image = &mut self.load_image;
+        self.images
+            .entry(image.source_relative_to_map())
+            .or_insert_with(|| {
+                let mut loaded = load_image(image)?;
+                if let Some(key) = image.transparent_colour {
+                    for pixel in loaded.pixels_mut() {
+                        if pixel.0[..3] == [key.red, key.green, key.blue] {
+                            pixel.0[3] = 0;
+                        }
+                    }
+                }
+                Some(loaded)
+            })
+            .as_ref()
+    }
+}
+
+/// The tiles drawn: finite maps whole, infinite maps within their used bounds.
+fn drawn_rect(map: &Map) -> TileRect {
+    if map.infinite {
+        map.used_bounds().unwrap_or(TileRect {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 0,
+        })
+    } else {
+        TileRect {
+            x: 0,
+            y: 0,
+            width: map.width,
+            height: map.height,
+        }
+    }
+}
+
+fn fill_background(map: &Map, canvas: &mut RgbaImage) {
+    if let Some(colour) = map.background_colour {
+        let pixel = Rgba([colour.red, colour.green, colour.blue, colour.alpha]);
+        for p in canvas.pixels_mut() {
+            *p = pixel;
+        }
+    }
+}
+
+/// The average colour of a rectangle of an image, weighting colours by their opacity, or `None`
+/// if the rectangle is outside of the image.
+fn average_colour(image: &RgbaImage, (x, y, width, height): PixelRect) -> Option<[u8; 4]> {
+    let (mut sums, mut alpha, mut count) = ([0u64; 3], 0u64, 0u64);
+    for v in y..y.saturating_add(height) {
+        for u in x..x.saturating_add(width) {
+            if let Some(&Rgba(pixel)) = image.get_pixel_checked(u, v) {
+                for c in 0..3 {
+                    sums[c] += pixel[c] as u64 * pixel[3] as u64;
+                }
+                alpha += pixel[3] as u64;
+                count += 1;
+            }
+        }
+    }
+    if count == 0 {
+        return None;
+    }
+    let channel = |sum: u64| (sum + alpha / 2).checked_div(alpha).unwrap_or(0) as u8;
+    Some([
+        channel(sums[0]),
+        channel(sums[1]),
+        channel(sums[2]),
+        ((alpha + count / 2) / count) as u8,
+    ])
+}
+
+/// The image of a tile and the rectangle of the tile in it.
+fn tile_image(tileset: &MapTileset, id: u32) -> Option<(&Image, PixelRect)> {
+    let tile_image = tileset.tile_image(TileId(id))?;
+    Some((tile_image.image, tile_image.rect))
+}
+
+/// The top-left and bottom-right corners of the pixels covered by the tiles of `rect`. Only
+/// the tiles along the edges of the rectangle need to be looked at.
+fn pixel_bounds(map: &Map, rect: TileRect) -> ((f32, f32), (f32, f32)) {
+    let (mut min, mut max) = ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN));
+    let (right, bottom) = (
+        rect.x + rect.width as i32 - 1,
+        rect.y + rect.height as i32 - 1,
+    );
+    let edges = (rect.x..=right)
+        .flat_map(|x| [(x, rect.y), (x, bottom)])
+        .chain((rect.y..=bottom).flat_map(|y| [(rect.x, y), (right, y)]));
+    for (x, y) in edges {
+        if let Some((px, py)) = map.tile_to_pixel(x, y) {
+            min = (min.0.min(px), min.1.min(py));
+            max = (
+                max.0.max(px + map.tile_width as f32),
+                max.1.max(py + map.tile_height as f32),
+            );
+        }
+    }
+    if min.0 > max.0 {
+        ((0.0, 0.0), (0.0, 0.0))
+    } else {
+        (min, max)
+    }
+}
@@ -0,0 +1,59 @@
+//! Conversion of object shapes into paths of the `lyon` crate, enabled by the `lyon` feature,
+//! for tessellating them on the GPU or drawing them for debugging.
+
+use ::lyon::{
+    geom::{euclid::Angle, Box2D},
+    math::{point, vector, Point, Transform},
+    path::{Path, Polygon, Winding},
+};
+
+use crate::objects::{Object, ObjectShape};
+
+impl Object {
+    /// Returns the outline of the object as a path, in the same coordinates as the object's
+    /// position and with its rotation applied around it, like [`Object::contains_point`].
+    ///
+    /// Rectangles, ellipses and polygons become closed paths, and polylines open ones. Tile
+    /// objects and text objects become the rectangle they cover. Returns `None` for points,
+    /// which have no outline.
+    pub fn to_lyon_path(&self) -> Option<Path> {
+        let transform = Transform::rotation(Angle::degrees(self.rotation))
+            .then_translate(vector(self.x, self.y));
+        let mut builder = Path::builder().transformed(transform);
+        let rectangle = |top: f32, width: f32, height: f32| {
+            Box2D::new(point(0.0, top), point(width, top + height))
+        };
+        let points = |points: &[(f32, f32)]| -> Vec<Point> {
+            points.iter().map(|&(x, y)| point(x, y)).collect()
+        };
+        match &self.shape {
+            ObjectShape::Point(..) => return None,
+            ObjectShape::Polyline { points: p } => builder.add_polygon(Polygon {
+                points: &points(p),
+                closed: false,
+            }),
+            ObjectShape::Polygon { points: p } => builder.add_polygon(Polygon {
+                points: &points(p),
+                closed: true,
+            }),
+            ObjectShape::Ellipse { width, height } => {
+                let radii = vector(width / 2.0, height / 2.0);
+                builder.add_ellipse(
+                    point(radii.x, radii.y),
+                    radii,
+                    Angle::zero(),
+                    Winding::Positive,
+                )
+            }
+            // Tile objects are anchored at their bottom-left corner.
+            _ if self.gid != 0 => builder.add_rectangle(
+                &rectangle(-self.height, self.width, self.height),
+                Winding::Positive,
+            ),
+            ObjectShape::Rect { width, height } | ObjectShape::Text { width, height, .. } => {
+                builder.add_rectangle(&rectangle(0.0, *width, *height), Winding::Positive)
+            }
+        }
+        Some(builder.build())
+    }
+}
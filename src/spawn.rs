@@ -0,0 +1,67 @@
+//! A hook for turning the objects of a map into the entities of a game engine, so that engine
+//! integrations only say how to build one entity instead of walking the map themselves.
+
+use crate::{map::Map, objects::MapObject, properties::Properties};
+
+/// Builds the entities of a game from the objects of a map, see [`Map::spawn_all`].
+///
+/// Closures taking an object and its properties implement it too:
+///
+/// ```no_run
+/// use tiled::{
+///     objects::MapObject,
+///     properties::{Properties, PropertiesExt},
+/// };
+///
+/// struct Enemy {
+///     x: f32,
+///     y: f32,
+///     speed: f32,
+/// }
+///
+/// # let map = tiled::parse_file(std::path::Path::new("map.tmx")).unwrap();
+/// let mut spawn_enemy = |object: MapObject<'_>, properties: &Properties| {
+///     if object.object.obj_type != "enemy" {
+///         return None;
+///     }
+///     Some(Enemy {
+///         x: object.object.x,
+///         y: object.object.y,
+///         speed: properties.get_as("speed").unwrap_or(1.0),
+///     })
+/// };
+/// let enemies: Vec<Enemy> = map.spawn_all(&mut spawn_enemy);
+/// ```
+pub trait SpawnFromObject {
+    /// What the objects are turned into.
+    type Entity;
+
+    /// Builds the entity for `object`, or returns `None` to skip it. `properties` are those of
+    /// the object as the editor presents them, see
+    /// [`Object::resolved_properties`](crate::objects::Object::resolved_properties).
+    fn spawn(&mut self, object: MapObject<'_>, properties: &Properties) -> Option<Self::Entity>;
+}
+
+impl<E, F> SpawnFromObject for F
+where
+    F: FnMut(MapObject<'_>, &Properties) -> Option<E>,
+{
+    type Entity = E;
+
+    fn spawn(&mut self, object: MapObject<'_>, properties: &Properties) -> Option<E> {
+        self(object, properties)
+    }
+}
+
+impl Map {
+    /// Calls `spawner` for every object of the map, in the order of [`Map::objects`], returning
+    /// the entities it built.
+    pub fn spawn_all<S: SpawnFromObject>(&self, spawner: &mut S) -> Vec<S::Entity> {
+        self.objects()
+            .filter_map(|object| {
+                let properties = object.object.resolved_properties(self);
+                spawner.spawn(object, &properties)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,399 @@
+//! Assembling maps from smaller ones, such as dungeons built from room templates at runtime.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{
+    error::TiledError,
+    journal::Journal,
+    layers::{combined_extent, LayerData, LayerTile},
+    map::{Map, Orientation},
+    objects::ObjectGroup,
+    properties::{Properties, PropertyValue},
+    template::Template,
+    tileset::{GidResolver, MapTileset},
+};
+
+/// The IDs changed by [`Map::reassign_ids`], as `(old, new)` pairs in draw order.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct ReassignedIds {
+    pub layers: Vec<(u32, u32)>,
+    pub objects: Vec<(u32, u32)>,
+}
+
+/// The IDs [`Map::instantiate`] gives to the layers and objects of the next copy. Use the same
+/// one for every copy placed into a world to keep their IDs unique.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct InstanceIds {
+    pub next_layer_id: u32,
+    pub next_object_id: u32,
+}
+
+impl InstanceIds {
+    /// Starts with the IDs following the ones used by `map`, such as the map the copies are
+    /// placed in.
+    pub fn after(map: &Map) -> InstanceIds {
+        InstanceIds {
+            next_layer_id: map.next_layer_id(),
+            next_object_id: map.next_object_id(),
+        }
+    }
+}
+
+impl Default for InstanceIds {
+    fn default() -> Self {
+        InstanceIds {
+            next_layer_id: 1,
+            next_object_id: 1,
+        }
+    }
+}
+
+impl Map {
+    /// Returns a copy of the map with new layer and object IDs taken from `ids`, for placing the
+    /// same authored room several times into a world. IDs are given in draw order, and object
+    /// properties referring to objects of the map are updated to the new IDs. The objects are
+    /// moved by `offset` pixels; the tiles stay where they are in their layers.
+    ///
+    /// The copy shares its tilesets and templates with the map, and doesn't keep its
+    /// [tracked changes](Map::track_changes).
+    pub fn instantiate(&self, ids: &mut InstanceIds, offset: (f32, f32)) -> Map {
+        let mut copy = self.clone();
+        copy.journal = Journal::default();
+
+        let mut layer_ids: Vec<(u32, &mut u32)> = copy
+            .layers
+            .iter_mut()
+            .map(|l| (l.layer_index, &mut l.id))
+            .chain(
+                copy.image_layers
+                    .iter_mut()
+                    .map(|l| (l.layer_index, &mut l.id)),
+            )
+            .chain(
+                copy.object_groups
+                    .iter_mut()
+                    .map(|g| (g.layer_index.unwrap_or(0), &mut g.id)),
+            )
+            .chain(copy.groups.iter_mut().map(|g| (g.layer_index, &mut g.id)))
+            .collect();
+        layer_ids.sort_by_key(|(layer_index, _)| *layer_index);
+        for (_, id) in layer_ids {
+            *id = ids.next_layer_id;
+            ids.next_layer_id += 1;
+        }
+
+        let mut new_object_ids = HashMap::new();
+        let mut groups: Vec<&mut ObjectGroup> = copy.object_groups.iter_mut().collect();
+        groups.sort_by_key(|g| g.layer_index);
+        for object in groups.into_iter().flat_map(|g| g.objects.iter_mut()) {
+            if object.id != 0 {
+                new_object_ids
+                    .entry(object.id)
+                    .or_insert(ids.next_object_id);
+            }
+            object.id = ids.next_object_id;
+            ids.next_object_id += 1;
+            object.x += offset.0;
+            object.y += offset.1;
+        }
+
+        let properties = copy
+            .layers
+            .iter_mut()
+            .map(|l| &mut l.properties)
+            .chain(copy.image_layers.iter_mut().map(|l| &mut l.properties))
+            .chain(copy.groups.iter_mut().map(|g| &mut g.properties))
+            .chain(copy.object_groups.iter_mut().flat_map(|g| {
+                let objects = g.objects.iter_mut().flat_map(|o| {
+                    let own = o.template.as_mut().map(|t| &mut t.own_properties);
+                    std::iter::once(&mut o.properties).chain(own)
+                });
+                std::iter::once(&mut g.properties).chain(objects)
+            }))
+            .chain(std::iter::once(&mut copy.properties));
+        for properties in properties {
+            remap_object_references(properties, &new_object_ids);
+        }
+        copy
+    }
+
+    /// Merges `other` into the map, with its tile (0, 0) at the tile `offset` of the map.
+    ///
+    /// Tilesets of `other` the map doesn't have yet are added after its own, and the gids of
+    /// the merged tiles are translated to the gids of the map. The non-empty tiles of each tile
+    /// layer of `other` are pasted into the first tile layer of the map with the same name, and
+    /// its objects are moved into the first object layer with the same name, with new IDs.
+    /// Layers the map doesn't have are added on top of the others, with new IDs and outside of
+    /// any group. Image layers and group layers of `other` are left out. Finite maps grow to
+    /// the right and down to fit `other`, and so do their tile layers.
+    ///
+    /// Fails if the orientations or tile sizes of the maps differ, or if tiles of `other` would
+    /// end up above or left of a finite map. The changes aren't recorded by
+    /// [`Map::track_changes`].
+    pub fn append(&mut self, other: &Map, offset: (i32, i32)) -> Result<(), TiledError> {
+        if (self.orientation, self.tile_width, self.tile_height)
+            != (other.orientation, other.tile_width, other.tile_height)
+        {
+            return Err(TiledError::Other(
+                "Only maps with the same orientation and tile size can be merged".to_string(),
+            ));
+        }
+        let extent = combined_extent(&other.layers);
+        let (right, bottom) = (
+            extent.x + extent.width as i32 + offset.0,
+            extent.y + extent.height as i32 + offset.1,
+        );
+        if !self.infinite {
+            if extent.x + offset.0 < 0 || extent.y + offset.1 < 0 {
+                return Err(TiledError::Other(
+                    "Maps can't be merged above or left of a finite map".to_string(),
+                ));
+            }
+            self.resize(
+                self.width.max(right.max(0) as u32),
+                self.height.max(bottom.max(0) as u32),
+            );
+        }
+
+        let mut next_gid = self
+            .tilesets
+            .iter()
+            .map(|t| t.first_gid + t.tile_count())
+            .max()
+            .unwrap_or(1);
+        let first_gids: Vec<u32> = other
+            .tilesets
+            .iter()
+            .map(|tileset| {
+                let existing = self.tilesets.iter().find(|t| {
+                    Arc::ptr_eq(&t.tileset, &tileset.tileset) || t.tileset == tileset.tileset
+                });
+                match existing {
+                    Some(existing) => existing.first_gid,
+                    None => {
+                        let first_gid = next_gid;
+                        next_gid += tileset.tile_count();
+                        self.tilesets
+                            .push(MapTileset::new(first_gid, tileset.tileset.clone()));
+                        first_gid
+                    }
+                }
+            })
+            .collect();
+        let mut resolver = GidResolver::new(&other.gid_table, &other.tilesets);
+        let mut translate = |raw: u32| {
+            let tile = LayerTile::new(raw);
+            match resolver.resolve(tile.gid) {
+                Some((index, id)) => LayerTile {
+                    gid: first_gids[index] + id.0,
+                    ..tile
+                }
+                .raw_gid(),
+                None => raw,
+            }
+        };
+
+        let mut next_layer_index = self.next_layer_index();
+        let mut next_layer_id = self.next_layer_id();
+        for layer in &other.layers {
+            let index = match self.layers.iter().position(|l| l.name == layer.name) {
+                Some(index) => index,
+                None => {
+                    let mut new = layer.clone();
+                    new.tiles = if self.infinite {
+                        LayerData::Infinite(HashMap::new())
+                    } else {
+                        LayerData::Finite(vec![
+                            vec![LayerTile::new(0); self.width as usize];
+                            self.height as usize
+                        ])
+                    };
+                    new.layer_index = next_layer_index;
+                    new.id = next_layer_id;
+                    new.parent_group = None;
+                    next_layer_index += 1;
+                    next_layer_id += 1;
+                    self.layers.push(new);
+                    self.layers.len() - 1
+                }
+            };
+            for tile in other.resolve_layer(layer) {
+                let (x, y) = (tile.x + offset.0, tile.y + offset.1);
+                let tile = LayerTile::new(translate(tile.tile.raw_gid()));
+                self.layers[index].tiles.set_tile(x, y, tile);
+            }
+        }
+
+        let (dx, dy) = tile_offset_to_pixels(self, offset);
+        let mut next_object_id = self.next_object_id();
+        // Templates are shared by their instances, so each is only translated once.
+        let mut templates: HashMap<*const Template, Arc<Template>> = HashMap::new();
+        for group in &other.object_groups {
+            let index = match self.object_groups.iter().position(|g| g.name == group.name) {
+                Some(index) => index,
+                None => {
+                    let mut new = group.clone();
+                    new.objects.clear();
+                    new.layer_index = Some(next_layer_index);
+                    new.id = next_layer_id;
+                    new.parent_group = None;
+                    next_layer_index += 1;
+                    next_layer_id += 1;
+                    self.object_groups.push(new);
+                    self.object_groups.len() - 1
+                }
+            };
+            for object in &group.objects {
+                let mut object = object.clone();
+                object.id = next_object_id;
+                next_object_id += 1;
+                object.x += dx;
+                object.y += dy;
+                if object.gid != 0 {
+                    object.gid = translate(object.gid);
+                }
+                if let Some(instance) = &mut object.template {
+                    if instance.template.object.gid != 0 {
+                        let template = templates
+                            .entry(Arc::as_ptr(&instance.template))
+                            .or_insert_with(|| {
+                                let mut template = Template::clone(&instance.template);
+                                template.object.gid = translate(template.object.gid);
+                                Arc::new(template)
+                            });
+                        instance.template = template.clone();
+                    }
+                }
+                self.object_groups[index].objects.push(object);
+            }
+        }
+        self.update_gid_table();
+        Ok(())
+    }
+
+    /// Gives new IDs to the layers and objects whose ID is 0 or was already used by another
+    /// layer or object drawn before, as happens when layers are copied between maps. New IDs
+    /// start from the next free ID, the way the editor allocates them. Layers of all kinds,
+    /// group layers included, share IDs.
+    ///
+    /// Object properties referring to a duplicated ID keep referring to the first object with
+    /// it; the returned pairs tell which IDs were replaced, so that references within copied
+    /// content can be redirected to the copies.
+    pub fn reassign_ids(&mut self) -> ReassignedIds {
+        let mut reassigned = ReassignedIds::default();
+        let mut next_id = self.next_layer_id();
+        let mut layer_ids: Vec<(u32, &mut u32)> = self
+            .layers
+            .iter_mut()
+            .map(|l| (l.layer_index, &mut l.id))
+            .chain(
+                self.image_layers
+                    .iter_mut()
+                    .map(|l| (l.layer_index, &mut l.id)),
+            )
+            .chain(
+                self.object_groups
+                    .iter_mut()
+                    .map(|g| (g.layer_index.unwrap_or(0), &mut g.id)),
+            )
+            .chain(self.groups.iter_mut().map(|g| (g.layer_index, &mut g.id)))
+            .collect();
+        layer_ids.sort_by_key(|(layer_index, _)| *layer_index);
+        let mut seen = HashSet::new();
+        for (_, id) in layer_ids {
+            if *id == 0 || !seen.insert(*id) {
+                reassigned.layers.push((*id, next_id));
+                *id = next_id;
+                next_id += 1;
+            }
+        }
+
+        let mut next_id = self.next_object_id();
+        let mut groups: Vec<&mut ObjectGroup> = self.object_groups.iter_mut().collect();
+        groups.sort_by_key(|g| g.layer_index);
+        let mut seen = HashSet::new();
+        for object in groups.into_iter().flat_map(|g| g.objects.iter_mut()) {
+            if object.id == 0 || !seen.insert(object.id) {
+                reassigned.objects.push((object.id, next_id));
+                object.id = next_id;
+                next_id += 1;
+            }
+        }
+        reassigned
+    }
+
+    /// Changes the size of a finite map and of its tile layers, filling new cells with empty
+    /// tiles.
+    fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        for layer in self.layers.iter_mut() {
+            if let LayerData::Finite(rows) = &mut layer.tiles {
+                for row in rows.iter_mut() {
+                    row.resize(width as usize, LayerTile::new(0));
+                }
+                rows.resize(height as usize, vec![LayerTile::new(0); width as usize]);
+            }
+        }
+    }
+
+    /// The draw order index of a layer added on top of all others.
+    fn next_layer_index(&self) -> u32 {
+        self.layers
+            .iter()
+            .map(|l| Some(l.layer_index))
+            .chain(self.image_layers.iter().map(|l| Some(l.layer_index)))
+            .chain(self.object_groups.iter().map(|g| g.layer_index))
+            .chain(self.groups.iter().map(|g| Some(g.layer_index)))
+            .flatten()
+            .max()
+            .map_or(0, |index| index + 1)
+    }
+
+    /// The ID the next new layer gets: one more than the highest layer ID in use.
+    pub(crate) fn next_layer_id(&self) -> u32 {
+        self.layers
+            .iter()
+            .map(|l| l.id)
+            .chain(self.image_layers.iter().map(|l| l.id))
+            .chain(self.object_groups.iter().map(|g| g.id))
+            .chain(self.groups.iter().map(|g| g.id))
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+
+    /// The ID the next new object gets: one more than the highest object ID in use.
+    pub(crate) fn next_object_id(&self) -> u32 {
+        self.object_groups
+            .iter()
+            .flat_map(|g| g.objects.iter().map(|o| o.id))
+            .max()
+            .unwrap_or(0)
+            + 1
+    }
+}
+
+/// Points `object` properties referring to the objects of a map to their new IDs.
+fn remap_object_references(properties: &mut Properties, new_ids: &HashMap<u32, u32>) {
+    for value in properties.values_mut() {
+        if let PropertyValue::ObjectValue(id) = value {
+            if let Some(&new_id) = new_ids.get(id) {
+                *id = new_id;
+            }
+        }
+    }
+}
+
+/// How far objects move when the tiles of `map` move by `offset`.
+pub(crate) fn tile_offset_to_pixels(map: &Map, offset: (i32, i32)) -> (f32, f32) {
+    let (tile_width, tile_height) = (map.tile_width as f32, map.tile_height as f32);
+    match map.orientation {
+        // Objects of isometric maps are positioned in units of the tile height on both axes.
+        Orientation::Isometric => (offset.0 as f32 * tile_height, offset.1 as f32 * tile_height),
+        _ => (offset.0 as f32 * tile_width, offset.1 as f32 * tile_height),
+    }
+}
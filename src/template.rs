@@ -0,0 +1,164 @@
+use std::io::{Read, Write};
+
+use xml::{reader::XmlEvent, EventReader};
+
+use crate::{
+    charset::read_xml,
+    error::TiledError,
+    layers::LayerTile,
+    loader::{ExternalFiles, FileRequest, ResourceKind},
+    objects::Object,
+    tileset::{MapTileset, Tileset},
+    util::{get_attrs, normalize_source, parse_tag},
+    xml_writer::{empty, end, new_writer, start, write_declaration, write_object},
+};
+
+/// An object template (`.tx` file): an object that can be instantiated many times in maps.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Template {
+    /// The tileset used by the template object, if it is a tile object.
+    pub tileset: Option<TemplateTileset>,
+    /// The template object. Its id and position are not part of the template, since each
+    /// instance has its own.
+    pub object: Object,
+}
+
+/// Reference to the external tileset a template's tile object uses.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TemplateTileset {
+    /// The first gid of the tileset, as used by the template object's `gid`.
+    pub first_gid: u32,
+    /// The path of the tileset file, relative to the template file, with backslashes replaced by
+    /// forward slashes.
+    pub source: String,
+}
+
+impl Template {
+    /// Creates a template from the given object.
+    ///
+    /// Tile objects need their tileset to be set before writing, since Tiled only supports
+    /// templates that refer to external tilesets.
+    pub fn new(object: &Object, tileset: Option<TemplateTileset>) -> Template {
+        let mut object = object.clone();
+        object.id = 0;
+        object.x = 0.0;
+        object.y = 0.0;
+        object.template = None;
+        Template { tileset, object }
+    }
+
+    /// Parses a template file on its own. Gids of tile objects stay relative to the template's
+    /// tileset reference.
+    pub fn parse<R: Read>(reader: R) -> Result<Template, TiledError> {
+        let mut parser = EventReader::new(read_xml(reader)?);
+        loop {
+            match parser.next().map_err(TiledError::XmlDecodingError)? {
+                XmlEvent::StartElement { name, .. } if name.local_name == "template" => {
+                    return Template::parse_template(&mut parser);
+                }
+                XmlEvent::EndDocument => {
+                    return Err(TiledError::PrematureEnd(
+                        "Template document ended before the template was parsed".to_string(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn parse_template<R: Read>(parser: &mut EventReader<R>) -> Result<Template, TiledError> {
+        let mut tileset = None;
+        let mut object = None;
+        parse_tag!(parser, "template", {
+            "tileset" => |attrs: Vec<xml::attribute::OwnedAttribute>| {
+                let ((), (first_gid, source)) = get_attrs!(
+                    attrs,
+                    optionals: [],
+                    required: [
+                        ("firstgid", first_gid, |v:String| v.parse().ok()),
+                        ("source", source, Some),
+                    ],
+                    TiledError::MalformedAttributes("template tilesets must have a firstgid and a source".to_string())
+                );
+                tileset = Some(TemplateTileset {
+                    first_gid,
+                    source: normalize_source(&source),
+                });
+                Ok(())
+            },
+            "object" => |attrs| {
+                object = Some(Object::parse(parser, attrs, None, true)?);
+                Ok(())
+            },
+        });
+        let object = object.ok_or_else(|| {
+            TiledError::MalformedAttributes("template must contain an object".to_string())
+        })?;
+        Ok(Template { tileset, object })
+    }
+
+    /// Loads the template referenced by an object of a map and translates the gid of its tile
+    /// object into the gids used by the map.
+    pub(crate) fn load(
+        source: &str,
+        tilesets: &[MapTileset],
+        file_loader: &mut ExternalFiles<'_>,
+    ) -> Result<Template, TiledError> {
+        let request = FileRequest::new(ResourceKind::Template, None, source);
+        let mut template = Template::parse(file_loader.load(request)?.as_slice())?;
+        if let (Some(reference), true) = (&template.tileset, template.object.gid != 0) {
+            let tileset = Tileset::new_external(
+                file_loader.load(FileRequest::new(
+                    ResourceKind::Tileset,
+                    Some(source),
+                    &reference.source,
+                ))?
+                .as_slice(),
+            )?;
+            let map_tileset = tilesets
+                .iter()
+                .find(|t| t.name == tileset.name)
+                .ok_or_else(|| {
+                    TiledError::Other(format!(
+                        "Template {} uses tileset {}, which the map does not reference",
+                        source, tileset.name
+                    ))
+                })?;
+            let tile = LayerTile::new(template.object.gid);
+            let flags = template.object.gid ^ tile.gid;
+            template.object.gid = (tile.gid - reference.first_gid + map_tileset.first_gid) | flags;
+        }
+        Ok(template)
+    }
+
+    /// Writes the template as a `.tx` file.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), TiledError> {
+        if self.object.gid != 0 && self.tileset.is_none() {
+            return Err(TiledError::Other(
+                "Templates of tile objects must reference an external tileset".to_string(),
+            ));
+        }
+        let mut w = new_writer(writer);
+        write_declaration(&mut w)?;
+        start(&mut w, "template", &[])?;
+        if let Some(tileset) = &self.tileset {
+            empty(
+                &mut w,
+                "tileset",
+                &[
+                    ("firstgid", tileset.first_gid.to_string()),
+                    ("source", tileset.source.clone()),
+                ],
+            )?;
+        }
+        write_object(&mut w, &self.object, false)?;
+        end(&mut w)
+    }
+}
+
+impl Object {
+    /// Extracts this object into a template. See [`Template::new`].
+    pub fn to_template(&self, tileset: Option<TemplateTileset>) -> Template {
+        Template::new(self, tileset)
+    }
+}
@@ -0,0 +1,202 @@
+use std::{fmt, io::Read, str::FromStr};
+
+use xml::{attribute::OwnedAttribute, reader::XmlEvent, EventReader};
+
+use crate::{error::TiledError, objects::ObjectGroup, properties::Colour, util::get_attrs};
+
+/// Horizontal alignment of the text inside of its object.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum HorizontalAlignment {
+    Left,
+    Center,
+    Right,
+    Justify,
+}
+
+impl FromStr for HorizontalAlignment {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<HorizontalAlignment, TiledError> {
+        match s {
+            "left" => Ok(Self::Left),
+            "center" => Ok(Self::Center),
+            "right" => Ok(Self::Right),
+            "justify" => Ok(Self::Justify),
+            _ => Err(TiledError::MalformedAttributes(format!(
+                "Invalid horizontal alignment '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for HorizontalAlignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HorizontalAlignment::Left => write!(f, "left"),
+            HorizontalAlignment::Center => write!(f, "center"),
+            HorizontalAlignment::Right => write!(f, "right"),
+            HorizontalAlignment::Justify => write!(f, "justify"),
+        }
+    }
+}
+
+/// Vertical alignment of the text inside of its object.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum VerticalAlignment {
+    Top,
+    Center,
+    Bottom,
+}
+
+impl FromStr for VerticalAlignment {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<VerticalAlignment, TiledError> {
+        match s {
+            "top" => Ok(Self::Top),
+            "center" => Ok(Self::Center),
+            "bottom" => Ok(Self::Bottom),
+            _ => Err(TiledError::MalformedAttributes(format!(
+                "Invalid vertical alignment '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for VerticalAlignment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerticalAlignment::Top => write!(f, "top"),
+            VerticalAlignment::Center => write!(f, "center"),
+            VerticalAlignment::Bottom => write!(f, "bottom"),
+        }
+    }
+}
+
+/// How the text of a text object is rendered. The defaults match the ones of the editor.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TextStyle {
+    pub font_family: String,
+    /// The font size, in pixels.
+    pub pixel_size: u32,
+    /// Whether lines wrap at the width of the object.
+    pub wrap: bool,
+    pub color: Colour,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikeout: bool,
+    pub kerning: bool,
+    pub halign: HorizontalAlignment,
+    pub valign: VerticalAlignment,
+}
+
+impl Default for TextStyle {
+    fn default() -> TextStyle {
+        TextStyle {
+            font_family: "sans-serif".to_string(),
+            pixel_size: 16,
+            wrap: false,
+            color: Colour {
+                red: 0,
+                green: 0,
+                blue: 0,
+                alpha: 255,
+            },
+            bold: false,
+            italic: false,
+            underline: false,
+            strikeout: false,
+            kerning: true,
+            halign: HorizontalAlignment::Left,
+            valign: VerticalAlignment::Top,
+        }
+    }
+}
+
+/// The text of a text object.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Text {
+    pub contents: String,
+    pub style: TextStyle,
+}
+
+impl Text {
+    pub(crate) fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+    ) -> Result<Text, TiledError> {
+        let bool_attr = |v: String| v.parse().ok().map(|x: i32| x == 1);
+        let ((ff, ps, wr, c, b, i, u, s, k, ha, va), ()) = get_attrs!(
+            attrs,
+            optionals: [
+                ("fontfamily", font_family, Some),
+                ("pixelsize", pixel_size, |v:String| v.parse().ok()),
+                ("wrap", wrap, bool_attr),
+                ("color", color, Some),
+                ("bold", bold, bool_attr),
+                ("italic", italic, bool_attr),
+                ("underline", underline, bool_attr),
+                ("strikeout", strikeout, bool_attr),
+                ("kerning", kerning, bool_attr),
+                ("halign", halign, |v:String| v.parse().ok()),
+                ("valign", valign, |v:String| v.parse().ok()),
+            ],
+            required: [],
+            // this error should never happen since there are no required attrs
+            TiledError::MalformedAttributes("text parsing error".to_string())
+        );
+        let c = c.map(|c: String| c.parse::<Colour>()).transpose()?;
+        let defaults = TextStyle::default();
+        let style = TextStyle {
+            font_family: ff.unwrap_or(defaults.font_family),
+            pixel_size: ps.unwrap_or(defaults.pixel_size),
+            wrap: wr.unwrap_or(defaults.wrap),
+            color: c.unwrap_or(defaults.color),
+            bold: b.unwrap_or(defaults.bold),
+            italic: i.unwrap_or(defaults.italic),
+            underline: u.unwrap_or(defaults.underline),
+            strikeout: s.unwrap_or(defaults.strikeout),
+            kerning: k.unwrap_or(defaults.kerning),
+            halign: ha.unwrap_or(defaults.halign),
+            valign: va.unwrap_or(defaults.valign),
+        };
+
+        let mut contents = String::new();
+        loop {
+            match parser.next().map_err(TiledError::XmlDecodingError)? {
+                XmlEvent::Characters(s) | XmlEvent::Whitespace(s) => contents.push_str(&s),
+                XmlEvent::EndElement { name, .. } if name.local_name == "text" => break,
+                XmlEvent::EndDocument => {
+                    return Err(TiledError::PrematureEnd(
+                        "Document ended before we expected.".to_string(),
+                    ))
+                }
+                _ => {}
+            }
+        }
+        Ok(Text { contents, style })
+    }
+
+    /// Returns the colour the text is drawn with in the given object layer: the text colour
+    /// multiplied by the layer's tint colour, with the layer's opacity applied to the alpha.
+    pub fn effective_color(&self, layer: &ObjectGroup) -> Colour {
+        let color = self.style.color;
+        let tint = layer.tint_colour.unwrap_or(Colour {
+            red: 255,
+            green: 255,
+            blue: 255,
+            alpha: 255,
+        });
+        let multiply = |a: u8, b: u8| ((a as u32 * b as u32 + 127) / 255) as u8;
+        let alpha = multiply(color.alpha, tint.alpha) as f32 * layer.opacity.clamp(0.0, 1.0);
+        Colour {
+            red: multiply(color.red, tint.red),
+            green: multiply(color.green, tint.green),
+            blue: multiply(color.blue, tint.blue),
+            alpha: alpha.round() as u8,
+        }
+    }
+}
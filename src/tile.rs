@@ -5,21 +5,30 @@ use xml::{attribute::OwnedAttribute, EventReader};
 use crate::{
     animation::Frame,
     error::TiledError,
+    ids::TileId,
     image::Image,
+    loader::{ExternalFiles, FileRequest},
     objects::ObjectGroup,
+    project::Project,
     properties::{parse_properties, Properties},
     util::{get_attrs, parse_animation, parse_tag},
+    wangset::parse_terrain_corners,
 };
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Tile {
-    pub id: u32,
+    pub id: TileId,
     pub images: Vec<Image>,
     pub properties: Properties,
     pub objectgroup: Option<ObjectGroup>,
     pub animation: Option<Vec<Frame>>,
+    /// The class of the tile, written as `type` before Tiled 1.9 and as `class` since.
     pub tile_type: Option<String>,
     pub probability: f32,
+    /// The legacy terrains of the top-left, top-right, bottom-left and bottom-right corners of
+    /// the tile, as indices into
+    /// [`Tileset::terrain_types`](crate::tileset::Tileset::terrain_types).
+    pub terrain: Option<[Option<u32>; 4]>,
 }
 
 impl Tile {
@@ -27,14 +36,16 @@ impl Tile {
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
     ) -> Result<Tile, TiledError> {
-        let ((tile_type, probability), id) = get_attrs!(
+        let ((tile_type, class, probability, terrain), id) = get_attrs!(
             attrs,
             optionals: [
                 ("type", tile_type, |v:String| v.parse().ok()),
+                ("class", class, Some),
                 ("probability", probability, |v:String| v.parse().ok()),
+                ("terrain", terrain, |v:String| parse_terrain_corners(&v)),
             ],
             required: [
-                ("id", id, |v:String| v.parse::<u32>().ok()),
+                ("id", id, |v:String| v.parse().ok().map(TileId)),
             ],
             TiledError::MalformedAttributes("tile must have an id with the correct type".to_string())
         );
@@ -53,7 +64,11 @@ impl Tile {
                 Ok(())
             },
             "objectgroup" => |attrs| {
-                objectgroup = Some(ObjectGroup::new(parser, attrs, None)?);
+                objectgroup = Some(ObjectGroup::new(parser, attrs, None, &[], &mut HashMap::new(), &mut ExternalFiles::new(|_: &FileRequest<'_>| {
+                    Err(TiledError::Other(
+                        "Tile collision objects cannot use templates".to_string(),
+                    ))
+                }))?);
                 Ok(())
             },
             "animation" => |_| {
@@ -67,8 +82,21 @@ impl Tile {
             properties,
             objectgroup,
             animation,
-            tile_type,
+            tile_type: class.or(tile_type),
             probability: probability.unwrap_or(1.0),
+            terrain,
         })
     }
+
+    /// Returns the properties of the tile as the editor presents them: the default values of
+    /// the members of its class in `project`, overridden by the tile's own properties. Tiles
+    /// without a class, or with one missing from the project, only have their own properties.
+    pub fn resolved_properties(&self, project: &Project) -> Properties {
+        let mut properties = match &self.tile_type {
+            Some(class) => project.class_defaults(class),
+            None => Properties::new(),
+        };
+        properties.extend(self.properties.clone());
+        properties
+    }
 }
@@ -1,45 +1,514 @@
+use crate::charset::read_xml;
+use crate::ids::{Gid, TileId};
+use crate::loader::{ExternalFiles, FileRequest, ResourceKind};
 use crate::properties::{parse_properties, Properties, Alignment};
 use crate::util::*;
+use crate::validation::{validate_tileset_image, TilesetMismatch};
+use crate::wangset::{parse_terrain_types, parse_wang_sets, pick_weighted, Terrain, WangSet};
 use crate::*; // FIXME
+use std::{collections::HashMap, ops::Deref, sync::Arc};
 
-/// A tileset, usually the tilesheet image.
+/// A tileset used by a map, along with the gids the map gives to its tiles.
+///
+/// The tileset itself doesn't depend on the map, so one parsed tileset can be shared by several
+/// maps. Fields and methods of the tileset can be used directly on a `MapTileset`.
 #[derive(Debug, PartialEq, Clone)]
-pub struct Tileset {
+pub struct MapTileset {
     /// The GID of the first tile stored
     pub first_gid: u32,
+    pub tileset: Arc<Tileset>,
+}
+
+impl MapTileset {
+    pub fn new(first_gid: u32, tileset: impl Into<Arc<Tileset>>) -> MapTileset {
+        MapTileset {
+            first_gid,
+            tileset: tileset.into(),
+        }
+    }
+
+    /// Whether `gid` belongs to one of the tiles of the tileset.
+    pub fn contains_gid(&self, gid: Gid) -> bool {
+        self.tile_id(gid)
+            .is_some_and(|id| id.0 < self.tileset.tile_count())
+    }
+
+    /// The gid of the tile with the id `id` in the tileset.
+    pub fn gid(&self, id: TileId) -> Gid {
+        Gid(self.first_gid + id.0)
+    }
+
+    /// The id in the tileset of the tile with the given gid, or `None` if the gid is below the
+    /// first gid of the tileset.
+    pub fn tile_id(&self, gid: Gid) -> Option<TileId> {
+        gid.0.checked_sub(self.first_gid).map(TileId)
+    }
+}
+
+impl Deref for MapTileset {
+    type Target = Tileset;
+
+    fn deref(&self) -> &Tileset {
+        &self.tileset
+    }
+}
+
+/// The tilesets of a map sorted by first gid, for finding the tileset of a gid with a binary
+/// search. It is a cache of [`Map::tilesets`], so maps compare equal whatever their tables hold.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct GidTable {
+    /// The first gid of each tileset and its index in [`Map::tilesets`]. When several tilesets
+    /// start at the same gid, only the first one is kept, as it is the one found by a linear scan.
+    ranges: Vec<(u32, usize)>,
+    /// The number of tilesets the table was built from.
+    len: usize,
+}
+
+impl GidTable {
+    pub(crate) fn new(tilesets: &[MapTileset]) -> GidTable {
+        let mut ranges: Vec<(u32, usize)> = tilesets
+            .iter()
+            .enumerate()
+            .map(|(index, t)| (t.first_gid, index))
+            .collect();
+        ranges.sort_by_key(|&(first_gid, _)| first_gid);
+        ranges.dedup_by_key(|&mut (first_gid, _)| first_gid);
+        GidTable {
+            ranges,
+            len: tilesets.len(),
+        }
+    }
+
+    /// Returns the index of the tileset with the highest first gid not above `gid`, along with
+    /// the range of gids sharing it: from its first gid up to the first gid of the next tileset.
+    /// Falls back to a linear scan if `tilesets` changed since the table was built.
+    pub(crate) fn find(&self, tilesets: &[MapTileset], gid: u32) -> Option<(usize, (u32, u32))> {
+        let position = self
+            .ranges
+            .partition_point(|&(first_gid, _)| first_gid <= gid);
+        let found = position.checked_sub(1).map(|p| self.ranges[p]);
+        let fresh = self.len == tilesets.len()
+            && found.map_or(true, |(first_gid, index)| {
+                tilesets.get(index).map(|t| t.first_gid) == Some(first_gid)
+            });
+        if !fresh {
+            return GidTable::scan(tilesets, gid);
+        }
+        let (first_gid, index) = found?;
+        let end = self
+            .ranges
+            .get(position)
+            .map_or(u32::MAX, |&(next_gid, _)| next_gid);
+        Some((index, (first_gid, end)))
+    }
+
+    fn scan(tilesets: &[MapTileset], gid: u32) -> Option<(usize, (u32, u32))> {
+        let mut found: Option<(usize, u32)> = None;
+        let mut end = u32::MAX;
+        for (index, tileset) in tilesets.iter().enumerate() {
+            let first_gid = tileset.first_gid;
+            if first_gid > gid {
+                end = end.min(first_gid);
+            } else if found.map_or(true, |(_, best)| first_gid > best) {
+                found = Some((index, first_gid));
+            }
+        }
+        found.map(|(index, first_gid)| (index, (first_gid, end)))
+    }
+
+    pub(crate) fn heap_size(&self) -> usize {
+        self.ranges.capacity() * std::mem::size_of::<(u32, usize)>()
+    }
+}
+
+/// Finds the tilesets of many gids, reusing the last tileset found as long as the gids stay in
+/// its range, as neighboring tiles usually come from the same tileset.
+pub(crate) struct GidResolver<'map> {
+    table: &'map GidTable,
+    tilesets: &'map [MapTileset],
+    last: Option<(usize, (u32, u32))>,
+}
+
+impl<'map> GidResolver<'map> {
+    pub(crate) fn new(table: &'map GidTable, tilesets: &'map [MapTileset]) -> GidResolver<'map> {
+        GidResolver {
+            table,
+            tilesets,
+            last: None,
+        }
+    }
+
+    /// Returns the index of the tileset of `gid` and the id of the tile in it, or `None` for
+    /// empty tiles and gids below the first gid of every tileset.
+    pub(crate) fn resolve(&mut self, gid: u32) -> Option<(usize, TileId)> {
+        if gid == 0 {
+            return None;
+        }
+        let (index, (first_gid, _)) = match self.last {
+            Some((_, (start, end))) if gid >= start && gid < end => self.last?,
+            _ => {
+                self.last = Some(self.table.find(self.tilesets, gid)?);
+                self.last?
+            }
+        };
+        Some((index, TileId(gid - first_gid)))
+    }
+}
+
+/// How often a map uses the tiles of one of its tilesets, as returned by
+/// [`Map::tile_usage`](crate::map::Map::tile_usage).
+#[derive(Debug, PartialEq, Clone)]
+pub struct TileUsage<'map> {
+    pub tileset: &'map MapTileset,
+    /// How many layer cells and tile objects use each tile, by id. Unused tiles are left out.
+    pub counts: HashMap<TileId, usize>,
+}
+
+impl<'map> TileUsage<'map> {
+    /// Whether the map uses the tile with the id `id`.
+    pub fn is_used(&self, id: TileId) -> bool {
+        self.counts.contains_key(&id)
+    }
+
+    /// The ids of the tiles of the tileset the map doesn't use, in ascending order.
+    pub fn unused_tiles(&self) -> Vec<TileId> {
+        let ids: Vec<TileId> = if self.tileset.images.is_empty() {
+            self.tileset.tiles.iter().map(|t| t.id).collect()
+        } else {
+            (0..self.tileset.tile_count()).map(TileId).collect()
+        };
+        let mut unused: Vec<TileId> = ids
+            .into_iter()
+            .filter(|id| !self.is_used(*id))
+            .collect();
+        unused.sort();
+        unused
+    }
+}
+
+/// The image a tile is drawn from and where the tile is in it, as returned by
+/// [`Tileset::tile_image`] and [`Map::tile_image_source`](crate::map::Map::tile_image_source).
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TileImage<'a> {
+    /// The image of the tileset, or of the tile in image collection tilesets.
+    pub image: &'a Image,
+    /// The rectangle of the tile in the image as `(x, y, width, height)`, in pixels. It covers
+    /// the whole image of tiles of image collection tilesets.
+    pub rect: (u32, u32, u32, u32),
+}
+
+impl<'a> TileImage<'a> {
+    /// The path of the image relative to the map. See [`Image::source_relative_to_map`].
+    pub fn path(&self) -> String {
+        self.image.source_relative_to_map()
+    }
+}
+
+impl PartialEq for GidTable {
+    fn eq(&self, _: &GidTable) -> bool {
+        true
+    }
+}
+
+/// Finds the tiles of a tileset by id without scanning [`Tileset::tiles`], as returned by
+/// [`Tileset::tile_properties_index`].
+#[derive(Debug, Clone)]
+pub struct TilePropertiesIndex<'a> {
+    tiles: &'a [Tile],
+    positions: HashMap<TileId, usize>,
+}
+
+impl<'a> TilePropertiesIndex<'a> {
+    /// Returns the tile with the id `id`, like [`Tileset::get_tile`].
+    pub fn tile(&self, id: TileId) -> Option<&'a Tile> {
+        let tiles = self.tiles;
+        self.positions.get(&id).map(|&i| &tiles[i])
+    }
+
+    /// Returns the properties of the tile with the id `id`, if it has any data.
+    pub fn properties(&self, id: TileId) -> Option<&'a Properties> {
+        self.tile(id).map(|t| &t.properties)
+    }
+}
+
+/// A tileset, usually the tilesheet image.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Tileset {
     pub name: String,
     pub tile_width: u32,
     pub tile_height: u32,
     pub spacing: u32,
     pub margin: u32,
     pub tilecount: Option<u32>,
+    /// The `columns` attribute, if the tileset has one. [`Tileset::columns`] computes the number
+    /// of columns from the image instead.
+    pub column_count: Option<u32>,
     /// The Tiled spec says that a tileset can have mutliple images so a `Vec`
     /// is used. Usually you will only use one.
     pub images: Vec<Image>,
     pub tiles: Vec<Tile>,
     pub properties: Properties,
     pub object_alignment: Option<Alignment>,
+    pub wang_sets: Vec<WangSet>,
+    /// Legacy terrain types, from tilesets saved before Tiled 1.5. They aren't written back;
+    /// see [`Map::upgrade_in_place`](crate::map::Map::upgrade_in_place).
+    pub terrain_types: Vec<Terrain>,
+    /// Why the external tileset couldn't be loaded, if it was replaced by an empty placeholder.
+    /// See [`Loader::with_tolerant_mode`](crate::loader::Loader::with_tolerant_mode).
+    pub load_error: Option<String>,
 }
 
 impl Tileset {
-    pub(crate) fn new<R: Read>(
+    /// Number of tile columns of the tileset image, or 0 for image collection tilesets.
+    pub fn columns(&self) -> u32 {
+        match self.images.first() {
+            Some(image) => self.fit_tiles(image.width, self.tile_width).0,
+            None => 0,
+        }
+    }
+
+    /// The number of tiles of `tile_size` pixels fitting along one side of the image, `size`
+    /// pixels long, and the pixels left over. Images without a size have no tiles.
+    pub(crate) fn fit_tiles(&self, size: Option<i32>, tile_size: u32) -> (u32, u32) {
+        let usable = (size.unwrap_or(0).max(0) as u32)
+            .saturating_sub(self.margin.saturating_mul(2))
+            .saturating_add(self.spacing);
+        let step = tile_size.saturating_add(self.spacing).max(1);
+        (usable / step, usable % step)
+    }
+
+    /// Number of tiles in the tileset. Uses the `tilecount` attribute when present, and
+    /// otherwise computes it from the image, or from the tiles of an image collection.
+    pub fn tile_count(&self) -> u32 {
+        if let Some(count) = self.tilecount {
+            return count;
+        }
+        match self.images.first() {
+            Some(image) => {
+                let rows = self.fit_tiles(image.height, self.tile_height).0;
+                rows.saturating_mul(self.columns())
+            }
+            None => self.tiles.iter().map(|t| t.id.0 + 1).max().unwrap_or(0),
+        }
+    }
+
+    /// Checks the tile size, margin, spacing, `tilecount` and `columns` of the tileset against
+    /// the size of its image, which is the usual reason for tiles being drawn shifted. Image
+    /// collection tilesets, and images without a size, aren't checked.
+    pub fn validate_image(&self) -> Vec<TilesetMismatch> {
+        validate_tileset_image(self)
+    }
+
+    /// Returns the tile with the id `id`, if it has any properties, animation or other data.
+    /// To look up many tiles, use [`Tileset::tile_properties_index`] instead.
+    pub fn get_tile(&self, id: TileId) -> Option<&Tile> {
+        self.tiles.iter().find(|t| t.id == id)
+    }
+
+    /// Returns an index finding the tiles of the tileset by id in constant time, for looking up
+    /// the data of many tiles. Building it goes through all of [`Tileset::tiles`], so keep it
+    /// around for as long as there are tiles to look up.
+    pub fn tile_properties_index(&self) -> TilePropertiesIndex<'_> {
+        TilePropertiesIndex {
+            tiles: &self.tiles,
+            positions: self
+                .tiles
+                .iter()
+                .enumerate()
+                .map(|(i, t)| (t.id, i))
+                .collect(),
+        }
+    }
+
+    /// Picks a random tile of the given class (or type), with chances proportional to the
+    /// tiles' probabilities, like the editor's random brush does. Tiles with a probability of 0
+    /// are never picked. `random` must return a number in `[0, 1)`.
+    pub fn random_tile_with_class(
+        &self,
+        class: &str,
+        random: impl FnOnce() -> f32,
+    ) -> Option<TileId> {
+        let candidates: Vec<(TileId, f32)> = self
+            .tiles
+            .iter()
+            .filter(|t| t.tile_type.as_deref() == Some(class) && t.probability > 0.0)
+            .map(|t| (t.id, t.probability))
+            .collect();
+        Self::pick_random(&candidates, random)
+    }
+
+    /// Picks a random tile of the wang set named `wang_set` that is filled with the color named
+    /// `color` only, which makes it usable anywhere inside an area of that terrain. Chances are
+    /// proportional to the tiles' probabilities, like in
+    /// [`Tileset::random_tile_with_class`]. `random` must return a number in `[0, 1)`.
+    pub fn random_tile_with_wang_color(
+        &self,
+        wang_set: &str,
+        color: &str,
+        random: impl FnOnce() -> f32,
+    ) -> Option<TileId> {
+        let wang_set = self.wang_sets.iter().find(|w| w.name == wang_set)?;
+        let index = wang_set.color_index(color)?;
+        let candidates: Vec<(TileId, f32)> = wang_set
+            .wang_tiles
+            .iter()
+            .filter(|t| t.wang_id.0.iter().all(|&c| c == 0 || c == index))
+            .filter(|t| t.wang_id.0.contains(&index))
+            .map(|t| {
+                let probability = self
+                    .get_tile(t.tile_id)
+                    .map_or(1.0, |t| t.probability);
+                (t.tile_id, probability)
+            })
+            .filter(|&(_, probability)| probability > 0.0)
+            .collect();
+        Self::pick_random(&candidates, random)
+    }
+
+    fn pick_random(candidates: &[(TileId, f32)], random: impl FnOnce() -> f32) -> Option<TileId> {
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(pick_weighted(candidates, random()))
+    }
+
+    /// Returns the image the tile with the id `id` is drawn from, with the rectangle of the tile
+    /// in it. Works for tilesets with a single image, where the rectangle is computed like by
+    /// [`Tileset::tile_rectangle`], as well as image collection tilesets, where each tile has its
+    /// own image. `None` is returned for tiles without an image and ids outside of the tileset.
+    pub fn tile_image(&self, id: TileId) -> Option<TileImage<'_>> {
+        match self.images.first() {
+            Some(_) if id.0 >= self.tile_count() => None,
+            Some(image) => Some(TileImage {
+                image,
+                rect: self.tile_rectangle(id)?,
+            }),
+            None => {
+                let image = self.get_tile(id)?.images.first()?;
+                let size = |s: Option<i32>| s.unwrap_or(0).max(0) as u32;
+                Some(TileImage {
+                    image,
+                    rect: (0, 0, size(image.width), size(image.height)),
+                })
+            }
+        }
+    }
+
+    /// Computes the rectangle on the image where the sprite is stored for the tile with the id
+    /// `id`. If there is no image associated with the tileset, `None` is returned. `None` is also
+    /// returned if the tile size of the tileset doesn't fit its image.
+    /// On success, returns `Some(x, y, w, h)`, where `(x, y)` is the coordinates of the top-left
+    /// corner, and `(w, h)` are the width and height of the rectangle.
+    pub fn tile_rectangle(&self, id: TileId) -> Option<(u32, u32, u32, u32)> {
+        // we suppose there is only 1 image per tileset, and there are no columns without one
+        let columns = self.columns();
+        if columns == 0 {
+            return None;
+        }
+
+        // coordinates in tiles
+        let x = id.0 % columns;
+        let y = id.0.div_euclid(columns);
+
+        // coordinates in pixels
+        let x = (self.tile_width.checked_add(self.spacing)?)
+            .checked_mul(x)?
+            .checked_add(self.margin)?;
+        let y = (self.tile_height.checked_add(self.spacing)?)
+            .checked_mul(y)?
+            .checked_add(self.margin)?;
+
+        Some((x, y, self.tile_width, self.tile_height))
+    }
+
+    /// Parses an external tileset (`.tsx`). The tileset is the same whatever map uses it; wrap
+    /// it in a [`MapTileset`] to give its tiles gids.
+    pub fn parse<R: Read>(reader: R) -> Result<Tileset, TiledError> {
+        Tileset::new_external(reader)
+    }
+
+    /// Writes the tileset in the Tiled JSON format (`.tsj`), as an external tileset.
+    #[cfg(feature = "json")]
+    pub fn write_json<W: std::io::Write>(&self, writer: W) -> std::io::Result<()> {
+        crate::json_writer::tileset_to_json(self, None).write_to(writer)
+    }
+
+    /// Writes the tileset like [`Tileset::write_json`], with the durations of its animation
+    /// frames rounded to multiples of `step` milliseconds, see
+    /// [`normalize_duration`](crate::animation::normalize_duration).
+    #[cfg(feature = "json")]
+    pub fn write_json_normalized<W: std::io::Write>(
+        &self,
+        writer: W,
+        step: u32,
+    ) -> std::io::Result<()> {
+        let mut tileset = self.clone();
+        tileset.normalize_animations(step);
+        tileset.write_json(writer)
+    }
+
+    /// Creates an empty tileset standing in for the external tileset referenced by `attrs`,
+    /// which failed to load with `error`. It is named after its source and uses the tile size
+    /// of the map. Embedded tilesets can't be replaced, so `error` is returned for them.
+    pub(crate) fn placeholder(
+        attrs: &[OwnedAttribute],
+        tile_width: u32,
+        tile_height: u32,
+        error: TiledError,
+    ) -> Result<MapTileset, TiledError> {
+        let (first_gid, source) = get_attrs!(
+            attrs,
+            optionals: [
+                ("firstgid", first_gid, |v:String| v.parse().ok()),
+                ("source", source, Some),
+            ],
+            required: [],
+            TiledError::MalformedAttributes("tileset parsing error".to_string())
+        ).0;
+        let (first_gid, source) = match (first_gid, source) {
+            (Some(first_gid), Some(source)) => (first_gid, source),
+            _ => return Err(error),
+        };
+        let tileset = Tileset {
+            name: source,
+            tile_width,
+            tile_height,
+            spacing: 0,
+            margin: 0,
+            tilecount: Some(0),
+            column_count: None,
+            images: Vec::new(),
+            tiles: Vec::new(),
+            properties: HashMap::new(),
+            object_alignment: None,
+            wang_sets: Vec::new(),
+            terrain_types: Vec::new(),
+            load_error: Some(error.to_string()),
+        };
+        Ok(MapTileset::new(first_gid, tileset))
+    }
+
+    /// Parses a `<tileset>` element of a map, which either embeds the tileset or references an
+    /// external one.
+    pub(crate) fn parse_map_tileset<R: Read>(
         parser: &mut EventReader<R>,
         attrs: Vec<OwnedAttribute>,
-        external_file_loader: impl FnMut(&str)->Result<Vec<u8>, TiledError>,
-    ) -> Result<Tileset, TiledError> {
+        external_file_loader: &mut ExternalFiles<'_>,
+    ) -> Result<MapTileset, TiledError> {
         Tileset::new_internal(parser, &attrs).or_else(|_| Tileset::new_reference(&attrs, external_file_loader))
     }
 
     fn new_internal<R: Read>(
         parser: &mut EventReader<R>,
         attrs: &Vec<OwnedAttribute>,
-    ) -> Result<Tileset, TiledError> {
-        let ((spacing, margin, tilecount, object_alignment), (first_gid, name, width, height)) = get_attrs!(
+    ) -> Result<MapTileset, TiledError> {
+        let ((spacing, margin, tilecount, column_count, object_alignment), (first_gid, name, width, height)) = get_attrs!(
            attrs,
            optionals: [
                 ("spacing", spacing, |v:String| v.parse().ok()),
                 ("margin", margin, |v:String| v.parse().ok()),
                 ("tilecount", tilecount, |v:String| v.parse().ok()),
+                ("columns", column_count, |v:String| v.parse().ok()),
                 ("objectalignment", object_alignment, |v:String| v.parse().ok()),
             ],
            required: [
@@ -54,6 +523,8 @@ impl Tileset {
         let mut images = Vec::new();
         let mut tiles = Vec::new();
         let mut properties = HashMap::new();
+        let mut wang_sets = Vec::new();
+        let mut terrain_types = Vec::new();
         parse_tag!(parser, "tileset", {
             "image" => |attrs| {
                 images.push(Image::new(parser, attrs)?);
@@ -67,27 +538,39 @@ impl Tileset {
                 tiles.push(Tile::new(parser, attrs)?);
                 Ok(())
             },
+            "wangsets" => |_| {
+                wang_sets = parse_wang_sets(parser)?;
+                Ok(())
+            },
+            "terraintypes" => |_| {
+                terrain_types = parse_terrain_types(parser)?;
+                Ok(())
+            },
         });
 
-        Ok(Tileset {
+        let tileset = Tileset {
             tile_width: width,
             tile_height: height,
             spacing: spacing.unwrap_or(0),
             margin: margin.unwrap_or(0),
-            first_gid,
             name,
             tilecount,
+            column_count,
             images,
             tiles,
             properties,
             object_alignment,
-        })
+            wang_sets,
+            terrain_types,
+            load_error: None,
+        };
+        Ok(MapTileset::new(first_gid, tileset))
     }
 
     fn new_reference(
         attrs: &Vec<OwnedAttribute>,
-        mut external_file_loader: impl FnMut(&str)->Result<Vec<u8>, TiledError>,
-    ) -> Result<Tileset, TiledError> {
+        external_file_loader: &mut ExternalFiles<'_>,
+    ) -> Result<MapTileset, TiledError> {
         let ((), (first_gid, source)) = get_attrs!(
             attrs,
             optionals: [],
@@ -98,12 +581,24 @@ impl Tileset {
             TiledError::MalformedAttributes("tileset must have a firstgid, name tile width and height with correct types".to_string())
         );
 
-        let tileset_bytes = external_file_loader(&source)?;
-        Tileset::new_external(tileset_bytes.as_slice(), first_gid)
+        let source = normalize_source(&source);
+        let request = FileRequest::new(ResourceKind::Tileset, None, &source);
+        let tileset = external_file_loader.load_tileset(request, |data| {
+            let mut tileset = Tileset::new_external(data)?;
+            let images = tileset
+                .images
+                .iter_mut()
+                .chain(tileset.tiles.iter_mut().flat_map(|t| t.images.iter_mut()));
+            for image in images {
+                image.defining_file = Some(source.clone());
+            }
+            Ok(tileset)
+        })?;
+        Ok(MapTileset::new(first_gid, tileset))
     }
 
-    pub(crate) fn new_external<R: Read>(file: R, first_gid: u32) -> Result<Tileset, TiledError> {
-        let mut tileset_parser = EventReader::new(file);
+    pub(crate) fn new_external<R: Read>(file: R) -> Result<Tileset, TiledError> {
+        let mut tileset_parser = EventReader::new(read_xml(file)?);
         loop {
             match tileset_parser
                 .next()
@@ -111,14 +606,8 @@ impl Tileset {
             {
                 XmlEvent::StartElement {
                     name, attributes, ..
-                } => {
-                    if name.local_name == "tileset" {
-                        return Tileset::parse_external_tileset(
-                            first_gid,
-                            &mut tileset_parser,
-                            &attributes,
-                        );
-                    }
+                } if name.local_name == "tileset" => {
+                    return Tileset::parse_external_tileset(&mut tileset_parser, &attributes);
                 }
                 XmlEvent::EndDocument => {
                     return Err(TiledError::PrematureEnd(
@@ -131,16 +620,16 @@ impl Tileset {
     }
 
     fn parse_external_tileset<R: Read>(
-        first_gid: u32,
         parser: &mut EventReader<R>,
         attrs: &Vec<OwnedAttribute>,
     ) -> Result<Tileset, TiledError> {
-        let ((spacing, margin, tilecount, object_alignment), (name, width, height)) = get_attrs!(
+        let ((spacing, margin, tilecount, column_count, object_alignment), (name, width, height)) = get_attrs!(
             attrs,
             optionals: [
                 ("spacing", spacing, |v:String| v.parse().ok()),
                 ("margin", margin, |v:String| v.parse().ok()),
                 ("tilecount", tilecount, |v:String| v.parse().ok()),
+                ("columns", column_count, |v:String| v.parse().ok()),
                 ("objectalignment", object_alignment, |v:String| v.parse().ok()),
             ],
             required: [
@@ -154,6 +643,8 @@ impl Tileset {
         let mut images = Vec::new();
         let mut tiles = Vec::new();
         let mut properties = HashMap::new();
+        let mut wang_sets = Vec::new();
+        let mut terrain_types = Vec::new();
         parse_tag!(parser, "tileset", {
             "image" => |attrs| {
                 images.push(Image::new(parser, attrs)?);
@@ -167,20 +658,31 @@ impl Tileset {
                 properties = parse_properties(parser)?;
                 Ok(())
             },
+            "wangsets" => |_| {
+                wang_sets = parse_wang_sets(parser)?;
+                Ok(())
+            },
+            "terraintypes" => |_| {
+                terrain_types = parse_terrain_types(parser)?;
+                Ok(())
+            },
         });
 
         Ok(Tileset {
-            first_gid: first_gid,
             name: name,
             tile_width: width,
             tile_height: height,
             spacing: spacing.unwrap_or(0),
             margin: margin.unwrap_or(0),
             tilecount: tilecount,
+            column_count,
             images: images,
             tiles: tiles,
             properties,
             object_alignment,
+            wang_sets,
+            terrain_types,
+            load_error: None,
         })
     }
 }
@@ -0,0 +1,503 @@
+//! Where the contents of layers are drawn, taking group layers, layer offsets and parallax
+//! scrolling into account the same way the editor does.
+
+use crate::{
+    layers::{GroupLayer, ImageLayer, Layer},
+    map::Map,
+    objects::{MapObject, ObjectGroup},
+    properties::{Colour, Properties},
+};
+
+/// The offset and parallax factor of a layer, combined with those of the group layers containing
+/// it: offsets add up, while parallax factors multiply.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct LayerTransform {
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub parallax_x: f32,
+    pub parallax_y: f32,
+}
+
+impl LayerTransform {
+    fn new(map: &Map, offset: (f32, f32), parallax: (f32, f32), parent: Option<usize>) -> Self {
+        let mut transform = LayerTransform {
+            offset_x: offset.0,
+            offset_y: offset.1,
+            parallax_x: parallax.0,
+            parallax_y: parallax.1,
+        };
+        let mut parent = parent.and_then(|i| map.groups.get(i));
+        while let Some(group) = parent {
+            transform.offset_x += group.offset_x;
+            transform.offset_y += group.offset_y;
+            transform.parallax_x *= group.parallax_x;
+            transform.parallax_y *= group.parallax_y;
+            parent = group.parent_group.and_then(|i| map.groups.get(i));
+        }
+        transform
+    }
+
+    /// Returns how far the contents of the layer are moved from their position in the map, in
+    /// pixels, when the view is centered on `(camera_x, camera_y)`. Layers without parallax
+    /// scrolling are only moved by their offset.
+    pub fn translation(&self, map: &Map, camera_x: f32, camera_y: f32) -> (f32, f32) {
+        let view_x = camera_x - map.parallax_origin_x;
+        let view_y = camera_y - map.parallax_origin_y;
+        (
+            self.offset_x + (1.0 - self.parallax_x) * view_x,
+            self.offset_y + (1.0 - self.parallax_y) * view_y,
+        )
+    }
+}
+
+/// A colour to multiply everything drawn in a layer with, combining the tint colours and
+/// opacities of the layer and of the groups containing it. All components range from 0 to 1.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub struct Tint {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub alpha: f32,
+}
+
+impl Tint {
+    fn new(map: &Map, tint: Option<Colour>, opacity: f32, parent: Option<usize>) -> Tint {
+        let mut result = Tint::from_layer(tint, opacity);
+        let mut parent = parent.and_then(|i| map.groups.get(i));
+        while let Some(group) = parent {
+            let tint = Tint::from_layer(group.tint_colour, group.opacity);
+            result.red *= tint.red;
+            result.green *= tint.green;
+            result.blue *= tint.blue;
+            result.alpha *= tint.alpha;
+            parent = group.parent_group.and_then(|i| map.groups.get(i));
+        }
+        result
+    }
+
+    fn from_layer(tint: Option<Colour>, opacity: f32) -> Tint {
+        let tint = tint.unwrap_or(Colour {
+            red: 255,
+            green: 255,
+            blue: 255,
+            alpha: 255,
+        });
+        Tint {
+            red: tint.red as f32 / 255.0,
+            green: tint.green as f32 / 255.0,
+            blue: tint.blue as f32 / 255.0,
+            alpha: tint.alpha as f32 / 255.0 * opacity.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Layer {
+    /// Returns the offset and parallax factor of this layer, including those of its groups.
+    pub fn transform(&self, map: &Map) -> LayerTransform {
+        LayerTransform::new(
+            map,
+            (self.offset_x, self.offset_y),
+            (self.parallax_x, self.parallax_y),
+            self.parent_group,
+        )
+    }
+
+    /// Returns the colour everything in this layer is multiplied with, combining its tint colour
+    /// and opacity with those of its groups.
+    pub fn effective_tint(&self, map: &Map) -> Tint {
+        Tint::new(map, self.tint_colour, self.opacity, self.parent_group)
+    }
+
+    /// Returns the names of the groups containing this layer followed by its own name, separated
+    /// by slashes, as accepted by [`Map::layer_by_path`].
+    pub fn path(&self, map: &Map) -> String {
+        layer_path(map, &self.name, self.parent_group)
+    }
+}
+
+impl ImageLayer {
+    /// Returns the offset and parallax factor of this layer, including those of its groups.
+    pub fn transform(&self, map: &Map) -> LayerTransform {
+        LayerTransform::new(
+            map,
+            (self.offset_x, self.offset_y),
+            (self.parallax_x, self.parallax_y),
+            self.parent_group,
+        )
+    }
+
+    /// Returns the colour everything in this layer is multiplied with, combining its tint colour
+    /// and opacity with those of its groups.
+    pub fn effective_tint(&self, map: &Map) -> Tint {
+        Tint::new(map, self.tint_colour, self.opacity, self.parent_group)
+    }
+
+    /// Returns the names of the groups containing this layer followed by its own name, separated
+    /// by slashes, as accepted by [`Map::layer_by_path`].
+    pub fn path(&self, map: &Map) -> String {
+        layer_path(map, &self.name, self.parent_group)
+    }
+}
+
+impl ObjectGroup {
+    /// Returns the offset and parallax factor of this layer, including those of its groups.
+    pub fn transform(&self, map: &Map) -> LayerTransform {
+        LayerTransform::new(
+            map,
+            (self.offset_x, self.offset_y),
+            (self.parallax_x, self.parallax_y),
+            self.parent_group,
+        )
+    }
+
+    /// Returns the colour everything in this layer is multiplied with, combining its tint colour
+    /// and opacity with those of its groups.
+    pub fn effective_tint(&self, map: &Map) -> Tint {
+        Tint::new(map, self.tint_colour, self.opacity, self.parent_group)
+    }
+
+    /// Returns the names of the groups containing this layer followed by its own name, separated
+    /// by slashes, as accepted by [`Map::layer_by_path`].
+    pub fn path(&self, map: &Map) -> String {
+        layer_path(map, &self.name, self.parent_group)
+    }
+}
+
+impl GroupLayer {
+    /// Returns the offset and parallax factor of this group, including those of its parents.
+    pub fn transform(&self, map: &Map) -> LayerTransform {
+        LayerTransform::new(
+            map,
+            (self.offset_x, self.offset_y),
+            (self.parallax_x, self.parallax_y),
+            self.parent_group,
+        )
+    }
+
+    /// Returns the colour everything in this group is multiplied with, combining its tint colour
+    /// and opacity with those of its parents.
+    pub fn effective_tint(&self, map: &Map) -> Tint {
+        Tint::new(map, self.tint_colour, self.opacity, self.parent_group)
+    }
+
+    /// Returns the names of the parents of this group followed by its own name, separated by
+    /// slashes, as accepted by [`Map::group_by_path`].
+    pub fn path(&self, map: &Map) -> String {
+        layer_path(map, &self.name, self.parent_group)
+    }
+}
+
+/// A tile, image or object layer of a map.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LayerRef<'map> {
+    Tile(&'map Layer),
+    Image(&'map ImageLayer),
+    Objects(&'map ObjectGroup),
+}
+
+impl<'map> LayerRef<'map> {
+    pub fn name(&self) -> &'map str {
+        match *self {
+            LayerRef::Tile(layer) => &layer.name,
+            LayerRef::Image(layer) => &layer.name,
+            LayerRef::Objects(layer) => &layer.name,
+        }
+    }
+
+    /// The ID of the layer, as shown in the editor.
+    pub fn id(&self) -> u32 {
+        match *self {
+            LayerRef::Tile(layer) => layer.id,
+            LayerRef::Image(layer) => layer.id,
+            LayerRef::Objects(layer) => layer.id,
+        }
+    }
+
+    /// The index of the layer among all layers of the map, counting layers of all kinds in file
+    /// order.
+    pub fn layer_index(&self) -> u32 {
+        match *self {
+            LayerRef::Tile(layer) => layer.layer_index,
+            LayerRef::Image(layer) => layer.layer_index,
+            LayerRef::Objects(layer) => layer.layer_index.unwrap_or(0),
+        }
+    }
+
+    /// The class of the layer, if it has one.
+    pub fn class(&self) -> Option<&'map str> {
+        match *self {
+            LayerRef::Tile(layer) => layer.user_type.as_deref(),
+            LayerRef::Image(layer) => layer.user_type.as_deref(),
+            LayerRef::Objects(layer) => layer.user_type.as_deref(),
+        }
+    }
+
+    pub fn properties(&self) -> &'map Properties {
+        match *self {
+            LayerRef::Tile(layer) => &layer.properties,
+            LayerRef::Image(layer) => &layer.properties,
+            LayerRef::Objects(layer) => &layer.properties,
+        }
+    }
+
+    /// The index in [`Map::groups`] of the group layer containing this layer, if any.
+    pub fn parent_group(&self) -> Option<usize> {
+        match *self {
+            LayerRef::Tile(layer) => layer.parent_group,
+            LayerRef::Image(layer) => layer.parent_group,
+            LayerRef::Objects(layer) => layer.parent_group,
+        }
+    }
+
+    fn visible(&self) -> bool {
+        match *self {
+            LayerRef::Tile(layer) => layer.visible,
+            LayerRef::Image(layer) => layer.visible,
+            LayerRef::Objects(layer) => layer.visible,
+        }
+    }
+
+    /// Returns the offset and parallax factor of this layer, including those of its groups.
+    pub fn transform(&self, map: &Map) -> LayerTransform {
+        match *self {
+            LayerRef::Tile(layer) => layer.transform(map),
+            LayerRef::Image(layer) => layer.transform(map),
+            LayerRef::Objects(layer) => layer.transform(map),
+        }
+    }
+
+    /// Returns the colour everything in this layer is multiplied with, combining its tint colour
+    /// and opacity with those of its groups.
+    pub fn effective_tint(&self, map: &Map) -> Tint {
+        match *self {
+            LayerRef::Tile(layer) => layer.effective_tint(map),
+            LayerRef::Image(layer) => layer.effective_tint(map),
+            LayerRef::Objects(layer) => layer.effective_tint(map),
+        }
+    }
+
+    /// Returns the names of the groups containing this layer followed by its own name, separated
+    /// by slashes, as accepted by [`Map::layer_by_path`].
+    pub fn path(&self, map: &Map) -> String {
+        layer_path(map, self.name(), self.parent_group())
+    }
+}
+
+/// A layer along with how it is drawn once the group layers containing it are taken into
+/// account, as returned by [`Map::iter_layers_recursive`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FlatLayer<'map> {
+    pub layer: LayerRef<'map>,
+    pub transform: LayerTransform,
+    pub tint: Tint,
+    /// Whether the layer and all groups containing it are visible.
+    pub visible: bool,
+}
+
+/// A layer of any kind, group layers included.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AnyLayer<'map> {
+    Layer(LayerRef<'map>),
+    Group(&'map GroupLayer),
+}
+
+/// A layer along with what [`Map::find_layers`] can match it on.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct LayerMeta<'map> {
+    pub layer: AnyLayer<'map>,
+    /// The ID of the layer, as shown in the editor.
+    pub id: u32,
+    pub name: &'map str,
+    pub class: Option<&'map str>,
+    pub properties: &'map Properties,
+    /// Whether the layer itself is visible.
+    pub visible: bool,
+    /// Whether the layer and all groups containing it are visible.
+    pub visible_in_map: bool,
+    /// The index in [`Map::groups`] of the group layer containing this layer, if any.
+    pub parent_group: Option<usize>,
+}
+
+impl<'map> LayerMeta<'map> {
+    fn new(map: &'map Map, layer: AnyLayer<'map>) -> LayerMeta<'map> {
+        let (id, name, class, properties, visible, parent_group) = match layer {
+            AnyLayer::Layer(l) => (
+                l.id(),
+                l.name(),
+                l.class(),
+                l.properties(),
+                l.visible(),
+                l.parent_group(),
+            ),
+            AnyLayer::Group(g) => (
+                g.id,
+                g.name.as_str(),
+                g.user_type.as_deref(),
+                &g.properties,
+                g.visible,
+                g.parent_group,
+            ),
+        };
+        let mut visible_in_map = visible;
+        let mut parent = parent_group.and_then(|i| map.groups.get(i));
+        while let Some(group) = parent {
+            visible_in_map &= group.visible;
+            parent = group.parent_group.and_then(|i| map.groups.get(i));
+        }
+        LayerMeta {
+            layer,
+            id,
+            name,
+            class,
+            properties,
+            visible,
+            visible_in_map,
+            parent_group,
+        }
+    }
+}
+
+/// An object or a tile of a map, as given to [`Map::world_position_of`].
+#[derive(Debug, Clone, Copy)]
+pub enum MapItem<'map> {
+    Object(MapObject<'map>),
+    Tile { layer: &'map Layer, x: i32, y: i32 },
+}
+
+impl<'map> From<MapObject<'map>> for MapItem<'map> {
+    fn from(object: MapObject<'map>) -> Self {
+        MapItem::Object(object)
+    }
+}
+
+impl Map {
+    /// Iterates over the tile, image and object layers of the map in the order they are drawn,
+    /// walking group layers depth-first, along with the offset, parallax factor, tint and
+    /// visibility each one gets from the groups containing it. Renderers can draw the layers
+    /// without knowing about groups.
+    pub fn iter_layers_recursive(&self) -> impl Iterator<Item = FlatLayer<'_>> {
+        let mut layers: Vec<LayerRef<'_>> = self
+            .layers
+            .iter()
+            .map(LayerRef::Tile)
+            .chain(self.image_layers.iter().map(LayerRef::Image))
+            .chain(self.object_groups.iter().map(LayerRef::Objects))
+            .collect();
+        layers.sort_by_key(|l| l.layer_index());
+        layers.into_iter().map(move |layer| {
+            let mut visible = layer.visible();
+            let mut parent = layer.parent_group().and_then(|i| self.groups.get(i));
+            while let Some(group) = parent {
+                visible &= group.visible;
+                parent = group.parent_group.and_then(|i| self.groups.get(i));
+            }
+            FlatLayer {
+                layer,
+                transform: layer.transform(self),
+                tint: layer.effective_tint(self),
+                visible,
+            }
+        })
+    }
+
+    /// Finds a tile, image or object layer by its path: the names of the groups containing it
+    /// followed by its own name, separated by slashes, like `"World/Background/Clouds"`. Layers
+    /// outside of any group are found by their name alone. If several layers have the same path,
+    /// the first one drawn is returned.
+    ///
+    /// Names containing slashes themselves are matched as they are, so paths can be ambiguous if
+    /// the map has any.
+    pub fn layer_by_path(&self, path: &str) -> Option<LayerRef<'_>> {
+        self.iter_layers_recursive()
+            .map(|l| l.layer)
+            .find(|layer| path_matches(self, path, layer.name(), layer.parent_group()))
+    }
+
+    /// Returns the layers of any kind matching `predicate`, in file order, walking group layers
+    /// depth-first. Unlike [`Map::layer_by_path`], layers can be matched on their class,
+    /// properties or visibility, such as every layer whose class starts with `"collision_"`.
+    pub fn find_layers(
+        &self,
+        mut predicate: impl FnMut(&LayerMeta<'_>) -> bool,
+    ) -> Vec<LayerMeta<'_>> {
+        let mut layers: Vec<(u32, AnyLayer<'_>)> = self
+            .layers
+            .iter()
+            .map(LayerRef::Tile)
+            .chain(self.image_layers.iter().map(LayerRef::Image))
+            .chain(self.object_groups.iter().map(LayerRef::Objects))
+            .map(|l| (l.layer_index(), AnyLayer::Layer(l)))
+            .chain(
+                self.groups
+                    .iter()
+                    .map(|g| (g.layer_index, AnyLayer::Group(g))),
+            )
+            .collect();
+        layers.sort_by_key(|&(index, _)| index);
+        layers
+            .into_iter()
+            .map(|(_, layer)| LayerMeta::new(self, layer))
+            .filter(|meta| predicate(meta))
+            .collect()
+    }
+
+    /// Finds a group layer by its path, like [`Map::layer_by_path`] does for other layers.
+    pub fn group_by_path(&self, path: &str) -> Option<&GroupLayer> {
+        self.groups
+            .iter()
+            .find(|group| path_matches(self, path, &group.name, group.parent_group))
+    }
+
+    /// Returns where an object or tile is drawn, in pixels, when the view is centered on
+    /// `(camera_x, camera_y)`: the position of objects, or the top-left corner of the bounding
+    /// box of tiles, moved by the offsets and parallax scrolling of their layer and its groups.
+    ///
+    /// Returns `None` for tiles of hexagonal maps, see [`Map::tile_to_pixel`].
+    pub fn world_position_of<'map>(
+        &self,
+        item: impl Into<MapItem<'map>>,
+        camera_x: f32,
+        camera_y: f32,
+    ) -> Option<(f32, f32)> {
+        let ((x, y), transform) = match item.into() {
+            MapItem::Object(MapObject { layer, object }) => {
+                ((object.x, object.y), layer.transform(self))
+            }
+            MapItem::Tile { layer, x, y } => (self.tile_to_pixel(x, y)?, layer.transform(self)),
+        };
+        let (dx, dy) = transform.translation(self, camera_x, camera_y);
+        Some((x + dx, y + dy))
+    }
+}
+
+fn layer_path(map: &Map, name: &str, parent: Option<usize>) -> String {
+    let mut names = vec![name];
+    let mut parent = parent.and_then(|i| map.groups.get(i));
+    while let Some(group) = parent {
+        names.push(&group.name);
+        parent = group.parent_group.and_then(|i| map.groups.get(i));
+    }
+    names.reverse();
+    names.join("/")
+}
+
+/// Checks whether `path` is the path of a layer, comparing names from the innermost outwards
+/// without building the path of every layer.
+fn path_matches(map: &Map, path: &str, name: &str, parent: Option<usize>) -> bool {
+    let mut rest = match path.strip_suffix(name) {
+        Some(rest) => rest,
+        None => return false,
+    };
+    let mut parent = parent.and_then(|i| map.groups.get(i));
+    while let Some(group) = parent {
+        rest = match rest
+            .strip_suffix('/')
+            .and_then(|rest| rest.strip_suffix(group.name.as_str()))
+        {
+            Some(rest) => rest,
+            None => return false,
+        };
+        parent = group.parent_group.and_then(|i| map.groups.get(i));
+    }
+    rest.is_empty()
+}
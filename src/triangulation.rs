@@ -0,0 +1,85 @@
+//! Ear clipping triangulation of polygon objects, enabled by the `triangulation` feature.
+
+use crate::objects::ObjectShape;
+
+impl ObjectShape {
+    /// Splits a polygon into triangles, returned as triples of indices into its points. Concave
+    /// polygons are supported, self-intersecting ones are not.
+    ///
+    /// Returns `None` for shapes other than polygons, and for polygons that can't be
+    /// triangulated (fewer than three points, no area, or self-intersecting).
+    pub fn triangulate(&self) -> Option<Vec<[usize; 3]>> {
+        match self {
+            ObjectShape::Polygon { points } => triangulate(points),
+            _ => None,
+        }
+    }
+}
+
+fn cross(a: (f32, f32), b: (f32, f32), c: (f32, f32)) -> f32 {
+    (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+}
+
+fn in_triangle(p: (f32, f32), a: (f32, f32), b: (f32, f32), c: (f32, f32), sign: f32) -> bool {
+    cross(a, b, p) * sign >= 0.0 && cross(b, c, p) * sign >= 0.0 && cross(c, a, p) * sign >= 0.0
+}
+
+fn triangulate(points: &[(f32, f32)]) -> Option<Vec<[usize; 3]>> {
+    if points.len() < 3 {
+        return None;
+    }
+    // Twice the signed area; its sign tells the winding order of the polygon.
+    let area: f32 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.0 * b.1 - b.0 * a.1)
+        .sum();
+    if area == 0.0 {
+        return None;
+    }
+    let sign = area.signum();
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let ear = (0..n).find_map(|i| {
+            let (a, b, c) = (
+                remaining[(i + n - 1) % n],
+                remaining[i],
+                remaining[(i + 1) % n],
+            );
+            let turn = cross(points[a], points[b], points[c]) * sign;
+            if turn == 0.0 {
+                // Collinear vertices can simply be dropped.
+                return Some((i, None));
+            }
+            if turn < 0.0 {
+                return None;
+            }
+            let blocked = remaining.iter().any(|&p| {
+                p != a
+                    && p != b
+                    && p != c
+                    && in_triangle(points[p], points[a], points[b], points[c], sign)
+            });
+            if blocked {
+                None
+            } else {
+                Some((i, Some([a, b, c])))
+            }
+        });
+        let (i, triangle) = ear?;
+        triangles.extend(triangle);
+        remaining.remove(i);
+    }
+    if cross(
+        points[remaining[0]],
+        points[remaining[1]],
+        points[remaining[2]],
+    ) != 0.0
+    {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    Some(triangles)
+}
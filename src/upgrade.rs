@@ -0,0 +1,103 @@
+//! Converting constructs of files saved by older versions of Tiled into the ones used since, so
+//! that code using maps only has to handle the current ones.
+
+use std::sync::Arc;
+
+use crate::{
+    layers::Encoding,
+    map::Map,
+    properties::Colour,
+    tileset::Tileset,
+    wangset::{WangColor, WangId, WangSet, WangSetType, WangTile},
+};
+
+/// The colours the editor gives to new wang colors, used for the converted terrains.
+const TERRAIN_COLOURS: [(u8, u8, u8); 8] = [
+    (255, 0, 0),
+    (0, 255, 0),
+    (0, 0, 255),
+    (255, 119, 0),
+    (0, 233, 255),
+    (255, 0, 216),
+    (255, 239, 0),
+    (106, 0, 255),
+];
+
+impl Map {
+    /// Converts legacy constructs of the map into the ones the editor uses nowadays:
+    ///
+    /// - The terrain types of tilesets saved before Tiled 1.5 become a corner wang set named
+    ///   "Terrains", with a color for each terrain, like the editor converts them. Tilesets
+    ///   shared with other maps are copied rather than changed.
+    /// - Tile layers stored as one XML element per tile, which the editor no longer writes,
+    ///   are set to be written as CSV.
+    ///
+    /// The `type` of objects and tiles, called `class` since Tiled 1.9, needs no conversion:
+    /// both are read into [`Object::obj_type`](crate::objects::Object::obj_type) and
+    /// [`Tile::tile_type`](crate::tile::Tile::tile_type).
+    ///
+    /// Returns whether anything was converted.
+    pub fn upgrade_in_place(&mut self) -> bool {
+        let mut upgraded = false;
+        for tileset in self.tilesets.iter_mut() {
+            if !tileset.terrain_types.is_empty() {
+                terrains_to_wang_set(Arc::make_mut(&mut tileset.tileset));
+                upgraded = true;
+            }
+        }
+        for layer in self.layers.iter_mut() {
+            if layer.encoding.is_none() {
+                layer.encoding = Some(Encoding::Csv);
+                layer.compression = None;
+                upgraded = true;
+            }
+        }
+        upgraded
+    }
+}
+
+fn terrains_to_wang_set(tileset: &mut Tileset) {
+    let colors = tileset
+        .terrain_types
+        .drain(..)
+        .enumerate()
+        .map(|(i, terrain)| {
+            let (red, green, blue) = TERRAIN_COLOURS[i % TERRAIN_COLOURS.len()];
+            WangColor {
+                name: terrain.name,
+                color: Colour {
+                    red,
+                    green,
+                    blue,
+                    alpha: 255,
+                },
+                tile: terrain.tile,
+                probability: 1.0,
+                properties: terrain.properties,
+            }
+        })
+        .collect();
+    let mut wang_tiles = Vec::new();
+    for tile in tileset.tiles.iter_mut() {
+        if let Some([top_left, top_right, bottom_left, bottom_right]) = tile.terrain.take() {
+            let color = |terrain: Option<u32>| terrain.map_or(0, |t| (t + 1).min(255) as u8);
+            let mut wang_id = WangId::default();
+            wang_id.0[WangId::TOP_LEFT] = color(top_left);
+            wang_id.0[WangId::TOP_RIGHT] = color(top_right);
+            wang_id.0[WangId::BOTTOM_LEFT] = color(bottom_left);
+            wang_id.0[WangId::BOTTOM_RIGHT] = color(bottom_right);
+            wang_tiles.push(WangTile {
+                tile_id: tile.id,
+                wang_id,
+            });
+        }
+    }
+    tileset.wang_sets.push(WangSet {
+        name: "Terrains".to_string(),
+        wang_set_type: WangSetType::Corner,
+        tile: None,
+        colors,
+        wang_tiles,
+        properties: Default::default(),
+    });
+}
@@ -42,10 +42,8 @@ macro_rules! parse_tag {
                         };
                     })*
                 }
-                xml::reader::XmlEvent::EndElement {name, ..} => {
-                    if name.local_name == $close_tag {
-                        break;
-                    }
+                xml::reader::XmlEvent::EndElement {name, ..} if name.local_name == $close_tag => {
+                    break;
                 }
                 xml::reader::XmlEvent::EndDocument => return Err(TiledError::PrematureEnd("Document ended before we expected.".to_string())),
                 _ => {}
@@ -55,9 +53,10 @@ macro_rules! parse_tag {
 }
 
 use std::{
+    borrow::Cow,
     collections::HashMap,
     fs::File,
-    io::{BufReader, Read},
+    io::Read,
     path::Path,
 };
 
@@ -67,12 +66,20 @@ use xml::{attribute::OwnedAttribute, reader::XmlEvent, EventReader};
 
 use crate::{
     animation::Frame,
+    charset::{decode_xml, read_xml},
     error::TiledError,
     layers::{Chunk, LayerData, LayerTile},
+    loader::{ExternalFiles, Loader},
     map::Map,
     tileset::Tileset,
 };
 
+/// Turns Windows-style backslashes of a `source` path into forward slashes, which every platform
+/// understands.
+pub(crate) fn normalize_source(source: &str) -> String {
+    source.replace('\\', "/")
+}
+
 pub(crate) fn parse_animation<R: Read>(
     parser: &mut EventReader<R>,
 ) -> Result<Vec<Frame>, TiledError> {
@@ -90,6 +97,7 @@ pub(crate) fn parse_infinite_data<R: Read>(
     parser: &mut EventReader<R>,
     attrs: Vec<OwnedAttribute>,
     width: u32,
+    options: &Loader,
 ) -> Result<LayerData, TiledError> {
     let ((e, c), ()) = get_attrs!(
         attrs,
@@ -104,7 +112,8 @@ pub(crate) fn parse_infinite_data<R: Read>(
     let mut chunks = HashMap::<(i32, i32), Chunk>::new();
     parse_tag!(parser, "data", {
         "chunk" => |attrs| {
-            let chunk = Chunk::new(parser, attrs, e.clone(), c.clone())?;
+            options.check_cancelled()?;
+            let chunk = Chunk::new(parser, attrs, e.clone(), c.clone(), options.cell_limit)?;
             chunks.insert((chunk.x, chunk.y), chunk);
             Ok(())
         }
@@ -117,6 +126,7 @@ pub(crate) fn parse_data<R: Read>(
     parser: &mut EventReader<R>,
     attrs: Vec<OwnedAttribute>,
     width: u32,
+    height: u32,
 ) -> Result<LayerData, TiledError> {
     let ((e, c), ()) = get_attrs!(
         attrs,
@@ -128,7 +138,7 @@ pub(crate) fn parse_data<R: Read>(
         TiledError::MalformedAttributes("data must have an encoding and a compression".to_string())
     );
 
-    let tiles = parse_data_line(e, c, parser, width)?;
+    let tiles = parse_data_line(e, c, parser, width, height)?;
 
     Ok(LayerData::Finite(tiles))
 }
@@ -138,33 +148,35 @@ pub(crate) fn parse_data_line<R: Read>(
     compression: Option<String>,
     parser: &mut EventReader<R>,
     width: u32,
+    height: u32,
 ) -> Result<Vec<Vec<LayerTile>>, TiledError> {
+    if width == 0 {
+        return Err(TiledError::ZeroSize("width of tile data".to_string()));
+    }
+    // The number of bytes of the decompressed tile data, if it is as large as declared.
+    #[cfg(any(feature = "zlib", feature = "gzip", feature = "zstd"))]
+    let expected = width as usize * height as usize * 4;
     match (encoding, compression) {
-        (None, None) => {
-            return Err(TiledError::Other(
-                "XML format is currently not supported".to_string(),
-            ))
-        }
+        (None, None) => return decode_xml_tiles(width, parser),
         (Some(e), None) => match e.as_ref() {
             "base64" => return parse_base64(parser).map(|v| convert_to_tile(&v, width)),
-            "csv" => return decode_csv(width, parser),
+            "csv" => return decode_csv(width, height, parser),
             e => return Err(TiledError::Other(format!("Unknown encoding format {}", e))),
         },
-        (Some(e), Some(c)) => match (e.as_ref(), c.as_ref()) {
+        (Some(e), Some(c)) => match (e.as_str(), c.as_str()) {
+            #[cfg(feature = "zlib")]
             ("base64", "zlib") => {
-                return parse_base64(parser)
-                    .and_then(decode_zlib)
+                return parse_compressed(parser, expected, decode_zlib)
                     .map(|v| convert_to_tile(&v, width))
             }
+            #[cfg(feature = "gzip")]
             ("base64", "gzip") => {
-                return parse_base64(parser)
-                    .and_then(decode_gzip)
+                return parse_compressed(parser, expected, decode_gzip)
                     .map(|v| convert_to_tile(&v, width))
             }
             #[cfg(feature = "zstd")]
             ("base64", "zstd") => {
-                return parse_base64(parser)
-                    .and_then(decode_zstd)
+                return parse_compressed(parser, expected, decode_zstd)
                     .map(|v| convert_to_tile(&v, width))
             }
             (e, c) => {
@@ -184,21 +196,44 @@ pub(crate) fn parse_base64<R: Read>(parser: &mut EventReader<R>) -> Result<Vec<u
             XmlEvent::Characters(s) => {
                 return base64::decode(s.trim().as_bytes()).map_err(TiledError::Base64DecodingError)
             }
-            XmlEvent::EndElement { name, .. } => {
-                if name.local_name == "data" {
-                    return Ok(Vec::new());
-                }
+            XmlEvent::EndElement { name, .. } if name.local_name == "data" => {
+                return Ok(Vec::new());
             }
             _ => {}
         }
     }
 }
 
-pub(crate) fn decode_zlib(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
+/// Parses base64 data and decompresses it with `decompress`. Empty data, written by some tools
+/// for layers without tiles, stays empty instead of failing to decompress.
+#[cfg(any(feature = "zlib", feature = "gzip", feature = "zstd"))]
+fn parse_compressed<R: Read>(
+    parser: &mut EventReader<R>,
+    expected: usize,
+    decompress: fn(Vec<u8>, usize) -> Result<Vec<u8>, TiledError>,
+) -> Result<Vec<u8>, TiledError> {
+    let data = parse_base64(parser)?;
+    if data.is_empty() {
+        return Ok(data);
+    }
+    decompress(data, expected)
+}
+
+/// The capacity to reserve for decompressing `compressed_len` bytes into what should be
+/// `expected` bytes. Crafted data declaring a huge size doesn't reserve more than DEFLATE, at its
+/// best ratio of about 1032:1, could decompress it to.
+#[cfg(any(feature = "zlib", feature = "gzip", feature = "zstd"))]
+fn decompressed_capacity(compressed_len: usize, expected: usize) -> usize {
+    expected.min(compressed_len.saturating_mul(1032))
+}
+
+#[cfg(feature = "zlib")]
+pub(crate) fn decode_zlib(data: Vec<u8>, expected: usize) -> Result<Vec<u8>, TiledError> {
     use libflate::zlib::Decoder;
+    use std::io::BufReader;
     let mut zd =
         Decoder::new(BufReader::new(&data[..])).map_err(|e| TiledError::DecompressingError(e))?;
-    let mut data = Vec::new();
+    let mut data = Vec::with_capacity(decompressed_capacity(data.len(), expected));
     match zd.read_to_end(&mut data) {
         Ok(_v) => {}
         Err(e) => return Err(TiledError::DecompressingError(e)),
@@ -206,26 +241,28 @@ pub(crate) fn decode_zlib(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
     Ok(data)
 }
 
-pub(crate) fn decode_gzip(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
+#[cfg(feature = "gzip")]
+pub(crate) fn decode_gzip(data: Vec<u8>, expected: usize) -> Result<Vec<u8>, TiledError> {
     use libflate::gzip::Decoder;
+    use std::io::BufReader;
     let mut zd =
         Decoder::new(BufReader::new(&data[..])).map_err(|e| TiledError::DecompressingError(e))?;
 
-    let mut data = Vec::new();
+    let mut data = Vec::with_capacity(decompressed_capacity(data.len(), expected));
     zd.read_to_end(&mut data)
         .map_err(|e| TiledError::DecompressingError(e))?;
     Ok(data)
 }
 
 #[cfg(feature = "zstd")]
-pub(crate) fn decode_zstd(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
+pub(crate) fn decode_zstd(data: Vec<u8>, expected: usize) -> Result<Vec<u8>, TiledError> {
     use std::io::Cursor;
     use zstd::stream::read::Decoder;
 
     let buff = Cursor::new(&data);
     let mut zd = Decoder::with_buffer(buff).map_err(|e| TiledError::DecompressingError(e))?;
 
-    let mut data = Vec::new();
+    let mut data = Vec::with_capacity(decompressed_capacity(data.len(), expected));
     zd.read_to_end(&mut data)
         .map_err(|e| TiledError::DecompressingError(e))?;
     Ok(data)
@@ -233,6 +270,7 @@ pub(crate) fn decode_zstd(data: Vec<u8>) -> Result<Vec<u8>, TiledError> {
 
 pub(crate) fn decode_csv<R: Read>(
     width: u32,
+    height: u32,
     parser: &mut EventReader<R>,
 ) -> Result<Vec<Vec<LayerTile>>, TiledError> {
     loop {
@@ -244,33 +282,72 @@ pub(crate) fn decode_csv<R: Read>(
                     .map(|v| v.parse().unwrap())
                     .map(LayerTile::new)
                     .peekable();
-                let mut rows = Vec::new();
+                // Each tile takes at least two characters, a digit and a separator.
+                let max_rows = s.len() / 2 / width as usize + 1;
+                let mut rows = Vec::with_capacity((height as usize).min(max_rows));
                 while tiles_it.peek().is_some() {
-                    let row = tiles_it.by_ref().take(width as usize).collect();
+                    let mut row = Vec::with_capacity((width as usize).min(s.len() / 2 + 1));
+                    row.extend(tiles_it.by_ref().take(width as usize));
                     rows.push(row);
                 }
                 return Ok(rows);
             }
-            XmlEvent::EndElement { name, .. } => {
-                if name.local_name == "data" {
-                    return Ok(Vec::new());
-                }
+            XmlEvent::EndElement { name, .. } if name.local_name == "data" => {
+                return Ok(Vec::new());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Reads tile data stored as one `<tile>` element per tile, the format of older versions of
+/// Tiled, up to the end of the element containing them.
+fn decode_xml_tiles<R: Read>(
+    width: u32,
+    parser: &mut EventReader<R>,
+) -> Result<Vec<Vec<LayerTile>>, TiledError> {
+    let mut tiles = Vec::new();
+    loop {
+        match parser.next().map_err(TiledError::XmlDecodingError)? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } if name.local_name == "tile" => {
+                let gid = match attributes.iter().find(|a| a.name.local_name == "gid") {
+                    Some(attr) => attr.value.parse().map_err(|_| {
+                        TiledError::MalformedAttributes(format!(
+                            "Invalid tile gid '{}'",
+                            attr.value
+                        ))
+                    })?,
+                    None => 0,
+                };
+                tiles.push(LayerTile::new(gid));
+            }
+            XmlEvent::EndElement { name, .. } if name.local_name != "tile" => break,
+            XmlEvent::EndDocument => {
+                return Err(TiledError::PrematureEnd(
+                    "Document ended before tile data was parsed".to_string(),
+                ))
             }
             _ => {}
         }
     }
+    Ok(tiles
+        .chunks(width as usize)
+        .map(<[LayerTile]>::to_vec)
+        .collect())
 }
 
 pub(crate) fn convert_to_tile(all: &Vec<u8>, width: u32) -> Vec<Vec<LayerTile>> {
-    let mut data = Vec::new();
-    for chunk in all.chunks((width * 4) as usize) {
-        let mut row = Vec::new();
-        for i in 0..width {
-            let start: usize = i as usize * 4;
-            let n = ((chunk[start + 3] as u32) << 24)
-                + ((chunk[start + 2] as u32) << 16)
-                + ((chunk[start + 1] as u32) << 8)
-                + chunk[start] as u32;
+    let row_len = width as usize * 4;
+    let mut data = Vec::with_capacity(all.len().div_ceil(row_len));
+    for chunk in all.chunks(row_len) {
+        let mut row = Vec::with_capacity(chunk.len() / 4);
+        for bytes in chunk.chunks_exact(4) {
+            let n = ((bytes[3] as u32) << 24)
+                + ((bytes[2] as u32) << 16)
+                + ((bytes[1] as u32) << 8)
+                + bytes[0] as u32;
             let n = LayerTile::new(n);
             row.push(n);
         }
@@ -279,16 +356,29 @@ pub(crate) fn convert_to_tile(all: &Vec<u8>, width: u32) -> Vec<Vec<LayerTile>>
     data
 }
 
-pub(crate) fn parse_impl<R: Read>(reader: R, external_file_loader: impl FnMut(&str)->Result<Vec<u8>, TiledError>) -> Result<Map, TiledError> {
+/// Parses a map with the settings of `options`. Tilesets, templates and other external files
+/// are requested from `external_file_loader` with a [`FileRequest`](crate::loader::FileRequest)
+/// each, and tilesets already parsed into its [`FileCache`](crate::loader::FileCache), if it
+/// has one, are reused.
+pub(crate) fn parse_impl<R: Read>(reader: R, external_file_loader: ExternalFiles<'_>, options: &Loader) -> Result<Map, TiledError> {
+    parse_decoded(read_xml(reader)?, external_file_loader, options)
+}
+
+/// Like [`parse_impl`], for a map already in memory. The data is only copied if it has to be
+/// transcoded.
+pub(crate) fn parse_slice_impl(data: &[u8], external_file_loader: ExternalFiles<'_>, options: &Loader) -> Result<Map, TiledError> {
+    let data = decode_xml(Cow::Borrowed(data))?;
+    parse_decoded(&*data, external_file_loader, options)
+}
+
+fn parse_decoded<R: Read>(reader: R, external_file_loader: ExternalFiles<'_>, options: &Loader) -> Result<Map, TiledError> {
     let mut parser = EventReader::new(reader);
     loop {
         match parser.next().map_err(TiledError::XmlDecodingError)? {
             XmlEvent::StartElement {
                 name, attributes, ..
-            } => {
-                if name.local_name == "map" {
-                    return Map::new(&mut parser, attributes, external_file_loader);
-                }
+            } if name.local_name == "map" => {
+                return Map::new(&mut parser, attributes, external_file_loader, options);
             }
             XmlEvent::EndDocument => {
                 return Err(TiledError::PrematureEnd(
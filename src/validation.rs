@@ -0,0 +1,539 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    ids::{Gid, TileId},
+    layers::{LayerData, LayerTile},
+    map::Map,
+    project::Project,
+    properties::{Properties, PropertyValue},
+    tileset::Tileset,
+};
+
+/// Where a gid is used in a map.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GidLocation {
+    /// A cell of a tile layer.
+    Tile { layer_id: u32, x: i32, y: i32 },
+    /// A tile object.
+    Object { layer_id: u32, object_id: u32 },
+}
+
+impl fmt::Display for GidLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GidLocation::Tile { layer_id, x, y } => {
+                write!(f, "tile ({}, {}) of layer {}", x, y, layer_id)
+            }
+            GidLocation::Object {
+                layer_id,
+                object_id,
+            } => write!(f, "object {} of layer {}", object_id, layer_id),
+        }
+    }
+}
+
+/// A gid that does not belong to any tileset of the map.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct InvalidGid {
+    pub gid: u32,
+    pub location: GidLocation,
+}
+
+impl fmt::Display for InvalidGid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "gid {} used by {} is not part of any tileset",
+            self.gid, self.location
+        )
+    }
+}
+
+fn is_valid_gid(map: &Map, gid: u32) -> bool {
+    gid == 0 || map.tilesets.iter().any(|t| t.contains_gid(Gid(gid)))
+}
+
+pub(crate) fn validate_gids(map: &Map) -> Vec<InvalidGid> {
+    let mut invalid = Vec::new();
+    for layer in &map.layers {
+        let mut check = |x: i32, y: i32, tile: LayerTile| {
+            if !is_valid_gid(map, tile.gid) {
+                invalid.push(InvalidGid {
+                    gid: tile.gid,
+                    location: GidLocation::Tile {
+                        layer_id: layer.id,
+                        x,
+                        y,
+                    },
+                });
+            }
+        };
+        match &layer.tiles {
+            LayerData::Finite(rows) => {
+                for (y, row) in rows.iter().enumerate() {
+                    for (x, &tile) in row.iter().enumerate() {
+                        check(x as i32, y as i32, tile);
+                    }
+                }
+            }
+            LayerData::Infinite(_) => {
+                for (_, chunk) in layer.chunks() {
+                    for ((x, y), tile) in chunk.tiles() {
+                        check(x, y, tile);
+                    }
+                }
+            }
+        }
+    }
+    for group in &map.object_groups {
+        for object in &group.objects {
+            let gid = LayerTile::new(object.gid).gid;
+            if !is_valid_gid(map, gid) {
+                invalid.push(InvalidGid {
+                    gid,
+                    location: GidLocation::Object {
+                        layer_id: group.id,
+                        object_id: object.id,
+                    },
+                });
+            }
+        }
+    }
+    invalid
+}
+
+/// A way the attributes of a tileset don't match the size of its image, found by
+/// [`Tileset::validate_image`]. Such tilesets usually draw their tiles shifted or cropped.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TilesetMismatch {
+    /// Not a single tile fits in the image with the margin of the tileset.
+    TileLargerThanImage,
+    /// The `columns` attribute differs from the number of tile columns fitting in the image.
+    Columns { declared: u32, fitting: u32 },
+    /// The `tilecount` attribute differs from the number of tiles fitting in the image.
+    TileCount { declared: u32, fitting: u32 },
+    /// Pixels are left over to the right or at the bottom of the image after the last tile,
+    /// which usually means that the tile size, margin or spacing is wrong.
+    LeftoverPixels { right: u32, bottom: u32 },
+}
+
+impl fmt::Display for TilesetMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TilesetMismatch::TileLargerThanImage => write!(f, "no tile fits in the image"),
+            TilesetMismatch::Columns { declared, fitting } => write!(
+                f,
+                "{} columns are declared, but the image fits {}",
+                declared, fitting
+            ),
+            TilesetMismatch::TileCount { declared, fitting } => write!(
+                f,
+                "{} tiles are declared, but the image fits {}",
+                declared, fitting
+            ),
+            TilesetMismatch::LeftoverPixels { right, bottom } => write!(
+                f,
+                "{} pixels are left over to the right of the tiles and {} below them",
+                right, bottom
+            ),
+        }
+    }
+}
+
+pub(crate) fn validate_tileset_image(tileset: &Tileset) -> Vec<TilesetMismatch> {
+    let (width, height) = match tileset.images.first().map(|i| (i.width, i.height)) {
+        Some((Some(width), Some(height))) if width > 0 && height > 0 => (width, height),
+        _ => return Vec::new(),
+    };
+    let (columns, right) = tileset.fit_tiles(Some(width), tileset.tile_width);
+    let (rows, bottom) = tileset.fit_tiles(Some(height), tileset.tile_height);
+    if columns == 0 || rows == 0 {
+        return vec![TilesetMismatch::TileLargerThanImage];
+    }
+
+    let mut mismatches = Vec::new();
+    match tileset.column_count {
+        Some(declared) if declared != columns => mismatches.push(TilesetMismatch::Columns {
+            declared,
+            fitting: columns,
+        }),
+        _ => {}
+    }
+    let fitting = columns.saturating_mul(rows);
+    match tileset.tilecount {
+        Some(declared) if declared != fitting => {
+            mismatches.push(TilesetMismatch::TileCount { declared, fitting })
+        }
+        _ => {}
+    }
+    if right != 0 || bottom != 0 {
+        mismatches.push(TilesetMismatch::LeftoverPixels { right, bottom });
+    }
+    mismatches
+}
+
+/// A problem found by [`Map::validate`]. None of them prevents using the map, but they make
+/// lookups by name, id or gid return unexpected results.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ValidationWarning {
+    /// Several layers share a name. Holds the `layer_index` of each of them, in order.
+    DuplicateLayerName {
+        name: String,
+        layer_indices: Vec<u32>,
+    },
+    /// Several objects share an id. Holds the id of the object layer containing each of them.
+    DuplicateObjectId { object_id: u32, layer_ids: Vec<u32> },
+    /// The gids of two tilesets overlap. Holds their indices in [`Map::tilesets`].
+    OverlappingTilesets { first: usize, second: usize },
+    /// A gid isn't part of any tileset, see [`Map::validate_gids`].
+    InvalidGid(InvalidGid),
+    /// The attributes of a tileset don't match its image. Holds its index in [`Map::tilesets`].
+    TilesetImage {
+        tileset: usize,
+        mismatch: TilesetMismatch,
+    },
+}
+
+impl fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationWarning::DuplicateLayerName {
+                name,
+                layer_indices,
+            } => write!(f, "layers {:?} are all named \"{}\"", layer_indices, name),
+            ValidationWarning::DuplicateObjectId {
+                object_id,
+                layer_ids,
+            } => write!(
+                f,
+                "object id {} is used by objects of layers {:?}",
+                object_id, layer_ids
+            ),
+            ValidationWarning::OverlappingTilesets { first, second } => {
+                write!(f, "the gids of tilesets {} and {} overlap", first, second)
+            }
+            ValidationWarning::InvalidGid(gid) => gid.fmt(f),
+            ValidationWarning::TilesetImage { tileset, mismatch } => {
+                write!(
+                    f,
+                    "tileset {} doesn't match its image: {}",
+                    tileset, mismatch
+                )
+            }
+        }
+    }
+}
+
+/// Groups `items` by key, keeping the groups with more than one value, in order of first
+/// appearance.
+fn duplicates<K: Eq + std::hash::Hash + Clone, V>(
+    items: impl Iterator<Item = (K, V)>,
+) -> Vec<(K, Vec<V>)> {
+    let mut groups: Vec<(K, Vec<V>)> = Vec::new();
+    let mut index: HashMap<K, usize> = HashMap::new();
+    for (key, value) in items {
+        match index.get(&key) {
+            Some(&i) => groups[i].1.push(value),
+            None => {
+                index.insert(key.clone(), groups.len());
+                groups.push((key, vec![value]));
+            }
+        }
+    }
+    groups.retain(|(_, values)| values.len() > 1);
+    groups
+}
+
+/// The indices of the pairs of tilesets whose gids overlap, in order.
+pub(crate) fn overlapping_tilesets(map: &Map) -> Vec<(usize, usize)> {
+    let ranges: Vec<(u32, u64)> = map
+        .tilesets
+        .iter()
+        .map(|t| (t.first_gid, t.first_gid as u64 + t.tile_count() as u64))
+        .collect();
+    let mut overlapping = Vec::new();
+    for (first, &(start_a, end_a)) in ranges.iter().enumerate() {
+        for (second, &(start_b, end_b)) in ranges.iter().enumerate().skip(first + 1) {
+            if (start_a as u64) < end_b && (start_b as u64) < end_a {
+                overlapping.push((first, second));
+            }
+        }
+    }
+    overlapping
+}
+
+pub(crate) fn validate(map: &Map) -> Vec<ValidationWarning> {
+    let mut warnings = Vec::new();
+
+    let mut names: Vec<(&str, u32)> = map
+        .layers
+        .iter()
+        .map(|l| (l.name.as_str(), l.layer_index))
+        .chain(
+            map.image_layers
+                .iter()
+                .map(|l| (l.name.as_str(), l.layer_index)),
+        )
+        .chain(
+            map.object_groups
+                .iter()
+                .filter_map(|l| Some((l.name.as_str(), l.layer_index?))),
+        )
+        .chain(map.groups.iter().map(|l| (l.name.as_str(), l.layer_index)))
+        .collect();
+    names.sort_by_key(|&(_, index)| index);
+    for (name, layer_indices) in duplicates(names.into_iter()) {
+        warnings.push(ValidationWarning::DuplicateLayerName {
+            name: name.to_string(),
+            layer_indices,
+        });
+    }
+
+    let objects = map.object_groups.iter().flat_map(|group| {
+        group
+            .objects
+            .iter()
+            .filter(|o| o.id != 0)
+            .map(move |o| (o.id, group.id))
+    });
+    for (object_id, layer_ids) in duplicates(objects) {
+        warnings.push(ValidationWarning::DuplicateObjectId {
+            object_id,
+            layer_ids,
+        });
+    }
+
+    for (first, second) in overlapping_tilesets(map) {
+        warnings.push(ValidationWarning::OverlappingTilesets { first, second });
+    }
+
+    warnings.extend(
+        validate_gids(map)
+            .into_iter()
+            .map(ValidationWarning::InvalidGid),
+    );
+    for (index, tileset) in map.tilesets.iter().enumerate() {
+        warnings.extend(validate_tileset_image(tileset).into_iter().map(|mismatch| {
+            ValidationWarning::TilesetImage {
+                tileset: index,
+                mismatch,
+            }
+        }));
+    }
+    warnings
+}
+
+/// What a [`SchemaViolation`] was found on.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SchemaLocation {
+    /// A tile, object, image or group layer.
+    Layer {
+        layer_id: u32,
+    },
+    Object {
+        layer_id: u32,
+        object_id: u32,
+    },
+    /// A tile of the tileset at index `tileset` of [`Map::tilesets`].
+    Tile {
+        tileset: usize,
+        tile_id: TileId,
+    },
+}
+
+impl fmt::Display for SchemaLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaLocation::Layer { layer_id } => write!(f, "layer {}", layer_id),
+            SchemaLocation::Object {
+                layer_id,
+                object_id,
+            } => write!(f, "object {} of layer {}", object_id, layer_id),
+            SchemaLocation::Tile { tileset, tile_id } => {
+                write!(f, "tile {} of tileset {}", tile_id.0, tileset)
+            }
+        }
+    }
+}
+
+/// How the class or properties of something disagree with the project, see [`SchemaViolation`].
+#[derive(Debug, PartialEq, Clone)]
+pub enum SchemaViolationKind {
+    /// The project has no class with this name.
+    UnknownClass(String),
+    /// The class can't be given to this kind of thing, such as a tile class given to an object.
+    NotUsableAs { class: String, kind: &'static str },
+    /// A property has another type than the member of the class with the same name.
+    WrongType {
+        member: String,
+        expected: String,
+        found: String,
+    },
+    /// A property using an enum holds a value that isn't part of it.
+    InvalidEnumValue {
+        member: String,
+        enum_name: String,
+        value: PropertyValue,
+    },
+}
+
+/// A class or property that disagrees with the custom types of a project, found by
+/// [`Map::validate_against`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct SchemaViolation {
+    pub location: SchemaLocation,
+    pub kind: SchemaViolationKind,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            SchemaViolationKind::UnknownClass(class) => {
+                write!(f, "{} has the unknown class \"{}\"", self.location, class)
+            }
+            SchemaViolationKind::NotUsableAs { class, kind } => write!(
+                f,
+                "{} has the class \"{}\", which can't be used for a {}",
+                self.location, class, kind
+            ),
+            SchemaViolationKind::WrongType {
+                member,
+                expected,
+                found,
+            } => write!(
+                f,
+                "property '{}' of {} should be of type {} but is of type {}",
+                member, self.location, expected, found
+            ),
+            SchemaViolationKind::InvalidEnumValue {
+                member,
+                enum_name,
+                value,
+            } => write!(
+                f,
+                "property '{}' of {} has the value {:?}, which isn't part of enum \"{}\"",
+                member, self.location, value, enum_name
+            ),
+        }
+    }
+}
+
+/// Checks the class of one thing and its properties against `project`. Things without a class
+/// are skipped.
+fn check_class(
+    project: &Project,
+    location: SchemaLocation,
+    kind: &'static str,
+    class: Option<&str>,
+    properties: &Properties,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let mut violation = |kind| violations.push(SchemaViolation { location, kind });
+    let class = match class.filter(|c| !c.is_empty()) {
+        Some(class) => match project.class(class) {
+            Some(class) => class,
+            None => return violation(SchemaViolationKind::UnknownClass(class.to_string())),
+        },
+        None => return,
+    };
+    if !class.usable_as(kind) {
+        violation(SchemaViolationKind::NotUsableAs {
+            class: class.name.clone(),
+            kind,
+        });
+    }
+    // Properties are sorted, so that the violations come out in a stable order.
+    let mut names: Vec<&String> = properties.keys().collect();
+    names.sort();
+    for name in names {
+        let (member, value) = match (class.member(name), &properties[name]) {
+            (Some(member), value) => (member, value),
+            // Things can have properties of their own on top of those of their class.
+            (None, _) => continue,
+        };
+        if value.type_name() != member.type_name {
+            violation(SchemaViolationKind::WrongType {
+                member: name.clone(),
+                expected: member.type_name.clone(),
+                found: value.type_name().to_string(),
+            });
+            continue;
+        }
+        let enum_type = member
+            .property_type
+            .as_deref()
+            .and_then(|t| project.enum_type(t));
+        if let Some(enum_type) = enum_type {
+            if !enum_type.accepts(value) {
+                violation(SchemaViolationKind::InvalidEnumValue {
+                    member: name.clone(),
+                    enum_name: enum_type.name.clone(),
+                    value: value.clone(),
+                });
+            }
+        }
+    }
+}
+
+pub(crate) fn validate_against(map: &Map, project: &Project) -> Vec<SchemaViolation> {
+    let mut violations = Vec::new();
+    let mut check = |location, kind, class: Option<&str>, properties: &Properties| {
+        check_class(project, location, kind, class, properties, &mut violations)
+    };
+    let layers = map
+        .layers
+        .iter()
+        .map(|l| (l.layer_index, l.id, &l.user_type, &l.properties))
+        .chain(
+            map.image_layers
+                .iter()
+                .map(|l| (l.layer_index, l.id, &l.user_type, &l.properties)),
+        )
+        .chain(
+            map.object_groups
+                .iter()
+                .filter_map(|l| Some((l.layer_index?, l.id, &l.user_type, &l.properties))),
+        )
+        .chain(
+            map.groups
+                .iter()
+                .map(|l| (l.layer_index, l.id, &l.user_type, &l.properties)),
+        );
+    let mut layers: Vec<_> = layers.collect();
+    layers.sort_by_key(|&(index, ..)| index);
+    for (_, layer_id, class, properties) in layers {
+        let location = SchemaLocation::Layer { layer_id };
+        check(location, "layer", class.as_deref(), properties);
+    }
+
+    for group in &map.object_groups {
+        for object in &group.objects {
+            let location = SchemaLocation::Object {
+                layer_id: group.id,
+                object_id: object.id,
+            };
+            check(
+                location,
+                "object",
+                Some(&object.obj_type),
+                &object.properties,
+            );
+        }
+    }
+
+    for (index, tileset) in map.tilesets.iter().enumerate() {
+        for tile in &tileset.tiles {
+            let location = SchemaLocation::Tile {
+                tileset: index,
+                tile_id: tile.id,
+            };
+            check(
+                location,
+                "tile",
+                tile.tile_type.as_deref(),
+                &tile.properties,
+            );
+        }
+    }
+    violations
+}
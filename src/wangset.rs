@@ -0,0 +1,557 @@
+use std::{collections::HashMap, fmt, io::Read, str::FromStr};
+
+use xml::{attribute::OwnedAttribute, EventReader};
+
+use crate::{
+    error::TiledError,
+    ids::{Gid, TileId},
+    layers::{Layer, LayerTile, TileRect},
+    properties::{parse_properties, Colour, Properties},
+    tileset::MapTileset,
+    util::{get_attrs, parse_tag},
+};
+
+/// Which parts of a tile the colors of a wang set apply to.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum WangSetType {
+    Corner,
+    Edge,
+    Mixed,
+}
+
+impl FromStr for WangSetType {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<WangSetType, TiledError> {
+        match s {
+            "corner" => Ok(Self::Corner),
+            "edge" => Ok(Self::Edge),
+            "mixed" => Ok(Self::Mixed),
+            _ => Err(TiledError::MalformedAttributes(format!(
+                "Invalid wang set type '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for WangSetType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WangSetType::Corner => write!(f, "corner"),
+            WangSetType::Edge => write!(f, "edge"),
+            WangSetType::Mixed => write!(f, "mixed"),
+        }
+    }
+}
+
+/// The colors of the edges and corners of a tile, as indices into [`WangSet::colors`] plus one.
+/// 0 means no color.
+///
+/// The order is the one used by Tiled: top, top right, right, bottom right, bottom, bottom left,
+/// left and top left.
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone, Default)]
+pub struct WangId(pub [u8; 8]);
+
+impl WangId {
+    pub const TOP: usize = 0;
+    pub const TOP_RIGHT: usize = 1;
+    pub const RIGHT: usize = 2;
+    pub const BOTTOM_RIGHT: usize = 3;
+    pub const BOTTOM: usize = 4;
+    pub const BOTTOM_LEFT: usize = 5;
+    pub const LEFT: usize = 6;
+    pub const TOP_LEFT: usize = 7;
+
+    /// Returns whether every position set in `pattern` has the same color in this id. `None`
+    /// positions match any color.
+    pub fn matches(&self, pattern: &[Option<u8>; 8]) -> bool {
+        self.0
+            .iter()
+            .zip(pattern.iter())
+            .all(|(color, expected)| !matches!(expected, Some(e) if e != color))
+    }
+}
+
+impl FromStr for WangId {
+    type Err = TiledError;
+
+    fn from_str(s: &str) -> Result<WangId, TiledError> {
+        let err = || TiledError::MalformedAttributes(format!("Invalid wang id '{}'", s));
+        let mut id = [0; 8];
+        let mut parts = s.split(',');
+        for color in id.iter_mut() {
+            *color = parts
+                .next()
+                .and_then(|p| p.trim().parse().ok())
+                .ok_or_else(err)?;
+        }
+        if parts.next().is_some() {
+            return Err(err());
+        }
+        Ok(WangId(id))
+    }
+}
+
+impl fmt::Display for WangId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, color) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            write!(f, "{}", color)?;
+        }
+        Ok(())
+    }
+}
+
+/// A terrain (or other kind of color) of a wang set.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WangColor {
+    pub name: String,
+    /// The color shown for this terrain in the editor.
+    pub color: Colour,
+    /// The local id of the tile representing this color, if any.
+    pub tile: Option<u32>,
+    /// The relative chance of this color being picked when filling.
+    pub probability: f32,
+    pub properties: Properties,
+}
+
+impl WangColor {
+    fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+    ) -> Result<WangColor, TiledError> {
+        let ((tile, probability), (name, color)) = get_attrs!(
+            attrs,
+            optionals: [
+                ("tile", tile, |v:String| v.parse::<i64>().ok()),
+                ("probability", probability, |v:String| v.parse().ok()),
+            ],
+            required: [
+                ("name", name, Some),
+                ("color", color, Some),
+            ],
+            TiledError::MalformedAttributes("wang colors must have a name and a color".to_string())
+        );
+        let mut properties = HashMap::new();
+        parse_tag!(parser, "wangcolor", {
+            "properties" => |_| {
+                properties = parse_properties(parser)?;
+                Ok(())
+            },
+        });
+        Ok(WangColor {
+            name,
+            color: color.parse()?,
+            tile: tile.filter(|t| *t >= 0).map(|t| t as u32),
+            probability: probability.unwrap_or(1.0),
+            properties,
+        })
+    }
+}
+
+/// The wang id of a tile of the tileset.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WangTile {
+    /// The local id of the tile.
+    pub tile_id: TileId,
+    pub wang_id: WangId,
+}
+
+/// A set of terrains, and how the tiles of a tileset combine them. Used by the editor's terrain
+/// brush, and by [`WangSet::tiles_matching`] for doing the same at runtime.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WangSet {
+    pub name: String,
+    pub wang_set_type: WangSetType,
+    /// The local id of the tile representing the set, if any.
+    pub tile: Option<u32>,
+    pub colors: Vec<WangColor>,
+    pub wang_tiles: Vec<WangTile>,
+    pub properties: Properties,
+}
+
+impl WangSet {
+    pub(crate) fn new<R: Read>(
+        parser: &mut EventReader<R>,
+        attrs: Vec<OwnedAttribute>,
+    ) -> Result<WangSet, TiledError> {
+        let ((wang_set_type, tile), name) = get_attrs!(
+            attrs,
+            optionals: [
+                ("type", wang_set_type, |v:String| v.parse().ok()),
+                ("tile", tile, |v:String| v.parse::<i64>().ok()),
+            ],
+            required: [
+                ("name", name, Some),
+            ],
+            TiledError::MalformedAttributes("wang sets must have a name".to_string())
+        );
+        let mut colors = Vec::new();
+        let mut wang_tiles = Vec::new();
+        let mut properties = HashMap::new();
+        parse_tag!(parser, "wangset", {
+            "wangcolor" => |attrs| {
+                colors.push(WangColor::new(parser, attrs)?);
+                Ok(())
+            },
+            "wangtile" => |attrs: Vec<OwnedAttribute>| {
+                let ((), (tile_id, wang_id)) = get_attrs!(
+                    attrs,
+                    optionals: [],
+                    required: [
+                        ("tileid", tile_id, |v:String| v.parse().ok().map(TileId)),
+                        ("wangid", wang_id, Some),
+                    ],
+                    TiledError::MalformedAttributes("wang tiles must have a tileid and a wangid".to_string())
+                );
+                let wang_id: String = wang_id;
+                wang_tiles.push(WangTile {
+                    tile_id,
+                    wang_id: wang_id.parse()?,
+                });
+                Ok(())
+            },
+            "properties" => |_| {
+                properties = parse_properties(parser)?;
+                Ok(())
+            },
+        });
+        Ok(WangSet {
+            name,
+            wang_set_type: wang_set_type.unwrap_or(WangSetType::Mixed),
+            tile: tile.filter(|t| *t >= 0).map(|t| t as u32),
+            colors,
+            wang_tiles,
+            properties,
+        })
+    }
+
+    /// Returns the wang id of the tile with the given local id, if it is part of this set.
+    pub fn wang_id_for_tile(&self, local_id: TileId) -> Option<WangId> {
+        self.wang_tiles
+            .iter()
+            .find(|t| t.tile_id == local_id)
+            .map(|t| t.wang_id)
+    }
+
+    /// Returns the local ids of all tiles whose wang id matches `pattern`, where `None`
+    /// positions match any color. See [`WangId`] for the order of the positions.
+    pub fn tiles_matching(&self, pattern: &[Option<u8>; 8]) -> Vec<TileId> {
+        self.wang_tiles
+            .iter()
+            .filter(|t| t.wang_id.matches(pattern))
+            .map(|t| t.tile_id)
+            .collect()
+    }
+
+    /// Returns the color index (as used in wang ids) of the color with the given name.
+    pub fn color_index(&self, name: &str) -> Option<u8> {
+        self.colors
+            .iter()
+            .position(|c| c.name == name)
+            .map(|i| i as u8 + 1)
+    }
+
+    /// Paints terrain into a tile layer, like the editor's terrain brush.
+    ///
+    /// `colors` holds the wanted color index of each cell, row by row, with the top-left cell
+    /// placed at `origin` in the layer. Cells with color 0 are left untouched. Where cells of
+    /// different colors meet, the color with the higher index covers the shared edges and
+    /// corners, so that neighboring tiles always agree; order the colors of the set by priority.
+    ///
+    /// For each cell, one of the tiles matching best is picked, weighted by the tile's
+    /// probability and those of its colors. `random` must return numbers in `[0, 1)`.
+    pub fn fill_layer(
+        &self,
+        tileset: &MapTileset,
+        layer: &mut Layer,
+        origin: (i32, i32),
+        colors: &[Vec<u8>],
+        mut random: impl FnMut() -> f32,
+    ) {
+        let color_at = |x: i32, y: i32| -> u8 {
+            if x < 0 || y < 0 {
+                return 0;
+            }
+            colors
+                .get(y as usize)
+                .and_then(|row| row.get(x as usize))
+                .copied()
+                .unwrap_or(0)
+        };
+        let used = |position: usize| self.uses_position(position);
+        let weights = self.tile_weights(tileset);
+
+        for (y, row) in colors.iter().enumerate() {
+            for (x, &color) in row.iter().enumerate() {
+                if color == 0 {
+                    continue;
+                }
+                let (x, y) = (x as i32, y as i32);
+                let mut wanted = [0; 8];
+                for (position, neighbors) in POSITION_NEIGHBORS.iter().enumerate() {
+                    if used(position) {
+                        wanted[position] = neighbors
+                            .iter()
+                            .map(|(dx, dy)| color_at(x + dx, y + dy))
+                            .fold(color, u8::max);
+                    }
+                }
+                let mismatches =
+                    |id: &WangId| (0..8).filter(|&p| used(p) && id.0[p] != wanted[p]).count();
+                let best = match self.wang_tiles.iter().map(|t| mismatches(&t.wang_id)).min() {
+                    Some(best) => best,
+                    None => return,
+                };
+                let candidates: Vec<(TileId, f32)> = self
+                    .wang_tiles
+                    .iter()
+                    .zip(weights.iter())
+                    .filter(|(t, _)| mismatches(&t.wang_id) == best)
+                    .map(|(t, &w)| (t.tile_id, w.max(0.0)))
+                    .collect();
+                let tile_id = pick_weighted(&candidates, random());
+                layer.set_tile(
+                    origin.0 + x,
+                    origin.1 + y,
+                    LayerTile::new(tileset.gid(tile_id).0),
+                );
+            }
+        }
+    }
+
+    /// Fills `rect` of a tile layer with tiles of this set, so that the colors of all adjacent
+    /// tiles agree, both inside the region and with the tiles of the set already surrounding it.
+    /// The tiles in the region before the fill are ignored; tiles around it that aren't part of
+    /// the set don't constrain it.
+    ///
+    /// The region is solved greedily, row by row: each cell gets one of the tiles agreeing with
+    /// the most of its known neighbors, weighted like in [`WangSet::fill_layer`]. `random` must
+    /// return numbers in `[0, 1)`. Returns how many cells couldn't be matched with all of their
+    /// neighbors, which is 0 if the fill is seamless. Nothing is filled if the set has no tiles.
+    pub fn fill_region(
+        &self,
+        tileset: &MapTileset,
+        layer: &mut Layer,
+        rect: TileRect,
+        mut random: impl FnMut() -> f32,
+    ) -> usize {
+        if self.wang_tiles.is_empty() {
+            return 0;
+        }
+        let weights = self.tile_weights(tileset);
+        let width = rect.width as usize;
+        let mut filled: Vec<Option<WangId>> = vec![None; width * rect.height as usize];
+        let mut mismatched = 0;
+
+        for index in 0..filled.len() {
+            let x = rect.x + (index % width) as i32;
+            let y = rect.y + (index / width) as i32;
+            let wang_id_at = |nx: i32, ny: i32| -> Option<WangId> {
+                if rect.contains(nx, ny) {
+                    let (cx, cy) = ((nx - rect.x) as usize, (ny - rect.y) as usize);
+                    return filled[cy * width + cx];
+                }
+                let gid = Gid(layer.get_tile(nx, ny)?.gid);
+                if !tileset.contains_gid(gid) {
+                    return None;
+                }
+                self.wang_id_for_tile(tileset.tile_id(gid)?)
+            };
+            let mut constraints = Vec::new();
+            for (position, neighbors) in POSITION_NEIGHBORS.iter().enumerate() {
+                if !self.uses_position(position) {
+                    continue;
+                }
+                for &(dx, dy) in neighbors.iter() {
+                    let color = wang_id_at(x + dx, y + dy)
+                        .map_or(0, |id| id.0[shared_position(position, (dx, dy))]);
+                    if color != 0 {
+                        constraints.push((position, color));
+                    }
+                }
+            }
+            let mismatches =
+                |id: &WangId| constraints.iter().filter(|&&(p, c)| id.0[p] != c).count();
+            let best = self
+                .wang_tiles
+                .iter()
+                .map(|t| mismatches(&t.wang_id))
+                .min()
+                .unwrap_or(0);
+            if best > 0 {
+                mismatched += 1;
+            }
+            let candidates: Vec<(TileId, f32)> = self
+                .wang_tiles
+                .iter()
+                .zip(weights.iter())
+                .filter(|(t, _)| mismatches(&t.wang_id) == best)
+                .map(|(t, &w)| (t.tile_id, w.max(0.0)))
+                .collect();
+            let tile_id = pick_weighted(&candidates, random());
+            filled[index] = self.wang_id_for_tile(tile_id);
+            layer.set_tile(x, y, LayerTile::new(tileset.gid(tile_id).0));
+        }
+        mismatched
+    }
+
+    /// Returns whether the colors at the given position of wang ids are used by this set.
+    fn uses_position(&self, position: usize) -> bool {
+        match self.wang_set_type {
+            WangSetType::Corner => position & 1 == 1,
+            WangSetType::Edge => position & 1 == 0,
+            WangSetType::Mixed => true,
+        }
+    }
+
+    /// The chance of each of the wang tiles being picked: the tile's probability multiplied
+    /// with those of its colors.
+    fn tile_weights(&self, tileset: &MapTileset) -> Vec<f32> {
+        self.wang_tiles
+            .iter()
+            .map(|t| {
+                let tile_probability = tileset
+                    .tiles
+                    .iter()
+                    .find(|tile| tile.id == t.tile_id)
+                    .map_or(1.0, |tile| tile.probability);
+                (0..8)
+                    .filter(|&p| self.uses_position(p))
+                    .filter_map(|p| self.colors.get((t.wang_id.0[p] as usize).checked_sub(1)?))
+                    .fold(tile_probability, |w, c| w * c.probability)
+            })
+            .collect()
+    }
+}
+
+/// For each position of a wang id, the cells other than the tile itself that share it.
+const POSITION_NEIGHBORS: [&[(i32, i32)]; 8] = [
+    &[(0, -1)],
+    &[(1, 0), (0, -1), (1, -1)],
+    &[(1, 0)],
+    &[(1, 0), (0, 1), (1, 1)],
+    &[(0, 1)],
+    &[(-1, 0), (0, 1), (-1, 1)],
+    &[(-1, 0)],
+    &[(-1, 0), (0, -1), (-1, -1)],
+];
+
+/// The offset of each position of a wang id from the center of the tile, in half tiles.
+const POSITION_OFFSETS: [(i32, i32); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+/// Returns the position of the neighbor at `(dx, dy)` that lies at `position` of the tile. The
+/// neighbor must be one of [`POSITION_NEIGHBORS`] of the position.
+fn shared_position(position: usize, (dx, dy): (i32, i32)) -> usize {
+    let (x, y) = POSITION_OFFSETS[position];
+    let offset = (x - 2 * dx, y - 2 * dy);
+    POSITION_OFFSETS
+        .iter()
+        .position(|&o| o == offset)
+        .unwrap_or(position)
+}
+
+/// Picks one of the candidates, with chances proportional to their weights. Falls back to the
+/// first candidate if all weights are 0.
+pub(crate) fn pick_weighted(candidates: &[(TileId, f32)], random: f32) -> TileId {
+    let total: f32 = candidates.iter().map(|(_, w)| w).sum();
+    let mut target = random * total;
+    for &(id, weight) in candidates {
+        if target < weight {
+            return id;
+        }
+        target -= weight;
+    }
+    candidates
+        .iter()
+        .rev()
+        .find(|(_, w)| *w > 0.0)
+        .or_else(|| candidates.first())
+        .map_or(TileId(0), |(id, _)| *id)
+}
+
+/// A terrain type of a tileset saved before Tiled 1.5, which replaced terrains with wang sets.
+/// [`Map::upgrade_in_place`](crate::map::Map::upgrade_in_place) converts them.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Terrain {
+    pub name: String,
+    /// The local id of the tile representing this terrain, if any.
+    pub tile: Option<u32>,
+    pub properties: Properties,
+}
+
+pub(crate) fn parse_terrain_types<R: Read>(
+    parser: &mut EventReader<R>,
+) -> Result<Vec<Terrain>, TiledError> {
+    let mut terrains = Vec::new();
+    parse_tag!(parser, "terraintypes", {
+        "terrain" => |attrs: Vec<OwnedAttribute>| {
+            let (tile, name) = get_attrs!(
+                attrs,
+                optionals: [
+                    ("tile", tile, |v:String| v.parse::<i64>().ok()),
+                ],
+                required: [
+                    ("name", name, Some),
+                ],
+                TiledError::MalformedAttributes("terrains must have a name".to_string())
+            );
+            let mut properties = HashMap::new();
+            parse_tag!(parser, "terrain", {
+                "properties" => |_| {
+                    properties = parse_properties(parser)?;
+                    Ok(())
+                },
+            });
+            terrains.push(Terrain {
+                name,
+                tile: tile.filter(|t| *t >= 0).map(|t| t as u32),
+                properties,
+            });
+            Ok(())
+        },
+    });
+    Ok(terrains)
+}
+
+/// Parses the legacy `terrain` attribute of a tile: the terrain of its top-left, top-right,
+/// bottom-left and bottom-right corners, empty for corners without one.
+pub(crate) fn parse_terrain_corners(value: &str) -> Option<[Option<u32>; 4]> {
+    let mut corners = [None; 4];
+    let mut parts = value.split(',');
+    for corner in corners.iter_mut() {
+        let part = parts.next()?.trim();
+        if !part.is_empty() {
+            *corner = Some(part.parse().ok()?);
+        }
+    }
+    match parts.next() {
+        Some(_) => None,
+        None => Some(corners),
+    }
+}
+
+pub(crate) fn parse_wang_sets<R: Read>(
+    parser: &mut EventReader<R>,
+) -> Result<Vec<WangSet>, TiledError> {
+    let mut wang_sets = Vec::new();
+    parse_tag!(parser, "wangsets", {
+        "wangset" => |attrs| {
+            wang_sets.push(WangSet::new(parser, attrs)?);
+            Ok(())
+        },
+    });
+    Ok(wang_sets)
+}
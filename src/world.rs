@@ -0,0 +1,265 @@
+use std::{io::Write, path::Path};
+
+use crate::{
+    error::TiledError,
+    json::{JsonObject, JsonValue},
+    loader::{FileCache, Loader},
+    map::Map,
+};
+
+/// A Tiled world (`.world` file), which lays out several maps next to each other.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct World {
+    /// The maps explicitly placed in the world.
+    pub maps: Vec<WorldMap>,
+    /// Patterns that place every map whose file name matches at a position derived from it.
+    pub patterns: Vec<WorldPattern>,
+    /// Whether the editor should only show the maps adjacent to the current one.
+    pub only_show_adjacent_maps: bool,
+}
+
+/// A single map placed in a [`World`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WorldMap {
+    /// The path of the map file, relative to the world file.
+    pub file_name: String,
+    /// Position of the map's top-left corner in the world, in pixels.
+    pub x: i32,
+    /// Position of the map's top-left corner in the world, in pixels.
+    pub y: i32,
+    /// Width of the map in pixels.
+    pub width: u32,
+    /// Height of the map in pixels.
+    pub height: u32,
+}
+
+impl WorldMap {
+    /// Places `map`, stored at `file_name`, at the given pixel position of the world. The size is
+    /// taken from the map itself.
+    pub fn new(file_name: impl Into<String>, map: &Map, x: i32, y: i32) -> WorldMap {
+        WorldMap {
+            file_name: file_name.into(),
+            x,
+            y,
+            width: map.width.saturating_mul(map.tile_width),
+            height: map.height.saturating_mul(map.tile_height),
+        }
+    }
+
+    /// Whether the world pixel position is inside of the map.
+    pub fn contains(&self, world_x: i32, world_y: i32) -> bool {
+        let (x, y) = (i64::from(world_x), i64::from(world_y));
+        x >= i64::from(self.x)
+            && y >= i64::from(self.y)
+            && x < i64::from(self.x) + i64::from(self.width)
+            && y < i64::from(self.y) + i64::from(self.height)
+    }
+}
+
+/// The maps of a [`World`], loaded by [`World::load_all`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct LoadedWorld {
+    /// The maps in the order they are listed in the world.
+    pub maps: Vec<LoadedMap>,
+}
+
+/// A map loaded by [`World::load_all`], along with its placement in the world.
+#[derive(Debug, PartialEq, Clone)]
+pub struct LoadedMap {
+    pub world_map: WorldMap,
+    pub map: Map,
+}
+
+impl LoadedMap {
+    /// Position of the map's top-left corner in the world, in pixels.
+    pub fn offset(&self) -> (i32, i32) {
+        (self.world_map.x, self.world_map.y)
+    }
+}
+
+impl LoadedWorld {
+    /// The map containing the world pixel position, if any. When maps overlap, the first one
+    /// listed wins.
+    pub fn map_at(&self, world_x: i32, world_y: i32) -> Option<&LoadedMap> {
+        self.map_index_at(world_x, world_y).map(|i| &self.maps[i])
+    }
+
+    /// Like [`LoadedWorld::map_at`], returning the index of the map in [`LoadedWorld::maps`].
+    pub fn map_index_at(&self, world_x: i32, world_y: i32) -> Option<usize> {
+        self.maps
+            .iter()
+            .position(|m| m.world_map.contains(world_x, world_y))
+    }
+
+    /// Converts a world pixel position into the map containing it and the position relative to
+    /// that map's top-left corner.
+    pub fn world_to_local(&self, world_x: f32, world_y: f32) -> Option<MapPosition> {
+        let map_index = self.map_index_at(world_x.floor() as i32, world_y.floor() as i32)?;
+        let (offset_x, offset_y) = self.maps[map_index].offset();
+        Some(MapPosition {
+            map_index,
+            x: world_x - offset_x as f32,
+            y: world_y - offset_y as f32,
+        })
+    }
+
+    /// Converts a pixel position relative to the top-left corner of the map at `map_index` into
+    /// a world pixel position. The position may lie outside of the map. Returns `None` if there
+    /// is no such map.
+    pub fn local_to_world(&self, map_index: usize, x: f32, y: f32) -> Option<(f32, f32)> {
+        let (offset_x, offset_y) = self.maps.get(map_index)?.offset();
+        Some((x + offset_x as f32, y + offset_y as f32))
+    }
+
+    /// Converts a world pixel position into the map containing it and the coordinates of the tile
+    /// under it, using the orientation and tile size of that map. See [`Map::tile_at_pixel`].
+    pub fn world_to_tile(&self, world_x: f32, world_y: f32) -> Option<(usize, i32, i32)> {
+        let local = self.world_to_local(world_x, world_y)?;
+        let (x, y) = self.maps[local.map_index]
+            .map
+            .tile_at_pixel(local.x, local.y)?;
+        Some((local.map_index, x, y))
+    }
+
+    /// Returns the world pixel position of the top-left corner of a tile of the map at
+    /// `map_index`. See [`Map::tile_to_pixel`].
+    pub fn tile_to_world(&self, map_index: usize, tile_x: i32, tile_y: i32) -> Option<(f32, f32)> {
+        let (x, y) = self
+            .maps
+            .get(map_index)?
+            .map
+            .tile_to_pixel(tile_x, tile_y)?;
+        self.local_to_world(map_index, x, y)
+    }
+
+    /// Converts a pixel position relative to the map at `from` into one relative to the map at
+    /// `to`, such as when an entity walks from one map into the next.
+    pub fn translate(&self, from: usize, x: f32, y: f32, to: usize) -> Option<(f32, f32)> {
+        let (world_x, world_y) = self.local_to_world(from, x, y)?;
+        let (offset_x, offset_y) = self.maps.get(to)?.offset();
+        Some((world_x - offset_x as f32, world_y - offset_y as f32))
+    }
+}
+
+/// A pixel position within one of the maps of a [`LoadedWorld`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct MapPosition {
+    /// Index of the map in [`LoadedWorld::maps`].
+    pub map_index: usize,
+    /// Position relative to the map's top-left corner, in pixels.
+    pub x: f32,
+    /// Position relative to the map's top-left corner, in pixels.
+    pub y: f32,
+}
+
+/// A rule placing every map whose file name matches `regexp`.
+///
+/// The first two capture groups of the regular expression are the x and y of the map, which are
+/// multiplied by the multipliers and then offset to get the pixel position.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct WorldPattern {
+    pub regexp: String,
+    pub multiplier_x: i32,
+    pub multiplier_y: i32,
+    pub offset_x: i32,
+    pub offset_y: i32,
+    pub map_width: Option<u32>,
+    pub map_height: Option<u32>,
+}
+
+impl World {
+    pub fn new() -> World {
+        World::default()
+    }
+
+    /// Places `map`, stored at `file_name` relative to the world file, at the given pixel
+    /// position.
+    pub fn add_map(&mut self, file_name: impl Into<String>, map: &Map, x: i32, y: i32) {
+        self.maps.push(WorldMap::new(file_name, map, x, y));
+    }
+
+    /// The map containing the world pixel position, if any. When maps overlap, the first one
+    /// listed wins. Only the explicitly placed maps are considered, not those of patterns.
+    pub fn map_at(&self, world_x: i32, world_y: i32) -> Option<&WorldMap> {
+        self.maps.iter().find(|m| m.contains(world_x, world_y))
+    }
+
+    /// Parses every explicitly placed map of the world stored at `world_path`, using `loader`.
+    ///
+    /// Map files are found with the loader's
+    /// [`PathResolver`](crate::loader::PathResolver), relative to the world file by default. The
+    /// maps share a [`FileCache`], so tilesets and templates used by several maps are read once,
+    /// and the maps hold the same parsed [`Tileset`](crate::tileset::Tileset). Maps placed by
+    /// [`World::patterns`] aren't loaded.
+    pub fn load_all(&self, loader: &Loader, world_path: &Path) -> Result<LoadedWorld, TiledError> {
+        self.load_all_cached(loader, world_path, &FileCache::new())
+    }
+
+    /// Like [`World::load_all`], reusing the files already in `cache` and adding the new ones.
+    pub fn load_all_cached(
+        &self,
+        loader: &Loader,
+        world_path: &Path,
+        cache: &FileCache,
+    ) -> Result<LoadedWorld, TiledError> {
+        let maps = self
+            .maps
+            .iter()
+            .map(|world_map| {
+                let path = loader
+                    .path_resolver
+                    .resolve(world_path, &world_map.file_name);
+                Ok(LoadedMap {
+                    world_map: world_map.clone(),
+                    map: loader.parse_file_cached(&path, cache)?,
+                })
+            })
+            .collect::<Result<_, TiledError>>()?;
+        Ok(LoadedWorld { maps })
+    }
+
+    /// Writes the world in the `.world` format the Tiled editor opens.
+    pub fn write<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        let maps: Vec<JsonValue> = self
+            .maps
+            .iter()
+            .map(|m| {
+                JsonObject::new()
+                    .field("fileName", m.file_name.as_str())
+                    .field("height", m.height)
+                    .field("width", m.width)
+                    .field("x", m.x)
+                    .field("y", m.y)
+                    .into()
+            })
+            .collect();
+        let patterns: Vec<JsonValue> = self
+            .patterns
+            .iter()
+            .map(|p| {
+                let obj = JsonObject::new();
+                let obj = match p.map_height {
+                    Some(h) => obj.field("mapHeight", h),
+                    None => obj,
+                };
+                let obj = match p.map_width {
+                    Some(w) => obj.field("mapWidth", w),
+                    None => obj,
+                };
+                obj.field("multiplierX", p.multiplier_x)
+                    .field("multiplierY", p.multiplier_y)
+                    .field("offsetX", p.offset_x)
+                    .field("offsetY", p.offset_y)
+                    .field("regexp", p.regexp.as_str())
+                    .into()
+            })
+            .collect();
+        let world: JsonValue = JsonObject::new()
+            .field("maps", maps)
+            .field("onlyShowAdjacentMaps", self.only_show_adjacent_maps)
+            .field_if(!patterns.is_empty(), "patterns", patterns)
+            .field("type", "world")
+            .into();
+        world.write_to(writer)
+    }
+}
@@ -0,0 +1,195 @@
+use std::io::Write;
+
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent};
+
+use crate::{
+    error::TiledError,
+    objects::{Object, ObjectShape},
+    properties::{Properties, PropertyValue},
+    text::{Text, TextStyle},
+};
+
+pub(crate) type Attrs<'a> = Vec<(&'a str, String)>;
+
+pub(crate) fn new_writer<W: Write>(writer: W) -> EventWriter<W> {
+    EmitterConfig::new()
+        .perform_indent(true)
+        .indent_string(" ")
+        .create_writer(writer)
+}
+
+pub(crate) fn write_declaration<W: Write>(w: &mut EventWriter<W>) -> Result<(), TiledError> {
+    w.write(XmlEvent::StartDocument {
+        version: xml::common::XmlVersion::Version10,
+        encoding: Some("UTF-8"),
+        standalone: None,
+    })
+    .map_err(TiledError::XmlEncodingError)
+}
+
+pub(crate) fn start<W: Write>(
+    w: &mut EventWriter<W>,
+    name: &str,
+    attrs: &[(&str, String)],
+) -> Result<(), TiledError> {
+    let mut element = XmlEvent::start_element(name);
+    for (k, v) in attrs {
+        element = element.attr(*k, v.as_str());
+    }
+    w.write(element).map_err(TiledError::XmlEncodingError)
+}
+
+pub(crate) fn end<W: Write>(w: &mut EventWriter<W>) -> Result<(), TiledError> {
+    w.write(XmlEvent::end_element())
+        .map_err(TiledError::XmlEncodingError)
+}
+
+/// Writes an element without children.
+pub(crate) fn empty<W: Write>(
+    w: &mut EventWriter<W>,
+    name: &str,
+    attrs: &[(&str, String)],
+) -> Result<(), TiledError> {
+    start(w, name, attrs)?;
+    end(w)
+}
+
+pub(crate) fn write_properties<W: Write>(
+    w: &mut EventWriter<W>,
+    properties: &Properties,
+) -> Result<(), TiledError> {
+    if properties.is_empty() {
+        return Ok(());
+    }
+    let mut names: Vec<&String> = properties.keys().collect();
+    names.sort();
+    start(w, "properties", &[])?;
+    for name in names {
+        let (ty, value) = match &properties[name] {
+            PropertyValue::BoolValue(v) => ("bool", v.to_string()),
+            PropertyValue::FloatValue(v) => ("float", v.to_string()),
+            PropertyValue::IntValue(v) => ("int", v.to_string()),
+            PropertyValue::ColorValue(v) => ("color", format!("#{:08x}", v)),
+            PropertyValue::StringValue(v) => ("string", v.clone()),
+            PropertyValue::FileValue(v) => ("file", v.clone()),
+            PropertyValue::ObjectValue(v) => ("object", v.to_string()),
+            PropertyValue::Unknown {
+                type_name,
+                raw_value,
+            } => (type_name.as_str(), raw_value.clone()),
+        };
+        let mut attrs: Attrs = vec![("name", name.clone())];
+        if ty != "string" {
+            attrs.push(("type", ty.to_owned()));
+        }
+        attrs.push(("value", value));
+        empty(w, "property", &attrs)?;
+    }
+    end(w)
+}
+
+fn points_to_string(points: &[(f32, f32)]) -> String {
+    points
+        .iter()
+        .map(|(x, y)| format!("{},{}", x, y))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Writes an `<object>` element. When `with_position` is false, the id and position are left out
+/// (as in template files, where they belong to the instances).
+pub(crate) fn write_object<W: Write>(
+    w: &mut EventWriter<W>,
+    object: &Object,
+    with_position: bool,
+) -> Result<(), TiledError> {
+    let mut attrs: Attrs = Vec::new();
+    if with_position && object.id != 0 {
+        attrs.push(("id", object.id.to_string()));
+    }
+    if !object.name.is_empty() {
+        attrs.push(("name", object.name.clone()));
+    }
+    if !object.obj_type.is_empty() {
+        attrs.push(("type", object.obj_type.clone()));
+    }
+    if object.gid != 0 {
+        attrs.push(("gid", object.gid.to_string()));
+    }
+    if with_position {
+        attrs.push(("x", object.x.to_string()));
+        attrs.push(("y", object.y.to_string()));
+    }
+    if object.width != 0.0 {
+        attrs.push(("width", object.width.to_string()));
+    }
+    if object.height != 0.0 {
+        attrs.push(("height", object.height.to_string()));
+    }
+    if object.rotation != 0.0 {
+        attrs.push(("rotation", object.rotation.to_string()));
+    }
+    if !object.visible {
+        attrs.push(("visible", "0".to_owned()));
+    }
+    start(w, "object", &attrs)?;
+    write_properties(w, &object.properties)?;
+    match &object.shape {
+        ObjectShape::Rect { .. } => {}
+        ObjectShape::Ellipse { .. } => empty(w, "ellipse", &[])?,
+        ObjectShape::Point(..) => empty(w, "point", &[])?,
+        ObjectShape::Polygon { points } => {
+            empty(w, "polygon", &[("points", points_to_string(points))])?
+        }
+        ObjectShape::Polyline { points } => {
+            empty(w, "polyline", &[("points", points_to_string(points))])?
+        }
+        ObjectShape::Text { text, .. } => write_text(w, text)?,
+    }
+    end(w)
+}
+
+/// Writes a `<text>` element, leaving out the style attributes that have their default value.
+fn write_text<W: Write>(w: &mut EventWriter<W>, text: &Text) -> Result<(), TiledError> {
+    let style = &text.style;
+    let defaults = TextStyle::default();
+    let flag = |v: bool| if v { "1" } else { "0" }.to_owned();
+    let mut attrs: Attrs = Vec::new();
+    if style.font_family != defaults.font_family {
+        attrs.push(("fontfamily", style.font_family.clone()));
+    }
+    if style.pixel_size != defaults.pixel_size {
+        attrs.push(("pixelsize", style.pixel_size.to_string()));
+    }
+    if style.wrap != defaults.wrap {
+        attrs.push(("wrap", flag(style.wrap)));
+    }
+    if style.color != defaults.color {
+        attrs.push(("color", style.color.to_string()));
+    }
+    if style.bold != defaults.bold {
+        attrs.push(("bold", flag(style.bold)));
+    }
+    if style.italic != defaults.italic {
+        attrs.push(("italic", flag(style.italic)));
+    }
+    if style.underline != defaults.underline {
+        attrs.push(("underline", flag(style.underline)));
+    }
+    if style.strikeout != defaults.strikeout {
+        attrs.push(("strikeout", flag(style.strikeout)));
+    }
+    if style.kerning != defaults.kerning {
+        attrs.push(("kerning", flag(style.kerning)));
+    }
+    if style.halign != defaults.halign {
+        attrs.push(("halign", style.halign.to_string()));
+    }
+    if style.valign != defaults.valign {
+        attrs.push(("valign", style.valign.to_string()));
+    }
+    start(w, "text", &attrs)?;
+    w.write(XmlEvent::characters(&text.contents))
+        .map_err(TiledError::XmlEncodingError)?;
+    end(w)
+}
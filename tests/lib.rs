@@ -1,8 +1,43 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::path::Path;
+use std::sync::Arc;
 use tiled::{
-    error::TiledError, layers::LayerData, map::Map, parse, parse_file, parse_tileset,
-    properties::PropertyValue,
+    animation::{frame_at, normalize_duration, total_duration, Frame},
+    automapping::AutomappingRules,
+    compat::{DowngradeWarning, TiledVersion},
+    descriptor::{PlainValue, ShapeDescriptor},
+    error::TiledError,
+    hex::{Axial, HexLayout},
+    ids::{Gid, TileId},
+    journal::Change,
+    layers::{
+        BoolGrid, Compression, Encoding, Layer, LayerData, LayerTile, TileGrid, TileRect, TileStamp,
+    },
+    loader::{
+        find_case_insensitive, AbsoluteSources, CancellationToken, FileCache, FileRequest,
+        LoadWarning, Loader, MemoryReader, PathResolver, ResourceKind,
+    },
+    map::{Map, Orientation, ResolvedTile, StaggerAxis, StaggerIndex},
+    navigation::CostGrid,
+    objects::{DrawOrder, MapObject, ObjectField, ObjectShape},
+    parse, parse_file,
+    project::{EnumStorage, Project},
+    properties::{
+        Colour, FloatProperty, FromProperties, Properties, PropertiesExt, PropertyError,
+        PropertyErrorKind, PropertyOwner, PropertyValue,
+    },
+    spawn::SpawnFromObject,
+    stitching::InstanceIds,
+    text::{HorizontalAlignment, TextStyle, VerticalAlignment},
+    tileset::{MapTileset, Tileset},
+    transform::{AnyLayer, LayerMeta, LayerRef, MapItem, Tint},
+    validation::{
+        GidLocation, SchemaLocation, SchemaViolation, SchemaViolationKind, TilesetMismatch,
+        ValidationWarning,
+    },
+    wangset::{WangId, WangSetType},
+    world::{MapPosition, World},
 };
 
 fn read_from_file(p: &Path) -> Result<Map, TiledError> {
@@ -14,13 +49,23 @@ fn read_from_file_with_path(p: &Path) -> Result<Map, TiledError> {
     return parse_file(p);
 }
 
+/// Forgets how the layers were stored, so that maps differing only by their encoding compare equal.
+fn without_data_format(mut map: Map) -> Map {
+    for layer in map.layers.iter_mut() {
+        layer.encoding = None;
+        layer.compression = None;
+    }
+    map
+}
+
 #[test]
 fn test_gzip_and_zlib_encoded_and_raw_are_the_same() {
-    let z = read_from_file(&Path::new("assets/tiled_base64_zlib.tmx")).unwrap();
-    let g = read_from_file(&Path::new("assets/tiled_base64_gzip.tmx")).unwrap();
-    let r = read_from_file(&Path::new("assets/tiled_base64.tmx")).unwrap();
-    let zstd = read_from_file(&Path::new("assets/tiled_base64_zstandard.tmx")).unwrap();
-    let c = read_from_file(&Path::new("assets/tiled_csv.tmx")).unwrap();
+    let z = without_data_format(read_from_file(Path::new("assets/tiled_base64_zlib.tmx")).unwrap());
+    let g = without_data_format(read_from_file(Path::new("assets/tiled_base64_gzip.tmx")).unwrap());
+    let r = without_data_format(read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap());
+    let zstd =
+        without_data_format(read_from_file(Path::new("assets/tiled_base64_zstandard.tmx")).unwrap());
+    let c = without_data_format(read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap());
     assert_eq!(z, g);
     assert_eq!(z, r);
     assert_eq!(z, c);
@@ -50,8 +95,1105 @@ fn test_external_tileset() {
 #[test]
 fn test_just_tileset() {
     let r = read_from_file(&Path::new("assets/tiled_base64.tmx")).unwrap();
-    let t = parse_tileset(File::open(Path::new("assets/tilesheet.tsx")).unwrap(), 1).unwrap();
-    assert_eq!(r.tilesets[0], t);
+    let t = Tileset::parse(File::open(Path::new("assets/tilesheet.tsx")).unwrap()).unwrap();
+    assert_eq!(*r.tilesets[0].tileset, t);
+
+    #[allow(deprecated)]
+    let old = tiled::parse_tileset(File::open(Path::new("assets/tilesheet.tsx")).unwrap(), 1).unwrap();
+    assert_eq!(old, t);
+}
+
+#[test]
+fn test_shared_tileset() {
+    let mut r = parse_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let e = parse_file(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    // The same tileset, whatever the gids the maps give it.
+    assert_eq!(r.tilesets[0].tileset, e.tilesets[0].tileset);
+
+    r.tilesets
+        .push(MapTileset::new(101, e.tilesets[0].tileset.clone()));
+    assert!(Arc::ptr_eq(&r.tilesets[1].tileset, &e.tilesets[0].tileset));
+    assert_eq!(r.tilesets[1].name, "tilesheet");
+
+    let tileset = r.get_tileset_by_gid(105).unwrap();
+    assert_eq!(tileset.first_gid, 101);
+    assert_eq!(tileset.tile_id(Gid(105)), Some(TileId(4)));
+    assert_eq!(tileset.gid(TileId(4)), Gid(105));
+    assert!(tileset.contains_gid(Gid(101)));
+    assert!(!tileset.contains_gid(Gid(100)));
+    assert_eq!(r.get_tileset_by_gid(5).unwrap().first_gid, 1);
+}
+
+#[test]
+fn test_gid_lookup() {
+    let mut r = parse_file(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    assert_eq!(r.get_tileset_by_gid(0), None);
+    assert_eq!(r.get_tileset_by_gid(84).unwrap().first_gid, 1);
+    assert_eq!(r.get_tileset_by_gid(85).unwrap().first_gid, 85);
+    assert_eq!(r.get_tileset_by_gid(1000).unwrap().first_gid, 85);
+
+    for layer in r.layers.iter() {
+        let resolved = r.resolve_layer(layer);
+        let mut expected = Vec::new();
+        for (_, chunk) in layer.chunks() {
+            for ((x, y), tile) in chunk.tiles() {
+                if tile.gid != 0 {
+                    expected.push(((x, y), tile.gid));
+                }
+            }
+        }
+        assert!(!resolved.is_empty());
+        let positions: Vec<_> = resolved.iter().map(|t| ((t.x, t.y), t.tile.gid)).collect();
+        assert_eq!(positions, expected);
+        for t in resolved.iter() {
+            assert_eq!(Some(t.tileset), r.get_tileset_by_gid(t.tile.gid));
+            assert_eq!(t.tileset, &r.tilesets[t.tileset_index]);
+            assert_eq!(t.id.gid(t.tileset), Gid(t.tile.gid));
+        }
+    }
+
+    let tileset = r.tilesets[0].tileset.clone();
+    r.add_tileset(MapTileset::new(200, tileset.clone()));
+    assert_eq!(r.get_tileset_by_gid(250).unwrap().first_gid, 200);
+    // Changing the tilesets directly still finds the right ones.
+    r.tilesets.reverse();
+    assert_eq!(r.get_tileset_by_gid(250).unwrap().first_gid, 200);
+    assert_eq!(r.get_tileset_by_gid(100).unwrap().first_gid, 85);
+    r.tilesets.push(MapTileset::new(300, tileset));
+    assert_eq!(r.get_tileset_by_gid(301).unwrap().first_gid, 300);
+    r.update_gid_table();
+    assert_eq!(r.get_tileset_by_gid(2).unwrap().first_gid, 1);
+}
+
+#[test]
+fn test_tile_ids() {
+    let r = parse_file(Path::new("assets/tiled_animated.tmx")).unwrap();
+    let tileset = r.get_tileset_by_gid(r.tilesets[1].first_gid + 2).unwrap();
+    assert_eq!(tileset.first_gid, r.tilesets[1].first_gid);
+    let gid = TileId(2).gid(tileset);
+    assert_eq!(gid.0, tileset.first_gid + 2);
+    assert_eq!(gid.tile_id(tileset), Some(TileId(2)));
+    assert_eq!(Gid(0).tile_id(tileset), None);
+    assert_eq!(Gid::EMPTY, Gid::default());
+    assert_eq!(u32::from(TileId::from(7)), 7);
+    assert_eq!(Gid(12).to_string(), "12");
+
+    // Tile rectangles are found by gid on the map, and by local id on the tileset.
+    assert_eq!(r.tile_rectangle(gid), tileset.tile_rectangle(TileId(2)));
+    assert_eq!(tileset.tile_rectangle(TileId(2)), Some((64, 0, 32, 32)));
+    #[allow(deprecated)]
+    let old = r.get_tile_rectangle_by_id(gid.0);
+    assert_eq!(old, r.tile_rectangle(gid));
+    assert_eq!(r.tile_rectangle(Gid(0)), None);
+    // The margin leaves room for 13 columns only.
+    let mut margin = (*r.tilesets[1].tileset).clone();
+    margin.margin = 4;
+    assert_eq!(margin.tile_rectangle(TileId(12)), Some((388, 4, 32, 32)));
+    assert_eq!(margin.tile_rectangle(TileId(13)), Some((4, 36, 32, 32)));
+
+    let water = &r.tilesets[1];
+    assert!(water.get_tile(TileId(3)).unwrap().animation.is_some());
+    assert_eq!(water.get_tile(TileId(4)), None);
+}
+
+#[test]
+fn test_layer_tile_tilesets() {
+    let mut r = parse_file(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let tiles = |r: &Map| -> Vec<LayerTile> {
+        r.layers
+            .iter()
+            .flat_map(|l| l.chunks().flat_map(|(_, c)| c.tiles().map(|(_, t)| t)))
+            .filter(|t| t.gid != 0)
+            .collect()
+    };
+    let parsed = tiles(&r);
+    assert!(parsed.iter().any(|t| t.tileset_index() == Some(1)));
+    for tile in parsed.iter() {
+        let index = tile.tileset_index().unwrap();
+        assert_eq!(r.tileset_of(tile), r.get_tileset_by_gid(tile.gid));
+        assert_eq!(r.tileset_of(tile), Some(&r.tilesets[index]));
+        assert_eq!(tile.tile_id(), r.tilesets[index].tile_id(Gid(tile.gid)));
+    }
+
+    // Tiles created by hand are compared by gid and looked up by gid.
+    let tile = LayerTile::new(parsed[0].raw_gid());
+    assert_eq!(tile, parsed[0]);
+    assert_eq!(tile.tileset_index(), None);
+    assert_eq!(r.tileset_of(&tile), r.tileset_of(&parsed[0]));
+    assert_eq!(LayerTile::new(0).tileset_index(), None);
+
+    r.tilesets.reverse();
+    r.update_gid_table();
+    for tile in tiles(&r).iter() {
+        let index = tile.tileset_index().unwrap();
+        assert_eq!(Some(&r.tilesets[index]), r.get_tileset_by_gid(tile.gid));
+    }
+}
+
+#[test]
+fn test_bool_grid() {
+    let r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let layer = &r.layers[0];
+    let grid = layer.to_bool_grid(&r, |tile| tile.id == TileId(34));
+    assert_eq!((grid.bounds.width, grid.bounds.height), (100, 100));
+    let mut expected = 0;
+    for y in 0..100 {
+        for x in 0..100 {
+            let solid = layer.get_tile(x, y).unwrap().gid == 35;
+            assert_eq!(grid.get(x, y), solid);
+            expected += solid as usize;
+        }
+    }
+    assert!(expected > 0);
+    assert_eq!(grid.count(), expected);
+    assert_eq!(grid.to_vec().iter().filter(|&&b| b).count(), expected);
+    assert_eq!(grid.words().len(), 157);
+    assert!(!grid.get(-1, 0));
+
+    let r = parse_file(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let layer = &r.layers[0];
+    let grid = layer.to_bool_grid(&r, |_| true);
+    let bounds = layer.used_bounds().unwrap();
+    assert_eq!(grid.bounds, bounds);
+    assert_eq!(grid.count(), r.resolve_layer(layer).len());
+
+    let mut grid = BoolGrid::new(TileRect {
+        x: -2,
+        y: -2,
+        width: 9,
+        height: 9,
+    });
+    grid.set(-2, -2, true);
+    grid.set(6, 6, true);
+    grid.set(7, 7, true);
+    assert_eq!(grid.words(), [1, 1 << 16]);
+    grid.set(-2, -2, false);
+    assert_eq!(grid.count(), 1);
+}
+
+#[test]
+fn test_cost_grid() {
+    let r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let layer = &r.layers[0];
+    let cost = |tile: &ResolvedTile| match tile.tile.gid {
+        35 => Some(f32::INFINITY),
+        17 => Some(2.0),
+        _ => None,
+    };
+    let grid = r.cost_grid(&[layer], 1.0, cost);
+    assert_eq!((grid.bounds.width, grid.bounds.height), (100, 100));
+    for y in 0..100 {
+        for x in 0..100 {
+            let expected = match layer.get_tile(x, y).unwrap().gid {
+                35 => f32::INFINITY,
+                17 => 2.0,
+                _ => 1.0,
+            };
+            assert_eq!(grid.get(x, y), expected);
+        }
+    }
+    assert!(!grid.is_walkable(-1, 0));
+    // Tiles of several layers: the highest cost wins.
+    let cheap = r.cost_grid(&[layer], 1.0, |tile| Some(tile.tile.gid as f32 / 100.0));
+    assert_eq!(cheap.get(0, 1), 0.17);
+    let both = r.cost_grid(&[layer, layer], 1.0, cost);
+    assert_eq!(both, grid);
+
+    let mut grid = CostGrid::new(
+        TileRect {
+            x: 0,
+            y: 0,
+            width: 3,
+            height: 3,
+        },
+        1.0,
+    );
+    grid.set(1, 0, f32::INFINITY);
+    grid.set(0, 1, 3.0);
+    let straight: Vec<_> = grid.neighbours(0, 0, false).collect();
+    assert_eq!(straight, [((0, 1), 3.0)]);
+    let diagonal: Vec<_> = grid.neighbours(1, 1, true).map(|(pos, _)| pos).collect();
+    assert_eq!(diagonal, [(2, 1), (1, 2), (0, 1), (2, 2), (0, 2)]);
+}
+
+#[test]
+fn test_automapping() {
+    let rule_map = parse_file(Path::new("assets/tiled_automapping_rules.tmx")).unwrap();
+    let rules = AutomappingRules::from_map(&rule_map).unwrap();
+    assert_eq!(rules.len(), 2);
+    assert!(!rules.delete_tiles && !rules.match_outside_map);
+
+    let mut map = parse_file(Path::new("assets/tiled_automapping_target.tmx")).unwrap();
+    // Decorate the right of tile 10 when it isn't empty, then replace tile 11 by tile 12 where
+    // it isn't decorated.
+    assert_eq!(rules.apply(&mut map).unwrap(), 5);
+    let gids = |layer: &Layer| -> Vec<u32> {
+        (0..3)
+            .flat_map(|y| (0..6).map(move |x| (x, y)))
+            .map(|(x, y)| layer.get_tile(x, y).unwrap().gid)
+            .collect()
+    };
+    assert_eq!(
+        gids(&map.layers[0]),
+        [11, 12, 0, 11, 12, 0, 11, 0, 13, 13, 11, 12, 0, 0, 0, 0, 0, 0]
+    );
+    assert_eq!(
+        gids(&map.layers[1]),
+        [0, 21, 0, 0, 21, 0, 0, 0, 0, 0, 0, 21, 0, 0, 0, 0, 0, 0]
+    );
+    let placed = map.layers[1].get_tile(1, 0).unwrap();
+    assert_eq!(placed.tile_id(), Some(TileId(20)));
+    // Nothing left to match for the second rule.
+    assert_eq!(rules.apply(&mut map).unwrap(), 3);
+
+    // Output layers must exist.
+    let mut map = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let unchanged = map.clone();
+    assert!(rules.apply(&mut map).is_err());
+    assert_eq!(map, unchanged);
+    assert!(AutomappingRules::from_map(&unchanged).is_err());
+}
+
+#[test]
+fn test_tile_usage() {
+    let r = parse_file(Path::new("assets/tiled_automapping_target.tmx")).unwrap();
+    let usage = r.tile_usage();
+    assert_eq!(usage.len(), 1);
+    assert_eq!(usage[0].tileset.name, "tilesheet");
+    assert_eq!(usage[0].counts.len(), 2);
+    assert_eq!(usage[0].counts[&TileId(10)], 4);
+    assert_eq!(usage[0].counts[&TileId(11)], 5);
+    assert!(usage[0].is_used(TileId(11)));
+    let unused = usage[0].unused_tiles();
+    assert_eq!(unused.len() as u32, r.tilesets[0].tile_count() - 2);
+    assert!(!unused.contains(&TileId(10)));
+    assert_eq!(unused[0], TileId(0));
+}
+
+#[test]
+fn test_prune_tilesets() {
+    let mut r = parse_file(Path::new("assets/tiled_pruning.tmx")).unwrap();
+    let pruned = r.prune_tilesets();
+    assert_eq!(pruned.len(), 2);
+    assert!(pruned[0].renumbered);
+    assert_eq!(pruned[0].kept, vec![TileId(1), TileId(3), TileId(5)]);
+    assert_eq!(pruned[0].new_id(TileId(5)), Some(TileId(2)));
+    assert_eq!(pruned[0].new_id(TileId(2)), None);
+    assert!(!pruned[1].renumbered);
+    assert_eq!((pruned[1].old_first_gid, pruned[1].first_gid), (7, 4));
+    assert_eq!(pruned[1].new_id(TileId(10)), Some(TileId(10)));
+
+    let props = &r.tilesets[0];
+    assert_eq!(props.tile_count(), 3);
+    let ids: Vec<u32> = props.tiles.iter().map(|t| t.id.0).collect();
+    assert_eq!(ids, vec![0, 1, 2]);
+    let frames = props.tiles[1].animation.as_ref().unwrap();
+    assert_eq!(frames[1].tile_id, TileId(2));
+    assert_eq!(r.tilesets[1].first_gid, 4);
+
+    let layer = &r.layers[0];
+    assert_eq!(layer.get_tile(0, 0), Some(LayerTile::new(1)));
+    assert_eq!(layer.get_tile(1, 0), Some(LayerTile::new(2)));
+    assert_eq!(layer.get_tile(0, 1), Some(LayerTile::new(14 | 0x80000000)));
+    assert_eq!(layer.get_tile(1, 1).unwrap().tileset_index(), Some(1));
+    assert_eq!(r.object_groups[0].objects[0].gid, 2);
+
+    let pruned = r.prune_tilesets();
+    assert_eq!(pruned[0].first_gid, 1);
+    assert_eq!(pruned[1].first_gid, 4);
+}
+
+#[test]
+fn test_append_maps() {
+    let mut r = parse_file(Path::new("assets/tiled_automapping_target.tmx")).unwrap();
+    let room = r.clone();
+    r.append(&room, (6, 0)).unwrap();
+    assert_eq!((r.width, r.height), (12, 3));
+    assert_eq!(r.tilesets.len(), 1);
+    assert_eq!(r.layers.len(), 2);
+    assert_eq!(r.layers[0].get_tile(6, 0), Some(LayerTile::new(11)));
+    assert_eq!(r.layers[0].get_tile(11, 1), Some(LayerTile::new(12)));
+
+    let props = parse_file(Path::new("assets/tiled_pruning.tmx")).unwrap();
+    r.append(&props, (0, 3)).unwrap();
+    assert_eq!((r.width, r.height), (12, 5));
+    assert_eq!(r.tilesets.len(), 2);
+    assert_eq!(r.tilesets[1].first_gid, 85);
+    assert_eq!(r.layers.len(), 3);
+    let layer = &r.layers[2];
+    assert_eq!((layer.name.as_str(), layer.id, layer.layer_index), ("Tiles", 3, 2));
+    assert_eq!(layer.get_tile(0, 3), Some(LayerTile::new(86)));
+    assert_eq!(layer.get_tile(0, 4), Some(LayerTile::new(11 | 0x80000000)));
+    assert_eq!(layer.get_tile(0, 4).unwrap().tileset_index(), Some(0));
+    assert_eq!(r.layers[0].get_tile(11, 4), Some(LayerTile::new(0)));
+    let object = &r.object_groups[0].objects[0];
+    assert_eq!((object.id, object.gid), (1, 88));
+    assert_eq!((object.x, object.y), (64.0, 128.0));
+
+    assert!(r.append(&props, (-1, 0)).is_err());
+    let hexagonal = read_from_file(Path::new("assets/tiled_hexagonal.tmx")).unwrap();
+    assert!(r.append(&hexagonal, (0, 0)).is_err());
+}
+
+#[test]
+fn test_move_layer() {
+    let mut r = read_from_file(Path::new("assets/tiled_groups.tmx")).unwrap();
+    let order = |r: &Map| -> Vec<(String, Option<usize>)> {
+        let mut layers: Vec<(u32, String, Option<usize>)> = r
+            .groups
+            .iter()
+            .map(|g| (g.layer_index, g.name.clone(), g.parent_group))
+            .chain(r.layers.iter().map(|l| (l.layer_index, l.name.clone(), l.parent_group)))
+            .chain(
+                r.image_layers
+                    .iter()
+                    .map(|l| (l.layer_index, l.name.clone(), l.parent_group)),
+            )
+            .chain(
+                r.object_groups
+                    .iter()
+                    .map(|g| (g.layer_index.unwrap(), g.name.clone(), g.parent_group)),
+            )
+            .collect();
+        layers.sort_by_key(|l| l.0);
+        for (i, layer) in layers.iter().enumerate() {
+            assert_eq!(layer.0, i as u32);
+        }
+        layers.into_iter().map(|l| (l.1, l.2)).collect()
+    };
+    let names = |r: &Map| -> Vec<String> { order(r).into_iter().map(|l| l.0).collect() };
+    assert_eq!(
+        names(&r),
+        ["World", "Ground", "Background", "Clouds", "Entities", "Top"]
+    );
+
+    assert!(r.move_layer(6, 0));
+    assert_eq!(
+        names(&r),
+        ["Top", "World", "Ground", "Background", "Clouds", "Entities"]
+    );
+    assert!(r.move_layer(2, 5));
+    assert_eq!(
+        names(&r),
+        ["Top", "World", "Background", "Clouds", "Entities", "Ground"]
+    );
+    assert!(r.move_layer_into(4, None, 1));
+    assert_eq!(order(&r)[1], ("Clouds".to_string(), None));
+    assert!(r.move_layer_into(6, Some(3), 0));
+    assert_eq!(
+        order(&r),
+        [
+            ("Clouds".to_string(), None),
+            ("World".to_string(), None),
+            ("Background".to_string(), Some(0)),
+            ("Top".to_string(), Some(1)),
+            ("Entities".to_string(), Some(0)),
+            ("Ground".to_string(), Some(0)),
+        ]
+    );
+    assert_eq!(r.layer_by_path("World/Background/Top").unwrap().id(), 6);
+
+    assert!(!r.move_layer_into(1, Some(3), 0));
+    assert!(!r.move_layer_into(2, Some(2), 0));
+    assert!(!r.move_layer(99, 0));
+}
+
+#[test]
+fn test_reassign_ids() {
+    let mut r = parse_file(Path::new("assets/tiled_pruning.tmx")).unwrap();
+    assert_eq!(r.reassign_ids(), Default::default());
+
+    r.layers.push(r.layers[0].clone());
+    r.layers[1].layer_index = 2;
+    let mut copy = r.object_groups[0].objects[0].clone();
+    r.object_groups[0].objects.push(copy.clone());
+    copy.id = 0;
+    r.object_groups[0].objects.push(copy);
+    let reassigned = r.reassign_ids();
+    assert_eq!(reassigned.layers, vec![(1, 3)]);
+    assert_eq!(reassigned.objects, vec![(1, 2), (0, 3)]);
+    assert_eq!(r.layers[1].id, 3);
+    let ids: Vec<u32> = r.object_groups[0].objects.iter().map(|o| o.id).collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+    assert_eq!(r.reassign_ids(), Default::default());
+}
+
+#[test]
+fn test_upgrade_in_place() {
+    let mut r = read_from_file(Path::new("assets/tiled_legacy.tmx")).unwrap();
+    let tileset = &r.tilesets[0];
+    assert_eq!(tileset.terrain_types.len(), 2);
+    assert_eq!(tileset.terrain_types[1].name, "Water");
+    assert_eq!(tileset.tiles[2].terrain, Some([None, Some(1), None, Some(1)]));
+    assert_eq!(tileset.tiles[1].tile_type.as_deref(), Some("shore"));
+    assert_eq!(r.layers[0].encoding, None);
+    assert_eq!(r.layers[0].get_tile(0, 1), Some(LayerTile::new(6)));
+    let objects = &r.object_groups[0].objects;
+    assert_eq!((objects[0].obj_type.as_str(), objects[1].obj_type.as_str()), ("spawn", "chest"));
+
+    assert!(r.upgrade_in_place());
+    let tileset = &r.tilesets[0];
+    assert!(tileset.terrain_types.is_empty());
+    assert!(tileset.tiles.iter().all(|t| t.terrain.is_none()));
+    let set = &tileset.wang_sets[0];
+    assert_eq!(set.wang_set_type, WangSetType::Corner);
+    let names: Vec<&str> = set.colors.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["Grass", "Water"]);
+    assert_eq!(set.colors[1].tile, Some(5));
+    assert!(set.colors[0].properties.contains_key("walkable"));
+    assert_eq!(set.wang_id_for_tile(TileId(1)), Some(WangId([0, 1, 0, 2, 0, 2, 0, 1])));
+    assert_eq!(set.wang_id_for_tile(TileId(4)), Some(WangId([0, 2, 0, 2, 0, 0, 0, 0])));
+    assert_eq!(r.layers[0].encoding, Some(Encoding::Csv));
+    assert!(!r.upgrade_in_place());
+}
+
+#[test]
+fn test_downgrade() {
+    assert_eq!("1.8.2".parse::<TiledVersion>().unwrap(), TiledVersion::new(1, 8));
+    assert!("1".parse::<TiledVersion>().is_err());
+
+    let r = read_from_file(Path::new("assets/tiled_groups.tmx")).unwrap();
+    let (downgraded, warnings) = r.downgrade(TiledVersion::new(1, 4));
+    assert_eq!(downgraded.version, "1.4");
+    assert_eq!(
+        warnings,
+        [
+            DowngradeWarning::ParallaxOrigin,
+            DowngradeWarning::Parallax { layer_id: 4 },
+            DowngradeWarning::Parallax { layer_id: 1 },
+            DowngradeWarning::Parallax { layer_id: 3 },
+        ]
+    );
+    assert_eq!(downgraded.groups[0].parallax_x, 1.0);
+    assert!(downgraded.layers[0].tint_colour.is_some());
+    let (_, warnings) = r.downgrade(TiledVersion::new(1, 3));
+    assert!(warnings.contains(&DowngradeWarning::TintColour { layer_id: 2 }));
+    assert!(r.downgrade(TiledVersion::new(1, 8)).1.is_empty());
+
+    #[cfg(feature = "json")]
+    {
+        let mut json = Vec::new();
+        let warnings = r
+            .write_json_for(&mut json, TiledVersion::new(1, 4))
+            .unwrap();
+        assert_eq!(warnings.len(), 4);
+        assert!(!String::from_utf8(json).unwrap().contains("parallaxx"));
+    }
+
+    let r = parse_file(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let bounds = r.used_bounds().unwrap();
+    let (downgraded, warnings) = r.downgrade(TiledVersion::new(1, 0));
+    assert_eq!(
+        warnings,
+        [DowngradeWarning::Infinite {
+            moved_x: -bounds.x,
+            moved_y: -bounds.y
+        }]
+    );
+    assert!(!downgraded.infinite);
+    assert_eq!((downgraded.width, downgraded.height), (bounds.width, bounds.height));
+    assert_eq!(
+        downgraded.layers[0].get_tile(0, 0),
+        r.layers[0].get_tile(bounds.x, bounds.y).or(Some(LayerTile::new(0)))
+    );
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn test_gzipped_map() {
+    let gzipped = parse_file(Path::new("assets/tiled_base64.tmx.gz")).unwrap();
+    let plain = parse_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(gzipped, plain);
+    let data = std::fs::read("assets/tiled_base64.tmx.gz").unwrap();
+    let r = Loader::new().parse_slice_with_path(&data, Path::new("assets/tiled_base64.tmx"));
+    assert_eq!(r.unwrap(), plain);
+}
+
+#[test]
+fn test_absolute_sources() {
+    let map = std::fs::read_to_string("assets/tiled_base64_external.tmx")
+        .unwrap()
+        .replace(
+            "\"tilesheet.tsx\"",
+            "\"C:/Users/author/tiles/tilesheet.tsx\"",
+        );
+    let mut files = MemoryReader::new();
+    files.insert("maps/level.tmx", map.into_bytes());
+    files.insert(
+        "maps/tilesheet.tsx",
+        &include_bytes!("../assets/tilesheet.tsx")[..],
+    );
+    let path = Path::new("maps/level.tmx");
+
+    let rejected = Loader::new().with_absolute_sources(AbsoluteSources::Reject);
+    let error = files.parse_map(&rejected, path).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("C:/Users/author/tiles/tilesheet.tsx"));
+
+    let stripped = Loader::new().with_absolute_sources(AbsoluteSources::StripToFileName);
+    let map = files.parse_map(&stripped, path).unwrap();
+    assert_eq!(map.tilesets[0].name, "tilesheet");
+    assert_eq!(
+        map.load_warnings,
+        vec![LoadWarning::AbsoluteSource {
+            kind: ResourceKind::Tileset,
+            source: "C:/Users/author/tiles/tilesheet.tsx".to_string(),
+            path: "tilesheet.tsx".to_string(),
+        }]
+    );
+
+    // Passed through, the file isn't found, but tolerant mode still records the warning.
+    let map = files
+        .parse_map(&Loader::new().with_tolerant_mode(true), path)
+        .unwrap();
+    assert!(map.tilesets[0].load_error.is_some());
+    assert_eq!(map.load_warnings.len(), 1);
+
+    let map = parse_file(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    assert!(map.load_warnings.is_empty());
+}
+
+#[test]
+fn test_asset_root() {
+    let map = std::fs::read_to_string("assets/tiled_base64_external.tmx")
+        .unwrap()
+        .replace("\"tilesheet.tsx\"", "\"../../tiles/tilesheet.tsx\"");
+    let mut files = MemoryReader::new();
+    files.insert("game/levels/deep/level.tmx", map.into_bytes());
+    files.insert(
+        "game/tiles/tilesheet.tsx",
+        &include_bytes!("../assets/tilesheet.tsx")[..],
+    );
+    let loader = Loader::new().with_path_resolver(PathResolver::AssetRoot("game".into()));
+
+    // The map can be given relative to the asset directory or including it.
+    for map_path in ["levels/deep/level.tmx", "game/levels/deep/level.tmx"].iter() {
+        let data = files.get(Path::new("game/levels/deep/level.tmx")).unwrap();
+        let mut requests = Vec::new();
+        let map = loader
+            .parse_with_resource_loader(data, Some(Path::new(map_path)), |request| {
+                requests.push((request.source.to_string(), request.asset_path.clone()));
+                files.read(request)
+            })
+            .unwrap();
+        assert_eq!(map.tilesets[0].name, "tilesheet");
+        assert_eq!(
+            requests,
+            vec![(
+                "../../tiles/tilesheet.tsx".to_string(),
+                Some("tiles/tilesheet.tsx".to_string())
+            )]
+        );
+    }
+
+    let resolver = PathResolver::AssetRoot("game".into());
+    let level = Path::new("levels/level.tmx");
+    assert_eq!(
+        resolver.resolve(level, "../../outside.png"),
+        Path::new("game/../outside.png")
+    );
+    assert_eq!(
+        resolver.asset_path(level, "./images/../sky.png").as_deref(),
+        Some("levels/sky.png")
+    );
+    assert_eq!(
+        PathResolver::ProjectRoot("game".into()).asset_path(level, "sky.png"),
+        None
+    );
+}
+
+#[test]
+fn test_case_insensitive_fallback() {
+    if Path::new("ASSETS/TILESHEET.TSX").exists() {
+        // The file system isn't case sensitive.
+        return;
+    }
+    let map = std::fs::read_to_string("assets/tiled_base64_external.tmx")
+        .unwrap()
+        .replace("\"tilesheet.tsx\"", "\"../Assets/TileSheet.tsx\"");
+    let path = Path::new("assets/level.tmx");
+    assert!(Loader::new().parse_with_path(map.as_bytes(), path).is_err());
+
+    let map = Loader::new()
+        .with_case_insensitive_fallback(true)
+        .parse_with_path(map.as_bytes(), path)
+        .unwrap();
+    assert_eq!(map.tilesets[0].name, "tilesheet");
+    assert_eq!(
+        map.load_warnings,
+        vec![LoadWarning::CaseMismatch {
+            kind: ResourceKind::Tileset,
+            source: "../Assets/TileSheet.tsx".to_string(),
+            path: Path::new("assets/../assets/tilesheet.tsx").to_path_buf(),
+        }]
+    );
+
+    assert_eq!(
+        find_case_insensitive(Path::new("Assets/TILED_CSV.tmx")),
+        Some(Path::new("assets/tiled_csv.tmx").to_path_buf())
+    );
+    assert_eq!(find_case_insensitive(Path::new("assets/missing.tmx")), None);
+    assert_eq!(find_case_insensitive(Path::new("Assets")), None);
+}
+
+#[test]
+fn test_empty_layers() {
+    let r = read_from_file(Path::new("assets/tiled_empty_layers.tmx")).unwrap();
+    let gids = |layer: &Layer| match &layer.tiles {
+        LayerData::Finite(rows) => rows
+            .iter()
+            .map(|row| row.iter().map(|t| t.gid).collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        LayerData::Infinite(_) => panic!("expected a finite layer"),
+    };
+    assert_eq!(r.layers.len(), 5);
+    assert!(gids(&r.layers[0]).is_empty());
+    assert_eq!(r.layers[0].get_tile(0, 0), None);
+    for layer in &r.layers[1..4] {
+        assert_eq!(
+            gids(layer),
+            vec![vec![0, 0, 0], vec![0, 0, 0]],
+            "{}",
+            layer.name
+        );
+        assert_eq!(layer.used_bounds(), None);
+    }
+    assert_eq!(
+        r.layers[3].properties.get("kind"),
+        Some(&PropertyValue::StringValue("placeholder".to_string()))
+    );
+    assert_eq!(gids(&r.layers[4]), vec![vec![1, 2, 3], vec![4, 0, 0]]);
+    assert_eq!(r.layers[4].get_tile(2, 1).unwrap().gid, 0);
+}
+
+#[test]
+fn test_tile_image_source() {
+    let r = parse_file(Path::new("assets/tiled_pruning.tmx")).unwrap();
+
+    let barrel = r.tile_image_source(Gid(2)).unwrap();
+    assert_eq!(barrel.image.source, "barrel.png");
+    assert_eq!(barrel.rect, (0, 0, 32, 32));
+
+    // Tile 15 of the tilesheet, in its second row.
+    let sheet = r.tile_image_source(Gid(7 + 15)).unwrap();
+    assert_eq!(sheet.path(), "tilesheet.png");
+    assert_eq!(sheet.rect, (32, 32, 32, 32));
+    assert_eq!(Some(sheet.rect), r.tile_rectangle(Gid(7 + 15)));
+    assert_eq!(r.tile_rectangle(Gid(2)), None);
+
+    assert_eq!(r.tile_image_source(Gid(6)).unwrap().path(), "torch2.png");
+    assert_eq!(r.tile_image_source(Gid(7 + 84)), None);
+    assert_eq!(r.tile_image_source(Gid(0)), None);
+}
+
+#[test]
+fn test_tile_rect_at_time() {
+    let r = parse_file(Path::new("assets/tiled_animated.tmx")).unwrap();
+    let rect_at = |elapsed_ms| r.tile_rect_at_time(Gid(85 + 3), elapsed_ms).unwrap().rect;
+    assert_eq!(rect_at(0), (96, 0, 32, 32));
+    assert_eq!(rect_at(250), (128, 0, 32, 32));
+    assert_eq!(rect_at(500), (160, 0, 32, 32));
+    assert_eq!(rect_at(850), (96, 0, 32, 32));
+    assert_eq!(
+        r.tile_rect_at_time(Gid(85), 250),
+        r.tile_image_source(Gid(85))
+    );
+
+    let r = parse_file(Path::new("assets/tiled_pruning.tmx")).unwrap();
+    let torch = r.tile_rect_at_time(Gid(4), 100).unwrap();
+    assert_eq!(torch.image.source, "torch2.png");
+    assert_eq!(r.tile_rect_at_time(Gid(0), 100), None);
+}
+
+#[test]
+fn test_instantiate() {
+    let room = read_from_file(Path::new("assets/tiled_object_property.tmx")).unwrap();
+    let mut world = room.clone();
+    let mut ids = InstanceIds::after(&world);
+    assert_eq!(
+        ids,
+        InstanceIds {
+            next_layer_id: 3,
+            next_object_id: 4
+        }
+    );
+
+    let first = room.instantiate(&mut ids, (64.0, 0.0));
+    let second = room.instantiate(&mut ids, (0.0, 64.0));
+    assert_eq!((first.layers[0].id, first.object_groups[0].id), (3, 4));
+    assert_eq!((second.layers[0].id, second.object_groups[0].id), (5, 6));
+    let objects = &second.object_groups[0].objects;
+    assert_eq!((objects[0].id, objects[1].id), (6, 7));
+    assert_eq!((objects[1].x, objects[1].y), (32.0, 96.0));
+    // References between the objects follow them, and empty references stay empty.
+    assert_eq!(
+        objects[0].properties.get("object property"),
+        Some(&PropertyValue::ObjectValue(7))
+    );
+    assert_eq!(
+        objects[1].properties.get("object property"),
+        Some(&PropertyValue::ObjectValue(0))
+    );
+    assert_eq!(first.layers[0].tiles, room.layers[0].tiles);
+
+    world.append(&first, (0, 0)).unwrap();
+    assert!(world.validate().is_empty());
+}
+
+#[test]
+fn test_drawing_primitives() {
+    let mut r = read_from_file(Path::new("assets/tiled_empty_layers.tmx")).unwrap();
+    let layer = &mut r.layers[3];
+    let gids = |layer: &Layer| -> Vec<u32> {
+        (0..2)
+            .flat_map(|y| (0..3).map(move |x| (x, y)))
+            .map(|(x, y)| layer.get_tile(x, y).unwrap().raw_gid())
+            .collect()
+    };
+    let rect = TileRect {
+        x: 1,
+        y: -1,
+        width: 5,
+        height: 2,
+    };
+    assert_eq!(layer.fill_rect(rect, LayerTile::new(1)), 2);
+    assert_eq!(layer.line((0, 1), (2, 1), LayerTile::new(2)), 3);
+    assert_eq!(gids(layer), vec![0, 1, 1, 2, 2, 2]);
+
+    assert_eq!(layer.flood_fill(1, 0, LayerTile::new(3)), 2);
+    assert_eq!(layer.flood_fill(1, 0, LayerTile::new(3)), 0);
+    assert_eq!(layer.flood_fill(5, 0, LayerTile::new(3)), 0);
+    let flipped = LayerTile::new(3 | 0x80000000);
+    assert_eq!(layer.flood_fill(0, 0, flipped), 1);
+    assert_eq!(gids(layer), vec![0x80000003, 3, 3, 2, 2, 2]);
+
+    assert_eq!(layer.replace(Gid(3), Gid(4)), 3);
+    assert_eq!(gids(layer), vec![0x80000004, 4, 4, 2, 2, 2]);
+    assert_eq!(layer.replace(Gid(4), Gid(4)), 0);
+
+    let mut r =
+        read_from_file_with_path(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let layer = &mut r.layers[0];
+    assert_eq!(layer.line((-40, -40), (40, 20), LayerTile::new(9)), 81);
+    assert_eq!(layer.get_tile(-40, -40).unwrap().gid, 9);
+    assert_eq!(layer.get_tile(0, -10).unwrap().gid, 9);
+    assert_eq!(layer.get_tile(40, 20).unwrap().gid, 9);
+}
+
+#[test]
+fn test_random_tile() {
+    let t = Tileset::parse(File::open(Path::new("assets/tiled_random.tsx")).unwrap()).unwrap();
+
+    // Flowers 0 and 1 have probabilities 3 and 1; flower 2 has 0 and is never picked.
+    assert_eq!(t.random_tile_with_class("flower", || 0.0), Some(TileId(0)));
+    assert_eq!(t.random_tile_with_class("flower", || 0.7), Some(TileId(0)));
+    assert_eq!(t.random_tile_with_class("flower", || 0.8), Some(TileId(1)));
+    assert_eq!(t.random_tile_with_class("flower", || 0.999), Some(TileId(1)));
+    assert_eq!(t.random_tile_with_class("rock", || 0.5), Some(TileId(3)));
+    assert_eq!(t.random_tile_with_class("tree", || 0.5), None);
+
+    // Only tiles 3 and 4 are all grass, with probabilities 1 and 0.5.
+    assert_eq!(t.random_tile_with_wang_color("Ground", "Grass", || 0.6), Some(TileId(3)));
+    assert_eq!(t.random_tile_with_wang_color("Ground", "Grass", || 0.7), Some(TileId(4)));
+    assert_eq!(t.random_tile_with_wang_color("Ground", "Dirt", || 0.5), None);
+    assert_eq!(t.random_tile_with_wang_color("Ground", "Sand", || 0.5), None);
+    assert_eq!(t.random_tile_with_wang_color("Water", "Grass", || 0.5), None);
+}
+
+#[test]
+fn test_replace_gids() {
+    let mut r = read_from_file_with_path(Path::new("assets/tiled_tile_objects.tmx")).unwrap();
+    let swap: HashMap<Gid, Gid> = vec![(Gid(1), Gid(2)), (Gid(2), Gid(1))].into_iter().collect();
+    // Three layer tiles and three objects.
+    assert_eq!(r.replace_gids(&swap), 6);
+    let layer = &r.layers[0];
+    assert_eq!(layer.get_tile(0, 0).unwrap().gid, 2);
+    assert_eq!(layer.get_tile(1, 0).unwrap().gid, 1);
+    assert_eq!(layer.get_tile(1, 0).unwrap().tile_id(), Some(TileId(0)));
+    assert_eq!(layer.get_tile(3, 3).unwrap().gid, 1);
+    assert_eq!(layer.get_tile(2, 0).unwrap().gid, 0);
+    let objects = &r.object_groups[0].objects;
+    assert_eq!(objects[0].gid, 1);
+    assert_eq!(objects[2].gid, 0x80000001);
+
+    let mut r = read_from_file_with_path(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let gid = r.layers[0].get_tile(0, 0).unwrap().gid;
+    let replacements: HashMap<Gid, Gid> = vec![(Gid(gid), Gid(gid + 1))].into_iter().collect();
+    assert!(r.replace_gids(&replacements) > 0);
+    assert_eq!(r.layers[0].get_tile(0, 0).unwrap().gid, gid + 1);
+}
+
+#[test]
+fn test_layer_classes() {
+    let r = read_from_file(Path::new("assets/tiled_layer_classes.tmx")).unwrap();
+    assert_eq!(r.groups[0].user_type.as_deref(), Some("room"));
+    assert_eq!(r.layers[0].user_type.as_deref(), Some("collision"));
+    assert_eq!(r.layers[1].user_type, None);
+    assert_eq!(r.object_groups[0].user_type.as_deref(), Some("spawner"));
+    assert_eq!(r.image_layers[0].user_type.as_deref(), Some("parallax"));
+
+    #[cfg(feature = "json")]
+    {
+        let mut json = Vec::new();
+        r.write_json(&mut json).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        for class in ["room", "collision", "spawner", "parallax"].iter() {
+            assert!(json.contains(&format!("\"class\":\"{}\"", class)));
+        }
+    }
+
+    let (downgraded, warnings) = r.downgrade(TiledVersion::new(1, 8));
+    assert_eq!(warnings.len(), 4);
+    assert!(warnings.contains(&DowngradeWarning::LayerClass { layer_id: 2 }));
+    assert_eq!(downgraded.groups[0].user_type, None);
+}
+
+#[test]
+fn test_find_layers() {
+    let mut r = read_from_file(Path::new("assets/tiled_layer_classes.tmx")).unwrap();
+    r.groups[0].visible = false;
+    r.layers[1]
+        .properties
+        .insert("solid".to_string(), PropertyValue::BoolValue(true));
+    let ids = |layers: Vec<LayerMeta<'_>>| -> Vec<u32> { layers.iter().map(|l| l.id).collect() };
+
+    assert_eq!(ids(r.find_layers(|_| true)), vec![1, 2, 3, 4, 5]);
+    let collision = r.find_layers(|l| l.class.is_some_and(|c| c.starts_with("coll")));
+    assert_eq!(ids(collision.clone()), vec![2]);
+    assert_eq!(
+        collision[0].layer,
+        AnyLayer::Layer(LayerRef::Tile(&r.layers[0]))
+    );
+    assert_eq!(collision[0].parent_group, Some(0));
+    assert!(collision[0].visible && !collision[0].visible_in_map);
+
+    assert_eq!(ids(r.find_layers(|l| l.visible_in_map)), vec![4, 5]);
+    assert_eq!(
+        ids(r.find_layers(|l| l.properties.contains_key("solid"))),
+        vec![5]
+    );
+    let groups = r.find_layers(|l| matches!(l.layer, AnyLayer::Group(_)));
+    assert_eq!(groups[0].name, "Level");
+    assert_eq!(groups[0].class, Some("room"));
+}
+
+#[test]
+fn test_geojson() {
+    let r = read_from_file(Path::new("assets/tiled_hit_testing.tmx")).unwrap();
+    let geojson: String = r.object_groups[0].to_geojson().split_whitespace().collect();
+    assert!(geojson.starts_with("{\"type\":\"FeatureCollection\",\"name\":\"Shapes\""));
+    assert_eq!(geojson.matches("\"type\":\"Feature\"").count(), 7);
+    assert!(
+        geojson.contains("\"coordinates\":[[[100,100],[140,100],[140,120],[100,120],[100,100]]]")
+    );
+    // Rotated objects turn around their position.
+    assert!(geojson.contains("[[[100,100],[100,140],[80,140]"));
+    assert!(
+        geojson.contains("\"type\":\"LineString\",\"coordinates\":[[400,400],[500,400],[500,500]]")
+    );
+    assert!(geojson.contains("\"type\":\"Point\",\"coordinates\":[500,500]"));
+    // Tile objects are anchored at their bottom-left corner.
+    assert!(geojson.contains("[[[600,568],[632,568],[632,600],[600,600],[600,568]]]"));
+    assert_eq!(geojson.matches("\"type\":\"Polygon\"").count(), 5);
+
+    let r = read_from_file(Path::new("assets/tiled_schema.tmx")).unwrap();
+    let geojson: String = r.object_groups[0].to_geojson().split_whitespace().collect();
+    assert!(geojson.contains(
+        "\"properties\":{\"name\":\"\",\"class\":\"Enemy\",\"dir\":\"N\",\"health\":5,\"loot\":\"coins\"}"
+    ));
+}
+
+#[test]
+fn test_project() {
+    let project =
+        Project::parse(File::open(Path::new("assets/tiled_project.tiled-project")).unwrap())
+            .unwrap();
+    assert_eq!(project.property_types.len(), 4);
+    let direction = project.enum_type("Direction").unwrap();
+    assert_eq!(direction.storage_type, EnumStorage::String);
+    assert!(direction.accepts(&PropertyValue::StringValue("W".to_string())));
+    assert!(!direction.accepts(&PropertyValue::StringValue("Up".to_string())));
+    let enemy = project.class("Enemy").unwrap();
+    assert!(enemy.usable_as("object"));
+    assert!(!enemy.usable_as("tile"));
+    assert_eq!(enemy.member("health").unwrap().value, PropertyValue::IntValue(100));
+    assert_eq!(enemy.member("speed").unwrap().value.type_name(), "float");
+    assert_eq!(enemy.member("boss").unwrap().value, PropertyValue::BoolValue(false));
+    let dir = enemy.member("dir").unwrap();
+    assert_eq!(dir.property_type.as_deref(), Some("Direction"));
+    assert_eq!(dir.value, PropertyValue::StringValue("S".to_string()));
+    assert_eq!(
+        enemy.member("title").unwrap().value,
+        PropertyValue::StringValue("Grunt élite".to_string())
+    );
+    assert!(project.class("Direction").is_none());
+
+    let escaped = r#"{"propertyTypes": [{"type": "class", "name": "A\u00e9\ud83d\ude00\"", "members": []}]}"#;
+    let project = Project::parse(escaped.as_bytes()).unwrap();
+    assert!(project.class("A\u{e9}\u{1f600}\"").is_some());
+    assert!(Project::parse(&b"{\"propertyTypes\": [}"[..]).is_err());
+}
+
+#[test]
+fn test_validate_against() {
+    let project =
+        Project::parse(File::open(Path::new("assets/tiled_project.tiled-project")).unwrap())
+            .unwrap();
+    let r = read_from_file(Path::new("assets/tiled_schema.tmx")).unwrap();
+    let object = |object_id| SchemaLocation::Object {
+        layer_id: 2,
+        object_id,
+    };
+    let violations = r.validate_against(&project);
+    assert_eq!(
+        violations,
+        [
+            SchemaViolation {
+                location: object(2),
+                kind: SchemaViolationKind::InvalidEnumValue {
+                    member: "dir".to_string(),
+                    enum_name: "Direction".to_string(),
+                    value: PropertyValue::StringValue("Up".to_string()),
+                },
+            },
+            SchemaViolation {
+                location: object(2),
+                kind: SchemaViolationKind::WrongType {
+                    member: "health".to_string(),
+                    expected: "int".to_string(),
+                    found: "string".to_string(),
+                },
+            },
+            SchemaViolation {
+                location: object(3),
+                kind: SchemaViolationKind::UnknownClass("Ghost".to_string()),
+            },
+            SchemaViolation {
+                location: object(4),
+                kind: SchemaViolationKind::NotUsableAs {
+                    class: "Grass".to_string(),
+                    kind: "object",
+                },
+            },
+            SchemaViolation {
+                location: SchemaLocation::Tile {
+                    tileset: 0,
+                    tile_id: TileId(1),
+                },
+                kind: SchemaViolationKind::NotUsableAs {
+                    class: "Enemy".to_string(),
+                    kind: "tile",
+                },
+            },
+        ]
+    );
+    assert_eq!(
+        violations[1].to_string(),
+        "property 'health' of object 2 of layer 2 should be of type int but is of type string"
+    );
+    assert!(!r.validate_against(&Project::default()).is_empty());
+}
+
+#[test]
+fn test_class_defaults() {
+    let project =
+        Project::parse(File::open(Path::new("assets/tiled_project.tiled-project")).unwrap())
+            .unwrap();
+    let r = read_from_file(Path::new("assets/tiled_schema.tmx")).unwrap();
+    let objects = &r.object_groups[0].objects;
+
+    let enemy = objects[0].resolved_properties_with_classes(&r, &project);
+    assert_eq!(enemy.len(), 6);
+    assert_eq!(enemy["health"], PropertyValue::IntValue(5));
+    assert_eq!(enemy["dir"], PropertyValue::StringValue("N".to_string()));
+    assert_eq!(enemy["boss"], PropertyValue::BoolValue(false));
+    assert_eq!(enemy["loot"], PropertyValue::StringValue("coins".to_string()));
+    // Unknown classes add nothing.
+    assert!(objects[2]
+        .resolved_properties_with_classes(&r, &project)
+        .is_empty());
+    // The tile object takes the class of its tile.
+    let tile_object = objects[4].resolved_properties_with_classes(&r, &project);
+    assert_eq!(tile_object["health"], PropertyValue::IntValue(100));
+
+    let tiles = &r.tilesets[0].tiles;
+    let grass = tiles[0].resolved_properties(&project);
+    assert_eq!(grass.len(), 1);
+    assert_eq!(grass["slow"].type_name(), "float");
+    assert_eq!(grass["slow"], tiles[0].properties["slow"]);
+    assert_eq!(tiles[1].resolved_properties(&project).len(), 5);
+    assert!(tiles[1].resolved_properties(&Project::default()).is_empty());
+}
+
+#[test]
+fn test_object_descriptors() {
+    let r = read_from_file(Path::new("assets/tiled_hit_testing.tmx")).unwrap();
+    let descriptors = r.object_groups[0].to_descriptors();
+    assert_eq!(descriptors.len(), 7);
+    let shapes: Vec<&ShapeDescriptor> = descriptors.iter().map(|d| &d.shape).collect();
+    assert_eq!(shapes[0], &ShapeDescriptor::Rectangle);
+    assert_eq!(shapes[2], &ShapeDescriptor::Ellipse);
+    assert_eq!(
+        shapes[4],
+        &ShapeDescriptor::Polyline(vec![(0.0, 0.0), (100.0, 0.0), (100.0, 100.0)])
+    );
+    assert_eq!(shapes[5], &ShapeDescriptor::Point);
+    assert_eq!(descriptors[1].rotation, 90.0);
+    assert_eq!((descriptors[1].width, descriptors[1].height), (40.0, 20.0));
+    assert_eq!(descriptors[6].gid, 1);
+
+    let r = read_from_file(Path::new("assets/tiled_schema.tmx")).unwrap();
+    let enemy = &r.object_groups[0].to_descriptors()[0];
+    assert_eq!((enemy.id, enemy.class.as_str()), (1, "Enemy"));
+    let properties: Vec<(&str, &PlainValue)> =
+        enemy.properties.iter().map(|(n, v)| (n.as_str(), v)).collect();
+    assert_eq!(
+        properties,
+        [
+            ("dir", &PlainValue::String("N".to_string())),
+            ("health", &PlainValue::Int(5)),
+            ("loot", &PlainValue::String("coins".to_string())),
+        ]
+    );
+    let colour = PropertyValue::ColorValue(0xff102030);
+    assert_eq!(PlainValue::from(&colour), PlainValue::String("#ff102030".to_string()));
+}
+
+#[test]
+fn test_spawn_all() {
+    struct EnemySpawner {
+        skipped: usize,
+    }
+
+    impl SpawnFromObject for EnemySpawner {
+        type Entity = (String, u32);
+
+        fn spawn(&mut self, object: MapObject<'_>, _: &Properties) -> Option<(String, u32)> {
+            if object.object.obj_type != "Enemy" {
+                self.skipped += 1;
+                return None;
+            }
+            Some((object.layer.name.clone(), object.object.id))
+        }
+    }
+
+    let r = read_from_file(Path::new("assets/tiled_schema.tmx")).unwrap();
+    let mut spawner = EnemySpawner { skipped: 0 };
+    assert_eq!(
+        r.spawn_all(&mut spawner),
+        [("Entities".to_string(), 1), ("Entities".to_string(), 2)]
+    );
+    assert_eq!(spawner.skipped, 3);
+
+    // Closures work too, and get the properties objects inherit from their tile.
+    let r = read_from_file_with_path(Path::new("assets/tiled_tile_objects.tmx")).unwrap();
+    let values = r.spawn_all(&mut |_: MapObject<'_>, properties: &Properties| {
+        properties.get("a tile property").cloned()
+    });
+    assert_eq!(values.len(), 3);
+    assert_eq!(values[0], PropertyValue::StringValue("123".to_string()));
+    assert_eq!(values[1], PropertyValue::StringValue("456".to_string()));
 }
 
 #[test]
@@ -92,11 +1234,145 @@ fn test_image_layers() {
             .as_ref()
             .expect(&format!("{}'s image shouldn't be None", second.name));
         assert_eq!(image.source, "tilesheet.png");
-        assert_eq!(image.width, 448);
-        assert_eq!(image.height, 192);
+        assert_eq!(image.width, Some(448));
+        assert_eq!(image.height, Some(192));
+        assert_eq!(image.format, None);
+        assert_eq!(image.file_format().as_deref(), Some("png"));
+    }
+}
+
+#[test]
+fn test_objects_in_draw_order() {
+    let map = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="4" height="4" tilewidth="32" tileheight="32">
+ <objectgroup id="1" name="Sorted">
+  <object id="1" x="0" y="64"/>
+  <object id="4" x="0" y="32"/>
+  <object id="2" x="32" y="32"/>
+  <object id="3" x="0" y="0"/>
+ </objectgroup>
+ <objectgroup id="2" name="Stored" draworder="index">
+  <object id="1" x="0" y="64"/>
+  <object id="2" x="0" y="0"/>
+ </objectgroup>
+</map>"#;
+    let r = parse(map.as_bytes()).unwrap();
+    let ids = |i: usize| -> Vec<u32> {
+        r.object_groups[i]
+            .objects_in_draw_order()
+            .map(|o| o.id)
+            .collect()
+    };
+    assert_eq!(r.object_groups[0].draw_order, DrawOrder::TopDown);
+    assert_eq!(ids(0), vec![3, 2, 4, 1]);
+    assert_eq!(r.object_groups[1].draw_order, DrawOrder::Index);
+    assert_eq!(ids(1), vec![1, 2]);
+
+    #[cfg(feature = "json")]
+    {
+        let mut out = Vec::new();
+        r.write_json(&mut out).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert!(json.contains(r#""draworder":"index""#));
+    }
+}
+
+#[test]
+fn test_image_layer_image() {
+    let map = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="1" height="1" tilewidth="32" tileheight="32">
+ <imagelayer id="1" name="Old">
+  <image source="backgrounds/Sky.PNG" trans="ff00ff"/>
+ </imagelayer>
+ <imagelayer id="2" name="Declared">
+  <image format="jpg" source="sky" width="64" height="32"/>
+ </imagelayer>
+</map>"#;
+    let r = parse(map.as_bytes()).unwrap();
+    let old = r.image_layers[0].image.as_ref().unwrap();
+    assert_eq!((old.width, old.height), (None, None));
+    assert_eq!(old.file_format().as_deref(), Some("png"));
+    assert_eq!(
+        old.transparent_colour,
+        Some(Colour {
+            alpha: 255,
+            red: 255,
+            green: 0,
+            blue: 255
+        })
+    );
+    let declared = r.image_layers[1].image.as_ref().unwrap();
+    assert_eq!(declared.format.as_deref(), Some("jpg"));
+    assert_eq!(declared.file_format().as_deref(), Some("jpg"));
+
+    #[cfg(feature = "json")]
+    {
+        let mut out = Vec::new();
+        r.write_json(&mut out).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert!(json.contains("\"imagewidth\":64"));
+        assert!(json.contains("\"transparentcolor\":\"#ff00ff\""));
     }
 }
 
+#[cfg(feature = "image")]
+#[test]
+fn test_decode_image() {
+    let r = read_from_file(Path::new("assets/tiled_image_layers.tmx")).unwrap();
+    let image = r.image_layers[1].image.as_ref().unwrap();
+    let data = std::fs::read("assets/tilesheet.png").unwrap();
+    let decoded = image.decode(&data).unwrap();
+    assert_eq!(
+        decoded.dimensions(),
+        (image.width.unwrap() as u32, image.height.unwrap() as u32)
+    );
+    assert!(image.decode(b"not an image").is_err());
+}
+
+#[test]
+fn test_image_paths() {
+    let map = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="1" height="1" tilewidth="32" tileheight="32">
+ <tileset firstgid="1" source="tilesets/forest.tsx"/>
+ <imagelayer id="1" name="Sky">
+  <image source="images/sky.png" width="64" height="32"/>
+ </imagelayer>
+</map>"#;
+    let tileset = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset name="forest" tilewidth="32" tileheight="32" tilecount="1" columns="1">
+ <image source="../images/forest.png" width="32" height="32"/>
+</tileset>"#;
+    let r = tiled::parse_with_file_loader(map.as_bytes(), |source| {
+        assert_eq!(source, "tilesets/forest.tsx");
+        Ok(tileset.as_bytes().to_vec())
+    })
+    .unwrap();
+    let image = &r.tilesets[0].images[0];
+    assert_eq!(image.source, "../images/forest.png");
+    assert_eq!(image.defining_file.as_deref(), Some("tilesets/forest.tsx"));
+    assert_eq!(
+        image.source_relative_to_map(),
+        "tilesets/../images/forest.png"
+    );
+    let map_path = Path::new("game/levels/level.tmx");
+    assert_eq!(
+        image.resolve_path(map_path, &PathResolver::RelativeToReferencingFile),
+        Path::new("game/levels/tilesets/../images/forest.png")
+    );
+    assert_eq!(
+        image.resolve_path(map_path, &PathResolver::ProjectRoot("game".into())),
+        Path::new("game/../images/forest.png")
+    );
+
+    let sky = r.image_layers[0].image.as_ref().unwrap();
+    assert_eq!(sky.defining_file, None);
+    assert_eq!(sky.source_relative_to_map(), "images/sky.png");
+    assert_eq!(
+        sky.resolve_path(map_path, &PathResolver::RelativeToReferencingFile),
+        Path::new("game/levels/images/sky.png")
+    );
+}
+
 #[test]
 fn test_tile_property() {
     let r = read_from_file(&Path::new("assets/tiled_base64.tmx")).unwrap();
@@ -227,3 +1503,2442 @@ fn test_object_property() {
     };
     assert_eq!(3, prop_value);
 }
+
+#[cfg(feature = "json")]
+#[test]
+fn test_write_json() {
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let mut out = Vec::new();
+    r.write_json(&mut out).unwrap();
+    let json = String::from_utf8(out).unwrap();
+    assert!(json.starts_with('{'));
+    assert!(json.contains("\"type\":\"map\""));
+    assert!(json.contains("\"type\":\"tilelayer\""));
+    assert!(json.contains("\"firstgid\":1"));
+    assert!(json.contains("\"name\":\"tileset property\""));
+
+    let mut out = Vec::new();
+    r.tilesets[0].write_json(&mut out).unwrap();
+    let json = String::from_utf8(out).unwrap();
+    assert!(json.contains("\"type\":\"tileset\""));
+    assert!(!json.contains("firstgid"));
+}
+
+#[test]
+fn test_write_world() {
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let mut world = World::new();
+    world.add_map("tiled_base64.tmx", &r, 0, 0);
+    world.add_map("tiled_base64.tmx", &r, 3200, 0);
+    let mut out = Vec::new();
+    world.write(&mut out).unwrap();
+    let json = String::from_utf8(out).unwrap();
+    assert!(json.contains("\"fileName\":\"tiled_base64.tmx\""));
+    assert!(json.contains("\"x\":3200"));
+    assert!(json.contains("\"width\":3200"));
+    assert!(json.contains("\"type\":\"world\""));
+}
+
+#[test]
+fn test_load_world() {
+    let flipped = parse_file(Path::new("assets/tiled_flipped.tmx")).unwrap();
+    let animated = parse_file(Path::new("assets/tiled_animated.tmx")).unwrap();
+    let mut world = World::new();
+    world.add_map("tiled_flipped.tmx", &flipped, 0, 0);
+    world.add_map("tiled_animated.tmx", &animated, 64, -32);
+    assert_eq!(world.map_at(63, 63).unwrap().file_name, "tiled_flipped.tmx");
+    assert_eq!(world.map_at(64, -32).unwrap().file_name, "tiled_animated.tmx");
+    assert!(world.map_at(64, 40).is_none());
+    assert!(world.map_at(-1, 0).is_none());
+
+    let world_path = Path::new("assets/example.world");
+    let cache = FileCache::new();
+    let loaded = world
+        .load_all_cached(&Loader::new(), world_path, &cache)
+        .unwrap();
+    // Both maps use the same external tileset.
+    assert_eq!(cache.len(), 1);
+    assert!(cache.contains(Path::new("assets/tilesheet.tsx")));
+    assert_eq!(loaded.maps[0].map, flipped);
+    assert_eq!(loaded.maps[1].map, animated);
+    assert!(Arc::ptr_eq(
+        &loaded.maps[0].map.tilesets[0].tileset,
+        &loaded.maps[1].map.tilesets[0].tileset
+    ));
+    assert_eq!(loaded.maps[1].offset(), (64, -32));
+    let at = loaded.map_at(100, 0).unwrap();
+    assert_eq!(at.world_map.file_name, "tiled_animated.tmx");
+    assert_eq!(world.load_all(&Loader::new(), world_path).unwrap(), loaded);
+
+    assert_eq!(
+        loaded.world_to_local(70.5, -10.0),
+        Some(MapPosition {
+            map_index: 1,
+            x: 6.5,
+            y: 22.0
+        })
+    );
+    assert_eq!(loaded.local_to_world(1, 6.5, 22.0), Some((70.5, -10.0)));
+    assert_eq!(loaded.world_to_tile(100.0, 0.0), Some((1, 1, 1)));
+    assert_eq!(loaded.tile_to_world(1, 1, 1), Some((96.0, 0.0)));
+    assert_eq!(loaded.tile_to_world(0, 1, 1), Some((32.0, 32.0)));
+    // Walking off the right edge of the first map into the second one.
+    assert_eq!(loaded.translate(0, 64.0, 10.0, 1), Some((0.0, 42.0)));
+    assert!(loaded.world_to_local(-0.5, 0.0).is_none());
+    assert!(loaded.local_to_world(2, 0.0, 0.0).is_none());
+
+    world.add_map("missing.tmx", &flipped, 0, 64);
+    assert!(world.load_all(&Loader::new(), world_path).is_err());
+}
+
+#[test]
+fn test_load_many() {
+    let paths = [
+        "assets/tiled_flipped.tmx",
+        "assets/tiled_base64.tmx",
+        "assets/tiled_animated.tmx",
+        "assets/tiled_parallax.tmx",
+        "assets/tiled_flipped.tmx",
+    ];
+    let cache = FileCache::new();
+    let maps = Loader::new().load_many_cached(&paths, &cache).unwrap();
+    assert_eq!(maps.len(), paths.len());
+    for (map, path) in maps.iter().zip(paths.iter()) {
+        assert_eq!(map, &parse_file(Path::new(path)).unwrap());
+    }
+    assert_eq!(cache.len(), 1);
+    // The tileset is parsed once, and shared by every map referencing it.
+    let tilesheet = &maps[0].tilesets[0].tileset;
+    for map in [&maps[2], &maps[3], &maps[4]] {
+        assert!(Arc::ptr_eq(tilesheet, &map.tilesets[0].tileset));
+    }
+    let more = Loader::new()
+        .parse_file_cached(Path::new("assets/tiled_flipped.tmx"), &cache)
+        .unwrap();
+    assert!(Arc::ptr_eq(tilesheet, &more.tilesets[0].tileset));
+    let uncached = parse_file(Path::new("assets/tiled_flipped.tmx")).unwrap();
+    assert!(!Arc::ptr_eq(tilesheet, &uncached.tilesets[0].tileset));
+    cache.clear();
+    let reparsed = Loader::new()
+        .parse_file_cached(Path::new("assets/tiled_flipped.tmx"), &cache)
+        .unwrap();
+    assert!(!Arc::ptr_eq(tilesheet, &reparsed.tilesets[0].tileset));
+
+    assert!(Loader::new().load_many::<&str>(&[]).unwrap().is_empty());
+    let error = Loader::new()
+        .load_many(&["assets/tiled_base64.tmx", "assets/missing.tmx"])
+        .unwrap_err();
+    assert!(error.to_string().contains("missing.tmx"));
+}
+
+#[test]
+fn test_cancellation() {
+    let token = CancellationToken::new();
+    let loader = Loader::new().with_cancellation(token.clone());
+    let path = Path::new("assets/tiled_base64_zlib_infinite.tmx");
+    assert!(loader.parse_file(path).is_ok());
+
+    // Cancelled while the external tileset is being loaded, so before the first layer.
+    let file = File::open("assets/tiled_base64_external.tmx").unwrap();
+    let result = loader.parse_with_file_loader(file, |source| {
+        token.cancel();
+        std::fs::read(Path::new("assets").join(source))
+            .map_err(|e| TiledError::Other(e.to_string()))
+    });
+    assert!(matches!(result, Err(TiledError::Cancelled)));
+    assert!(matches!(loader.parse_file(path), Err(TiledError::Cancelled)));
+
+    let flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+    let loader = Loader::new().with_cancellation(flag.into());
+    assert!(matches!(
+        loader.load_many(&["assets/tiled_base64.tmx"]),
+        Err(TiledError::Cancelled)
+    ));
+}
+
+#[test]
+fn test_parse_slice() {
+    let path = Path::new("assets/tiled_base64_external.tmx");
+    let data = std::fs::read(path).unwrap();
+    let map = Loader::new().parse_slice_with_path(&data, path).unwrap();
+    assert_eq!(map, parse_file(path).unwrap());
+
+    let data = std::fs::read("assets/tiled_csv.tmx").unwrap();
+    let mut with_bom = b"\xEF\xBB\xBF".to_vec();
+    with_bom.extend_from_slice(&data);
+    assert_eq!(
+        Loader::new().parse_slice(&with_bom).unwrap(),
+        parse(data.as_slice()).unwrap()
+    );
+    assert!(Loader::new().parse_slice(&data[..data.len() / 2]).is_err());
+}
+
+#[cfg(feature = "memmap2")]
+#[test]
+fn test_parse_file_mapped() {
+    let path = Path::new("assets/tiled_base64_external.tmx");
+    let map = unsafe { Loader::new().parse_file_mapped(path) }.unwrap();
+    assert_eq!(map, parse_file(path).unwrap());
+    let missing = unsafe { Loader::new().parse_file_mapped(Path::new("assets/missing.tmx")) };
+    assert!(missing.is_err());
+}
+
+#[test]
+fn test_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Map>();
+    assert_send_sync::<tiled::tileset::Tileset>();
+    assert_send_sync::<MapTileset>();
+    assert_send_sync::<tiled::layers::Layer>();
+    assert_send_sync::<tiled::layers::ImageLayer>();
+    assert_send_sync::<tiled::layers::GroupLayer>();
+    assert_send_sync::<tiled::objects::ObjectGroup>();
+    assert_send_sync::<tiled::objects::Object>();
+    assert_send_sync::<tiled::objects::MapObject<'static>>();
+    assert_send_sync::<tiled::template::Template>();
+    assert_send_sync::<tiled::tile::Tile>();
+    assert_send_sync::<tiled::wangset::WangSet>();
+    assert_send_sync::<tiled::image::Image>();
+    assert_send_sync::<tiled::text::Text>();
+    assert_send_sync::<PropertyValue>();
+    assert_send_sync::<PropertyError>();
+    assert_send_sync::<ValidationWarning>();
+    assert_send_sync::<World>();
+    assert_send_sync::<tiled::world::LoadedWorld>();
+    assert_send_sync::<Loader>();
+    assert_send_sync::<FileCache>();
+    assert_send_sync::<CancellationToken>();
+    assert_send_sync::<TiledError>();
+
+    let map = std::thread::spawn(|| parse_file(Path::new("assets/tiled_base64.tmx")))
+        .join()
+        .unwrap()
+        .unwrap();
+    let tileset = map.tilesets[0].tileset.clone();
+    let shared = tileset.clone();
+    let name = std::thread::spawn(move || shared.name.clone())
+        .join()
+        .unwrap();
+    assert_eq!(name, tileset.name);
+}
+
+#[test]
+fn test_content_hash() {
+    let base64 = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let zlib = read_from_file(Path::new("assets/tiled_base64_zlib.tmx")).unwrap();
+    let csv = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    assert_eq!(base64.content_hash(), zlib.content_hash());
+    assert_eq!(base64.content_hash(), csv.content_hash());
+    assert_eq!(base64.content_hash(), base64.clone().content_hash());
+
+    let mut changed = base64.clone();
+    changed.properties.insert(
+        "new".to_string(),
+        PropertyValue::StringValue("value".to_string()),
+    );
+    assert_ne!(base64.content_hash(), changed.content_hash());
+
+    let a = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="1" height="1" tilewidth="32" tileheight="32">
+ <properties>
+  <property name="a" value="1"/>
+  <property name="b" type="int" value="2"/>
+ </properties>
+</map>"#;
+    let b = r#"<map tileheight="32" tilewidth="32" height="1" width="1" orientation="orthogonal" version="1.0"><properties><property type="int" value="2" name="b"/><property value="1" name="a"/></properties></map>"#;
+    assert_eq!(
+        parse(a.as_bytes()).unwrap().content_hash(),
+        parse(b.as_bytes()).unwrap().content_hash()
+    );
+}
+
+#[test]
+fn test_update_from() {
+    let path = Path::new("assets/tiled_groups.tmx");
+    let original = parse_file(path).unwrap();
+    let mut map = original.clone();
+    let changes = map.reload(&Loader::new(), path).unwrap();
+    assert!(changes.is_empty());
+    assert_eq!(map, original);
+
+    let mut new = original.clone();
+    new.layers[0].tiles.set_tile(0, 0, LayerTile::new(2));
+    new.properties.insert("reloaded".to_string(), PropertyValue::BoolValue(true));
+    let changes = map.update_from(new.clone());
+    assert_eq!(changes.layers, vec![original.layers[0].id]);
+    assert!(changes.map);
+    assert!(!changes.layers_restructured);
+    assert!(changes.tilesets.is_empty());
+    assert_eq!(map, new);
+
+    let mut new = map.clone();
+    new.layers.remove(0);
+    let changes = map.update_from(new.clone());
+    assert!(changes.layers_restructured);
+    assert!(changes.layers.is_empty());
+    assert!(!changes.map);
+    assert_eq!(map, new);
+}
+
+#[test]
+fn test_memory_usage() {
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let usage = r.memory_usage();
+    assert_eq!(usage.layers.len(), r.layers.len());
+    assert_eq!(usage.layers[0].0, r.layers[0].id);
+    // 100x100 tiles, each stored in a row of its layer.
+    let tiles = 100 * 100 * std::mem::size_of::<LayerTile>();
+    let csv = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let zlib = read_from_file(Path::new("assets/tiled_base64_zlib.tmx")).unwrap();
+    assert_eq!(csv.memory_usage().layers, usage.layers);
+    assert_eq!(zlib.memory_usage().layers, usage.layers);
+    // The rows are allocated with the size of the map, without spare capacity.
+    for map in [&r, &csv, &zlib].iter() {
+        match &map.layers[0].tiles {
+            LayerData::Finite(rows) => {
+                assert_eq!(rows.capacity(), 100);
+                assert!(rows.iter().all(|row| row.capacity() == 100));
+            }
+            LayerData::Infinite(_) => panic!("expected a finite layer"),
+        }
+    }
+    assert_eq!(usage.tilesets.len(), 1);
+    assert!(usage.total() > tiles);
+
+    let r = parse_file(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let usage = r.memory_usage();
+    assert!(usage.layers.iter().all(|&(_, size)| size > 0));
+    assert_eq!(
+        usage.total(),
+        usage.layers.iter().map(|l| l.1).sum::<usize>()
+            + usage.image_layers.iter().map(|l| l.1).sum::<usize>()
+            + usage.object_groups.iter().map(|l| l.1).sum::<usize>()
+            + usage.tilesets.iter().sum::<usize>()
+            + usage.other
+    );
+}
+
+#[test]
+fn test_resource_loader() {
+    let path = Path::new("assets/tiled_templates.tmx");
+    let mut requests = Vec::new();
+    let map = Loader::new()
+        .parse_with_resource_loader(File::open(path).unwrap(), Some(path), |request| {
+            requests.push((
+                request.kind,
+                request.source.to_string(),
+                request.referencing_file.map(str::to_string),
+                request.path.clone(),
+            ));
+            let resolved = request.resolved_path.as_ref().unwrap();
+            assert_eq!(resolved, &Path::new("assets").join(&request.path));
+            std::fs::read(resolved).map_err(|e| TiledError::Other(e.to_string()))
+        })
+        .unwrap();
+    assert_eq!(map, parse_file(path).unwrap());
+    assert_eq!(
+        requests[0],
+        (
+            ResourceKind::Tileset,
+            "tilesheet.tsx".to_string(),
+            None,
+            "tilesheet.tsx".to_string()
+        )
+    );
+    assert!(requests.iter().any(|r| r.0 == ResourceKind::Template));
+    let (_, _, referencing_file, _) = requests
+        .iter()
+        .find(|r| r.0 == ResourceKind::Tileset && r.2.is_some())
+        .unwrap();
+    assert_eq!(referencing_file.as_deref(), Some("tiled_template_chest.tx"));
+
+    // Without the path of the map, nothing is resolved.
+    let file = File::open("assets/tiled_base64_external.tmx").unwrap();
+    Loader::new()
+        .parse_with_resource_loader(file, None, |request: &FileRequest| {
+            assert_eq!(request.resolved_path, None);
+            std::fs::read(Path::new("assets").join(&request.path))
+                .map_err(|e| TiledError::Other(e.to_string()))
+        })
+        .unwrap();
+}
+
+#[test]
+fn test_memory_reader() {
+    let mut files = MemoryReader::new();
+    for name in [
+        "tiled_templates.tmx",
+        "tiled_template_chest.tx",
+        "tiled_template_zone.tx",
+        "tilesheet.tsx",
+    ]
+    .iter()
+    {
+        let data = std::fs::read(Path::new("assets").join(name)).unwrap();
+        files.insert(Path::new("bundle/maps/../").join(name), data);
+    }
+    assert!(files.get(Path::new("bundle/tilesheet.tsx")).is_some());
+    assert!(files.get(Path::new("assets/tilesheet.tsx")).is_none());
+
+    let map = files
+        .parse_map(&Loader::new(), Path::new("bundle/tiled_templates.tmx"))
+        .unwrap();
+    assert_eq!(
+        map,
+        parse_file(Path::new("assets/tiled_templates.tmx")).unwrap()
+    );
+
+    // Without the path of the map, files are looked up relative to it.
+    let mut relative = MemoryReader::new();
+    relative.insert("tilesheet.tsx", &include_bytes!("../assets/tilesheet.tsx")[..]);
+    let file = File::open("assets/tiled_base64_external.tmx").unwrap();
+    Loader::new()
+        .parse_with_resource_loader(file, None, |r| relative.read(r))
+        .unwrap();
+
+    let error = files
+        .parse_map(&Loader::new(), Path::new("bundle/missing.tmx"))
+        .unwrap_err();
+    assert!(error.to_string().contains("missing.tmx"));
+}
+
+#[test]
+fn test_include_files() {
+    let files = tiled::include_files!(
+        "../assets",
+        "tiled_templates.tmx",
+        "tiled_template_chest.tx",
+        "tiled_template_zone.tx",
+        "tilesheet.tsx",
+    );
+    let map = files
+        .parse_map(&Loader::new(), Path::new("tiled_templates.tmx"))
+        .unwrap();
+    assert_eq!(
+        map,
+        parse_file(Path::new("assets/tiled_templates.tmx")).unwrap()
+    );
+
+    let collected: MemoryReader = vec![("a/b.tsx", &b"tileset"[..])].into_iter().collect();
+    assert_eq!(collected.get(Path::new("a/./b.tsx")), Some(&b"tileset"[..]));
+}
+
+#[cfg(feature = "include_dir")]
+#[test]
+fn test_include_dir() {
+    static ASSETS: include_dir::Dir = include_dir::include_dir!("$CARGO_MANIFEST_DIR/assets");
+
+    let files = MemoryReader::from_include_dir(&ASSETS);
+    let map = files
+        .parse_map(&Loader::new(), Path::new("tiled_templates.tmx"))
+        .unwrap();
+    assert_eq!(
+        map,
+        parse_file(Path::new("assets/tiled_templates.tmx")).unwrap()
+    );
+}
+
+#[cfg(feature = "rust-embed")]
+#[test]
+fn test_rust_embed() {
+    #[derive(rust_embed::RustEmbed)]
+    #[folder = "assets/"]
+    struct Assets;
+
+    let files = MemoryReader::from_rust_embed::<Assets>();
+    let map = files
+        .parse_map(&Loader::new(), Path::new("tiled_templates.tmx"))
+        .unwrap();
+    assert_eq!(
+        map,
+        parse_file(Path::new("assets/tiled_templates.tmx")).unwrap()
+    );
+}
+
+#[test]
+fn test_write_template() {
+    let r = read_from_file(Path::new("assets/tiled_object_property.tmx")).unwrap();
+    let template = r.object_groups[0].objects[0].to_template(None);
+    let mut out = Vec::new();
+    template.write(&mut out).unwrap();
+    let xml = String::from_utf8(out).unwrap();
+    assert!(xml.contains("<template>"));
+    assert!(xml.contains("<object width=\"32\" height=\"32\">"));
+    assert!(xml.contains("<property name=\"object property\" type=\"object\" value=\"3\" />"));
+}
+
+#[test]
+fn test_map_objects_query() {
+    let r = read_from_file(Path::new("assets/tiled_object_property.tmx")).unwrap();
+    assert_eq!(r.objects().count(), 2);
+    let found: Vec<_> = r
+        .objects()
+        .with_property("object property", PropertyValue::ObjectValue(3))
+        .collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].object.id, 2);
+    assert_eq!(found[0].layer.name, "Object Layer 1");
+    assert_eq!(r.objects().with_class("spawn").count(), 0);
+}
+
+#[test]
+fn test_find_objects_with_property() {
+    let r = parse_file(Path::new("assets/tiled_templates.tmx")).unwrap();
+    let ids = |name: &str, value: PropertyValue| -> Vec<u32> {
+        r.find_objects_with_property(name, value)
+            .map(|o| o.object.id)
+            .collect()
+    };
+    // Own properties, including those inherited from templates.
+    assert_eq!(ids("gold", PropertyValue::IntValue(100)), [2]);
+    assert_eq!(ids("gold", PropertyValue::IntValue(10)), [1]);
+    // Properties of the tile and tileset of tile objects.
+    let tile_property = PropertyValue::StringValue("123".to_string());
+    let tileset_property = PropertyValue::StringValue("tsp".to_string());
+    assert_eq!(ids("a tile property", tile_property.clone()), [1, 2]);
+    assert_eq!(ids("tileset property", tileset_property), [1, 2]);
+    let own = r.objects().with_property("a tile property", tile_property);
+    assert_eq!(own.count(), 0);
+    assert_eq!(ids("gold", PropertyValue::IntValue(0)), []);
+
+    let chest = &r.object_groups[0].objects[0];
+    assert_eq!(
+        chest.resolved_property(&r, "locked"),
+        Some(&PropertyValue::BoolValue(false))
+    );
+    assert_eq!(chest.resolved_property(&r, "missing"), None);
+}
+
+#[test]
+fn test_object_resolved_properties() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_tile_objects.tmx")).unwrap();
+    let objects = &r.object_groups[0].objects;
+
+    let plain = objects[0].resolved_properties(&r);
+    assert_eq!(
+        plain.get("a tile property"),
+        Some(&PropertyValue::StringValue("123".to_string()))
+    );
+    assert_eq!(
+        plain.get("tileset property"),
+        Some(&PropertyValue::StringValue("tsp".to_string()))
+    );
+
+    let overridden = objects[1].resolved_properties(&r);
+    assert_eq!(
+        overridden.get("a tile property"),
+        Some(&PropertyValue::StringValue("456".to_string()))
+    );
+    assert_eq!(
+        overridden.get("own property"),
+        Some(&PropertyValue::IntValue(7))
+    );
+
+    let flipped = objects[2].resolved_properties(&r);
+    assert_eq!(flipped, plain);
+}
+
+#[test]
+fn test_object_templates() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_templates.tmx")).unwrap();
+    let objects = &r.object_groups[0].objects;
+
+    let chest = &objects[0];
+    assert_eq!(chest.name, "chest");
+    assert_eq!(chest.obj_type, "pickup");
+    // The template's gid is relative to its own tileset reference.
+    assert_eq!(chest.gid, 4);
+    assert_eq!((chest.x, chest.y, chest.width), (0.0, 32.0, 32.0));
+    assert_eq!(chest.properties.get("gold"), Some(&PropertyValue::IntValue(10)));
+    assert!(!chest.is_overridden(ObjectField::Name));
+    let instance = chest.template.as_ref().unwrap();
+    assert_eq!(instance.source, "tiled_template_chest.tx");
+    assert!(instance.own_properties.is_empty());
+
+    let big_chest = &objects[1];
+    assert_eq!(big_chest.name, "big chest");
+    assert_eq!(big_chest.width, 64.0);
+    assert!(big_chest.is_overridden(ObjectField::Name));
+    assert!(big_chest.is_overridden(ObjectField::Width));
+    assert!(!big_chest.is_overridden(ObjectField::Gid));
+    assert_eq!(big_chest.properties.get("gold"), Some(&PropertyValue::IntValue(100)));
+    assert_eq!(big_chest.properties.get("locked"), Some(&PropertyValue::BoolValue(false)));
+    let instance = big_chest.template.as_ref().unwrap();
+    assert_eq!(instance.own_properties.len(), 1);
+    assert_eq!(instance.template.object.width, 32.0);
+
+    let zone = &objects[2];
+    assert_eq!(
+        zone.shape,
+        ObjectShape::Ellipse {
+            width: 96.0,
+            height: 48.0
+        }
+    );
+    assert!(!zone.is_overridden(ObjectField::Shape));
+}
+
+#[test]
+fn test_template_instances() {
+    let mut r = read_from_file_with_path(Path::new("assets/tiled_templates.tmx")).unwrap();
+    let objects = &mut r.object_groups[0].objects;
+    let template = objects[0].template.as_ref().unwrap().template.clone();
+    assert!(Arc::ptr_eq(
+        &template,
+        &objects[1].template.as_ref().unwrap().template
+    ));
+    assert!(objects.iter().all(|o| o.template_drift().is_empty()));
+
+    let chest = &mut objects[0];
+    chest.name = "renamed".to_string();
+    chest.properties.remove("gold");
+    assert_eq!(chest.template_drift(), vec![ObjectField::Name]);
+    chest.reapply_template();
+    assert_eq!(chest.name, "chest");
+    assert_eq!(chest.properties.get("gold"), Some(&PropertyValue::IntValue(10)));
+    assert!(chest.template_drift().is_empty());
+
+    // A newer version of the template.
+    let mut changed = (*template).clone();
+    changed.object.obj_type = "loot".to_string();
+    changed.object.width = 48.0;
+    changed
+        .object
+        .properties
+        .insert("gold".to_string(), PropertyValue::IntValue(5));
+    let changed = Arc::new(changed);
+    let big_chest = &mut objects[1];
+    big_chest.relink_template("tiled_template_chest.tx", changed.clone());
+    assert_eq!(big_chest.name, "big chest");
+    assert_eq!(big_chest.obj_type, "loot");
+    assert_eq!(big_chest.width, 64.0);
+    assert_eq!(big_chest.properties.get("gold"), Some(&PropertyValue::IntValue(100)));
+    assert!(Arc::ptr_eq(
+        &big_chest.template.as_ref().unwrap().template,
+        &changed
+    ));
+
+    // Objects that are not instances keep what differs from the template.
+    let mut plain = objects[0].clone();
+    plain.template = None;
+    plain.name = "plain".to_string();
+    assert!(plain.template_drift().is_empty());
+    plain.relink_template("chests\\chest.tx", changed);
+    let instance = plain.template.as_ref().unwrap();
+    assert_eq!(instance.source, "chests/chest.tx");
+    assert!(plain.is_overridden(ObjectField::Name));
+    assert!(plain.is_overridden(ObjectField::Width));
+    assert!(!plain.is_overridden(ObjectField::Gid));
+    assert!(!plain.is_overridden(ObjectField::Shape));
+    assert_eq!(plain.name, "plain");
+    assert_eq!(plain.obj_type, "pickup");
+    assert_eq!(plain.width, 32.0);
+    assert_eq!(plain.properties.get("gold"), Some(&PropertyValue::IntValue(10)));
+    assert_eq!(instance.own_properties.len(), 1);
+}
+
+#[test]
+fn test_animation_timing() {
+    let frames = vec![
+        Frame {
+            tile_id: TileId(1),
+            duration: 100,
+        },
+        Frame {
+            tile_id: TileId(2),
+            duration: 50,
+        },
+    ];
+    assert_eq!(total_duration(&frames), 150);
+    assert_eq!(frame_at(&frames, 0), Some((&frames[0], 100)));
+    assert_eq!(frame_at(&frames, 99), Some((&frames[0], 1)));
+    assert_eq!(frame_at(&frames, 100), Some((&frames[1], 50)));
+    assert_eq!(frame_at(&frames, 320), Some((&frames[0], 80)));
+    assert_eq!(frame_at(&[], 10), None);
+}
+
+#[test]
+fn test_edit_animation() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_animated.tmx")).unwrap();
+    let mut water = (*r.tilesets[1].tileset).clone();
+    let frame = |tile_id| Frame {
+        tile_id: TileId(tile_id),
+        duration: 100,
+    };
+    let frames = |water: &Tileset, id| {
+        water
+            .get_tile(TileId(id))
+            .and_then(|t| t.animation.as_ref())
+            .map(|f| f.iter().map(|f| f.tile_id.0).collect::<Vec<_>>())
+    };
+
+    water.push_frame(TileId(3), frame(6)).unwrap();
+    water.insert_frame(TileId(3), 0, frame(7)).unwrap();
+    assert_eq!(frames(&water, 3), Some(vec![7, 3, 4, 5, 6]));
+    assert!(water.move_frame(TileId(3), 0, 4));
+    assert!(!water.move_frame(TileId(3), 0, 5));
+    assert_eq!(frames(&water, 3), Some(vec![3, 4, 5, 6, 7]));
+    assert_eq!(water.remove_frame(TileId(3), 1).unwrap().tile_id, TileId(4));
+    assert_eq!(water.remove_frame(TileId(3), 4), None);
+
+    // Frames must show tiles of the tileset, and invalid edits change nothing.
+    assert!(water.push_frame(TileId(3), frame(84)).is_err());
+    assert!(water.insert_frame(TileId(3), 5, frame(1)).is_err());
+    assert!(water.set_animation(TileId(84), vec![frame(1)]).is_err());
+    assert_eq!(frames(&water, 3), Some(vec![3, 5, 6, 7]));
+
+    // Tiles without data get it when animated, in order of their id.
+    water
+        .set_animation(TileId(10), vec![frame(10), frame(11)])
+        .unwrap();
+    let ids: Vec<_> = water.tiles.iter().map(|t| t.id.0).collect();
+    assert_eq!(ids, vec![3, 10, 20]);
+    assert_eq!(water.get_tile(TileId(10)).unwrap().probability, 1.0);
+
+    water.set_animation(TileId(20), Vec::new()).unwrap();
+    assert_eq!(frames(&water, 20), None);
+    assert_eq!(water.remove_frame(TileId(10), 0), Some(frame(10)));
+    assert_eq!(water.remove_frame(TileId(10), 0), Some(frame(11)));
+    assert_eq!(frames(&water, 10), None);
+}
+
+#[test]
+fn test_edit_animation_unsorted() {
+    let tileset = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" name="unsorted" tilewidth="32" tileheight="32" tilecount="16" columns="4">
+ <image source="tiles.png" width="128" height="128"/>
+ <tile id="9" probability="0.5"/>
+ <tile id="2">
+  <animation>
+   <frame tileid="2" duration="100"/>
+  </animation>
+ </tile>
+ <tile id="5" probability="0.25"/>
+</tileset>
+"#;
+    let mut t = Tileset::parse(tileset.as_bytes()).unwrap();
+    let frame = Frame {
+        tile_id: TileId(3),
+        duration: 100,
+    };
+    t.push_frame(TileId(2), frame.clone()).unwrap();
+    t.push_frame(TileId(5), frame.clone()).unwrap();
+    t.push_frame(TileId(7), frame).unwrap();
+    let ids: Vec<_> = t.tiles.iter().map(|t| t.id.0).collect();
+    assert_eq!(ids, vec![7, 9, 2, 5]);
+    let frames = |id| t.get_tile(TileId(id))?.animation.as_ref().map(Vec::len);
+    assert_eq!(
+        (frames(2), frames(5), frames(7)),
+        (Some(2), Some(1), Some(1))
+    );
+    assert_eq!(t.get_tile(TileId(5)).unwrap().probability, 0.25);
+}
+
+#[test]
+fn test_write_animations() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_animated.tmx")).unwrap();
+    let mut water = (*r.tilesets[1].tileset).clone();
+    water
+        .set_animation(
+            TileId(10),
+            vec![
+                Frame {
+                    tile_id: TileId(11),
+                    duration: 90,
+                },
+                Frame {
+                    tile_id: TileId(10),
+                    duration: 20,
+                },
+            ],
+        )
+        .unwrap();
+
+    #[cfg(feature = "json")]
+    {
+        let mut out = Vec::new();
+        water.write_json(&mut out).unwrap();
+        let json: String = String::from_utf8(out).unwrap().split_whitespace().collect();
+        assert!(json.contains(
+            "\"animation\":[{\"duration\":200,\"tileid\":3},{\"duration\":200,\"tileid\":4},{\"duration\":400,\"tileid\":5}]"
+        ));
+        assert!(json.contains(
+            "\"animation\":[{\"duration\":90,\"tileid\":11},{\"duration\":20,\"tileid\":10}],\"id\":10"
+        ));
+
+        let mut out = Vec::new();
+        water.write_json_normalized(&mut out, 50).unwrap();
+        let json: String = String::from_utf8(out).unwrap().split_whitespace().collect();
+        assert!(json.contains(
+            "\"animation\":[{\"duration\":100,\"tileid\":11},{\"duration\":50,\"tileid\":10}]"
+        ));
+        assert!(json.contains("{\"duration\":400,\"tileid\":5}"));
+    }
+
+    assert_eq!(normalize_duration(124, 50), 100);
+    assert_eq!(normalize_duration(125, 50), 150);
+    assert_eq!(normalize_duration(0, 16), 16);
+    assert_eq!(normalize_duration(33, 0), 33);
+}
+
+#[test]
+fn test_tile_properties_index() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_animated.tmx")).unwrap();
+    let mut water = (*r.tilesets[1].tileset).clone();
+    let index = water.tile_properties_index();
+    for id in (0..water.tile_count()).map(TileId) {
+        assert_eq!(index.tile(id), water.get_tile(id));
+    }
+    assert_eq!(index.tile(TileId(20)).unwrap().id, TileId(20));
+    assert_eq!(index.properties(TileId(3)), Some(&HashMap::new()));
+    assert_eq!(index.properties(TileId(4)), None);
+
+    // Indices built after the tiles change see the changes, through the API or not.
+    water
+        .set_animation(
+            TileId(10),
+            vec![Frame {
+                tile_id: TileId(10),
+                duration: 100,
+            }],
+        )
+        .unwrap();
+    assert!(water.tile_properties_index().tile(TileId(10)).is_some());
+    water.tiles[0].id = TileId(4);
+    water.tiles[0]
+        .properties
+        .insert("deep".to_string(), PropertyValue::BoolValue(true));
+    let index = water.tile_properties_index();
+    assert_eq!(index.tile(TileId(3)), None);
+    assert_eq!(
+        index.properties(TileId(4)).unwrap()["deep"],
+        PropertyValue::BoolValue(true)
+    );
+    assert_eq!(water.clone(), water);
+}
+
+#[test]
+fn test_property_index() {
+    let mut r = read_from_file(Path::new("assets/tiled_schema.tmx")).unwrap();
+    r.properties.insert(
+        "health".to_string(),
+        PropertyValue::StringValue("map".to_string()),
+    );
+    let index = r.property_index();
+    assert_eq!(index.uses().len(), 8);
+    assert_eq!(index.names(), vec!["dark", "dir", "health", "loot", "slow"]);
+
+    let health: Vec<_> = index.named("health").map(|u| u.owner).collect();
+    assert_eq!(
+        health,
+        vec![
+            PropertyOwner::Map,
+            PropertyOwner::Object {
+                layer_id: 2,
+                object_id: 1
+            },
+            PropertyOwner::Object {
+                layer_id: 2,
+                object_id: 2
+            },
+        ]
+    );
+    let slow = index.named("slow").next().unwrap();
+    assert_eq!(
+        slow.owner,
+        PropertyOwner::Tile {
+            tileset: 0,
+            tile_id: TileId(0)
+        }
+    );
+    assert_eq!(slow.owner.to_string(), "tile 0 of tileset 0");
+    assert_eq!(slow.value, &PropertyValue::FloatValue(0.25.into()));
+    assert_eq!(index.uses()[1].owner, PropertyOwner::Layer { layer_id: 1 });
+    assert_eq!(index.named("deprecated_flag").count(), 0);
+}
+
+#[test]
+fn test_animated_tiles() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_animated.tmx")).unwrap();
+    let animated = r.animated_tiles();
+    assert_eq!(animated.len(), 1);
+    assert_eq!(animated[0].tileset.name, "water");
+    assert_eq!(animated[0].tiles.len(), 2);
+    assert_eq!(animated[0].tiles[&Gid(88)].len(), 3);
+    assert_eq!(animated[0].tiles[&Gid(105)][1].tile_id, TileId(21));
+}
+
+#[test]
+fn test_hex_coordinates() {
+    let r = read_from_file(Path::new("assets/tiled_hexagonal.tmx")).unwrap();
+    assert_eq!(r.orientation, Orientation::Hexagonal);
+    assert_eq!(r.hex_side_length, Some(16));
+    let layout = r.hex_layout();
+    assert_eq!(layout.stagger_axis, StaggerAxis::Y);
+    assert_eq!(layout.stagger_index, StaggerIndex::Odd);
+
+    assert_eq!(layout.offset_to_axial(2, 3), Axial { q: 1, r: 3 });
+    let mut neighbors = layout.neighbors(1, 1);
+    neighbors.sort();
+    assert_eq!(neighbors, [(0, 1), (1, 0), (1, 2), (2, 0), (2, 1), (2, 2)]);
+    assert_eq!(layout.distance((0, 0), (3, 3)), 5);
+
+    for layout in [
+        HexLayout {
+            stagger_axis: StaggerAxis::X,
+            stagger_index: StaggerIndex::Even,
+        },
+        HexLayout {
+            stagger_axis: StaggerAxis::Y,
+            stagger_index: StaggerIndex::Even,
+        },
+    ] {
+        for x in -3..3 {
+            for y in -3..3 {
+                let axial = layout.offset_to_axial(x, y);
+                assert_eq!(layout.axial_to_offset(axial), (x, y));
+                assert!(layout.neighbors(x, y).iter().all(|&n| layout.distance((x, y), n) == 1));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_isometric_picking() {
+    let r = read_from_file(Path::new("assets/tiled_isometric.tmx")).unwrap();
+    assert_eq!(r.tile_at_pixel(128.0, 16.0), Some((0, 0)));
+    assert_eq!(r.tile_at_pixel(160.0, 32.0), Some((1, 0)));
+    assert_eq!(r.tile_at_pixel(96.0, 32.0), Some((0, 1)));
+    assert_eq!(r.tile_at_pixel(10.0, 10.0), Some((-2, 2)));
+    assert_eq!(r.layers[0].tile_at_pixel(&r, 170.0, 27.0), Some((1, 0)));
+}
+
+#[test]
+fn test_staggered_picking() {
+    let r = read_from_file(Path::new("assets/tiled_staggered.tmx")).unwrap();
+    assert_eq!(r.tile_at_pixel(32.0, 16.0), Some((0, 0)));
+    assert_eq!(r.tile_at_pixel(64.0, 32.0), Some((0, 1)));
+    assert_eq!(r.tile_at_pixel(32.0, 48.0), Some((0, 2)));
+    assert_eq!(r.tile_at_pixel(2.0, 2.0), Some((-1, -1)));
+    assert_eq!(r.layers[0].tile_at_pixel(&r, 74.0, 27.0), Some((0, 1)));
+}
+
+#[test]
+fn test_staggered_projection() {
+    let mut r = read_from_file(Path::new("assets/tiled_staggered.tmx")).unwrap();
+    // Positions as shown by the editor for a 64x32 staggered map.
+    let cases = [
+        (StaggerAxis::Y, StaggerIndex::Odd, (1, 3), (96.0, 48.0)),
+        (StaggerAxis::Y, StaggerIndex::Odd, (2, 2), (128.0, 32.0)),
+        (StaggerAxis::Y, StaggerIndex::Even, (1, 2), (96.0, 32.0)),
+        (StaggerAxis::Y, StaggerIndex::Even, (1, 3), (64.0, 48.0)),
+        (StaggerAxis::X, StaggerIndex::Odd, (3, 1), (96.0, 48.0)),
+        (StaggerAxis::X, StaggerIndex::Odd, (2, 1), (64.0, 32.0)),
+        (StaggerAxis::X, StaggerIndex::Even, (2, 1), (64.0, 48.0)),
+        (StaggerAxis::X, StaggerIndex::Even, (3, 1), (96.0, 32.0)),
+    ];
+    for &(axis, index, tile, pixel) in cases.iter() {
+        r.stagger_axis = axis;
+        r.stagger_index = index;
+        assert_eq!(r.tile_to_pixel(tile.0, tile.1), Some(pixel));
+        let center = (pixel.0 + 32.0, pixel.1 + 16.0);
+        assert_eq!(r.tile_at_pixel(center.0, center.1), Some(tile));
+    }
+
+    for &axis in [StaggerAxis::X, StaggerAxis::Y].iter() {
+        for &index in [StaggerIndex::Odd, StaggerIndex::Even].iter() {
+            r.stagger_axis = axis;
+            r.stagger_index = index;
+            for x in -2..4 {
+                for y in -2..4 {
+                    let (px, py) = r.tile_to_pixel(x, y).unwrap();
+                    assert_eq!(r.tile_at_pixel(px + 32.0, py + 16.0), Some((x, y)));
+                    // Points just inside the left and right corners of the diamond.
+                    assert_eq!(r.tile_at_pixel(px + 1.0, py + 16.0), Some((x, y)));
+                    assert_eq!(r.tile_at_pixel(px + 63.0, py + 16.0), Some((x, y)));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_layer_chunks() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let chunks: Vec<_> = r.layers[0].chunks().collect();
+    assert_eq!(chunks.len(), 4);
+    assert_eq!(chunks[0].0, (-32, 0));
+    assert_eq!(chunks[3].0, (0, 32));
+    let (origin, chunk) = chunks[0];
+    let tiles: Vec<_> = chunk.tiles().collect();
+    assert_eq!(tiles.len(), 32 * 32);
+    assert_eq!(tiles[0].0, origin);
+    assert_eq!(tiles[33].0, (-31, 1));
+
+    let finite = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert_eq!(finite.layers[0].chunks().count(), 0);
+}
+
+#[test]
+fn test_used_bounds() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_tile_objects.tmx")).unwrap();
+    assert_eq!(
+        r.layers[0].used_bounds(),
+        Some(TileRect {
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 4
+        })
+    );
+    assert_eq!(
+        r.used_bounds(),
+        Some(TileRect {
+            x: 0,
+            y: 0,
+            width: 4,
+            height: 4
+        })
+    );
+
+    let r = read_from_file_with_path(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let bounds = r.layers[0].used_bounds().unwrap();
+    assert!(bounds.x < 0);
+    assert!(bounds.width > 0 && bounds.height > 0);
+
+    let r = read_from_file(Path::new("assets/tiled_hexagonal.tmx")).unwrap();
+    assert_eq!(r.used_bounds(), None);
+}
+
+#[test]
+fn test_signed_tile_access() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let layer = &r.layers[0];
+    for (origin, chunk) in layer.chunks() {
+        for ((x, y), tile) in chunk.tiles() {
+            assert_eq!(layer.get_tile(x, y), Some(tile));
+        }
+        assert_eq!(chunk.get_tile(origin.0 - 1, origin.1), None);
+    }
+    assert_eq!(layer.get_tile(-1000, -1000), None);
+
+    let r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let layer = &r.layers[0];
+    assert_eq!(layer.get_tile(0, 0).map(|t| t.gid), Some(35));
+    assert_eq!(layer.get_tile(0, 1).map(|t| t.gid), Some(17));
+    assert_eq!(layer.get_tile(-1, 0), None);
+    assert_eq!(layer.get_tile(100, 0), None);
+}
+
+#[test]
+fn test_copy_region() {
+    let r = read_from_file(Path::new("assets/tiled_csv.tmx")).unwrap();
+    let grid: TileGrid = r.layers[0].copy_region(TileRect {
+        x: -1,
+        y: 0,
+        width: 3,
+        height: 3,
+    });
+    assert_eq!((grid.width, grid.height), (3, 3));
+    assert_eq!(grid.get(0, 0).unwrap().gid, 0);
+    assert_eq!(grid.get(1, 0).unwrap().gid, 35);
+    assert_eq!(grid.get(1, 1).unwrap().gid, 17);
+    assert_eq!(grid.get(2, 2).unwrap().gid, 17);
+    assert_eq!(grid.get(3, 0), None);
+    assert_eq!(grid.rows().count(), 3);
+}
+
+#[test]
+fn test_tile_stamps() {
+    let mut r = parse_file(Path::new("assets/tiled_automapping_target.tmx")).unwrap();
+    r.layers[1].set_tile(1, 1, LayerTile::new(21));
+    let rect = TileRect {
+        x: 0,
+        y: 0,
+        width: 2,
+        height: 2,
+    };
+    let stamp = TileStamp::from_map(&r, rect);
+    assert_eq!((stamp.width(), stamp.height()), (2, 2));
+    assert_eq!(stamp.layers[1].0, "Decoration");
+
+    assert_eq!(r.apply_stamp(4, 1, &stamp), 2);
+    let ground = &r.layers[0];
+    assert_eq!(ground.get_tile(4, 1).unwrap().gid, 11);
+    assert_eq!(ground.get_tile(5, 1).unwrap().gid, 12);
+    assert_eq!(ground.get_tile(4, 2).unwrap().gid, 11);
+    // Empty tiles of the stamp don't erase, and tiles outside of the layer are dropped.
+    assert_eq!(ground.get_tile(5, 2).unwrap().gid, 0);
+    assert_eq!(ground.get_tile(6, 1), None);
+    let decoration = r.layers[1].get_tile(5, 2).unwrap();
+    assert_eq!(decoration.gid, 21);
+    assert_eq!(decoration.tile_id(), Some(TileId(20)));
+
+    let grid = TileStamp::from_layer(&r.layers[0], rect).layers.remove(0).1;
+    r.layers[1].apply_stamp(0, 1, &grid);
+    assert_eq!(r.layers[1].get_tile(0, 1).unwrap().gid, 11);
+    assert_eq!(r.layers[1].get_tile(1, 1).unwrap().gid, 12);
+    assert_eq!(r.layers[1].get_tile(0, 1).unwrap().tileset_index(), None);
+
+    let other = TileStamp {
+        layers: vec![("Missing".to_string(), grid)],
+    };
+    assert_eq!(r.apply_stamp(0, 0, &other), 0);
+}
+
+#[test]
+fn test_change_tracking() {
+    let mut r = parse_file(Path::new("assets/tiled_automapping_target.tmx")).unwrap();
+    assert!(r.set_tile(0, 0, 0, LayerTile::new(5)));
+    assert!(r.take_changes().is_empty());
+
+    r.track_changes(true);
+    assert!(r.set_tile(0, 0, 0, LayerTile::new(7)));
+    assert!(!r.set_tile(0, 6, 0, LayerTile::new(7)));
+    // Setting a tile to what it already is isn't a change.
+    assert!(r.set_tile(1, 0, 0, LayerTile::new(0)));
+    let rect = TileRect {
+        x: 0,
+        y: 0,
+        width: 2,
+        height: 1,
+    };
+    let stamp = TileStamp::from_layer(&r.layers[0], rect);
+    assert_eq!(r.apply_stamp(0, 2, &stamp), 1);
+    let changes = r.take_changes();
+    assert_eq!(changes.len(), 3);
+    assert_eq!(
+        changes[0],
+        Change::Tile {
+            layer_id: 1,
+            x: 0,
+            y: 0,
+            old: LayerTile::new(5),
+            new: LayerTile::new(7),
+        }
+    );
+    assert_eq!(r.layers[0].get_tile(0, 0).unwrap().tile_id(), Some(TileId(6)));
+    assert!(r.take_changes().is_empty());
+
+    let templates = parse_file(Path::new("assets/tiled_templates.tmx")).unwrap();
+    let mut r = templates.clone();
+    r.track_changes(true);
+    let chest = r.remove_object(0, 1).unwrap();
+    assert_eq!(chest.id, 1);
+    assert_eq!(r.remove_object(0, 1), None);
+    assert!(r.add_object(0, chest.clone()));
+    assert!(!r.add_object(1, chest));
+    let changes = r.take_changes();
+    assert_eq!(changes.len(), 2);
+    assert!(matches!(
+        changes[0],
+        Change::ObjectRemoved {
+            layer_id: 2,
+            index: 0,
+            ..
+        }
+    ));
+    assert!(matches!(
+        changes[1],
+        Change::ObjectAdded {
+            layer_id: 2,
+            index: 2,
+            ..
+        }
+    ));
+
+    // Undoing the changes in reverse order restores the map, recording the undo in turn.
+    for change in changes.iter().rev() {
+        assert!(r.apply_change(&change.inverse()));
+    }
+    assert_eq!(r, templates);
+    let undone = r.take_changes();
+    assert_eq!(undone.len(), 2);
+    for change in &changes {
+        assert!(r.apply_change(change));
+    }
+    assert_eq!(r.object_groups[0].objects[2].id, 1);
+
+    r.track_changes(false);
+    assert!(r.remove_object(0, 2).is_some());
+    assert!(r.take_changes().is_empty());
+}
+
+#[test]
+fn test_csv_export() {
+    let r = parse_file(Path::new("assets/tiled_automapping_target.tmx")).unwrap();
+    assert_eq!(
+        r.layers[0].to_csv(),
+        "11,12,0,11,12,0\n11,0,12,12,11,12\n0,0,0,0,0,0\n"
+    );
+    let layers = r.layers_to_csv();
+    assert_eq!(layers.len(), 2);
+    assert_eq!(layers[1].0, "Decoration");
+    assert_eq!(layers[1].1, "0,0,0,0,0,0\n".repeat(3));
+    let region = r.layers[0].region_to_csv(TileRect {
+        x: 5,
+        y: 1,
+        width: 2,
+        height: 1,
+    });
+    assert_eq!(region, "12,0\n");
+
+    // Flip flags are kept, and infinite layers are written within their used bounds.
+    let r = parse_file(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let layer = &r.layers[0];
+    let bounds = layer.used_bounds().unwrap();
+    let csv = layer.to_csv();
+    assert_eq!(csv.lines().count(), bounds.height as usize);
+    let first: Vec<u32> = csv
+        .lines()
+        .next()
+        .unwrap()
+        .split(',')
+        .map(|gid| gid.parse().unwrap())
+        .collect();
+    assert_eq!(first.len(), bounds.width as usize);
+    let expected = layer.get_tile(bounds.x, bounds.y).unwrap().raw_gid();
+    assert_eq!(first[0], expected);
+}
+
+#[test]
+fn test_gid_validation() {
+    let r = read_from_file(Path::new("assets/tiled_invalid_gid.tmx")).unwrap();
+    let invalid = r.validate_gids();
+    assert_eq!(invalid.len(), 2);
+    assert_eq!(invalid[0].gid, 5);
+    assert_eq!(
+        invalid[0].location,
+        GidLocation::Tile {
+            layer_id: 1,
+            x: 0,
+            y: 1
+        }
+    );
+    assert_eq!(
+        invalid[1].location,
+        GidLocation::Object {
+            layer_id: 2,
+            object_id: 1
+        }
+    );
+
+    let result = Loader::new()
+        .with_gid_validation(true)
+        .parse_file(Path::new("assets/tiled_invalid_gid.tmx"));
+    assert!(matches!(result, Err(TiledError::InvalidGids(gids)) if gids.len() == 2));
+
+    let valid = read_from_file_with_path(Path::new("assets/tiled_base64_external.tmx")).unwrap();
+    assert!(valid.validate_gids().is_empty());
+
+    // Tile counts computed from extreme attributes saturate instead of overflowing.
+    let map = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="1" height="1" tilewidth="32" tileheight="32">
+ <tileset firstgid="1" name="huge" tilewidth="32" tileheight="32" margin="3000000000" spacing="4000000000">
+  <image source="tilesheet.png" width="448" height="192"/>
+ </tileset>
+ <layer id="1" name="Ground" width="1" height="1">
+  <data encoding="csv">1</data>
+ </layer>
+</map>"#;
+    let r = Loader::new()
+        .with_gid_validation(true)
+        .parse(map.as_bytes());
+    assert!(matches!(r, Err(TiledError::InvalidGids(gids)) if gids.len() == 1));
+    let r = parse(map.as_bytes()).unwrap();
+    assert_eq!(r.tilesets[0].tile_count(), 0);
+    assert_eq!(r.validate_gids().len(), 1);
+}
+
+#[test]
+fn test_text_objects() {
+    let r = read_from_file(Path::new("assets/tiled_text.tmx")).unwrap();
+    let group = &r.object_groups[0];
+    assert_eq!(
+        group.tint_colour,
+        Some(Colour {
+            red: 255,
+            green: 128,
+            blue: 128,
+            alpha: 255
+        })
+    );
+
+    let text = match &group.objects[0].shape {
+        ObjectShape::Text {
+            width,
+            height,
+            text,
+        } => {
+            assert_eq!((*width, *height), (160.0, 40.0));
+            text
+        }
+        s => panic!("expected a text object, got {:?}", s),
+    };
+    assert_eq!(text.contents, "Hello, world!");
+    assert_eq!(text.style.font_family, "Serif");
+    assert_eq!(text.style.pixel_size, 24);
+    assert!(text.style.wrap && text.style.bold && text.style.italic);
+    assert!(text.style.underline && text.style.strikeout && !text.style.kerning);
+    assert_eq!(text.style.halign, HorizontalAlignment::Center);
+    assert_eq!(text.style.valign, VerticalAlignment::Bottom);
+    assert_eq!(
+        text.style.color,
+        Colour {
+            red: 255,
+            green: 0,
+            blue: 0,
+            alpha: 128
+        }
+    );
+    assert_eq!(
+        text.effective_color(group),
+        Colour {
+            red: 255,
+            green: 0,
+            blue: 0,
+            alpha: 64
+        }
+    );
+
+    match &group.objects[1].shape {
+        ObjectShape::Text { text, .. } => {
+            assert_eq!(text.contents, "Plain text");
+            assert_eq!(text.style, TextStyle::default());
+        }
+        s => panic!("expected a text object, got {:?}", s),
+    }
+}
+
+#[test]
+fn test_wang_sets() {
+    let t = Tileset::parse(File::open(Path::new("assets/tiled_wangset.tsx")).unwrap()).unwrap();
+    // Properties nested in the wang set must not replace those of the tileset.
+    assert_eq!(
+        t.properties.get("tileset property"),
+        Some(&PropertyValue::StringValue("yes".to_string()))
+    );
+
+    let set = &t.wang_sets[0];
+    assert_eq!(set.name, "Ground");
+    assert_eq!(set.wang_set_type, WangSetType::Corner);
+    assert_eq!(set.tile, Some(0));
+    assert_eq!(set.colors.len(), 2);
+    assert_eq!(set.colors[1].probability, 0.5);
+    assert_eq!(
+        set.colors[1].properties.get("walkable"),
+        Some(&PropertyValue::BoolValue(true))
+    );
+    assert_eq!(set.color_index("Dirt"), Some(2));
+
+    assert_eq!(
+        set.wang_id_for_tile(TileId(2)),
+        Some(WangId([0, 2, 0, 2, 0, 1, 0, 1]))
+    );
+    assert_eq!(set.wang_id_for_tile(TileId(4)), None);
+
+    // Grass on the top corners and dirt on the bottom ones.
+    let mut pattern = [None; 8];
+    pattern[WangId::TOP_LEFT] = Some(1);
+    pattern[WangId::TOP_RIGHT] = Some(1);
+    pattern[WangId::BOTTOM_LEFT] = Some(2);
+    pattern[WangId::BOTTOM_RIGHT] = Some(2);
+    assert_eq!(set.tiles_matching(&pattern), vec![TileId(1), TileId(3)]);
+    assert_eq!(set.tiles_matching(&[None; 8]).len(), 5);
+}
+
+#[test]
+fn test_set_tile() {
+    let mut r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let layer = &mut r.layers[0];
+    assert!(layer.set_tile(2, 3, LayerTile::new(7)));
+    assert_eq!(layer.get_tile(2, 3).unwrap().gid, 7);
+    assert!(!layer.set_tile(-1, 0, LayerTile::new(7)));
+
+    let mut r = read_from_file_with_path(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    let layer = &mut r.layers[0];
+    assert!(layer.set_tile(-1000, 500, LayerTile::new(9)));
+    assert_eq!(layer.get_tile(-1000, 500).unwrap().gid, 9);
+    assert_eq!(layer.get_tile(-1001, 500).unwrap().gid, 0);
+}
+
+#[test]
+fn test_wang_fill() {
+    let t = Tileset::parse(File::open(Path::new("assets/tiled_wangset.tsx")).unwrap()).unwrap();
+    let t = MapTileset::new(1, t);
+    let set = &t.wang_sets[0];
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    // Grass above dirt.
+    let colors = vec![vec![1, 1], vec![2, 2]];
+
+    let mut layer = r.layers[0].clone();
+    set.fill_layer(&t, &mut layer, (1, 1), &colors, || 0.0);
+    // Both tiles 1 and 3 have grass on top and dirt below.
+    assert_eq!(layer.get_tile(1, 1).unwrap().gid, 2);
+    assert_eq!(layer.get_tile(2, 1).unwrap().gid, 2);
+    assert_eq!(layer.get_tile(1, 2).unwrap().gid, 6);
+    assert_eq!(layer.get_tile(2, 2).unwrap().gid, 6);
+    assert_eq!(layer.get_tile(0, 0), r.layers[0].get_tile(0, 0));
+
+    let mut layer = r.layers[0].clone();
+    set.fill_layer(&t, &mut layer, (1, 1), &colors, || 0.99);
+    assert_eq!(layer.get_tile(1, 1).unwrap().gid, 4);
+}
+
+#[test]
+fn test_wang_fill_region() {
+    let t = Tileset::parse(File::open(Path::new("assets/tiled_wangset.tsx")).unwrap()).unwrap();
+    let t = MapTileset::new(1, t);
+    let set = &t.wang_sets[0];
+    let r = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    let rect = |x, y, width, height| TileRect {
+        x,
+        y,
+        width,
+        height,
+    };
+    let mut empty = r.layers[0].clone();
+    empty.fill_rect(rect(0, 0, 5, 5), LayerTile::new(0));
+
+    // Grass above the region and dirt below it: the region gets the shore in between.
+    let mut layer = empty.clone();
+    layer.fill_rect(rect(0, 0, 4, 1), LayerTile::new(1));
+    layer.fill_rect(rect(0, 3, 4, 1), LayerTile::new(6));
+    assert_eq!(set.fill_region(&t, &mut layer, rect(0, 1, 3, 2), || 0.0), 0);
+    for x in 0..3 {
+        assert_eq!(layer.get_tile(x, 1).unwrap().gid, 1);
+        assert!([2, 4].contains(&layer.get_tile(x, 2).unwrap().gid));
+    }
+    assert_eq!(layer.get_tile(3, 1).unwrap().gid, 0);
+    assert_eq!(layer.get_tile(3, 3).unwrap().gid, 6);
+
+    // Grass on the left and above, dirt on the right and below: no tile fits them all.
+    let mut layer = empty.clone();
+    layer.set_tile(0, 1, LayerTile::new(1));
+    layer.set_tile(1, 0, LayerTile::new(1));
+    layer.set_tile(2, 1, LayerTile::new(6));
+    layer.set_tile(1, 2, LayerTile::new(6));
+    assert_eq!(set.fill_region(&t, &mut layer, rect(1, 1, 1, 1), || 0.0), 1);
+    assert_ne!(layer.get_tile(1, 1).unwrap().gid, 0);
+}
+
+#[test]
+fn test_contains_point() {
+    let r = read_from_file(Path::new("assets/tiled_hit_testing.tmx")).unwrap();
+    let object = |name: &str| {
+        r.object_groups[0]
+            .objects
+            .iter()
+            .find(|o| o.name == name)
+            .unwrap()
+    };
+
+    let rect = object("rect");
+    assert!(rect.contains_point(120.0, 110.0));
+    assert!(!rect.contains_point(120.0, 125.0));
+
+    // Rotated clockwise around its top-left corner, the rectangle now extends down and left.
+    let rotated = object("rotated");
+    assert!(rotated.contains_point(90.0, 130.0));
+    assert!(!rotated.contains_point(120.0, 110.0));
+
+    let ellipse = object("ellipse");
+    assert!(ellipse.contains_point(250.0, 225.0));
+    assert!(ellipse.contains_point(205.0, 225.0));
+    assert!(!ellipse.contains_point(205.0, 205.0));
+
+    // The notch at the bottom of the polygon is outside of it.
+    let polygon = object("polygon");
+    assert!(polygon.contains_point(350.0, 320.0));
+    assert!(polygon.contains_point(305.0, 390.0));
+    assert!(!polygon.contains_point(350.0, 390.0));
+
+    let polyline = object("polyline");
+    assert!(polyline.contains_point(450.0, 400.5));
+    assert!(!polyline.contains_point(450.0, 450.0));
+    assert!(polyline.contains_point_with_tolerance(450.0, 405.0, 10.0));
+
+    let point = object("point");
+    assert!(point.contains_point(500.5, 500.0));
+    assert!(!point.contains_point(510.0, 500.0));
+
+    // Tile objects extend up from their position.
+    let tile = object("tile");
+    assert!(tile.contains_point(610.0, 590.0));
+    assert!(!tile.contains_point(610.0, 610.0));
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn test_render_to_image() {
+    use tiled::render::{RenderOptions, RgbaImage};
+
+    // Stands in for decoding the images: each pixel holds its own coordinates.
+    let load = |image: &tiled::image::Image| {
+        let (width, height) = (image.width? as u32, image.height? as u32);
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).flat_map(move |x| [(x % 256) as u8, y as u8, 0, 255]))
+            .collect();
+        RgbaImage::from_raw(width, height, pixels)
+    };
+    let mut r = parse_file(Path::new("assets/tiled_automapping_target.tmx")).unwrap();
+    r.layers[0].set_tile(2, 2, LayerTile::new(11 | 0x80000000));
+    let image = r.render_to_image(&RenderOptions::default(), load).unwrap();
+    assert_eq!(image.dimensions(), (192, 96));
+    // Tile 10 is at (320, 0) in the tilesheet, tile 11 at (352, 0).
+    assert_eq!(image.get_pixel(0, 0).0, [64, 0, 0, 255]);
+    assert_eq!(image.get_pixel(33, 5).0, [97, 5, 0, 255]);
+    assert_eq!(image.get_pixel(64, 0).0, [0, 0, 0, 0]);
+    // Flipped horizontally.
+    assert_eq!(image.get_pixel(64, 64).0, [95, 0, 0, 255]);
+    assert_eq!(image.get_pixel(95, 64).0, [64, 0, 0, 255]);
+
+    r.layers[0].opacity = 0.5;
+    let image = r.render_to_image(&RenderOptions::default(), load).unwrap();
+    assert_eq!(image.get_pixel(0, 0).0, [64, 0, 0, 128]);
+    r.layers[0].visible = false;
+    let image = r.render_to_image(&RenderOptions::default(), load).unwrap();
+    assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 0]);
+    let options = RenderOptions {
+        hidden_layers: true,
+        ..RenderOptions::default()
+    };
+    let image = r.render_to_image(&options, |_| None).unwrap();
+    assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 0]);
+
+    let r = read_from_file(Path::new("assets/tiled_hexagonal.tmx")).unwrap();
+    assert!(r.render_to_image(&RenderOptions::default(), load).is_err());
+
+    // Huge sprites are only sampled where they cover the canvas.
+    let map = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="2" height="2" tilewidth="32" tileheight="32">
+ <tileset firstgid="1" name="tiles" tilewidth="32" tileheight="32">
+  <image source="tilesheet.png" width="448" height="192"/>
+ </tileset>
+ <objectgroup id="1" name="Objects">
+  <object id="1" gid="1" x="-100000000" y="100000000" width="200000000" height="200000000"/>
+  <object id="2" gid="1" x="-900000000" y="0" width="800000000" height="800000000"/>
+ </objectgroup>
+</map>"#;
+    let r = parse(map.as_bytes()).unwrap();
+    let image = r.render_to_image(&RenderOptions::default(), load).unwrap();
+    assert_eq!(image.dimensions(), (64, 64));
+    assert_eq!(image.get_pixel(0, 0).0, [16, 16, 0, 255]);
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn test_render_minimap() {
+    use tiled::render::{RenderOptions, RgbaImage};
+
+    // Each pixel holds its column, so the average of a tile is the middle of its columns.
+    let mut loaded = 0;
+    let load = |image: &tiled::image::Image| {
+        loaded += 1;
+        let (width, height) = (image.width? as u32, image.height? as u32);
+        let pixels = (0..height)
+            .flat_map(|_| (0..width).flat_map(move |x| [(x % 256) as u8, 0, 0, 255]))
+            .collect();
+        RgbaImage::from_raw(width, height, pixels)
+    };
+    let mut r = parse_file(Path::new("assets/tiled_automapping_target.tmx")).unwrap();
+    r.layers[0].set_tile(2, 2, LayerTile::new(12));
+    let image = r.render_minimap(&RenderOptions::default(), load);
+    assert_eq!(loaded, 1);
+    assert_eq!(image.dimensions(), (6, 3));
+    // Tile 10 covers columns 320 to 351 of the tilesheet, tile 11 columns 352 to 383.
+    assert_eq!(image.get_pixel(0, 0).0, [80, 0, 0, 255]);
+    assert_eq!(image.get_pixel(2, 2).0, [112, 0, 0, 255]);
+    assert_eq!(image.get_pixel(2, 0).0, [0, 0, 0, 0]);
+
+    r.layers[0].opacity = 0.5;
+    let image = r.render_minimap(&RenderOptions::default(), |_| None);
+    assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 0]);
+}
+
+#[cfg(feature = "triangulation")]
+#[test]
+fn test_triangulate() {
+    let r = read_from_file(Path::new("assets/tiled_hit_testing.tmx")).unwrap();
+    let objects = &r.object_groups[0].objects;
+    let polygon = objects.iter().find(|o| o.name == "polygon").unwrap();
+    let points = match &polygon.shape {
+        ObjectShape::Polygon { points } => points,
+        _ => panic!("expected a polygon"),
+    };
+    let triangles = polygon.shape.triangulate().unwrap();
+    assert_eq!(triangles.len(), 3);
+    let area: f32 = triangles
+        .iter()
+        .map(|&[a, b, c]| {
+            let (a, b, c) = (points[a], points[b], points[c]);
+            ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)).abs() / 2.0
+        })
+        .sum();
+    assert_eq!(area, 7500.0);
+
+    let polyline = objects.iter().find(|o| o.name == "polyline").unwrap();
+    assert_eq!(polyline.shape.triangulate(), None);
+}
+
+#[cfg(feature = "lyon")]
+#[test]
+fn test_lyon_path() {
+    use lyon::{algorithms::aabb::bounding_box, path::PathEvent};
+
+    let r = read_from_file(Path::new("assets/tiled_hit_testing.tmx")).unwrap();
+    let path = |name: &str| {
+        let objects = &r.object_groups[0].objects;
+        objects
+            .iter()
+            .find(|o| o.name == name)
+            .unwrap()
+            .to_lyon_path()
+    };
+    let bounds = |name: &str| {
+        let b = bounding_box(path(name).unwrap().iter());
+        [b.min.x, b.min.y, b.max.x, b.max.y].map(|v| (v * 100.0).round() / 100.0)
+    };
+    assert_eq!(bounds("rect"), [100.0, 100.0, 140.0, 120.0]);
+    assert_eq!(bounds("rotated"), [80.0, 100.0, 100.0, 140.0]);
+    assert_eq!(bounds("ellipse"), [200.0, 200.0, 300.0, 250.0]);
+    assert_eq!(bounds("polygon"), [300.0, 300.0, 400.0, 400.0]);
+    assert_eq!(bounds("tile"), [600.0, 568.0, 632.0, 600.0]);
+    assert!(path("point").is_none());
+
+    let polyline = path("polyline").unwrap();
+    assert_eq!(bounding_box(polyline.iter()).max.to_tuple(), (500.0, 500.0));
+    assert!(matches!(
+        polyline.iter().last(),
+        Some(PathEvent::End { close: false, .. })
+    ));
+    assert!(matches!(
+        path("polygon").unwrap().iter().last(),
+        Some(PathEvent::End { close: true, .. })
+    ));
+}
+
+#[test]
+fn test_group_layers() {
+    let r = read_from_file(Path::new("assets/tiled_groups.tmx")).unwrap();
+    assert_eq!(r.groups.len(), 2);
+    let world = &r.groups[0];
+    assert_eq!(world.name, "World");
+    assert_eq!(world.parent_group, None);
+    assert_eq!(world.layer_index, 0);
+    assert_eq!(
+        world.properties.get("group property"),
+        Some(&PropertyValue::StringValue("outer".to_string()))
+    );
+    assert_eq!(r.groups[1].parent_group, Some(0));
+
+    assert_eq!(r.layers[0].name, "Ground");
+    assert_eq!(r.layers[0].parent_group, Some(0));
+    assert_eq!(r.layers[0].layer_index, 1);
+    assert!(r.layers[0].properties.contains_key("layer property"));
+    assert_eq!(r.image_layers[0].parent_group, Some(1));
+    assert_eq!(r.image_layers[0].layer_index, 3);
+    assert_eq!(r.object_groups[0].parent_group, Some(0));
+    assert_eq!(r.layers[1].name, "Top");
+    assert_eq!(r.layers[1].parent_group, None);
+    assert_eq!(r.layers[1].layer_index, 5);
+}
+
+#[test]
+fn test_iter_layers_recursive() {
+    let mut r = read_from_file(Path::new("assets/tiled_groups.tmx")).unwrap();
+    let names: Vec<_> = r.iter_layers_recursive().map(|l| l.layer.name()).collect();
+    assert_eq!(names, ["Ground", "Clouds", "Entities", "Top"]);
+
+    let flat: Vec<_> = r.iter_layers_recursive().collect();
+    assert_eq!(flat[0].layer, LayerRef::Tile(&r.layers[0]));
+    assert_eq!(
+        (flat[0].transform.offset_x, flat[0].transform.offset_y),
+        (11.0, 22.0)
+    );
+    assert_eq!(flat[0].tint, r.layers[0].effective_tint(&r));
+    assert_eq!(flat[1].transform, r.image_layers[0].transform(&r));
+    assert_eq!(flat[1].layer.parent_group(), Some(1));
+    assert_eq!(flat[3].layer.parent_group(), None);
+    assert!(flat.iter().all(|l| l.visible));
+
+    // Hiding a group hides everything nested in it.
+    r.groups[1].visible = false;
+    let visible: Vec<_> = r
+        .iter_layers_recursive()
+        .map(|l| (l.layer.name(), l.visible))
+        .collect();
+    assert_eq!(
+        visible,
+        [
+            ("Ground", true),
+            ("Clouds", false),
+            ("Entities", true),
+            ("Top", true)
+        ]
+    );
+}
+
+#[test]
+fn test_layer_paths() {
+    let r = read_from_file(Path::new("assets/tiled_groups.tmx")).unwrap();
+    assert_eq!(
+        r.layer_by_path("World/Background/Clouds"),
+        Some(LayerRef::Image(&r.image_layers[0]))
+    );
+    assert_eq!(
+        r.layer_by_path("World/Ground"),
+        Some(LayerRef::Tile(&r.layers[0]))
+    );
+    assert_eq!(r.layer_by_path("Top"), Some(LayerRef::Tile(&r.layers[1])));
+    assert_eq!(r.layer_by_path("Clouds"), None);
+    assert_eq!(r.layer_by_path("Background/Clouds"), None);
+    assert_eq!(r.layer_by_path("World/Background"), None);
+    assert_eq!(r.group_by_path("World/Background").map(|g| g.id), Some(3));
+
+    assert_eq!(r.image_layers[0].path(&r), "World/Background/Clouds");
+    assert_eq!(r.object_groups[0].path(&r), "World/Entities");
+    assert_eq!(r.layers[1].path(&r), "Top");
+    assert_eq!(r.groups[1].path(&r), "World/Background");
+    for layer in r.iter_layers_recursive() {
+        assert_eq!(r.layer_by_path(&layer.layer.path(&r)), Some(layer.layer));
+    }
+}
+
+#[test]
+fn test_world_position() {
+    let r = read_from_file(Path::new("assets/tiled_groups.tmx")).unwrap();
+    let (origin_x, origin_y) = (r.parallax_origin_x, r.parallax_origin_y);
+    assert_eq!((origin_x, origin_y), (100.0, 50.0));
+
+    let ground = &r.layers[0];
+    let transform = ground.transform(&r);
+    assert_eq!((transform.offset_x, transform.offset_y), (11.0, 22.0));
+    assert_eq!((transform.parallax_x, transform.parallax_y), (0.5, 0.5));
+    // With the view centered on the parallax origin, only offsets apply.
+    assert_eq!(
+        r.world_position_of(
+            MapItem::Tile {
+                layer: ground,
+                x: 1,
+                y: 1
+            },
+            origin_x,
+            origin_y
+        ),
+        Some((43.0, 54.0))
+    );
+    assert_eq!(
+        r.world_position_of(
+            MapItem::Tile {
+                layer: ground,
+                x: 1,
+                y: 1
+            },
+            origin_x + 100.0,
+            origin_y
+        ),
+        Some((93.0, 54.0))
+    );
+
+    let clouds = r.image_layers[0].transform(&r);
+    assert_eq!((clouds.offset_x, clouds.offset_y), (110.0, 20.0));
+    assert_eq!((clouds.parallax_x, clouds.parallax_y), (0.25, 1.0));
+
+    let player = r.objects().with_name("player").next().unwrap();
+    assert_eq!(
+        r.world_position_of(player, origin_x, origin_y + 10.0),
+        Some((79.0, 57.0))
+    );
+
+    let top = &r.layers[1];
+    assert_eq!(
+        r.world_position_of(
+            MapItem::Tile {
+                layer: top,
+                x: 0,
+                y: 1
+            },
+            500.0,
+            500.0
+        ),
+        Some((0.0, 32.0))
+    );
+}
+
+#[test]
+fn test_effective_tint() {
+    let r = read_from_file(Path::new("assets/tiled_groups.tmx")).unwrap();
+    assert_eq!(
+        r.layers[0].effective_tint(&r),
+        Tint {
+            red: 0.0,
+            green: 1.0,
+            blue: 0.0,
+            alpha: 0.5
+        }
+    );
+    // Half-grey image layer at half opacity, in a red group inside of a half-transparent group.
+    let clouds = r.image_layers[0].effective_tint(&r);
+    assert!((clouds.red - 128.0 / 255.0).abs() < 1e-6);
+    assert_eq!((clouds.green, clouds.blue, clouds.alpha), (0.0, 0.0, 0.25));
+    assert_eq!(
+        r.layers[1].effective_tint(&r),
+        Tint {
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+            alpha: 1.0
+        }
+    );
+}
+
+#[test]
+fn test_layer_data_format() {
+    let format = |file: &str| {
+        let map = read_from_file(Path::new(file)).unwrap();
+        (map.layers[0].encoding, map.layers[0].compression)
+    };
+    assert_eq!(format("assets/tiled_csv.tmx"), (Some(Encoding::Csv), None));
+    assert_eq!(format("assets/tiled_base64.tmx"), (Some(Encoding::Base64), None));
+    assert_eq!(
+        format("assets/tiled_base64_zlib.tmx"),
+        (Some(Encoding::Base64), Some(Compression::Zlib))
+    );
+    assert_eq!(
+        format("assets/tiled_base64_gzip.tmx"),
+        (Some(Encoding::Base64), Some(Compression::Gzip))
+    );
+    assert_eq!(
+        format("assets/tiled_base64_zstandard.tmx"),
+        (Some(Encoding::Base64), Some(Compression::Zstd))
+    );
+
+    let infinite = read_from_file_with_path(Path::new("assets/tiled_base64_zlib_infinite.tmx")).unwrap();
+    assert_eq!(infinite.layers[0].encoding, Some(Encoding::Base64));
+    assert_eq!(infinite.layers[0].compression, Some(Compression::Zlib));
+
+    assert_eq!("zstd".parse::<Compression>().unwrap(), Compression::Zstd);
+    assert_eq!(Encoding::Csv.to_string(), "csv");
+    assert!("lz4".parse::<Compression>().is_err());
+}
+
+#[test]
+fn test_colour_parsing() {
+    let colour = |red, green, blue, alpha| Colour {
+        red,
+        green,
+        blue,
+        alpha,
+    };
+    assert_eq!("#ff8000".parse::<Colour>().unwrap(), colour(255, 128, 0, 255));
+    assert_eq!("ff8000".parse::<Colour>().unwrap(), colour(255, 128, 0, 255));
+    assert_eq!("#80ff8000".parse::<Colour>().unwrap(), colour(255, 128, 0, 128));
+    assert_eq!("#f80".parse::<Colour>().unwrap(), colour(255, 136, 0, 255));
+    assert_eq!("8f80".parse::<Colour>().unwrap(), colour(255, 136, 0, 136));
+    assert_eq!(" #FF8000 ".parse::<Colour>().unwrap(), colour(255, 128, 0, 255));
+    for invalid in &["", "#", "#ff", "#ff800", "#gg8000", "#ff8000ff00", "#ffé00"] {
+        match invalid.parse::<Colour>() {
+            Err(TiledError::InvalidColour(s)) => assert_eq!(&s, invalid),
+            other => panic!("{:?} parsed as {:?}", invalid, other),
+        }
+    }
+
+    let map = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="1" height="1" tilewidth="32" tileheight="32" backgroundcolor="#zz0000">
+</map>"##;
+    match parse(map.as_bytes()) {
+        Err(TiledError::InvalidColour(s)) => assert_eq!(s, "#zz0000"),
+        other => panic!("expected an invalid colour error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_character_encodings() {
+    let map = |declaration: &str, name: &[u8]| {
+        let mut data = format!(
+            "<?xml version=\"1.0\" encoding=\"{}\"?>\n<map version=\"1.0\" orientation=\"orthogonal\" width=\"1\" height=\"1\" tilewidth=\"32\" tileheight=\"32\">\n<objectgroup name=\"",
+            declaration
+        )
+        .into_bytes();
+        data.extend_from_slice(name);
+        data.extend_from_slice(b"\"/>\n</map>");
+        data
+    };
+    let name = |data: Vec<u8>| parse(data.as_slice()).map(|m| m.object_groups[0].name.clone());
+
+    let mut bom = b"\xEF\xBB\xBF".to_vec();
+    bom.extend(map("UTF-8", "Caf\u{e9}".as_bytes()));
+    assert_eq!(name(bom).unwrap(), "Caf\u{e9}");
+
+    let mut mislabeled = b"\xEF\xBB\xBF".to_vec();
+    mislabeled.extend(map("windows-1252", "Caf\u{e9}".as_bytes()));
+    assert_eq!(name(mislabeled).unwrap(), "Caf\u{e9}");
+
+    let windows = map("windows-1252", b"Caf\xE9 \x80\x96");
+    assert_eq!(name(windows).unwrap(), "Caf\u{e9} \u{20ac}\u{2013}");
+
+    let latin1 = map("ISO-8859-1", b"Caf\xE9");
+    assert_eq!(name(latin1).unwrap(), "Caf\u{e9}");
+
+    match name(map("Shift_JIS", b"Cafe")) {
+        Err(TiledError::UnsupportedEncoding(e)) => assert_eq!(e, "Shift_JIS"),
+        other => panic!("expected an unsupported encoding error, got {:?}", other),
+    }
+
+    // The encoding is found even if the reader hands out the declaration a byte at a time.
+    struct Trickle<'a>(&'a [u8]);
+    impl std::io::Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = self.0.len().min(buf.len()).min(1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+    let trickled = |data: &[u8]| parse(Trickle(data)).map(|m| m.object_groups[0].name.clone());
+    let mut bom = b"\xEF\xBB\xBF".to_vec();
+    bom.extend(map("UTF-8", "Caf\u{e9}".as_bytes()));
+    assert_eq!(trickled(&bom).unwrap(), "Caf\u{e9}");
+    let windows = map("windows-1252", b"Caf\xE9 \x80\x96");
+    assert_eq!(trickled(&windows).unwrap(), "Caf\u{e9} \u{20ac}\u{2013}");
+}
+
+#[test]
+fn test_backslash_sources() {
+    let r = read_from_file_with_path(Path::new("assets/tiled_backslash_paths.tmx")).unwrap();
+    assert_eq!(r.tilesets[0].name, "tilesheet");
+
+    let image = r.image_layers[0].image.as_ref().unwrap();
+    assert_eq!(image.source, "../assets/tilesheet.png");
+    assert_eq!(image.original_source, "..\\assets\\tilesheet.png");
+
+    let chest = &r.object_groups[0].objects[0];
+    let template = chest.template.as_ref().unwrap();
+    assert_eq!(template.source, "../assets/tiled_template_chest.tx");
+    assert_eq!(template.original_source, "..\\assets\\tiled_template_chest.tx");
+    assert_eq!(chest.name, "chest");
+    assert_eq!(chest.gid, 2);
+}
+
+#[test]
+fn test_path_resolver() {
+    // The map is read from somewhere else than where its external files are.
+    let elsewhere = Path::new("maps/elsewhere.tmx");
+    let open = || File::open("assets/tiled_templates.tmx").unwrap();
+    assert!(Loader::new().parse_with_path(open(), elsewhere).is_err());
+    let r = Loader::new()
+        .with_path_resolver(PathResolver::ProjectRoot("assets".into()))
+        .parse_with_path(open(), elsewhere)
+        .unwrap();
+    assert_eq!(r.tilesets[1].name, "tilesheet");
+    assert_eq!(r.object_groups[0].objects[0].name, "chest");
+
+    let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+    let resolver = PathResolver::custom(move |referencing: &Path, source: &str| {
+        recorded
+            .lock()
+            .unwrap()
+            .push((referencing.to_path_buf(), source.to_string()));
+        Path::new("assets").join(source)
+    });
+    Loader::new()
+        .with_path_resolver(resolver)
+        .parse_with_path(open(), elsewhere)
+        .unwrap();
+    let requests = requests.lock().unwrap();
+    // Sources in the template are resolved relative to the template itself.
+    assert!(requests.contains(&(elsewhere.into(), "tiled_template_chest.tx".to_string())));
+    assert!(requests.contains(&(
+        Path::new("assets/tiled_template_chest.tx").into(),
+        "tilesheet.tsx".to_string()
+    )));
+
+    assert_eq!(
+        PathResolver::default().resolve(Path::new("assets/map.tmx"), "tilesheet.tsx"),
+        Path::new("assets/tilesheet.tsx")
+    );
+}
+
+#[test]
+fn test_tolerant_mode() {
+    let path = Path::new("assets/tiled_missing_tileset.tmx");
+    assert!(Loader::new().parse_file(path).is_err());
+
+    let r = Loader::new().with_tolerant_mode(true).parse_file(path).unwrap();
+    assert_eq!(r.tilesets.len(), 2);
+    assert_eq!(r.tilesets[0].name, "tilesheet");
+    assert_eq!(r.tilesets[0].load_error, None);
+    let placeholder = &r.tilesets[1];
+    assert_eq!(placeholder.first_gid, 85);
+    assert_eq!(placeholder.name, "missing.tsx");
+    assert_eq!((placeholder.tile_width, placeholder.tile_height), (16, 16));
+    assert!(placeholder.load_error.as_ref().unwrap().contains("missing.tsx"));
+    assert_eq!(r.layers[0].get_tile(1, 0).unwrap().gid, 85);
+}
+
+#[test]
+fn test_dimension_guards() {
+    let map = |size: &str, tile_size: &str, data: &str| {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" {} {}>
+ <layer id="1" name="Tile Layer 1">
+  <data encoding="csv">{}</data>
+ </layer>
+</map>"#,
+            size, tile_size, data
+        )
+    };
+    let tile_size = r#"tilewidth="32" tileheight="32""#;
+
+    match parse(map(r#"width="0" height="2""#, tile_size, "").as_bytes()) {
+        Err(TiledError::ZeroSize(what)) => assert_eq!(what, "map width"),
+        other => panic!("expected a zero size error, got {:?}", other),
+    }
+    match parse(map(r#"width="2" height="2""#, r#"tilewidth="0" tileheight="32""#, "").as_bytes()) {
+        Err(TiledError::ZeroSize(what)) => assert_eq!(what, "map tile width"),
+        other => panic!("expected a zero size error, got {:?}", other),
+    }
+    match parse(map(r#"width="100000" height="100000""#, tile_size, "1").as_bytes()) {
+        Err(TiledError::TooManyCells {
+            width: 100000,
+            height: 100000,
+            limit: Loader::DEFAULT_CELL_LIMIT,
+        }) => {}
+        other => panic!("expected a cell limit error, got {:?}", other),
+    }
+
+    let small = map(r#"width="2" height="2""#, tile_size, "1,2,3,4");
+    assert!(parse(small.as_bytes()).is_ok());
+    match Loader::new().with_cell_limit(3).parse(small.as_bytes()) {
+        Err(TiledError::TooManyCells { limit: 3, .. }) => {}
+        other => panic!("expected a cell limit error, got {:?}", other),
+    }
+
+    let infinite = Path::new("assets/tiled_base64_zlib_infinite.tmx");
+    assert!(Loader::new().parse_file(infinite).is_ok());
+    assert!(matches!(
+        Loader::new().with_cell_limit(1023).parse_file(infinite),
+        Err(TiledError::TooManyCells {
+            width: 32,
+            height: 32,
+            ..
+        })
+    ));
+
+    // The declared size of chunks isn't trusted when allocating their tiles.
+    let huge_chunk = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="1" height="1" tilewidth="32" tileheight="32" infinite="1">
+ <layer id="1" name="Tile Layer 1">
+  <data encoding="csv"><chunk x="0" y="0" width="4000000000" height="1">1,2</chunk></data>
+ </layer>
+</map>"#;
+    let r = Loader::new()
+        .with_cell_limit(u64::MAX)
+        .parse(huge_chunk.as_bytes())
+        .unwrap();
+    assert_eq!(r.layers[0].get_tile(1, 0).unwrap().gid, 2);
+    // Chunks over the limit are rejected before their tiles are read.
+    assert!(matches!(
+        parse(huge_chunk.as_bytes()),
+        Err(TiledError::TooManyCells {
+            width: 4000000000,
+            height: 1,
+            ..
+        })
+    ));
+
+    // Infinite maps are saved with a size of 0.
+    let unsized_infinite = huge_chunk
+        .replace(r#"width="1" height="1""#, r#"width="0" height="0""#)
+        .replace("4000000000", "2");
+    let r = parse(unsized_infinite.as_bytes()).unwrap();
+    assert!(r.infinite);
+    assert_eq!((r.width, r.height), (0, 0));
+    assert_eq!(r.layers[0].get_tile(1, 0).unwrap().gid, 2);
+}
+
+#[test]
+fn test_float_property_text() {
+    let map = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="1" height="1" tilewidth="32" tileheight="32">
+ <properties>
+  <property name="precise" type="float" value="0.30000000001"/>
+  <property name="exponent" type="float" value="1e-3"/>
+  <property name="short" type="float" value=".5"/>
+ </properties>
+ <objectgroup id="1" name="Objects">
+  <object id="1" x="0" y="0">
+   <properties>
+    <property name="speed" type="float" value="0.1"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>"#;
+    let r = parse(map.as_bytes()).unwrap();
+    match &r.properties["precise"] {
+        PropertyValue::FloatValue(f) => {
+            assert_eq!(f.value, 0.30000000001);
+            assert_eq!(f.text.as_deref(), Some("0.30000000001"));
+            assert_eq!(f.as_f32(), 0.3);
+        }
+        other => panic!("expected a float, got {:?}", other),
+    }
+    assert_eq!(
+        r.properties["exponent"],
+        PropertyValue::FloatValue(0.001f64.into())
+    );
+
+    #[cfg(feature = "json")]
+    {
+        let mut out = Vec::new();
+        r.write_json(&mut out).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert!(json.contains("\"value\":0.30000000001"));
+        assert!(json.contains("\"value\":1e-3"));
+        // `.5` isn't valid JSON, so the value is written instead.
+        assert!(json.contains("\"value\":0.5"));
+    }
+
+    let mut out = Vec::new();
+    r.object_groups[0].objects[0]
+        .to_template(None)
+        .write(&mut out)
+        .unwrap();
+    let xml = String::from_utf8(out).unwrap();
+    assert!(xml.contains("type=\"float\" value=\"0.1\""));
+
+    // Changing the value makes the original text obsolete.
+    let changed = FloatProperty {
+        value: 2.5,
+        text: Some("0.1".to_string()),
+    };
+    assert_eq!(changed.to_string(), "2.5");
+    assert_eq!(FloatProperty::from(0.3f32).value, 0.3);
+}
+
+#[test]
+fn test_unknown_property_type() {
+    let map = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="1" height="1" tilewidth="32" tileheight="32">
+ <objectgroup id="1" name="Objects">
+  <object id="1" x="0" y="0">
+   <properties>
+    <property name="future" type="vector2" value="3,4"/>
+    <property name="unset" type="color" value=""/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>"#;
+    let r = parse(map.as_bytes()).unwrap();
+    let object = &r.object_groups[0].objects[0];
+    let value = &object.properties["future"];
+    assert_eq!(
+        value,
+        &PropertyValue::Unknown {
+            type_name: "vector2".to_string(),
+            raw_value: "3,4".to_string(),
+        }
+    );
+    assert_eq!(value.type_name(), "vector2");
+    assert_eq!(object.properties["unset"], PropertyValue::ColorValue(0));
+    let short = map.replace(r#"value="""#, r##"value="#""##);
+    assert!(matches!(
+        parse(short.as_bytes()),
+        Err(TiledError::InvalidColour(_))
+    ));
+
+    #[cfg(feature = "json")]
+    {
+        let mut out = Vec::new();
+        r.write_json(&mut out).unwrap();
+        let json = String::from_utf8(out).unwrap();
+        assert!(json.contains("\"type\":\"vector2\""));
+        assert!(json.contains("\"value\":\"3,4\""));
+    }
+
+    let mut out = Vec::new();
+    object.to_template(None).write(&mut out).unwrap();
+    let xml = String::from_utf8(out).unwrap();
+    assert!(xml.contains("type=\"vector2\" value=\"3,4\""));
+}
+
+#[test]
+fn test_validate() {
+    let r = read_from_file(Path::new("assets/tiled_duplicates.tmx")).unwrap();
+    assert_eq!(
+        r.validate(),
+        vec![
+            ValidationWarning::DuplicateLayerName {
+                name: "Ground".to_string(),
+                layer_indices: vec![0, 2],
+            },
+            ValidationWarning::DuplicateObjectId {
+                object_id: 2,
+                layer_ids: vec![2, 3],
+            },
+            ValidationWarning::OverlappingTilesets {
+                first: 0,
+                second: 1
+            },
+        ]
+    );
+    assert_eq!(
+        r.validate()[0].to_string(),
+        "layers [0, 2] are all named \"Ground\""
+    );
+
+    assert_eq!(
+        r.load_warnings,
+        vec![LoadWarning::OverlappingTilesets {
+            first: 0,
+            second: 1
+        }]
+    );
+    let strict = Loader::new()
+        .with_gid_validation(true)
+        .parse_file(Path::new("assets/tiled_duplicates.tmx"));
+    assert!(matches!(
+        strict,
+        Err(TiledError::OverlappingTilesets {
+            first: 0,
+            second: 1
+        })
+    ));
+
+    let clean = read_from_file(Path::new("assets/tiled_base64.tmx")).unwrap();
+    assert!(clean.validate().is_empty());
+    assert!(clean.load_warnings.is_empty());
+    assert_eq!(clean.tilesets[0].column_count, Some(14));
+
+    let mut shifted = clean.clone();
+    Arc::make_mut(&mut shifted.tilesets[0].tileset).spacing = 2;
+    assert_eq!(
+        shifted.tilesets[0].validate_image(),
+        vec![
+            TilesetMismatch::Columns {
+                declared: 14,
+                fitting: 13
+            },
+            TilesetMismatch::TileCount {
+                declared: 84,
+                fitting: 65
+            },
+            TilesetMismatch::LeftoverPixels {
+                right: 8,
+                bottom: 24
+            },
+        ]
+    );
+    assert_eq!(
+        shifted.validate()[0],
+        ValidationWarning::TilesetImage {
+            tileset: 0,
+            mismatch: TilesetMismatch::Columns {
+                declared: 14,
+                fitting: 13
+            },
+        }
+    );
+    Arc::make_mut(&mut shifted.tilesets[0].tileset).tile_width = 500;
+    assert_eq!(
+        shifted.tilesets[0].validate_image(),
+        vec![TilesetMismatch::TileLargerThanImage]
+    );
+
+    // Overlaps are still found next to tilesets with extreme margins and spacings.
+    let map = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="1" height="1" tilewidth="32" tileheight="32">
+ <tileset firstgid="1" name="spaced" tilewidth="32" tileheight="32" spacing="4000000000">
+  <image source="tilesheet.png" width="448" height="192"/>
+ </tileset>
+ <tileset firstgid="1" name="margin" tilewidth="32" tileheight="32" margin="3000000000">
+  <image source="tilesheet.png" width="448" height="192"/>
+ </tileset>
+ <tileset firstgid="1" name="counted" tilewidth="32" tileheight="32" tilecount="4">
+  <image source="tilesheet.png" width="448" height="192"/>
+ </tileset>
+</map>"#;
+    let r = parse(map.as_bytes()).unwrap();
+    assert_eq!(r.tilesets[0].tile_count(), 1);
+    assert_eq!(
+        r.tilesets[1].validate_image(),
+        vec![TilesetMismatch::TileLargerThanImage]
+    );
+    assert_eq!(
+        r.tilesets[0].validate_image(),
+        vec![TilesetMismatch::LeftoverPixels {
+            right: 416,
+            bottom: 160
+        }]
+    );
+    assert_eq!(r.validate().len(), 4);
+    assert_eq!(
+        r.load_warnings,
+        vec![LoadWarning::OverlappingTilesets {
+            first: 0,
+            second: 2
+        }]
+    );
+}
+
+#[derive(Debug, PartialEq)]
+struct Spawner {
+    enemy: String,
+    count: u32,
+    interval: f32,
+    tint: Colour,
+    boss: Option<bool>,
+}
+
+impl FromProperties for Spawner {
+    fn from_properties(properties: &Properties) -> Result<Self, PropertyError> {
+        Ok(Spawner {
+            enemy: properties.get_as("enemy")?,
+            count: properties.get_as("count")?,
+            interval: properties.get_as("interval")?,
+            tint: properties.get_as("tint")?,
+            boss: properties.get_as("boss")?,
+        })
+    }
+}
+
+#[test]
+fn test_from_properties() {
+    let map = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.0" orientation="orthogonal" width="1" height="1" tilewidth="32" tileheight="32">
+ <objectgroup id="1" name="Objects">
+  <object id="1" x="0" y="0">
+   <properties>
+    <property name="enemy" value="slime"/>
+    <property name="count" type="int" value="3"/>
+    <property name="interval" type="float" value="1.5"/>
+    <property name="tint" type="color" value="#ff00ff00"/>
+   </properties>
+  </object>
+  <object id="2" x="0" y="0">
+   <properties>
+    <property name="enemy" value="bat"/>
+    <property name="count" type="int" value="-1"/>
+    <property name="interval" type="int" value="2"/>
+    <property name="tint" type="color" value="#ff00ff00"/>
+   </properties>
+  </object>
+  <object id="3" x="0" y="0">
+   <properties>
+    <property name="enemy" type="int" value="4"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>"##;
+    let r = parse(map.as_bytes()).unwrap();
+    let objects = &r.object_groups[0].objects;
+    assert_eq!(
+        objects[0].properties.deserialize_into::<Spawner>().unwrap(),
+        Spawner {
+            enemy: "slime".to_string(),
+            count: 3,
+            interval: 1.5,
+            tint: Colour {
+                red: 0,
+                green: 255,
+                blue: 0,
+                alpha: 255
+            },
+            boss: None,
+        }
+    );
+
+    let error = objects[1]
+        .properties
+        .deserialize_into::<Spawner>()
+        .unwrap_err();
+    assert_eq!(error.name, "count");
+    assert_eq!(
+        error.kind,
+        PropertyErrorKind::Invalid("-1 is negative".to_string())
+    );
+    assert_eq!(objects[1].properties.get_as::<f64>("interval").unwrap(), 2.0);
+
+    let error = objects[2]
+        .properties
+        .deserialize_into::<Spawner>()
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "property 'enemy' should be of type string but is of type int"
+    );
+    assert_eq!(
+        objects[2].properties.get_as::<PropertyValue>("enemy").unwrap(),
+        PropertyValue::IntValue(4)
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_deserialize_properties() {
+    use tiled::properties::from_properties;
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Kind {
+        Slime,
+        Bat,
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Spawner<'a> {
+        #[serde(rename = "enemy")]
+        kind: Kind,
+        name: &'a str,
+        count: u8,
+        interval: f32,
+        tint: u32,
+        target: Option<u32>,
+        #[serde(default)]
+        boss: bool,
+    }
+
+    let mut properties = Properties::new();
+    let mut set = |name: &str, value| properties.insert(name.to_string(), value);
+    set("enemy", PropertyValue::StringValue("slime".to_string()));
+    set("name", PropertyValue::StringValue("Blob".to_string()));
+    set("count", PropertyValue::IntValue(3));
+    set("interval", PropertyValue::IntValue(2));
+    set("tint", PropertyValue::ColorValue(0xff00ff00));
+    set("target", PropertyValue::ObjectValue(7));
+    set("unused", PropertyValue::BoolValue(true));
+    assert_eq!(
+        from_properties::<Spawner>(&properties).unwrap(),
+        Spawner {
+            kind: Kind::Slime,
+            name: "Blob",
+            count: 3,
+            interval: 2.0,
+            tint: 0xff00ff00,
+            target: Some(7),
+            boss: false,
+        }
+    );
+
+    let mut wrong = properties.clone();
+    wrong.insert("count".to_string(), PropertyValue::IntValue(300));
+    let error = from_properties::<Spawner>(&wrong).unwrap_err();
+    assert_eq!(error.name, "count");
+    assert!(matches!(error.kind, PropertyErrorKind::Invalid(_)));
+    let mut wrong = properties.clone();
+    wrong.insert(
+        "enemy".to_string(),
+        PropertyValue::StringValue("dragon".to_string()),
+    );
+    assert_eq!(
+        from_properties::<Spawner>(&wrong).unwrap_err().name,
+        "enemy"
+    );
+    let mut missing = properties.clone();
+    missing.remove("name");
+    let error = from_properties::<Spawner>(&missing).unwrap_err();
+    assert_eq!(error.to_string(), "property 'name' is missing");
+}